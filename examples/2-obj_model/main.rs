@@ -1,8 +1,11 @@
-use raytracing_lib::run;
+use raytracing_lib::{run, RunConfig};
 
 /// Entry point for the application.
 ///
 /// It then calls the `run` function and blocks until it completes.
 fn main() {
-    pollster::block_on(run(Some("examples/2-obj_model/config.toml")));
+    pollster::block_on(run(RunConfig {
+        config_path: Some("examples/2-obj_model/config.toml".to_string()),
+        ..Default::default()
+    }));
 }
\ No newline at end of file
@@ -1,9 +1,12 @@
-use raytracing_lib::run;
+use raytracing_lib::{run, RunConfig};
 
 /// Entry point for the application.
 ///
 /// It then calls the `run` function and blocks until it completes.
 fn main() {
     std::env::set_var("RUST_BACKTRACE", "1"); //Sometimes the GPU causes a crash, if this isnt set only a way to short nonsense error message is shown. Left it in here since the possiblility for a crsh rises in this example.
-    pollster::block_on(run(Some("examples/5-cornell_box/config.toml")));
+    pollster::block_on(run(RunConfig {
+        config_path: Some("examples/5-cornell_box/config.toml".to_string()),
+        ..Default::default()
+    }));
 }
\ No newline at end of file
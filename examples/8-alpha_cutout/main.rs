@@ -0,0 +1,11 @@
+use raytracing_lib::{run, RunConfig};
+
+/// Entry point for the application.
+///
+/// It then calls the `run` function and blocks until it completes.
+fn main() {
+    pollster::block_on(run(RunConfig {
+        config_path: Some("examples/8-alpha_cutout/Config.toml".to_string()),
+        ..Default::default()
+    }));
+}
@@ -1,4 +1,4 @@
-use raytracing_lib::run;
+use raytracing_lib::{run, RunConfig};
 
 /// Entry point for the application.
 ///
@@ -16,5 +16,8 @@ use raytracing_lib::run;
 /// 
 fn main() {
     std::env::set_var("RUST_BACKTRACE", "1"); //Keep this on to hav any Idead what happened if the GPU causes a crash.
-    pollster::block_on(run(Some("examples/99-caution_max_scene/config.toml")));
+    pollster::block_on(run(RunConfig {
+        config_path: Some("examples/99-caution_max_scene/config.toml".to_string()),
+        ..Default::default()
+    }));
 }
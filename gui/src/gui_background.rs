@@ -0,0 +1,54 @@
+use egui::{Context, Margin};
+use scene::{Background, BackgroundMode};
+
+
+/// Shows the background panel and returns true if the background was changed this frame.
+///
+/// The HDRI mode is only offered if `texture_loaded` is set, i.e. the config defines a background texture.
+pub fn background_gui(ui: &Context, background: &mut Background, texture_loaded: bool) -> bool {
+    let mut changed = false;
+
+    egui::SidePanel::left("Background")
+        .frame(egui::Frame::default()
+            .fill(egui::Color32::from_black_alpha(200))
+            .inner_margin(Margin{ left:10.0, right:10.0, top:10.0, bottom:10.0}))
+        .show(ui, |ui| {
+            ui.heading("Background");
+
+            let mut mode = background.mode();
+            egui::ComboBox::from_label("Mode")
+                .selected_text(mode.name())
+                .show_ui(ui, |ui| {
+                    for option in BackgroundMode::ALL {
+                        if option == BackgroundMode::Texture && !(texture_loaded && background.has_texture()) {
+                            continue;
+                        }
+                        ui.selectable_value(&mut mode, option, option.name());
+                    }
+                });
+            if mode != background.mode() {
+                background.set_mode(mode);
+                changed = true;
+            }
+
+            changed |= ui.add(egui::Slider::new(&mut background.intensity, 0.0..=10.0).text("Intensity").logarithmic(true)).changed();
+            ui.add_enabled_ui(mode == BackgroundMode::Color, |ui| {
+                ui.horizontal(|ui| {
+                    changed |= ui.color_edit_button_rgb(&mut background.color).changed();
+                    ui.label("Color");
+                });
+            });
+            if mode == BackgroundMode::Gradient {
+                let mut gradient = background.gradient();
+                for (color, label) in [(&mut gradient.top, "Top"), (&mut gradient.horizon, "Horizon"), (&mut gradient.bottom, "Bottom")] {
+                    ui.horizontal(|ui| {
+                        changed |= ui.color_edit_button_rgb(color).changed();
+                        ui.label(label);
+                    });
+                }
+                background.set_gradient(gradient);
+            }
+        });
+
+    changed
+}
@@ -15,6 +15,7 @@ pub fn denoising_settings_gui(ui: &Context, shader_config: &mut ShaderConfig) ->
             ui.radio_value(&mut shader_config.first_pass, 2, "Non local means denoising");
             ui.radio_value(&mut shader_config.first_pass, 3, "Temporal denoising");
             ui.radio_value(&mut shader_config.first_pass, 4, "Adaptive Temporal denoising");
+            ui.radio_value(&mut shader_config.first_pass, ShaderConfig::ATROUS_PASS, "À-Trous wavelet denoising");
             ui.radio_value(&mut shader_config.first_pass, 5, "None");
             ui.separator();
             ui.label("Second Denoising Step");
@@ -23,6 +24,7 @@ pub fn denoising_settings_gui(ui: &Context, shader_config: &mut ShaderConfig) ->
             ui.radio_value(&mut shader_config.second_pass, 2, "Non local means denoising");
             ui.radio_value(&mut shader_config.second_pass, 3, "Temporal denoising");
             ui.radio_value(&mut shader_config.second_pass, 4, "Adaptive Temporal denoising");
+            ui.radio_value(&mut shader_config.second_pass, ShaderConfig::ATROUS_PASS, "À-Trous wavelet denoising");
             ui.radio_value(&mut shader_config.second_pass, 5, "None");
             ui.separator();
 
@@ -63,6 +65,14 @@ pub fn denoising_settings_gui(ui: &Context, shader_config: &mut ShaderConfig) ->
                 ui.add(egui::Slider::new(&mut shader_config.temporal_adaptive_low_blend_factor, 0.0..=0.1).text("Low Blend Factor"));
                 ui.add(egui::Slider::new(&mut shader_config.temporal_adaptive_high_blend_factor, 0.0..=0.1).text("High Blend Factor"));
             }
+
+            if shader_config.first_pass == ShaderConfig::ATROUS_PASS || shader_config.second_pass == ShaderConfig::ATROUS_PASS {
+                ui.label("À-Trous Wavelet Denoising Settings");
+                ui.add(egui::Slider::new(&mut shader_config.atrous_iterations, 1..=ShaderConfig::ATROUS_MAX_ITERATIONS).text("Iterations"));
+                ui.add(egui::Slider::new(&mut shader_config.atrous_color_phi, 0.001..=2.0).logarithmic(true).text("Color Phi"));
+                ui.add(egui::Slider::new(&mut shader_config.atrous_normal_phi, 1.0..=256.0).logarithmic(true).text("Normal Phi"));
+                ui.add(egui::Slider::new(&mut shader_config.atrous_depth_phi, 0.001..=1.0).logarithmic(true).text("Depth Phi"));
+            }
             
             ui.separator();
             // Reset Button
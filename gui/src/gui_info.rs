@@ -1,6 +1,8 @@
 use egui::{Context, InnerResponse, Margin, RichText};
+use scene::{KeyAction, KeyBindings};
 
-pub fn info_gui(ui: &Context) -> InnerResponse<()> {
+/// Shows the controls with the keys of `key_bindings` and the `device_info` of the gpu, which can be copied for bug reports.
+pub fn info_gui(ui: &Context, device_info: &str, key_bindings: &KeyBindings) -> InnerResponse<()> {
 
     egui::SidePanel::left("Info")
         .frame(egui::Frame::default()
@@ -10,12 +12,19 @@ pub fn info_gui(ui: &Context) -> InnerResponse<()> {
         .show(ui, |ui| {
             ui.heading("Info");
             ui.label(RichText::new("Controlls").strong());
-            ui.label("Movement: WASD");
-            ui.label("Up/Down: Space/Shift");
             ui.label("Camera: MouseMovement+Lbutton");
-            ui.label(RichText::new("Performance/Safety").strong());
-            ui.label("Reduce Shader Setting to min:'x'");
-            ui.label(RichText::new("Exit").strong());
-            ui.label("Close Programm: 'ESC'");
+            ui.label("Capture/release mouse: Rbutton");
+            egui::Grid::new("key_bindings").num_columns(2).show(ui, |ui| {
+                for action in KeyAction::ALL {
+                    ui.label(action.description());
+                    ui.label(RichText::new(key_bindings.keys(action).join(" / ")).monospace());
+                    ui.end_row();
+                }
+            });
+            ui.label(RichText::new("GPU").strong());
+            ui.label(RichText::new(device_info).monospace());
+            if ui.button("Copy").on_hover_text("Copies the gpu info, e.g. for a bug report").clicked() {
+                ui.output_mut(|output| output.copied_text = device_info.to_string());
+            }
         })
 }
@@ -0,0 +1,191 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use egui::{Color32, Context, RichText};
+use log::{Level, Log, Metadata, Record};
+
+/// Number of messages kept by the buffer of `log_buffer`, older messages are dropped.
+pub const LOG_BUFFER_CAPACITY: usize = 500;
+
+/// A message captured by a `LogBuffer`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogEntry {
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{} {}] {}", self.level, self.target, self.message)
+    }
+}
+
+/// Ring buffer of the most recent log messages, shown by `log_gui`.
+///
+/// Clones share the same messages, so the logger and the GUI can each hold one.
+#[derive(Debug, Clone)]
+pub struct LogBuffer {
+    entries: Arc<Mutex<VecDeque<LogEntry>>>,
+    capacity: usize,
+}
+
+impl LogBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    /// Adds a message, dropping the oldest one if the buffer is full.
+    pub fn push(&self, entry: LogEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+        if let Ok(mut entries) = self.entries.lock() {
+            if entries.len() == self.capacity {
+                entries.pop_front();
+            }
+            entries.push_back(entry);
+        }
+    }
+
+    /// The buffered messages, oldest first.
+    pub fn entries(&self) -> Vec<LogEntry> {
+        self.entries.lock().map(|entries| entries.iter().cloned().collect()).unwrap_or_default()
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.clear();
+        }
+    }
+
+    /// All buffered messages, one per line, e.g. for the clipboard.
+    pub fn to_text(&self) -> String {
+        self.entries().iter().map(|entry| format!("{}\n", entry)).collect()
+    }
+}
+
+/// The buffer of the application log, filled by a `BufferLogger` that is installed at startup.
+pub fn log_buffer() -> LogBuffer {
+    static LOG_BUFFER: OnceLock<LogBuffer> = OnceLock::new();
+    LOG_BUFFER.get_or_init(|| LogBuffer::new(LOG_BUFFER_CAPACITY)).clone()
+}
+
+/// Logger that writes every message to an inner logger (e.g. `env_logger` for stderr) and to a `LogBuffer`.
+///
+/// Only the messages enabled by the inner logger are buffered, so the panel shows the same messages as stderr.
+pub struct BufferLogger {
+    inner: Box<dyn Log>,
+    buffer: LogBuffer,
+}
+
+impl BufferLogger {
+    pub fn new(inner: Box<dyn Log>, buffer: LogBuffer) -> Self {
+        Self { inner, buffer }
+    }
+}
+
+impl Log for BufferLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        self.inner.log(record);
+        self.buffer.push(LogEntry {
+            level: record.level(),
+            target: record.target().to_string(),
+            message: record.args().to_string(),
+        });
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Shows the buffered log messages in a collapsible window, closed with its close button.
+///
+/// The messages can be copied, e.g. for a bug report, or cleared.
+pub fn log_gui(ui: &Context, open: &mut bool, buffer: &LogBuffer) {
+    egui::Window::new("Log")
+        .open(open)
+        .collapsible(true)
+        .default_width(600.0)
+        .default_height(300.0)
+        .show(ui, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Copy").on_hover_text("Copies all messages, e.g. for a bug report").clicked() {
+                    ui.output_mut(|output| output.copied_text = buffer.to_text());
+                }
+                if ui.button("Clear").clicked() {
+                    buffer.clear();
+                }
+            });
+            ui.separator();
+            egui::ScrollArea::vertical()
+                .auto_shrink([false, false])
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    for entry in buffer.entries() {
+                        let color = match entry.level {
+                            Level::Error => Color32::from_rgb(255, 80, 80),
+                            Level::Warn => Color32::from_rgb(255, 165, 0),
+                            Level::Info => Color32::WHITE,
+                            Level::Debug | Level::Trace => Color32::GRAY,
+                        };
+                        ui.label(RichText::new(entry.to_string()).monospace().color(color));
+                    }
+                });
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::LevelFilter;
+
+    fn entry(message: &str) -> LogEntry {
+        LogEntry { level: Level::Warn, target: "test".to_string(), message: message.to_string() }
+    }
+
+    #[test]
+    fn test_log_buffer_capacity() {
+        let buffer = LogBuffer::new(3);
+        for i in 0..5 {
+            buffer.push(entry(&i.to_string()));
+        }
+        let messages: Vec<String> = buffer.entries().into_iter().map(|entry| entry.message).collect();
+        assert_eq!(messages, ["2", "3", "4"]);
+        assert_eq!(buffer.to_text(), "[WARN test] 2\n[WARN test] 3\n[WARN test] 4\n");
+
+        buffer.clear();
+        assert!(buffer.entries().is_empty());
+        LogBuffer::new(0).push(entry("dropped"));
+    }
+
+    struct InfoLogger;
+
+    impl Log for InfoLogger {
+        fn enabled(&self, metadata: &Metadata) -> bool {
+            metadata.level() <= LevelFilter::Info
+        }
+        fn log(&self, _record: &Record) {}
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn test_buffer_logger_filter() {
+        let buffer = LogBuffer::new(10);
+        let logger = BufferLogger::new(Box::new(InfoLogger), buffer.clone());
+        logger.log(&Record::builder().level(Level::Info).target("raytracing_lib").args(format_args!("BVH ready")).build());
+        logger.log(&Record::builder().level(Level::Trace).target("raytracing_lib").args(format_args!("frame")).build());
+        assert_eq!(buffer.entries(), vec![LogEntry { level: Level::Info, target: "raytracing_lib".to_string(), message: "BVH ready".to_string() }]);
+    }
+}
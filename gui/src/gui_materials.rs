@@ -0,0 +1,63 @@
+use egui::{Context, Margin};
+use scene::{AlphaMode, Material};
+
+
+/// Shows the materials panel and returns the indices of the materials that were changed this frame.
+pub fn materials_gui(ui: &Context, materials: &mut [Material]) -> Vec<usize> {
+    let mut changed_materials = Vec::new();
+
+    egui::SidePanel::left("Materials")
+        .frame(egui::Frame::default()
+            .fill(egui::Color32::from_black_alpha(200))
+            .inner_margin(Margin{ left:10.0, right:10.0, top:10.0, bottom:10.0}))
+        .show(ui, |ui| {
+            ui.heading("Materials");
+
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for (index, material) in materials.iter_mut().enumerate() {
+                    egui::CollapsingHeader::new(format!("Material {}", index))
+                        .id_source(index)
+                        .show(ui, |ui| {
+                            let mut changed = false;
+
+                            // Colors are stored as vec4 for the shader, only rgb is editable
+                            let mut albedo = [material.albedo[0], material.albedo[1], material.albedo[2]];
+                            let mut attenuation = [material.attenuation[0], material.attenuation[1], material.attenuation[2]];
+                            ui.horizontal(|ui| {
+                                changed |= ui.color_edit_button_rgb(&mut albedo).changed();
+                                ui.label("Color");
+                            });
+                            ui.horizontal(|ui| {
+                                changed |= ui.color_edit_button_rgb(&mut attenuation).changed();
+                                ui.label(if material.ior > 0.0 { "Absorption" } else { "Attenuation" });
+                            });
+                            material.albedo[..3].copy_from_slice(&albedo);
+                            material.attenuation[..3].copy_from_slice(&attenuation);
+
+                            changed |= ui.add(egui::Slider::new(&mut material.roughness, 0.0..=1.0).text("Roughness")).changed();
+                            changed |= ui.add(egui::Slider::new(&mut material.metallic, 0.0..=1.0).text("Metallic")).changed();
+                            changed |= ui.add(egui::Slider::new(&mut material.clearcoat, 0.0..=1.0).text("Clearcoat")).changed();
+                            if material.clearcoat > 0.0 {
+                                changed |= ui.add(egui::Slider::new(&mut material.clearcoat_roughness, 0.0..=1.0).text("Clearcoat Roughness")).changed();
+                            }
+                            changed |= ui.add(egui::Slider::new(&mut material.emission, 0.0..=100.0).text("Emission").logarithmic(true)).changed();
+                            changed |= ui.add(egui::Slider::new(&mut material.ior, 0.0..=3.0).text("IOR (0 = opaque)")).changed();
+                            let mut double_sided = material.is_double_sided();
+                            if ui.checkbox(&mut double_sided, "Double-sided").changed() {
+                                *material = material.with_double_sided(double_sided);
+                                changed = true;
+                            }
+                            if let AlphaMode::Mask { .. } = material.alpha() {
+                                changed |= ui.add(egui::Slider::new(&mut material.alpha_cutoff, 0.0..=1.0).text("Alpha Cutoff")).changed();
+                            }
+
+                            if changed {
+                                changed_materials.push(index);
+                            }
+                        });
+                }
+            });
+        });
+
+    changed_materials
+}
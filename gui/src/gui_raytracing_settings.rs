@@ -1,5 +1,5 @@
 use egui::{Context, InnerResponse, Margin, RichText};
-use scene::ShaderConfig;
+use scene::{DebugView, JitterMode, SamplingPattern, ShaderConfig};
 use crate::GuiConfig;
 
 
@@ -19,15 +19,83 @@ pub fn raytracing_settings_gui(ui: &Context, gui_config: &mut GuiConfig, shader_
                 ui.checkbox(&mut gui_config.frame_limit_unlimited, "Unlimited");
                 ui.add(egui::Slider::new(&mut gui_config.frame_limit, 1..=240).text("FPS"));
             });
+            // Vsync lets the GPU wait for the display instead of rendering frames that are never shown
+            egui::ComboBox::from_label("Present Mode")
+                .selected_text(present_mode_name(gui_config.present_mode))
+                .show_ui(ui, |ui| {
+                    for present_mode in gui_config.present_modes.clone() {
+                        ui.selectable_value(&mut gui_config.present_mode, present_mode, present_mode_name(present_mode));
+                    }
+                });
 
             ui.add(egui::Slider::new(&mut shader_config.ray_max_bounces, 0..=200).text("Max Bounces").logarithmic(true));
             ui.add(egui::Slider::new(&mut shader_config.ray_samples_per_pixel, 1..=50).text("Samples per Pixel"));
             ui.add(egui::Slider::new(&mut shader_config.ray_max_ray_distance, 1.0..=100_000.0).text("Max Ray Distance").logarithmic(true));
+            ui.add(egui::Slider::new(&mut shader_config.ray_bias, ShaderConfig::RAY_BIAS_RANGE).text("Ray Bias").logarithmic(true))
+                .on_hover_text("Distance bounced rays start above the surface. Increase it for large scenes if surfaces show dark speckles \
+                    (shadow acne), decrease it for small scenes if contact shadows and thin objects disappear");
+            ui.separator();
+            // Adaptive sampling accumulates samples while the camera is still and skips converged pixels
+            let mut adaptive_sampling_enabled: bool = shader_config.adaptive_sampling_enabled != 0;
+            ui.checkbox(&mut adaptive_sampling_enabled, "Adaptive Sampling");
+            shader_config.adaptive_sampling_enabled = if adaptive_sampling_enabled { 1 } else { 0 };
+            ui.add_enabled(adaptive_sampling_enabled,
+                egui::Slider::new(&mut shader_config.adaptive_variance_threshold, 0.000_001..=0.01).text("Variance Threshold").logarithmic(true));
+            let mut mis_enabled: bool = shader_config.mis_enabled != 0;
+            ui.checkbox(&mut mis_enabled, "Multiple Importance Sampling")
+                .on_hover_text("Weights light samples and hits of emissive triangles, reduces fireflies of small lights");
+            shader_config.mis_enabled = if mis_enabled { 1 } else { 0 };
+            let mut roughness_linear: bool = shader_config.roughness_linear != 0;
+            ui.checkbox(&mut roughness_linear, "Linear Roughness")
+                .on_hover_text("The roughness of the materials is used as the width of the reflections as is. \
+                    Off, it is perceptual roughness like in glTF and squared first");
+            shader_config.roughness_linear = if roughness_linear { 1 } else { 0 };
+            ui.add(egui::Slider::new(&mut shader_config.firefly_clamp, 1.0..=ShaderConfig::FIREFLY_CLAMP_OFF).text("Firefly Clamp").logarithmic(true))
+                .on_hover_text("Maximum brightness of a single sample. Low values remove bright speckles, \
+                    but also darken small lights and caustics, so the image no longer converges to the correct result");
+            // Low-discrepancy patterns place the rays more evenly inside the pixels than random offsets
+            let mut sampling_pattern = SamplingPattern::from_u32(shader_config.sampling_pattern);
+            egui::ComboBox::from_label("Sampling Pattern")
+                .selected_text(sampling_pattern.name())
+                .show_ui(ui, |ui| {
+                    for pattern in SamplingPattern::ALL {
+                        ui.selectable_value(&mut sampling_pattern, pattern, pattern.name());
+                    }
+                });
+            shader_config.sampling_pattern = sampling_pattern as u32;
+            let mut jitter_mode = JitterMode::from_u32(shader_config.jitter_mode);
+            egui::ComboBox::from_label("Jitter")
+                .selected_text(jitter_mode.name())
+                .show_ui(ui, |ui| {
+                    for mode in JitterMode::ALL {
+                        ui.selectable_value(&mut jitter_mode, mode, mode.name());
+                    }
+                })
+                .response
+                .on_hover_text("Auto jitters the camera rays only while the camera stands still, \
+                    so moving images stay sharp and still images are anti-aliased");
+            shader_config.jitter_mode = jitter_mode as u32;
+            ui.separator();
+            ui.add(egui::Slider::new(&mut shader_config.exposure, ShaderConfig::EXPOSURE_RANGE).text("Exposure (EV)").step_by(0.1))
+                .on_hover_text("Brightens or darkens the displayed image in stops, also changed with the + and - keys");
+            ui.add(egui::Slider::new(&mut shader_config.supersample, 1..=ShaderConfig::MAX_SUPERSAMPLE).text("Supersampling"))
+                .on_hover_text("Renders at this many times the window resolution in each direction for sharper screenshots, \
+                    limited by the largest texture size of the GPU");
             ui.separator();
             ui.add(egui::Slider::new(&mut shader_config.ray_focus_distance, 0.1..=5.0).text("Focus Distance"));
             ui.add(egui::Slider::new(&mut shader_config.ray_aperture, 0.1..=0.6).text("Aperture"));
             ui.add(egui::Slider::new(&mut shader_config.ray_lens_radius, 0.0..=0.5).text("Lens Radius"));
             ui.separator();
+            // Shows a value of the first hit instead of the traced color, cycled with TAB
+            let mut debug_view = DebugView::from_u32(shader_config.debug_view);
+            egui::ComboBox::from_label("Debug View")
+                .selected_text(debug_view.name())
+                .show_ui(ui, |ui| {
+                    for view in DebugView::ALL {
+                        ui.selectable_value(&mut debug_view, view, view.name());
+                    }
+                });
+            shader_config.debug_view = debug_view as u32;
             // convert to bool
             let mut ray_debug_rand_color: bool = shader_config.ray_debug_rand_color != 0;
             let mut ray_focus_viewer_visible: bool = shader_config.ray_focus_viewer_visible != 0;
@@ -35,7 +103,8 @@ pub fn raytracing_settings_gui(ui: &Context, gui_config: &mut GuiConfig, shader_
             let mut ray_debug_bvh_bounding_color: bool = shader_config.ray_debug_bvh_bounding_color != 0;
 
             ui.checkbox(&mut ray_debug_rand_color, "Debug Random Colors");
-            ui.checkbox(&mut ray_focus_viewer_visible,"Focus Viewer On/Off");
+            ui.checkbox(&mut ray_focus_viewer_visible,"Focus Viewer On/Off")
+                .on_hover_text("Tints the surfaces at the focus distance red");
             ui.checkbox(&mut ray_debug_bvh_bounding_box, "Debug BVH Bounding Box");
             ui.checkbox(&mut ray_debug_bvh_bounding_color, "Debug BVH Bounding Color");
 
@@ -84,4 +153,16 @@ pub fn raytracing_settings_gui(ui: &Context, gui_config: &mut GuiConfig, shader_
             }
             
         })
-}
\ No newline at end of file
+}
+
+/// Name of a present mode as shown in the GUI.
+pub fn present_mode_name(present_mode: wgpu::PresentMode) -> &'static str {
+    match present_mode {
+        wgpu::PresentMode::Fifo => "VSync (Fifo)",
+        wgpu::PresentMode::FifoRelaxed => "Adaptive VSync (FifoRelaxed)",
+        wgpu::PresentMode::Immediate => "Uncapped (Immediate)",
+        wgpu::PresentMode::Mailbox => "Uncapped (Mailbox)",
+        wgpu::PresentMode::AutoVsync => "Auto VSync",
+        wgpu::PresentMode::AutoNoVsync => "Auto Uncapped",
+    }
+}
@@ -2,19 +2,38 @@ use std::collections::VecDeque;
 use egui::{Align2, Context};
 use egui_plot::{AxisHints, GridMark, PlotPoints};
 use std::ops::RangeInclusive;
-use scene::ShaderConfig;
+use scene::{Background, KeyBindings, Material, ShaderConfig};
 
 use crate::gui_raytracing_settings::raytracing_settings_gui;
 use crate::gui_denoising_settings::denoising_settings_gui;
 use crate::gui_info::info_gui;
+use crate::gui_materials::materials_gui;
+use crate::gui_background::background_gui;
+use crate::gui_log::{log_gui, log_buffer};
 
 
 pub struct GuiConfig {
     pub ray_settings_open: bool,
     pub denoise_settings_open: bool,
     pub info_open: bool,
+    pub materials_open: bool,
+    pub background_open: bool,
+    /// The log window with the messages of `log_buffer`
+    pub log_open: bool,
+    /// A background texture was loaded, so the HDRI mode can be selected
+    pub background_texture_loaded: bool,
     pub frame_limit: u32,
-    pub frame_limit_unlimited: bool
+    pub frame_limit_unlimited: bool,
+    /// Present mode selected in the GUI, `Fifo` is vsync
+    pub present_mode: wgpu::PresentMode,
+    /// Present modes supported by the surface, only these are offered in the GUI
+    pub present_modes: Vec<wgpu::PresentMode>,
+    /// Rendering is paused, toggled by the pause button and the window focus
+    pub paused: bool,
+    /// Adapter, limits and features of the gpu, shown in the info window
+    pub device_info: String,
+    /// Keyboard controls listed in the info window
+    pub key_bindings: KeyBindings,
 }
 
 impl Default for GuiConfig {
@@ -23,14 +42,27 @@ impl Default for GuiConfig {
             ray_settings_open: false,
             denoise_settings_open: false,
             info_open: false,
+            materials_open: false,
+            background_open: false,
+            log_open: false,
+            background_texture_loaded: false,
             frame_limit: 60,
-            frame_limit_unlimited: false
+            frame_limit_unlimited: false,
+            present_mode: wgpu::PresentMode::Fifo,
+            present_modes: vec![wgpu::PresentMode::Fifo],
+            paused: false,
+            device_info: String::new(),
+            key_bindings: KeyBindings::default(),
         }
     }
 }
 
 
-pub fn gui(ui: &Context, fps: &VecDeque<f32>, gui_config: &mut GuiConfig, shader_config: &mut ShaderConfig) {
+/// Draws the complete GUI and returns the indices of the materials that were edited this frame
+/// and whether the background was changed.
+///
+/// Changes of the background are written to `background` directly.
+pub fn gui(ui: &Context, fps: &VecDeque<f32>, gui_config: &mut GuiConfig, shader_config: &mut ShaderConfig, materials: &mut [Material], background: &mut Background) -> (Vec<usize>, bool) {
     // Top bar
     egui::TopBottomPanel::top("top").show(ui, |ui| {
         ui.horizontal(|ui| {
@@ -45,10 +77,30 @@ pub fn gui(ui: &Context, fps: &VecDeque<f32>, gui_config: &mut GuiConfig, shader
             }
             ui.separator();
             
+            if ui.button("Materials").clicked() {
+                gui_config.materials_open = !gui_config.materials_open;
+            }
+            ui.separator();
+
+            if ui.button("Background").clicked() {
+                gui_config.background_open = !gui_config.background_open;
+            }
+            ui.separator();
+
             if ui.button("Info").clicked() {
                 gui_config.info_open = !gui_config.info_open;
             }
             ui.separator();
+
+            if ui.button("Log").clicked() {
+                gui_config.log_open = !gui_config.log_open;
+            }
+            ui.separator();
+
+            if ui.button(if gui_config.paused { "Resume" } else { "Pause" }).clicked() {
+                gui_config.paused = !gui_config.paused;
+            }
+            ui.separator();
         });
     });
 
@@ -129,8 +181,21 @@ pub fn gui(ui: &Context, fps: &VecDeque<f32>, gui_config: &mut GuiConfig, shader
     if gui_config.denoise_settings_open {
         denoising_settings_gui(ui, shader_config);
     }
+    let mut changed_materials = Vec::new();
+    if gui_config.materials_open {
+        changed_materials = materials_gui(ui, materials);
+    }
+    let mut background_changed = false;
+    if gui_config.background_open {
+        background_changed = background_gui(ui, background, gui_config.background_texture_loaded);
+    }
     if gui_config.info_open {
-        info_gui(ui);
+        info_gui(ui, &gui_config.device_info, &gui_config.key_bindings);
     }
+    if gui_config.log_open {
+        log_gui(ui, &mut gui_config.log_open, &log_buffer());
+    }
+
+    (changed_materials, background_changed)
 
 }
\ No newline at end of file
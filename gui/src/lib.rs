@@ -8,7 +8,10 @@
 //! - `gui_structure`: Defines the [`GuiConfig`](gui/src/gui_structure.rs) struct which holds the configuration for the GUI and the `gui` function which is the main function for rendering the GUI.
 //! - `gui_raytracing_settings`: Contains the [`raytracing_settings_gui`](gui/src/gui_raytracing_settings.rs) function which renders the GUI for the raytracing settings.
 //! - `gui_denoising_settings`: Contains the [`denoising_settings_gui`](gui/src/gui_denoising_settings.rs) function which renders the GUI for the denoising settings.
+//! - `gui_materials`: Contains the [`materials_gui`](gui/src/gui_materials.rs) function which renders the material editor.
+//! - `gui_background`: Contains the [`background_gui`](gui/src/gui_background.rs) function which renders the background settings.
 //! - `gui_info`: Contains the [`info_gui`](gui/src/gui_info.rs) function which renders the general information window.
+//! - `gui_log`: Contains the [`log_gui`](gui/src/gui_log.rs) function which renders the log window and the [`BufferLogger`](gui/src/gui_log.rs) that fills it.
 //!
 //! ## Usage
 //!
@@ -30,17 +33,26 @@
 //! - FPS counter with color coding based on performance.
 //! - Raytracing settings GUI for adjusting various raytracing parameters.
 //! - Denoising settings GUI for adjusting various denoising parameters.
+//! - Material editor for live changes of the loaded materials.
+//! - Background settings to switch between sky, HDRI and a solid color and to change the intensity.
 //! - Frame limiting with an option for unlimited framerate.
 //! - General Informaton window with information how to use the application.
+//! - Log window with the recent log messages, e.g. shader errors and missing textures.
 
 mod gui;
 mod gui_structure;
 mod gui_raytracing_settings;
 mod gui_denoising_settings;
 mod gui_info;
+mod gui_materials;
+mod gui_background;
+mod gui_log;
 
 pub use gui::EguiRenderer;
 pub use gui_structure::{GuiConfig, gui};
 pub use gui_raytracing_settings::raytracing_settings_gui;
 pub use gui_denoising_settings::denoising_settings_gui;
 pub use gui_info::info_gui;
+pub use gui_materials::materials_gui;
+pub use gui_background::background_gui;
+pub use gui_log::{log_gui, log_buffer, BufferLogger, LogBuffer, LogEntry, LOG_BUFFER_CAPACITY};
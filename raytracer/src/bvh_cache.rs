@@ -0,0 +1,145 @@
+//! Saves the BVH of a scene to disk, so large models don't have to be rebuilt on every launch (`[bvh] cache` in the config).
+//!
+//! The cache file is stored next to the model with a `.bvh` extension appended, e.g. `res/assets/model.glb.bvh`.
+//! It contains a hash of the bounding boxes of all primitives and the BVH settings, the BVH is rebuilt and
+//! the file overwritten as soon as the geometry or the settings change.
+
+use std::path::{Path, PathBuf};
+
+use bincode::Options;
+use rtbvh::Aabb;
+use scene::{BvhBuilder, BvhUniform, ModelPaths};
+use serde::{Deserialize, Serialize};
+
+/// Version of the file format, files written by another version are ignored and rebuilt.
+const CACHE_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct BvhCache {
+    version: u32,
+    geometry_hash: u64,
+    nodes: Vec<BvhUniform>,
+    prim_indices: Vec<[f32; 2]>,
+}
+
+/// Path of the cache file for the models of a scene, next to the first of the glTF, OBJ and SVG files.
+///
+/// Returns `None` for scenes without a model file, e.g. scenes with only spheres.
+pub fn cache_path(model_paths: &ModelPaths) -> Option<PathBuf> {
+    [&model_paths.gltf_path, &model_paths.obj_path, &model_paths.svg_path]
+        .into_iter()
+        .flatten()
+        .find(|path| !path.is_empty())
+        .map(|path| PathBuf::from(format!("{}.bvh", path)))
+}
+
+/// Hashes everything the BVH is built from: the bounding boxes and ids of the primitives and the BVH settings.
+///
+/// Uses 64 bit FNV-1a, which unlike `DefaultHasher` gives the same hash in every build of the application.
+pub fn geometry_hash(aabbs: &[Aabb], prim_ids: &[[f32; 2]], leaf_size: usize, bvh_builder: BvhBuilder) -> u64 {
+    const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+    let mut hash = FNV_OFFSET;
+    let mut write = |bytes: &[u8]| {
+        for byte in bytes {
+            hash = (hash ^ *byte as u64).wrapping_mul(FNV_PRIME);
+        }
+    };
+    write(&(leaf_size as u64).to_le_bytes());
+    write(&[bvh_builder as u8]);
+    for aabb in aabbs {
+        for value in aabb.min.to_array().into_iter().chain(aabb.max.to_array()) {
+            write(&value.to_le_bytes());
+        }
+    }
+    for value in prim_ids.iter().flatten() {
+        write(&value.to_le_bytes());
+    }
+    hash
+}
+
+/// Loads the BVH nodes and primitive indices from `path` if the file was written for the same `geometry_hash`.
+///
+/// Returns `None` if the file doesn't exist, can't be read or belongs to other geometry.
+pub fn load(path: &Path, geometry_hash: u64) -> Option<(Vec<BvhUniform>, Vec<[f32; 2]>)> {
+    let bytes = std::fs::read(path).ok()?;
+    // The same encoding as `bincode::serialize`, the limit keeps a corrupt length from allocating more than the file size
+    let options = bincode::options().with_fixint_encoding().allow_trailing_bytes().with_limit(bytes.len() as u64);
+    let cache: BvhCache = match options.deserialize(&bytes) {
+        Ok(cache) => cache,
+        Err(e) => {
+            log::warn!("Ignoring the BVH cache {}: {}", path.display(), e);
+            return None;
+        }
+    };
+    if cache.version != CACHE_VERSION || cache.geometry_hash != geometry_hash {
+        log::info!("BVH cache {} is outdated", path.display());
+        return None;
+    }
+    Some((cache.nodes, cache.prim_indices))
+}
+
+/// Writes the BVH nodes and primitive indices to `path`, replacing an existing cache file.
+///
+/// # Errors
+///
+/// Returns a message if the file can't be written.
+pub fn save(path: &Path, geometry_hash: u64, nodes: &[BvhUniform], prim_indices: &[[f32; 2]]) -> Result<(), String> {
+    let cache = BvhCache {
+        version: CACHE_VERSION,
+        geometry_hash,
+        nodes: nodes.to_vec(),
+        prim_indices: prim_indices.to_vec(),
+    };
+    let bytes = bincode::serialize(&cache).map_err(|e| format!("Could not serialize the BVH cache: {}", e))?;
+    std::fs::write(path, bytes).map_err(|e| format!("Could not write the BVH cache {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::{generate_aabbs, no_progress, setup_bvh, synthetic_triangles};
+    use scene::BvhPrimitive;
+
+    #[test]
+    fn test_bvh_cache_roundtrip() {
+        let path = std::env::temp_dir().join(format!("raytracer_bvh_cache_{}.bvh", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let triangles = synthetic_triangles(32);
+
+        // The first build writes the cache, the second one loads it
+        let built = setup_bvh(&triangles, &[], 2, BvhBuilder::Sah, Some(&path), &mut no_progress).unwrap();
+        assert!(path.exists());
+        let (primitives, prim_ids) = BvhPrimitive::collect(&triangles, &[]);
+        let hash = geometry_hash(&generate_aabbs(&primitives), &prim_ids, 2, BvhBuilder::Sah);
+        assert_eq!(load(&path, hash), Some(built.clone()));
+        assert_eq!(setup_bvh(&triangles, &[], 2, BvhBuilder::Sah, Some(&path), &mut no_progress).unwrap(), built);
+
+        // Other geometry or settings don't match the cache
+        assert!(load(&path, hash ^ 1).is_none());
+        let moved = synthetic_triangles(33);
+        let (primitives, prim_ids) = BvhPrimitive::collect(&moved, &[]);
+        assert_ne!(geometry_hash(&generate_aabbs(&primitives), &prim_ids, 2, BvhBuilder::Sah), hash);
+        let (primitives, prim_ids) = BvhPrimitive::collect(&triangles, &[]);
+        assert_ne!(geometry_hash(&generate_aabbs(&primitives), &prim_ids, 1, BvhBuilder::Sah), hash);
+        assert_ne!(geometry_hash(&generate_aabbs(&primitives), &prim_ids, 2, BvhBuilder::Locb), hash);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_bvh_cache_path() {
+        assert_eq!(cache_path(&ModelPaths::default()), None);
+        let model_paths = ModelPaths { obj_path: Some("res/assets/model.obj".to_string()), ..Default::default() };
+        assert_eq!(cache_path(&model_paths), Some(PathBuf::from("res/assets/model.obj.bvh")));
+    }
+
+    #[test]
+    fn test_bvh_cache_invalid_file() {
+        let path = std::env::temp_dir().join(format!("raytracer_bvh_cache_invalid_{}.bvh", std::process::id()));
+        std::fs::write(&path, b"not a bvh").unwrap();
+        assert!(load(&path, 0).is_none());
+        std::fs::remove_file(&path).unwrap();
+        assert!(load(&path, 0).is_none());
+    }
+}
@@ -1,9 +1,30 @@
+use std::path::Path;
+
 use image::{DynamicImage, GenericImageView};
+use rayon::prelude::*;
 use rtbvh::{Aabb, Builder, Primitive};
 use wgpu::SurfaceConfiguration;
-use scene::{Camera, CameraController, CameraUniform, Projection, Config, Textureset, 
-    load_gltf, load_obj, BvhUniform, Material, Triangle, TriangleUniform, 
-    create_texture, load_textures_from_image, scale_texture, load_hdr};
+use scene::{Camera, CameraController, CameraUniform, Projection, Config, ConfigError, Textureset, BvhBuilder,
+    load_gltf, load_obj, load_svg, ModelPaths, BvhPrimitive, BvhUniform, Material, Sphere, Triangle, TriangleUniform, 
+    create_texture, load_textures_from_image, load_texture_into_atlas, scale_texture, TextureFilter, load_hdr, load_texture, missing_texture,
+    procedural_texture, to_linear, ColorSpace, pack_atlas, TextureRegion, ATLAS_MAX_PAGE_SIZE, ATLAS_PADDING, PROCEDURAL_PREFIX,
+    blue_noise_image, precomputed_blue_noise, BLUE_NOISE_SIZE};
+
+use crate::bvh_cache;
+
+/// Step of the scene setup reported to the progress callback of `setup_tris_objects`, `setup_textures` and `setup_bvh`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadStage {
+    /// Loading the OBJ, glTF and SVG models.
+    Models,
+    /// Uploading the textures to the gpu.
+    Textures,
+    /// Building the bounding volume hierarchy of the triangles.
+    Bvh,
+}
+
+/// Progress callback that ignores all updates, for callers that don't show the loading progress.
+pub fn no_progress(_stage: LoadStage, _progress: f32) {}
 
 /// Sets up the camera for the rendering scene.
 ///
@@ -13,25 +34,31 @@ use scene::{Camera, CameraController, CameraUniform, Projection, Config, Texture
 /// # Arguments
 ///
 /// * `config` - A reference to the surface configuration which includes the width and height of the surface.
-/// * `userconfig` - A reference to the user configuration which includes the camera position, rotation, field of view (fov), and near and far clipping planes.
+/// * `userconfig` - A reference to the user configuration which includes the camera position, rotation, field of view (fov), near and far clipping planes and the roll lock.
 ///
 /// # Returns
 ///
-/// * `Camera` - The initialized camera with the position and rotation specified in the user configuration.
+/// * `Camera` - The initialized camera with the position and rotation specified in the user configuration, facing the target if one is configured.
 /// * `Projection` - The initialized projection with the width, height, fov, and near and far clipping planes specified in the configurations.
-/// * `CameraController` - The initialized camera controller with a speed of 4.0 and a sensitivity of 1.6.
+/// * `CameraController` - The initialized camera controller with a speed of 4.0, a sensitivity of 1.6 and roll locked as configured.
 /// * `CameraUniform` - The initialized camera uniform which is updated with the view projection of the camera and projection.
 ///
 pub fn setup_camera(config: &SurfaceConfiguration, userconfig: &Config) -> (Camera, Projection, CameraController, CameraUniform) {
-    let camera = Camera::new(userconfig.camera_position, 
-                                        cgmath::Deg(userconfig.camera_rotation[0]), 
-                                            cgmath::Deg(userconfig.camera_rotation[1]));
+    let camera = match userconfig.camera_target {
+        Some(target) => Camera::look_at(userconfig.camera_position.into(), target.into(), cgmath::Vector3::unit_y()),
+        None => Camera::new(userconfig.camera_position, 
+                            cgmath::Deg(userconfig.camera_rotation[0]), 
+                            cgmath::Deg(userconfig.camera_rotation[1])),
+    };
     let projection = Projection::new(config.width, 
                                                         config.height, 
                                                         cgmath::Deg(userconfig.camera_fov),
                                                          userconfig.camera_near_far[0], 
                                                          userconfig.camera_near_far[1]);
-    let camera_controller = CameraController::new(4.0, 1.6);
+    let mut camera_controller = CameraController::new(4.0, 1.6);
+    camera_controller.set_lock_roll(userconfig.camera_lock_roll);
+    camera_controller.set_scroll_mode(userconfig.camera_scroll_mode);
+    camera_controller.set_key_bindings(userconfig.key_bindings.clone());
 
     let mut camera_uniform = CameraUniform::new();
     camera_uniform.update_view_proj(&camera, &projection);
@@ -39,17 +66,33 @@ pub fn setup_camera(config: &SurfaceConfiguration, userconfig: &Config) -> (Came
     return (camera, projection, camera_controller, camera_uniform)
 }
 
+/// Returns `true` if the config has triangles to load, from a 3D model or meshes built in code.
+///
+/// Without any, `setup_tris_objects` returns a placeholder triangle, since the gpu buffer can't be empty.
+pub fn has_triangles(userconfig: &Config) -> bool {
+    let model_paths = &userconfig.model_paths;
+    model_paths.obj_path.is_some() || model_paths.gltf_path.is_some() || model_paths.svg_path.is_some() || userconfig.meshes.is_some()
+}
+
 /// Sets up the triangle objects for the rendering scene.
 ///
 /// This function initializes a list of triangles
 /// It loads materials, textures and the triangle data from the .obj and .gltf files if specified in the configuration.
+/// Meshes added in code with the `SceneBuilder` are appended after the model triangles, their ids refer to the config materials and textures.
+/// Degenerate (zero-area) triangles are dropped with a warning before they reach the BVH builder.
 /// These get stored in the respective vectors passed as arguments.
 ///
+/// The config materials and textures have to be added before calling this function. The buffers are then ordered:
+/// materials: config, OBJ, GLTF; textures: config, GLTF. The GLTF ids are offset by the number of entries before them,
+/// and triangles referencing a material or texture that doesn't exist are reset with a warning.
+///
 /// # Arguments
 ///
 /// * `userconfig` - A user configuration which includes the paths to the .obj and .gltf files, the materials and textures to be used.
 /// * `materials` - A mutable reference to the vector of materials to which the user-defined materials will be added.
 /// * `textures` - A mutable reference to the vector of textures to which the user-defined textures will be added.
+/// * `warnings` - The invalid material and texture ids of the triangles are added to it, see `validate_scene`.
+/// * `progress` - Called with `LoadStage::Models` and the progress between 0.0 and 1.0 after each model type, see `no_progress`.
 ///
 /// # Returns
 ///
@@ -57,7 +100,10 @@ pub fn setup_camera(config: &SurfaceConfiguration, userconfig: &Config) -> (Came
 /// * `Vec<TriangleUniform>` - The list of triangle uniforms created from the triangles in a GPU friendly format.
 /// * `Config` - The original user configuration.
 ///
-pub fn setup_tris_objects(userconfig: Config, materials: &mut Vec<Material>, textures: &mut Vec<DynamicImage>) -> (Vec<Triangle>, Vec<TriangleUniform>, Config) {
+/// # Errors
+///
+/// Returns a `SceneWarning::MissingAsset` for the first model file that can't be loaded.
+pub fn setup_tris_objects(userconfig: Config, materials: &mut Vec<Material>, textures: &mut Vec<DynamicImage>, warnings: &mut Vec<SceneWarning>, progress: &mut dyn FnMut(LoadStage, f32)) -> Result<(Vec<Triangle>, Vec<TriangleUniform>, Config), SceneWarning> {
     let gltf_path = userconfig.model_paths.gltf_path.clone();
     let obj_path = userconfig.model_paths.obj_path.clone();
     let obj_material_id = match userconfig.model_paths.obj_material_id {
@@ -68,27 +114,53 @@ pub fn setup_tris_objects(userconfig: Config, materials: &mut Vec<Material>, tex
     let mut triangles: Vec<Triangle> = Vec::new();
     let mut triangles_uniform: Vec<TriangleUniform> = Vec::new();
 
-    let are_paths_empty: bool = obj_path.is_none() && gltf_path.is_none();
-
-    if are_paths_empty {
+    progress(LoadStage::Models, 0.0);
+    if !has_triangles(&userconfig) {
         // Push Triangle with empty flag to avoid driver crash since the buffer can't be empty
         triangles_uniform.push(TriangleUniform::empty());
         triangles.push(Triangle::empty());
     } else {
-        load_obj_file(&mut triangles, materials, obj_path, obj_material_id);
-        load_gltf_file(&mut triangles, materials, textures, gltf_path);
-        // Convert Triangles in a GPU friendly format (no complex data types because of the C interface limits)
-        triangles_uniform = triangles.iter().map(|triangle| TriangleUniform::new(*triangle)).collect();
+        load_obj_file(&mut triangles, materials, obj_path, obj_material_id)?;
+        progress(LoadStage::Models, 0.25);
+        load_gltf_file(&mut triangles, materials, textures, gltf_path)?;
+        progress(LoadStage::Models, 0.5);
+        load_svg_file(&mut triangles, &userconfig.model_paths)?;
+        progress(LoadStage::Models, 0.75);
+        // Meshes built in code reference the config materials like the OBJ file
+        if let Some(meshes) = &userconfig.meshes {
+            log::info!("Mesh Triangle count: {}", meshes.len());
+            triangles.extend_from_slice(meshes);
+        }
+
+        // A scene without materials gets material 0 from `add_placeholder_material` afterwards
+        warnings.extend(validate_scene(&mut triangles, &mut [], materials.len(), textures.len()));
+
+        // Zero-area triangles can't be hit and break the SAH binning of the BVH builder
+        let triangle_count = triangles.len();
+        triangles.retain(|triangle| !triangle.is_degenerate());
+        if triangles.len() < triangle_count {
+            log::warn!("Dropped {} degenerate (zero-area) triangles", triangle_count - triangles.len());
+        }
+
+        if triangles.is_empty() {
+            // Same as without models, the buffer can't be empty
+            triangles_uniform.push(TriangleUniform::empty());
+            triangles.push(Triangle::empty());
+        } else {
+            // Convert Triangles in a GPU friendly format (no complex data types because of the C interface limits)
+            triangles_uniform = triangles.iter().map(|triangle| TriangleUniform::new(*triangle)).collect();
+        }
     }
+    progress(LoadStage::Models, 1.0);
 
 
-    (triangles, triangles_uniform, userconfig)
+    Ok((triangles, triangles_uniform, userconfig))
 }
 
 /// Adds materials from the user configuration to the materials vector.
 ///
 /// This function checks if there are any user-defined materials in the configuration. If there are, it appends them to the existing materials vector.
-/// If there are no user-defined materials, it prints a message indicating that no materials were found in the configuration.
+/// If there are no user-defined materials, it logs a message indicating that no materials were found in the configuration.
 ///
 /// # Arguments
 ///
@@ -98,21 +170,42 @@ pub fn setup_tris_objects(userconfig: Config, materials: &mut Vec<Material>, tex
 ///
 /// # Output
 ///
-/// Prints the number of materials in the configuration after the user-defined materials have been added.
-/// If there are no materials in the configuration, it prints a message indicating that no materials were found.
+/// Logs the number of materials in the configuration after the user-defined materials have been added.
+/// If there are no materials in the configuration, it logs a message indicating that no materials were found.
 pub fn add_materials_from_config(materials: &mut Vec<Material>, user_materials: &Option<Vec<Material>>) {
     if let Some(user_materials) = user_materials {
         materials.append(&mut user_materials.clone());
     } else {
-        println!("No materials in config");
+        log::info!("No materials in config");
+    }
+    log::info!("Config Material count: {}", materials.len());
+}
+
+/// Adds a default material if the scene has none, because the material buffer can't be empty.
+///
+/// The placeholder triangle of a scene without models uses material 0, so the empty scene only shows the background.
+pub fn add_placeholder_material(materials: &mut Vec<Material>) {
+    if materials.is_empty() {
+        log::info!("No materials in the scene, adding a default material");
+        materials.push(Material::default());
+    }
+}
+
+/// Returns the spheres of the config, or a single sphere with a radius of 0 if there are none.
+///
+/// The sphere buffer can't be empty, the placeholder is left out of the BVH, see `BvhPrimitive::collect`.
+pub fn setup_spheres(userconfig: &Config) -> Vec<Sphere> {
+    match &userconfig.spheres {
+        Some(spheres) if !spheres.is_empty() => spheres.clone(),
+        _ => vec![Sphere::empty()],
     }
-    println!("Config Material count: {}", materials.len());
 }
 
 /// Adds textures from the user configuration to the textures vector.
 ///
 /// This function checks if there are any user-defined textures in the configuration. If there are, it loads them and appends them to the existing textures vector.
-/// If there are no user-defined textures, it prints a message indicating that no textures were found in the configuration.
+/// If there are no user-defined textures, it logs a message indicating that no textures were found in the configuration.
+/// Texture paths ending in `.hdr` or `.exr` are loaded as float images, so emissive maps can hold values above 1.0.
 ///
 /// # Arguments
 ///
@@ -122,94 +215,126 @@ pub fn add_materials_from_config(materials: &mut Vec<Material>, user_materials:
 ///
 /// # Output
 ///
-/// Prints the number of textures in the configuration after the user-defined textures have been added.
-/// If there are no textures in the configuration, it prints a message indicating that no textures were found.
-/// If there is an error loading a texture file, it prints an error message and exits the program.
-pub fn add_textures_from_config(textures: &mut Vec<DynamicImage>, user_texturesets: &Option<Vec<Textureset>>) {
+/// Logs the number of textures in the configuration after the user-defined textures have been added.
+/// If there are no textures in the configuration, it logs a message indicating that no textures were found.
+/// If a texture file can't be loaded, it adds a `SceneWarning::MissingAsset` to `warnings` and the magenta missing texture placeholder to `textures`.
+/// Paths like `"procedural:checker?squares=8"` generate a texture instead of loading a file (checker, uv_grid or solid).
+/// Diffuse textures are decoded from sRGB to linear unless the textureset sets them linear, normal and roughness maps stay as they are.
+/// The textures of a set are added as diffuse, roughness and normal, see `Textureset::texture_ids`.
+/// The textures of all sets are decoded in parallel, see `decode_textures`.
+///
+/// Returns the scaling filter of each added texture, in the same order, for `setup_textures`.
+pub fn add_textures_from_config(textures: &mut Vec<DynamicImage>, user_texturesets: &Option<Vec<Textureset>>, warnings: &mut Vec<SceneWarning>) -> Vec<TextureFilter> {
+    let mut filters = Vec::new();
     if let Some(user_texturesets) = user_texturesets { 
-        for user_textureset in user_texturesets {
-            //load diffuse, normal and roughness textures
-            if let Some(diffuse_path) = &user_textureset.diffuse_path {
-                let diffuse_texture = match image::open(diffuse_path) {
-                    Err(error) => {
-                        eprintln!("Error loading texture file: {:?}", error);
-                        std::process::exit(1);
-                    }
-                    Ok(data) => data,
-                };
-                textures.push(diffuse_texture);
-            }
-            if let Some(normal_path) = &user_textureset.normal_path {
-                let normal_texture = match image::open(normal_path) {
-                    Err(error) => {
-                        eprintln!("Error loading texture file: {:?}", error);
-                        std::process::exit(1);
-                    }
-                    Ok(data) => data,
-                };
-                textures.push(normal_texture);
-            }
-            if let Some(roughness_path) = &user_textureset.roughness_path {
-                let roughness_texture = match image::open(roughness_path) {
-                    Err(error) => {
-                        eprintln!("Error loading texture file: {:?}", error);
-                        std::process::exit(1);
-                    }
-                    Ok(data) => data,
-                };
-                textures.push(roughness_texture);
+        let mut slots = Vec::new();
+        for (index, user_textureset) in user_texturesets.iter().enumerate() {
+            log::info!("Textureset {} has the texture ids {:?} (diffuse, roughness, normal)", index, user_textureset.texture_ids((textures.len() + slots.len()) as i32));
+            //load diffuse, roughness and normal textures, in the order of the texture ids of spheres and triangles
+            for (path, color_space) in user_textureset.slots() {
+                if let Some(path) = path {
+                    slots.push((path, color_space));
+                    filters.push(user_textureset.filter);
+                }
             }
         }
+        // The placeholder keeps the slot, so the ids of the following textures stay the same
+        textures.extend(decode_textures(&slots).into_iter().map(|texture| texture.unwrap_or_else(|warning| {
+            warnings.push(warning);
+            missing_texture()
+        })));
     } else {
-        println!("No textures in config");
+        log::info!("No textures in config");
     }
-    println!("Config Texture count: {}", textures.len());
+    log::info!("Config Texture count: {}", textures.len());
+    filters
+}
+
+/// Loads the texture files on all cores, see `decode_texture`.
+///
+/// The textures are returned in the order of `slots`, so the texture ids don't depend on which file is decoded first.
+fn decode_textures(slots: &[(&str, ColorSpace)]) -> Vec<Result<DynamicImage, SceneWarning>> {
+    slots.par_iter().map(|&(path, color_space)| decode_texture(path, color_space)).collect()
+}
+
+/// Loads a texture file and converts it to linear colors, or returns a `SceneWarning::MissingAsset` if it can't be loaded.
+///
+/// Paths starting with `procedural:` are generated instead, see `scene::procedural_texture`.
+fn decode_texture(path: &str, color_space: ColorSpace) -> Result<DynamicImage, SceneWarning> {
+    let texture = if path.starts_with(PROCEDURAL_PREFIX) {
+        procedural_texture(path).map_err(|error| error.to_string())
+    } else {
+        load_texture(path).map_err(|error| error.to_string())
+    };
+    texture
+        .map(|texture| to_linear(texture, color_space))
+        .map_err(|error| SceneWarning::MissingAsset { path: path.to_string(), error })
 }
 
 /// Loads an OBJ file and appends the triangles and materials to the provided vectors.
 ///
-/// This function takes an optional path to an OBJ file. If the path is `None` or an empty string, it returns early or prints a message indicating that no path was provided.
-/// If the path is valid, it attempts to load the OBJ file. If the loading fails, it prints an error message and exits the program.
-/// If the loading succeeds, it appends the triangles and materials from the OBJ file to the provided vectors and prints the number of triangles loaded.
+/// This function takes an optional path to an OBJ file. If the path is `None` or an empty string, it returns early or logs a message indicating that no path was provided.
+/// If the path is valid, it attempts to load the OBJ file. If the loading fails, it returns the error.
+/// If the loading succeeds, it appends the triangles and materials from the OBJ file to the provided vectors and logs the number of triangles loaded.
 ///
 /// # Arguments
 ///
 /// * `triangles` - A mutable reference to the vector of triangles to which the triangles from the OBJ file will be added.
 /// * `materials` - A mutable reference to the vector of materials to which the materials from the OBJ file will be added.
 /// * `obj_path` - An optional string representing the path to the OBJ file.
+/// * `obj_material_id` - The material of all OBJ triangles. It indexes the materials already in `materials` (usually the config materials),
+///   so it isn't offset by materials the OBJ file adds.
 ///
 ///
 /// # Output
 ///
-/// Prints the number of triangles loaded from the OBJ file, or a message indicating that no OBJ path was provided.
-/// If there is an error loading the OBJ file, it prints an error message and exits the program.
+/// Logs the number of triangles loaded from the OBJ file, or a message indicating that no OBJ path was provided.
+/// If there is an error loading the OBJ file, it returns a `SceneWarning::MissingAsset` with the path and the error.
 /// If the OBJ path is empty or `None`, it returns early without loading the OBJ file.
-fn load_obj_file(triangles: &mut Vec<Triangle>, materials: &mut Vec<Material>, obj_path: Option<String>, obj_material_id: i32) {
+fn load_obj_file(triangles: &mut Vec<Triangle>, materials: &mut Vec<Material>, obj_path: Option<String>, obj_material_id: i32) -> Result<(), SceneWarning> {
     let obj_path: String = match obj_path {
         Some(obj_path) => obj_path,
-        None => return,
+        None => return Ok(()),
     };
     if obj_path != "" {
-        let (mut obj_triangles, mut obj_materials) = match load_obj(obj_path, obj_material_id) {
-            Err(error) => {
-                eprintln!("Error loading OBJ file: {:?}", error);
-                std::process::exit(1);
-            }
+        let (mut obj_triangles, mut obj_materials) = match load_obj(obj_path.clone(), obj_material_id) {
+            Err(error) => return Err(SceneWarning::MissingAsset { path: obj_path, error: error.to_string() }),
             Ok(data) => data,
         };
-        println!("OBJ Triangle count: {}", obj_triangles.len());
+        log::info!("OBJ Triangle count: {}", obj_triangles.len());
         triangles.append(&mut obj_triangles);
         materials.append(&mut obj_materials);
     } else {
-        println!("No OBJ path in config");
+        log::info!("No OBJ path in config");
+    }
+    Ok(())
+}
+
+/// Loads the polygons of an SVG file as extruded triangles and appends them to the provided vector.
+///
+/// Uses the `svg_*` settings of the model paths. Does nothing if no SVG path is set.
+/// If there is an error loading the SVG file, it returns a `SceneWarning::MissingAsset` with the path and the error.
+fn load_svg_file(triangles: &mut Vec<Triangle>, model_paths: &ModelPaths) -> Result<(), SceneWarning> {
+    let svg_path = match &model_paths.svg_path {
+        Some(svg_path) if !svg_path.is_empty() => svg_path,
+        _ => return Ok(()),
+    };
+    let material_id = model_paths.svg_material_id.unwrap_or(0);
+    match load_svg(svg_path, model_paths.svg_z, model_paths.svg_depth, material_id) {
+        Err(error) => Err(SceneWarning::MissingAsset { path: svg_path.clone(), error: error.to_string() }),
+        Ok(mut svg_triangles) => {
+            log::info!("SVG Triangle count: {}", svg_triangles.len());
+            triangles.append(&mut svg_triangles);
+            Ok(())
+        }
     }
 }
 
 /// Loads an GLTF file and appends the triangles, materials, and textures to the provided vectors.
 /// 
-/// This function takes an optional path to a GLTF file. If the path is `None` or an empty string, it returns early or prints a message indicating that no path was provided.
-/// If the path is valid, it attempts to load the GLTF file. If the loading fails, it prints an error message and exits the program.
-/// If the loading succeeds, it appends the triangles, materials, and textures from the GLTF file to the provided vectors and prints the number of triangles loaded.
+/// This function takes an optional path to a GLTF file. If the path is `None` or an empty string, it returns early or logs a message indicating that no path was provided.
+/// If the path is valid, it attempts to load the GLTF file. If the loading fails, it returns the error.
+/// If the loading succeeds, it appends the triangles, materials, and textures from the GLTF file to the provided vectors and logs the number of triangles loaded.
 /// 
 /// # Arguments
 /// 
@@ -221,51 +346,242 @@ fn load_obj_file(triangles: &mut Vec<Triangle>, materials: &mut Vec<Material>, o
 /// 
 /// # Output
 /// 
-/// Prints the number of triangles loaded from the GLTF file, or a message indicating that no GLTF path was provided.
-/// If there is an error loading the GLTF file, it prints an error message and exits the program.
+/// Logs the number of triangles loaded from the GLTF file, or a message indicating that no GLTF path was provided.
+/// If there is an error loading the GLTF file, it returns a `SceneWarning::MissingAsset` with the path and the error.
 /// If the GLTF path is empty or `None`, it returns early without loading the GLTF file.
-fn load_gltf_file(triangles: &mut Vec<Triangle>, materials: &mut Vec<Material>, textures: &mut Vec<DynamicImage>, gltf_path: Option<String>) {
+fn load_gltf_file(triangles: &mut Vec<Triangle>, materials: &mut Vec<Material>, textures: &mut Vec<DynamicImage>, gltf_path: Option<String>) -> Result<(), SceneWarning> {
     let gltf_path: String = match gltf_path {
         Some(gltf_path) => gltf_path,
-        None => return,
+        None => return Ok(()),
     };
     if gltf_path != "" {
-        let (mut gltf_triangles, mut gltf_materials, mut gltf_textures) = match load_gltf(gltf_path, materials.len() as i32, textures.len() as i32) {
-            Err(error) => {
-                eprintln!("Error loading GLTF file: {:?}", error);
-                std::process::exit(1);
-            }
+        // The GLTF ids start after everything that is already loaded (config and OBJ)
+        let material_offset = materials.len();
+        let texture_offset = textures.len();
+        let (mut gltf_triangles, mut gltf_materials, mut gltf_textures) = match load_gltf(gltf_path.clone(), material_offset as i32, texture_offset as i32) {
+            Err(error) => return Err(SceneWarning::MissingAsset { path: gltf_path, error: error.to_string() }),
             Ok(data) => data,
         };
-        println!("GLTF Triangle count: {}", gltf_triangles.len());
-        println!("GLTF Material count: {}", gltf_materials.len());
+        let gltf_material_ids = material_offset as i32..(material_offset + gltf_materials.len()) as i32;
+        if gltf_triangles.iter().any(|triangle| !gltf_material_ids.contains(&triangle.material_id)) {
+            log::warn!("GLTF triangles reference materials outside of the GLTF material range {:?}", gltf_material_ids);
+        }
+        log::info!("GLTF Triangle count: {}", gltf_triangles.len());
+        log::info!("GLTF Material count: {}", gltf_materials.len());
         triangles.append(&mut gltf_triangles);
         materials.append(&mut gltf_materials);
         textures.append(&mut gltf_textures);
     } else {
-        println!("No GLTF path in config");
+        log::info!("No GLTF path in config");
     }
+    Ok(())
+}
+
+/// Kind of object a `SceneWarning` refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SceneObject {
+    Triangle,
+    Sphere,
+}
+
+/// An id of a scene object that doesn't reference a loaded material or texture, found by `validate_scene`,
+/// or an asset of the config that can't be loaded, found by `validate_config`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SceneWarning {
+    /// The material id is outside of the material buffer, it was reset to material 0.
+    MissingMaterial { object: SceneObject, index: usize, id: f32 },
+    /// The texture id is outside of the texture array, it was reset to -1 (no texture).
+    MissingTexture { object: SceneObject, index: usize, id: f32 },
+    /// A model, texture or HDRI file can't be read or decoded.
+    MissingAsset { path: String, error: String },
+}
+
+impl std::fmt::Display for SceneWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SceneWarning::MissingMaterial { object, index, id } =>
+                write!(f, "{:?} {} references missing material {}, using material 0", object, index, id),
+            SceneWarning::MissingTexture { object, index, id } =>
+                write!(f, "{:?} {} references missing texture {}, using no texture", object, index, id),
+            SceneWarning::MissingAsset { path, error } =>
+                write!(f, "Could not load {}: {}", path, error),
+        }
+    }
+}
+
+/// Checks a config file without rendering it, e.g. to check the scene files in CI.
+///
+/// Loads the config, its models, textures and HDRI background with the setup functions of `State::new`
+/// (`setup_tris_objects`, `setup_spheres` and `add_textures_from_config`), but without a window or a GPU device,
+/// and checks the material and texture ids of the triangles and spheres with `validate_scene`.
+/// Files that can't be loaded are reported as `SceneWarning::MissingAsset`, the renderer would fail for a
+/// missing model and show the missing texture placeholder for a missing texture.
+///
+/// # Errors
+///
+/// Returns the error of the config file itself, e.g. if it can't be read or has an invalid value.
+pub fn validate_config(path: &str) -> Result<Vec<SceneWarning>, ConfigError> {
+    Ok(validate_loaded_config(Config::new(path)?))
+}
+
+// Runs the CPU part of the scene setup of `State::new`, so the ids match the ones of the renderer
+fn validate_loaded_config(userconfig: Config) -> Vec<SceneWarning> {
+    let mut warnings = Vec::new();
+    let mut materials = Vec::new();
+    let mut textures = Vec::new();
+    add_materials_from_config(&mut materials, &userconfig.materials);
+    add_textures_from_config(&mut textures, &userconfig.textures, &mut warnings);
+
+    let mut spheres = setup_spheres(&userconfig);
+    if let Some(path) = userconfig.background_path.as_deref().filter(|path| !path.is_empty()) {
+        if let Err(error) = load_hdr(path.to_string()) {
+            warnings.push(SceneWarning::MissingAsset { path: path.to_string(), error: error.to_string() });
+        }
+    }
+    // The renderer stops at the first model that can't be loaded, the spheres are checked with the materials loaded so far
+    if let Err(warning) = setup_tris_objects(userconfig, &mut materials, &mut textures, &mut warnings, &mut no_progress) {
+        warnings.push(warning);
+    }
+
+    add_placeholder_material(&mut materials);
+    warnings.extend(validate_scene(&mut [], &mut spheres, materials.len(), textures.len()));
+    warnings
+}
+
+/// Checks that every triangle and sphere references an existing material and texture.
+///
+/// Ids outside of the buffers would make the shader read past the end of the material buffer or texture array,
+/// so invalid texture ids are reset to -1 (no texture). The shader has no fallback for a missing material,
+/// so invalid material ids are reset to material 0 instead. With a `material_count` of 0 the material ids
+/// are left as they are, the scene only gets the placeholder of `add_placeholder_material` that wgpu's
+/// clamped buffer indexing reads for every id.
+///
+/// # Returns
+///
+/// One warning per invalid id, in the order of the objects.
+pub fn validate_scene(triangles: &mut [Triangle], spheres: &mut [Sphere], material_count: usize, texture_count: usize) -> Vec<SceneWarning> {
+    let mut warnings = Vec::new();
+    for (index, triangle) in triangles.iter_mut().enumerate() {
+        if material_count > 0 && (triangle.material_id < 0 || triangle.material_id as usize >= material_count) {
+            warnings.push(SceneWarning::MissingMaterial { object: SceneObject::Triangle, index, id: triangle.material_id as f32 });
+            triangle.material_id = 0;
+        }
+        validate_texture_ids(&mut triangle.texture_ids, texture_count, SceneObject::Triangle, index, &mut warnings);
+    }
+    for (index, sphere) in spheres.iter_mut().enumerate() {
+        let material_id = sphere.material_texture_id[0];
+        if material_count > 0 && !is_valid_id(material_id, material_count) {
+            warnings.push(SceneWarning::MissingMaterial { object: SceneObject::Sphere, index, id: material_id });
+            sphere.material_texture_id[0] = 0.0;
+        }
+        validate_texture_ids(&mut sphere.material_texture_id[1..], texture_count, SceneObject::Sphere, index, &mut warnings);
+        validate_texture_ids(&mut sphere.emission_texture_id[..1], texture_count, SceneObject::Sphere, index, &mut warnings);
+    }
+    warnings
+}
+
+fn is_valid_id(id: f32, count: usize) -> bool {
+    id >= 0.0 && (id as usize) < count
+}
+
+fn validate_texture_ids(texture_ids: &mut [f32], texture_count: usize, object: SceneObject, index: usize, warnings: &mut Vec<SceneWarning>) {
+    for texture_id in texture_ids.iter_mut() {
+        if *texture_id != -1.0 && !is_valid_id(*texture_id, texture_count) {
+            warnings.push(SceneWarning::MissingTexture { object, index, id: *texture_id });
+            *texture_id = -1.0;
+        }
+    }
+}
+
+/// Collects the indices of all triangles that emit light, so the shader can sample them directly (next-event estimation).
+///
+/// A triangle is emissive if its material has an emission above 0. Triangles with a diffuse texture are skipped,
+/// because the shader uses the texture color instead of the emission for them, as are triangles with an emission
+/// texture, whose emission varies over the surface, and zero-area triangles (e.g. the placeholder of an empty scene).
+///
+/// # Returns
+///
+/// The indices of the emissive triangles in `triangles`, which may be empty.
+pub fn collect_emissive_triangles(triangles: &[Triangle], materials: &[Material]) -> Vec<u32> {
+    triangles.iter().enumerate()
+        .filter(|(_, triangle)| triangle.texture_ids[0] < 0.0 && triangle.texture_ids[3] < 0.0 && !triangle.is_degenerate())
+        .filter(|(_, triangle)| {
+            let material = usize::try_from(triangle.material_id).ok().and_then(|id| materials.get(id));
+            material.is_some_and(|material| material.emission > 0.0)
+        })
+        .map(|(index, _)| index as u32)
+        .collect()
+}
+
+/// Sums up the surface area of the triangles of every material, e.g. to find materials whose meshes are degenerate.
+///
+/// # Returns
+///
+/// The area per material id, triangles with an id outside of `0..material_count` are left out.
+pub fn surface_area_per_material(triangles: &[Triangle], material_count: usize) -> Vec<f32> {
+    let mut areas = vec![0.0; material_count];
+    for triangle in triangles {
+        if let Some(area) = usize::try_from(triangle.material_id).ok().and_then(|id| areas.get_mut(id)) {
+            *area += triangle.area();
+        }
+    }
+    areas
+}
+
+/// Drops the textures that don't fit into a texture array with `max_layers` layers.
+///
+/// The texture ids are indices into `textures`, so the first textures are kept and the ids of the others become invalid.
+/// Reset them with `validate_scene` afterwards.
+///
+/// # Returns
+///
+/// The number of dropped textures.
+pub fn limit_texture_layers(textures: &mut Vec<DynamicImage>, max_layers: u32) -> usize {
+    let max_layers = max_layers as usize;
+    if textures.len() <= max_layers {
+        return 0;
+    }
+    let dropped = textures.len() - max_layers;
+    log::warn!("The scene has {} textures, but the GPU supports only {} texture array layers. Dropping the last {} textures (ids {}..{}).",
+        textures.len(), max_layers, dropped, max_layers, textures.len() - 1);
+    textures.truncate(max_layers);
+    dropped
 }
 
 /// Sets up the textures for the application.
 ///
 /// This function takes a vector of `DynamicImage` objects, a reference to a `wgpu::Device`, a reference to a `wgpu::Queue`, and a reference to a `SurfaceConfiguration`.
-/// It creates a texture buffer, then iterates over the vector of `DynamicImage` objects, loading each image into the texture buffer.
-/// If an error occurs while loading an image, it prints an error message and exits the program.
-/// After all images have been loaded, it prints a message indicating the number of textures that have been loaded and returns the texture buffer.
+/// By default the textures keep their size and are packed into as few atlas pages as possible, see `scene::pack_atlas`.
+/// With `texture_array_layers` every texture is scaled to 1024x1024 and gets its own layer of the texture array instead.
+/// If an image can't be uploaded, it logs a warning and uses the missing texture placeholder in its slot.
+/// Textures beyond the `max_texture_array_layers` limit of the device are left out, see `limit_texture_layers`.
+/// Their ids become invalid, validate the texture ids against the number of returned regions with `validate_scene`.
+/// After all images have been loaded, it logs a message indicating the number of textures that have been loaded and returns the texture buffer
+/// together with the region of every texture, which the shader uses to find a texture by its id.
 ///
 /// # Arguments
 ///
 /// * `textures` - A vector of `DynamicImage` objects representing the textures to be loaded.
+/// * `filters` - The filter used to scale each texture, textures without one use the default nearest filter.
 /// * `device` - A reference to a `wgpu::Device`.
 /// * `queue` - A reference to a `wgpu::Queue`.
 /// * `config` - A reference to a `SurfaceConfiguration`.
+/// * `texture_array_layers` - Uses one array layer per texture instead of the atlas.
+/// * `progress` - Called with `LoadStage::Textures` and the part of the textures uploaded so far, see `no_progress`.
 ///
 ///
 /// # Output
 ///
-/// Prints the number of textures loaded.
-pub fn setup_textures(mut textures: Vec<DynamicImage>, device: &wgpu::Device, queue: &wgpu::Queue, config: &SurfaceConfiguration) -> wgpu::Texture {
+/// Logs the number of textures loaded.
+pub fn setup_textures(mut textures: Vec<DynamicImage>, filters: &[TextureFilter], device: &wgpu::Device, queue: &wgpu::Queue, config: &SurfaceConfiguration, texture_array_layers: bool, progress: &mut dyn FnMut(LoadStage, f32)) -> (wgpu::Texture, Vec<TextureRegion>) {
+    progress(LoadStage::Textures, 0.0);
+    if !texture_array_layers {
+        match setup_texture_atlas(&textures, filters, device, queue, config, progress) {
+            Ok(atlas) => return atlas,
+            Err(error) => log::warn!("Could not pack the textures into an atlas: {}. Using one array layer per texture.", error),
+        }
+    }
+
     let mut num_textureslots = textures.len() as u32;
 
     // If there are no Textures added via the config or the 3d model imports,
@@ -275,36 +591,89 @@ pub fn setup_textures(mut textures: Vec<DynamicImage>, device: &wgpu::Device, qu
         textures.push(DynamicImage::new_rgb8(1024, 1024));
         num_textureslots = 2;
     }
+    // Only reached with too many textures if the atlas couldn't be used, the caller resets the ids of the dropped textures
+    let max_layers = device.limits().max_texture_array_layers;
+    if limit_texture_layers(&mut textures, max_layers) > 0 {
+        num_textureslots = max_layers;
+    }
 
-
-    let mut textures_buffer = create_texture(&device, &config, 1024, 1024, num_textureslots);
+    let textures_buffer = create_texture(&device, &config, 1024, 1024, num_textureslots);
     let mut texture_count = 0;
-    println!("Textures ready ({})", texture_count);
+    log::info!("Textures ready ({})", texture_count);
+
+    // Scaling is done on all cores, the uploads stay on this thread in the order of the texture ids
+    let resized_textures: Vec<DynamicImage> = textures.par_iter()
+        .enumerate()
+        .map(|(i, texture)| scale_texture(texture, 1024, 1024, filters.get(i).copied().unwrap_or_default()))
+        .collect();
 
     // Add textures from config to textureset
-    for i in 0..textures.len(){        
-        let resized_img = scale_texture(&textures[i], 1024, 1024, i as i32);
-        match load_textures_from_image(&queue, textures_buffer, &resized_img, i as i32) {   //TODO: originally load_textures and broke
+    for (i, resized_img) in resized_textures.iter().enumerate() {
+        match load_textures_from_image(&queue, &textures_buffer, resized_img, i as i32) {   //TODO: originally load_textures and broke
             Err(error) => {
-                // Handle the error
-                eprintln!("Error loading texture file: {:?}", error);
-                std::process::exit(1);
+                // The slot is kept so the following texture ids stay the same
+                log::warn!("Could not upload texture {}: {}. Using the missing texture placeholder.", i, error);
+                let placeholder = scale_texture(&missing_texture(), 1024, 1024, TextureFilter::Nearest);
+                if let Err(error) = load_textures_from_image(&queue, &textures_buffer, &placeholder, i as i32) {
+                    log::warn!("Could not upload the missing texture placeholder: {}", error);
+                }
             }
-            Ok(data) => {
-                textures_buffer = data;
+            Ok(()) => {
                 texture_count += 1;
             }	
         }
+        progress(LoadStage::Textures, (i + 1) as f32 / resized_textures.len() as f32);
     }
-    println!("Textures ready ({})", num_textureslots);
+    log::info!("Textures ready ({})", num_textureslots);
 
-    return textures_buffer;
+    let regions = (0..num_textureslots).map(TextureRegion::layer).collect();
+    (textures_buffer, regions)
 }
 
-/// Sets up the Bounding Volume Hierarchy (BVH) for the given triangles.
+/// Packs the textures into atlas pages, the layers of the returned texture array.
 ///
-/// This function takes a vector of `Triangle` objects and constructs a BVH for them.
-/// It first generates Axis-Aligned Bounding Boxes (AABBs) for each triangle and then uses the `Builder` struct to construct the BVH.
+/// Textures larger than `ATLAS_MAX_PAGE_SIZE` are scaled down to fit a page, all others keep their size.
+fn setup_texture_atlas(textures: &[DynamicImage], filters: &[TextureFilter], device: &wgpu::Device, queue: &wgpu::Queue, config: &SurfaceConfiguration, progress: &mut dyn FnMut(LoadStage, f32)) -> Result<(wgpu::Texture, Vec<TextureRegion>), String> {
+    let max_size = ATLAS_MAX_PAGE_SIZE - 2 * ATLAS_PADDING;
+    let textures: Vec<DynamicImage> = textures.par_iter()
+        .enumerate()
+        .map(|(i, texture)| if texture.width() > max_size || texture.height() > max_size {
+            // Keeps the aspect ratio
+            scale_texture(texture, max_size, max_size, filters.get(i).copied().unwrap_or_default())
+        } else {
+            texture.clone()
+        })
+        .collect();
+    let sizes: Vec<(u32, u32)> = textures.iter().map(|texture| (texture.width(), texture.height())).collect();
+    let layout = pack_atlas(&sizes, ATLAS_MAX_PAGE_SIZE, ATLAS_PADDING)?;
+    let max_layers = device.limits().max_texture_array_layers;
+    if layout.page_count > max_layers {
+        return Err(format!("The atlas needs {} pages, but the GPU supports only {} texture array layers", layout.page_count, max_layers));
+    }
+
+    // A texture array with a single layer would be bound as a 2D texture, see `setup_textures`
+    let textures_buffer = create_texture(device, config, layout.page_size, layout.page_size, layout.page_count.max(2));
+    for (i, (texture, rect)) in textures.iter().zip(layout.rects.iter()).enumerate() {
+        if let Err(error) = load_texture_into_atlas(queue, &textures_buffer, texture, rect.x, rect.y, rect.page) {
+            log::warn!("Could not upload texture {}: {}", i, error);
+        }
+        progress(LoadStage::Textures, (i + 1) as f32 / textures.len() as f32);
+    }
+    progress(LoadStage::Textures, 1.0);
+    log::info!("Textures ready ({} in {} atlas pages of {}x{})", textures.len(), layout.page_count, layout.page_size, layout.page_size);
+
+    let mut regions: Vec<TextureRegion> = layout.rects.iter().map(|rect| TextureRegion::atlas(rect, layout.page_size)).collect();
+    if regions.is_empty() {
+        // Storage buffers can't be empty
+        regions.push(TextureRegion::layer(0));
+    }
+    Ok((textures_buffer, regions))
+}
+
+/// Sets up the Bounding Volume Hierarchy (BVH) for the given triangles and spheres.
+///
+/// This function puts the triangles and spheres into one list of `BvhPrimitive`s and constructs a BVH for them.
+/// It first generates Axis-Aligned Bounding Boxes (AABBs) for each primitive and then uses the `Builder` struct to construct the BVH.
 /// The BVH construction algorithm used is the Surface Area Heuristic (SAH) with binning.
 /// After the BVH is constructed, it is validated and transformed into raw data.
 /// The raw data is then converted into a format compatible with a uniform buffer and the indices of the primitives are collected.
@@ -312,84 +681,380 @@ pub fn setup_textures(mut textures: Vec<DynamicImage>, device: &wgpu::Device, qu
 /// # Arguments
 ///
 /// * `triangles` - A reference to a vector of `Triangle` objects for which the BVH is to be constructed.
+/// * `spheres` - The spheres of the scene, spheres with a radius of 0 (the placeholder of a scene without spheres) are left out.
+/// * `leaf_size` - Maximum number of primitives in a leaf, see `[bvh] leaf_size` in the config.
+/// * `bvh_builder` - Algorithm that builds the tree, see `[bvh] builder` in the config.
+/// * `cache` - Cache file of the BVH, see `bvh_cache`. It is loaded instead of building the BVH if it was written for the same
+///   primitives and settings, otherwise it is written after the build. `None` always builds the BVH.
+/// * `progress` - Called with `LoadStage::Bvh` and the progress between 0.0 and 1.0 after each step, see `no_progress`.
 ///
 /// # Returns
 ///
-/// A tuple containing a vector of `BvhUniform` objects representing the BVH in a format compatible with a uniform buffer, and a vector of `f32` representing the indices of the primitives.
+/// A tuple containing a vector of `BvhUniform` objects representing the BVH in a format compatible with a uniform buffer,
+/// and for every leaf entry the kind of the primitive (`BVH_PRIM_TRIANGLE` or `BVH_PRIM_SPHERE`) and its index in the triangle or sphere buffer.
+///
+///
+/// # Errors
 ///
+/// Returns a message if the BVH can't be built.
 ///
 /// # Output
 ///
-/// Prints the progress of the AABB generation, BVH construction, and BVH validation.
-pub fn setup_bvh(triangles: &Vec<Triangle>) ->(Vec<BvhUniform>, Vec<f32>){
-    // Build BVH for triangles
-    println!("AABB generation   0%");
-    let aabbs = triangles.iter().map(|t| t.aabb()).collect::<Vec<Aabb>>();
-    println!("AABB generation 100%");
+/// Logs the progress of the AABB generation, BVH construction, and BVH validation.
+pub fn setup_bvh(triangles: &[Triangle], spheres: &[Sphere], leaf_size: usize, bvh_builder: BvhBuilder, cache: Option<&Path>, progress: &mut dyn FnMut(LoadStage, f32)) -> Result<(Vec<BvhUniform>, Vec<[f32; 2]>), String> {
+    // Build one BVH for triangles and spheres
+    progress(LoadStage::Bvh, 0.0);
+    let (primitives, prim_ids) = BvhPrimitive::collect(triangles, spheres);
+    log::debug!("AABB generation   0%");
+    let aabbs = generate_aabbs(&primitives);
+    log::debug!("AABB generation 100%");
+    progress(LoadStage::Bvh, 0.2);
 
-    //Add Sphere AABBs
-    // for sphere in userconfig.spheres.iter(){
-    //     aabbs.push(sphere.aabb());               # Doesnt work because the bvh can only take one type of Data
-    // }
+    let geometry_hash = bvh_cache::geometry_hash(&aabbs, &prim_ids, leaf_size.max(1), bvh_builder);
+    if let Some(path) = cache {
+        if let Some(cached) = bvh_cache::load(path, geometry_hash) {
+            log::info!("BVH loaded from {}", path.display());
+            progress(LoadStage::Bvh, 1.0);
+            return Ok(cached);
+        }
+    }
 
-    let prim_per_leaf = Some(std::num::NonZeroUsize::new(1).expect("NonZeroUsize creation failed"));
-    let primitives = triangles.as_slice();
+    // The shader loops over all primitives of a leaf, so any leaf size works on the gpu
+    let prim_per_leaf = std::num::NonZeroUsize::new(leaf_size.max(1));
 
     let builder = Builder {
         aabbs: Some(aabbs.as_slice()),
-        primitives: primitives,
+        primitives: &primitives,
         primitives_per_leaf: prim_per_leaf,
     };
-    println!("BVH Builder created");
+    log::debug!("BVH Builder created");
 
-    // Choose one of these algorithms:
-    //let bvh = builder.construct_locally_ordered_clustered().unwrap();
-    //let bvh = builder.construct_binned_sah().unwrap();
-    //let bvh = builder.construct_spatial_sah().unwrap();
-    let bvh = match builder.construct_locally_ordered_clustered() {
-        Err(error) => {
-            // Handle the error
-            eprintln!("Error constructing BVH: {:?}", error);
-            std::process::exit(1);
-        }
-        Ok(data) => data
+    // Larger leaves and the clustering builder are faster to build, the SAH builder is faster to traverse
+    let build_start = crate::timing::Instant::now();
+    let bvh = match bvh_builder {
+        BvhBuilder::Sah => builder.construct_binned_sah(),
+        BvhBuilder::Locb => builder.construct_locally_ordered_clustered(),
     };
+    let bvh = bvh.map_err(|error| format!("Error constructing BVH: {:?}", error))?;
 
-    println!("BVH generated");
+    log::info!("BVH generated with {:?} and up to {} primitives per leaf in {:.2?}", bvh_builder, leaf_size.max(1), build_start.elapsed());
+    progress(LoadStage::Bvh, 0.8);
 
     // Validate the BVH tree
-    if bvh.validate(triangles.len()) {
-        println!("BVH is valid");
+    if bvh.validate(primitives.len()) {
+        log::info!("BVH is valid");
     } else {
-        println!("BVH is invalid");
+        log::warn!("BVH is invalid");
     }
 
     let raw = bvh.into_raw();
-    println!("BVH transformed to raw data");
+    log::debug!("BVH transformed to raw data");
 
     //convert format of bvh nodes to uniform buffer compativble
-    let mut bvh_uniform: Vec<BvhUniform> = vec![];
-    for i in 0..raw.0.len(){
-        bvh_uniform.push(BvhUniform::new(&raw.0[i]));
+    // (par_iter keeps the node order, so the indices stored in the nodes stay valid)
+    let bvh_uniform: Vec<BvhUniform> = raw.0.par_iter().map(BvhUniform::new).collect();
+
+    //Get the kind and index of the primitives in the leaves
+    let bvh_prim_indices: Vec<[f32; 2]> = raw.1.par_iter().map(|x| prim_ids[*x as usize]).collect();
+    if let Some(path) = cache {
+        match bvh_cache::save(path, geometry_hash, &bvh_uniform, &bvh_prim_indices) {
+            Ok(()) => log::info!("BVH saved to {}", path.display()),
+            Err(e) => log::warn!("{}", e),
+        }
+    }
+    progress(LoadStage::Bvh, 1.0);
+
+    Ok((bvh_uniform, bvh_prim_indices))
+}
+
+/// Cursor grab modes tried in order to capture the cursor for mouse-look.
+///
+/// `Locked` keeps the cursor in place, but isn't supported everywhere (e.g. on Windows), `Confined` keeps it inside the window.
+pub const CURSOR_GRAB_MODES: [winit::window::CursorGrabMode; 2] = [winit::window::CursorGrabMode::Locked, winit::window::CursorGrabMode::Confined];
+
+/// Calls `grab` with the `CURSOR_GRAB_MODES` until one succeeds and returns that mode, or the error of the last one.
+pub fn grab_cursor<E>(mut grab: impl FnMut(winit::window::CursorGrabMode) -> Result<(), E>) -> Result<winit::window::CursorGrabMode, E> {
+    let (last, first) = CURSOR_GRAB_MODES.split_last().expect("No cursor grab modes");
+    for &mode in first {
+        if grab(mode).is_ok() {
+            return Ok(mode);
+        }
     }
+    grab(*last).map(|_| *last)
+}
 
-    //Get the indices of the primitives
-    let bvh_prim_indices: Vec<f32> = raw.1.iter().map(|x| *x as f32).collect();
+/// Returns `true` if a window of the given size can't be rendered to, e.g. because it is minimized.
+///
+/// On some platforms (Windows) a minimized window reports a width and height of 0.
+pub fn is_minimized(size: winit::dpi::PhysicalSize<u32>) -> bool {
+    size.width == 0 || size.height == 0
+}
 
-    return (bvh_uniform, bvh_prim_indices);
+/// Window size saved in the session, the size of the previous session while the window is minimized.
+///
+/// A minimized window has a size of 0, which would restore the next session as an unusable window.
+pub fn session_window_size(size: winit::dpi::PhysicalSize<u32>, previous: Option<[u32; 2]>) -> [u32; 2] {
+    match previous {
+        Some(previous) if is_minimized(size) => previous,
+        _ => [size.width, size.height],
+    }
+}
+
+/// Physical size of the window after its scale factor changed, e.g. when it moved to a monitor with a different DPI.
+///
+/// The logical size stays the same, like winit suggests by default.
+pub fn scaled_window_size(size: winit::dpi::PhysicalSize<u32>, old_scale_factor: f64, new_scale_factor: f64) -> winit::dpi::PhysicalSize<u32> {
+    size.to_logical::<f64>(old_scale_factor).to_physical(new_scale_factor)
+}
+
+/// Largest supersampling factor up to `factor` whose render textures still fit into `max_dimension`.
+///
+/// # Returns
+///
+/// The factor to use, at least 1 even if the window itself is larger than `max_dimension`.
+pub fn clamp_supersample(width: u32, height: u32, factor: u32, max_dimension: u32) -> u32 {
+    let largest = width.max(height).max(1);
+    factor.min(max_dimension / largest).max(1)
+}
+
+/// Work done by `State::render` in a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderMode {
+    /// Nothing is rendered or presented.
+    Skip,
+    /// The last traced frame is presented again with the GUI on top, no compute passes are dispatched.
+    Present,
+    /// The scene is traced and denoised before it is presented.
+    Trace,
+}
+
+/// Returns what to render for a window in the given state.
+///
+/// A minimized window has no surface to present to. While paused the GUI is still drawn,
+/// so rendering can be resumed with its button.
+pub fn render_mode(minimized: bool, paused: bool) -> RenderMode {
+    if minimized {
+        RenderMode::Skip
+    } else if paused {
+        RenderMode::Present
+    } else {
+        RenderMode::Trace
+    }
+}
+
+/// Rectangle of the render textures traced by one dispatch of the raytracing pass, in texels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Tile {
+    /// Value of the tile uniform read by `raygen.wgsl`: the offset followed by the size.
+    pub fn to_uniform(self) -> [u32; 4] {
+        [self.x, self.y, self.width, self.height]
+    }
+}
+
+/// Order in which the tiles of the raytracing pass are dispatched.
+///
+/// Splitting the screen keeps every submit short, so large scenes don't hit the timeout after which
+/// the driver resets the GPU. The tiles are traced row by row, a pass over all tiles can be spread over several frames.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TileSchedule {
+    width: u32,
+    height: u32,
+    tile_size: u32,
+    // Index of the next tile to trace, 0 at the start of a pass
+    next: u32,
+}
+
+impl TileSchedule {
+    /// Splits a `width` x `height` texture into square tiles, a `tile_size` of 0 makes the whole texture a single tile.
+    ///
+    /// The tiles in the last row and column are cut off at the edge of the texture.
+    pub fn new(width: u32, height: u32, tile_size: u32) -> Self {
+        let tile_size = if tile_size == 0 { width.max(height).max(1) } else { tile_size };
+        Self { width, height, tile_size, next: 0 }
+    }
+
+    /// Number of tiles covering the texture.
+    pub fn tile_count(&self) -> u32 {
+        self.columns() * self.rows()
+    }
+
+    /// Tile with the given index, counted row by row from the top left corner.
+    pub fn tile(&self, index: u32) -> Tile {
+        let x = (index % self.columns()) * self.tile_size;
+        let y = (index / self.columns()) * self.tile_size;
+        Tile {
+            x,
+            y,
+            width: self.tile_size.min(self.width.saturating_sub(x)),
+            height: self.tile_size.min(self.height.saturating_sub(y)),
+        }
+    }
+
+    /// Returns the tiles to trace in this frame and moves on to the following ones.
+    ///
+    /// At most `max_tiles` tiles are returned, 0 returns all remaining tiles of the pass.
+    /// The tiles of a frame never continue into the next pass, so every pass ends with a complete frame.
+    pub fn next_tiles(&mut self, max_tiles: u32) -> Vec<Tile> {
+        let remaining = self.tile_count() - self.next;
+        let count = if max_tiles == 0 { remaining } else { max_tiles.min(remaining) };
+        let tiles = (self.next..self.next + count).map(|index| self.tile(index)).collect();
+        self.next = (self.next + count) % self.tile_count();
+        tiles
+    }
+
+    /// Returns `true` if the last call of `next_tiles` traced the last tile of a pass, or no tile was traced yet.
+    pub fn pass_complete(&self) -> bool {
+        self.next == 0
+    }
+
+    /// Fraction of the tiles of the current pass that were already traced.
+    pub fn progress(&self) -> f32 {
+        self.next as f32 / self.tile_count() as f32
+    }
+
+    /// Starts a new pass with the first tile.
+    pub fn restart(&mut self) {
+        self.next = 0;
+    }
+
+    fn columns(&self) -> u32 {
+        self.width.div_ceil(self.tile_size).max(1)
+    }
+
+    fn rows(&self) -> u32 {
+        self.height.div_ceil(self.tile_size).max(1)
+    }
+}
+
+/// Converts an image with premultiplied colors to straight alpha, as expected by PNG files.
+///
+/// Fully opaque and fully transparent pixels are left unchanged.
+pub fn unpremultiply_alpha(image: &mut image::RgbaImage) {
+    for pixel in image.pixels_mut() {
+        let alpha = pixel[3] as u32;
+        if alpha > 0 && alpha < 255 {
+            for channel in 0..3 {
+                pixel[channel] = ((pixel[channel] as u32 * 255 + alpha / 2) / alpha).min(255) as u8;
+            }
+        }
+    }
+}
+
+/// File formats of `State::save_screenshot` and `State::render_to_file`, selected by the file extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// 8 bit PNG of the denoised color buffer.
+    Png,
+    /// 32 bit float OpenEXR of the linear color, keeps values above 1.0 (e.g. emissive surfaces) for compositing.
+    Exr,
+    /// 8 bit binary PPM of the linear color clamped to 0..1, without alpha, for quick debugging.
+    Ppm,
+}
+
+impl OutputFormat {
+    /// Parses a file extension (without the dot, case insensitive).
+    pub fn from_extension(extension: &str) -> Result<Self, String> {
+        match extension.to_lowercase().as_str() {
+            "png" => Ok(OutputFormat::Png),
+            "exr" => Ok(OutputFormat::Exr),
+            "ppm" => Ok(OutputFormat::Ppm),
+            _ => Err(format!("Unsupported output format \"{}\", supported formats are: png, exr, ppm", extension)),
+        }
+    }
+
+    /// Selects the format by the extension of `path`.
+    pub fn from_path(path: &str) -> Result<Self, String> {
+        let extension = std::path::Path::new(path).extension().and_then(|extension| extension.to_str()).unwrap_or("");
+        Self::from_extension(extension)
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            OutputFormat::Exr => "exr",
+            OutputFormat::Ppm => "ppm",
+        }
+    }
+
+    /// `true` if the format is written from the linear float color instead of the 8 bit color buffer.
+    pub fn is_hdr(&self) -> bool {
+        *self != OutputFormat::Png
+    }
+}
+
+/// Writes linear RGBA float pixels (row by row, premultiplied alpha) as an EXR or PPM file.
+///
+/// EXR files keep the premultiplied alpha, as expected by compositing tools.
+pub fn save_hdr_image(path: &str, width: u32, height: u32, pixels: &[f32], format: OutputFormat) -> Result<(), String> {
+    let (width, height) = (width as usize, height as usize);
+    if pixels.len() != width * height * 4 {
+        return Err(format!("Expected {} values for an image of {}x{}, got {}", width * height * 4, width, height, pixels.len()));
+    }
+    match format {
+        OutputFormat::Exr => {
+            exr::prelude::write_rgba_file(path, width, height, |x, y| {
+                let index = (y * width + x) * 4;
+                (pixels[index], pixels[index + 1], pixels[index + 2], pixels[index + 3])
+            }).map_err(|e| format!("Could not save {}: {}", path, e))
+        }
+        OutputFormat::Ppm => {
+            let mut data = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+            data.extend(pixels.chunks_exact(4).flat_map(|pixel| pixel[..3].iter().map(|value| (value.clamp(0.0, 1.0) * 255.0).round() as u8)));
+            std::fs::write(path, data).map_err(|e| format!("Could not save {}: {}", path, e))
+        }
+        OutputFormat::Png => Err("PNG files are written from the 8 bit color buffer".to_string()),
+    }
+}
+
+/// Generates the Axis-Aligned Bounding Boxes (AABBs) for the given triangles.
+///
+/// The AABBs are computed in parallel using `rayon`. Every triangle is independent of the others,
+/// and the output keeps the order of the input, so `aabbs[i]` always belongs to `triangles[i]`.
+///
+/// # Arguments
+///
+/// * `triangles` - A slice of `Triangle` objects for which the AABBs are to be generated.
+///
+/// # Returns
+///
+/// A vector of `Aabb` objects in the same order as the input triangles.
+pub fn generate_aabbs<T: Primitive>(primitives: &[T]) -> Vec<Aabb> {
+    primitives.par_iter().map(|t| t.aabb()).collect::<Vec<Aabb>>()
+}
+
+/// Loads the High Dynamic Range Imaging (HDRI) background of the config.
+///
+/// Returns `None` if no background is configured or the path is empty, the background is then drawn without texture.
+///
+/// # Errors
+///
+/// If the HDRI file can't be loaded, a warning is logged and the missing texture placeholder is used as background.
+pub fn load_background(userconfig: &Config) -> Option<DynamicImage> {
+    let background_path = userconfig.background_path.as_deref().filter(|path| !path.is_empty())?;
+    match load_hdr(background_path.to_string()) {
+        Err(error) => {
+            log::warn!("Could not load HDRI file {}: {}. Using the missing texture placeholder.", background_path, error);
+            Some(missing_texture())
+        }
+        Ok(data) => Some(data),
+    }
 }
 
 /// Sets up the High Dynamic Range Imaging (HDRI) texture for the application.
 ///
-/// This function takes the user configuration, device, queue, and surface configuration as input.
-/// It checks if a background is configured in the user configuration.
-/// If a background is configured and the path is not empty, it loads the background image from the specified path.
-/// If a background is not configured or the path is empty, it creates a default texture.
-/// After loading the background image, it creates a texture from the image and loads the texture.
+/// Creates a texture from the background image loaded with `load_background` and uploads the image.
+/// Without a background image, it creates a default texture.
 ///
 /// # Arguments
 ///
-/// * `userconfig` - A reference to the `Config` object containing the user configuration.
+/// * `background_img` - The background image, see `load_background`.
 /// * `device` - A reference to the `wgpu::Device` object representing the GPU device.
 /// * `queue` - A reference to the `wgpu::Queue` object representing the command queue.
 /// * `config` - A reference to the `SurfaceConfiguration` object representing the surface configuration.
@@ -397,48 +1062,724 @@ pub fn setup_bvh(triangles: &Vec<Triangle>) ->(Vec<BvhUniform>, Vec<f32>){
 /// # Returns
 ///
 /// A `wgpu::Texture` object representing the HDRI texture.
+pub fn setup_hdri(background_img: Option<&DynamicImage>, device: &wgpu::Device, queue: &wgpu::Queue, config: &SurfaceConfiguration) -> wgpu::Texture {
+    let Some(background_img) = background_img else {
+        return create_texture(&device, &config, 1024, 1024, 1);
+    };
+
+    // Create texture from background image
+    let background_texture = create_texture(&device, &config, background_img.dimensions().0, background_img.dimensions().1, 1);
+    if let Err(error) = load_textures_from_image(&queue, &background_texture, background_img, 0) {
+        log::warn!("Could not upload the background texture: {}", error);
+    }
+
+    return background_texture;
+}
+
+/// Creates the blue noise tile for the blue noise sampling pattern.
 ///
+/// The tile is precomputed and shipped with the application, see `precomputed_blue_noise`.
+pub fn setup_blue_noise(device: &wgpu::Device, queue: &wgpu::Queue, config: &SurfaceConfiguration) -> wgpu::Texture {
+    let blue_noise_texture = create_texture(device, config, BLUE_NOISE_SIZE, BLUE_NOISE_SIZE, 1);
+    let image = blue_noise_image(&precomputed_blue_noise(), BLUE_NOISE_SIZE);
+    if let Err(error) = load_textures_from_image(queue, &blue_noise_texture, &image, 0) {
+        log::warn!("Could not upload the blue noise texture: {}", error);
+    }
+    blue_noise_texture
+}
+
+/// Result of `State::benchmark`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BenchmarkResult {
+    pub frames: u32,
+    pub width: u32,
+    pub height: u32,
+    pub samples_per_pixel: u32,
+    pub max_bounces: u32,
+    /// Average time of the raytracing pass per frame in milliseconds.
+    pub average_pass_ms: f64,
+    /// True if the pass was timed on the GPU with timestamp queries, otherwise the CPU waited for every frame.
+    pub gpu_timed: bool,
+}
+
+impl BenchmarkResult {
+    /// Estimated rays per frame, assuming every path bounces `max_bounces` times.
+    ///
+    /// Paths that leave the scene or are terminated early trace less rays, so this is an upper bound.
+    pub fn rays_per_frame(&self) -> u64 {
+        self.width as u64 * self.height as u64 * self.samples_per_pixel as u64 * (self.max_bounces as u64 + 1)
+    }
+
+    pub fn rays_per_second(&self) -> f64 {
+        if self.average_pass_ms > 0.0 {
+            self.rays_per_frame() as f64 / (self.average_pass_ms / 1000.0)
+        } else {
+            0.0
+        }
+    }
+
+    /// A single line of `key=value` pairs for scripts, e.g. `bench frames=100 width=1200 ... rays_per_sec=1.5e9`.
+    pub fn to_line(&self) -> String {
+        format!(
+            "bench frames={} width={} height={} samples={} bounces={} timer={} avg_pass_ms={:.4} rays_per_sec={:.4e}",
+            self.frames, self.width, self.height, self.samples_per_pixel, self.max_bounces,
+            if self.gpu_timed { "gpu" } else { "cpu" }, self.average_pass_ms, self.rays_per_second()
+        )
+    }
+}
+
+/// Packs the value of the denoising pass uniform read by `denoising.wgsl`.
+///
+/// The low byte holds the pass (0 = first, 1 = second, 2 = first with reset history), the second byte
+/// the iteration of the À-Trous filter and the third byte the layer of the temporal history holding the current frame.
+pub fn denoising_pass_value(pass: u32, atrous_iteration: u32, history_index: u32) -> u32 {
+    (pass & 0xff) | ((atrous_iteration & 0xff) << 8) | ((history_index & 0xff) << 16)
+}
+
+/// Parses and validates a WGSL shader without a GPU device.
+///
+/// # Errors
+///
+/// Returns a message with the shader name and the location of the first parse or validation error.
+pub fn validate_wgsl(name: &str, source: &str) -> Result<(), String> {
+    let module = wgpu::naga::front::wgsl::parse_str(source)
+        .map_err(|error| format!("Shader {} failed to compile:\n{}", name, error.emit_to_string(source)))?;
+    wgpu::naga::valid::Validator::new(wgpu::naga::valid::ValidationFlags::all(), wgpu::naga::valid::Capabilities::all())
+        .validate(&module)
+        .map_err(|error| format!("Shader {} is invalid:\n{}", name, error.emit_to_string(source)))?;
+    Ok(())
+}
+
+/// Creates a shader module from WGSL source.
+///
+/// The shader is validated first, and any error the device reports while creating the module is caught
+/// in an error scope, so a broken shader doesn't surface as a panic during pipeline creation.
+///
+/// # Errors
+///
+/// Returns a message with the shader name and the compilation error.
+pub fn create_shader_module(device: &wgpu::Device, name: &str, source: &str) -> Result<wgpu::ShaderModule, String> {
+    validate_wgsl(name, source)?;
+
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(name),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    });
+    match pollster::block_on(device.pop_error_scope()) {
+        Some(error) => Err(format!("Shader {} failed to compile: {}", name, error)),
+        None => Ok(module),
+    }
+}
+
+/// Creates a compute pipeline with the entry point `main` from WGSL source, e.g. to recreate it after the shader changed.
+///
+/// Like `create_shader_module`, errors of the device are caught in an error scope instead of panicking.
 ///
 /// # Errors
 ///
-/// This function will terminate the process if there is an error loading the HDRI file or the texture file.
-pub fn setup_hdri(userconfig: &Config, device: &wgpu::Device, queue: &wgpu::Queue, config: &SurfaceConfiguration) -> wgpu::Texture {
-    // Check if a background is configured
-    let background_path = userconfig.background_path.clone();
-    
-    let background_path = match background_path {
-        Some(background_path) => {
-            if background_path == "" {
-                return create_texture(&device, &config, 1024, 1024, 1);
-            } else {
-                background_path
+/// Returns a message with the shader name and the compilation error, or the error of the pipeline creation
+/// if the shader doesn't match `layout`.
+pub fn create_compute_pipeline(device: &wgpu::Device, layout: &wgpu::PipelineLayout, label: &str, shader_name: &str, source: &str) -> Result<wgpu::ComputePipeline, String> {
+    let module = create_shader_module(device, shader_name, source)?;
+
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some(label),
+        layout: Some(layout),
+        module: &module,
+        entry_point: "main",
+    });
+    match pollster::block_on(device.pop_error_scope()) {
+        Some(error) => Err(format!("{} doesn't match the shader {}: {}", label, shader_name, error)),
+        None => Ok(pipeline),
+    }
+}
+
+
+/// Triangles spread out along the x axis for the tests of the BVH, `count` of them with the default material.
+#[cfg(test)]
+pub(crate) fn synthetic_triangles(count: usize) -> Vec<Triangle> {
+    (0..count).map(|i| {
+        let offset = i as f32 * 0.5;
+        Triangle::new([[offset, 0.0, -offset], [offset + 1.0, (i % 7) as f32, 0.0], [0.0, offset, offset * 2.0]],
+                      [0.0, 1.0, 0.0], 0, [-1.0; 4], [[0.0; 2]; 3])
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Point3;
+    use scene::{SceneBuilder, BVH_PRIM_SPHERE, BVH_PRIM_TRIANGLE};
+
+    use super::*;
+
+    #[test]
+    fn test_empty_scene_placeholders() {
+        let config = SceneBuilder::new().build().unwrap();
+        let mut materials = Vec::new();
+        add_materials_from_config(&mut materials, &config.materials);
+        add_placeholder_material(&mut materials);
+        assert_eq!(materials.len(), 1);
+        // Scenes with materials are left as they are
+        add_placeholder_material(&mut materials);
+        assert_eq!(materials.len(), 1);
+
+        let spheres = setup_spheres(&config);
+        assert_eq!(spheres.len(), 1);
+        assert_eq!(spheres[0].radius[0], 0.0);
+        let config = Config { spheres: Some(Vec::new()), ..config };
+        assert_eq!(setup_spheres(&config).len(), 1);
+
+        let mut textures = Vec::new();
+        let (triangles, triangles_uniform, _) = setup_tris_objects(config, &mut materials, &mut textures, &mut Vec::new(), &mut no_progress).unwrap();
+        assert_eq!(triangles.len(), 1);
+        assert_eq!(triangles_uniform.len(), 1);
+        assert!(triangles[0].is_degenerate());
+        assert!(textures.is_empty());
+    }
+
+    #[test]
+    fn test_add_textures_from_config_missing_file() {
+        let mut textures = Vec::new();
+        let texturesets = Some(vec![Textureset {
+            diffuse_path: Some("../scene/src/test_files/does_not_exist.png".to_string()),
+            normal_path: None,
+            roughness_path: Some("../scene/src/test_files/image.png".to_string()),
+            diffuse_color_space: ColorSpace::Srgb,
+            filter: TextureFilter::Nearest,
+        }]);
+        let mut warnings = Vec::new();
+        add_textures_from_config(&mut textures, &texturesets, &mut warnings);
+
+        // The placeholder keeps the slot, so the roughness texture stays at index 1
+        assert_eq!(warnings.len(), 1);
+        assert!(matches!(&warnings[0], SceneWarning::MissingAsset { path, .. } if path.ends_with("does_not_exist.png")));
+        assert_eq!(textures.len(), 2);
+        assert_eq!(textures[0], missing_texture());
+        assert_ne!(textures[1], missing_texture());
+    }
+
+    #[test]
+    fn test_add_textures_from_config_slot_order() {
+        let mut textures = Vec::new();
+        let textureset = Textureset {
+            diffuse_path: Some("procedural:solid?color=1,0,0".to_string()),
+            normal_path: Some("procedural:solid?color=0,0,1".to_string()),
+            roughness_path: Some("procedural:solid?color=0,1,0".to_string()),
+            diffuse_color_space: ColorSpace::Linear,
+            filter: TextureFilter::Nearest,
+        };
+        let texture_ids = textureset.texture_ids(0);
+        add_textures_from_config(&mut textures, &Some(vec![textureset]), &mut Vec::new());
+
+        let sphere = Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, 0, [texture_ids[0], texture_ids[1], texture_ids[2], -1]);
+        let color = |id: f32| textures[id as usize].to_rgba8().get_pixel(0, 0).0;
+        assert_eq!(color(sphere.material_texture_id[1]), [255, 0, 0, 255]);
+        // The roughness id points at the roughness map, not at the normal map
+        assert_eq!(color(sphere.material_texture_id[2]), [0, 255, 0, 255]);
+        assert_eq!(color(sphere.material_texture_id[3]), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn test_add_textures_from_config_procedural() {
+        let mut textures = Vec::new();
+        let texturesets = Some(vec![Textureset {
+            diffuse_path: Some("procedural:checker?squares=4&size=64".to_string()),
+            normal_path: None,
+            roughness_path: Some("procedural:unknown".to_string()),
+            diffuse_color_space: ColorSpace::Srgb,
+            filter: TextureFilter::Nearest,
+        }]);
+        add_textures_from_config(&mut textures, &texturesets, &mut Vec::new());
+
+        assert_eq!(textures.len(), 2);
+        assert_eq!(textures[0].dimensions(), (64, 64));
+        assert_ne!(textures[0].get_pixel(0, 0), textures[0].get_pixel(16, 0));
+        assert_eq!(textures[1], missing_texture());
+    }
+
+    #[test]
+    fn test_decode_textures_matches_serial() {
+        let slots: Vec<(String, ColorSpace)> = (0..16)
+            .map(|i| match i % 4 {
+                0 => ("../scene/src/test_files/image.png".to_string(), ColorSpace::Srgb),
+                1 => (format!("procedural:solid?color={},0,0", i as f32 / 16.0), ColorSpace::Linear),
+                2 => (format!("procedural:checker?squares={}&size=32", i), ColorSpace::Srgb),
+                _ => ("../scene/src/test_files/does_not_exist.png".to_string(), ColorSpace::Srgb),
+            })
+            .collect();
+        let slots: Vec<(&str, ColorSpace)> = slots.iter().map(|(path, color_space)| (path.as_str(), *color_space)).collect();
+
+        let serial: Vec<Result<DynamicImage, SceneWarning>> = slots.iter().map(|&(path, color_space)| decode_texture(path, color_space)).collect();
+        assert_eq!(decode_textures(&slots), serial);
+    }
+
+    #[test]
+    fn test_limit_texture_layers() {
+        let mut textures: Vec<DynamicImage> = (0..5).map(|i| DynamicImage::new_rgb8(i + 1, 1)).collect();
+        assert_eq!(limit_texture_layers(&mut textures, 8), 0);
+        assert_eq!(textures.len(), 5);
+
+        assert_eq!(limit_texture_layers(&mut textures, 3), 2);
+        assert_eq!(textures.iter().map(|texture| texture.width()).collect::<Vec<_>>(), vec![1, 2, 3]);
+
+        // Ids of the dropped textures are reset by the validation
+        let mut triangles = synthetic_triangles(1);
+        triangles[0].texture_ids = [2.0, 3.0, 4.0, -1.0];
+        assert_eq!(validate_scene(&mut triangles, &mut [], 1, textures.len()).len(), 2);
+        assert_eq!(triangles[0].texture_ids, [2.0, -1.0, -1.0, -1.0]);
+    }
+
+    #[test]
+    fn test_validate_scene_triangles() {
+        let mut triangles = synthetic_triangles(3);
+        triangles[0].material_id = 1;
+        triangles[0].texture_ids = [0.0, -1.0, 1.0, 1.0];
+        triangles[1].material_id = 2;               // only two materials
+        triangles[2].texture_ids = [2.0, -1.0, -3.0, 4.0]; // only two textures
+
+        let warnings = validate_scene(&mut triangles, &mut [], 2, 2);
+        assert_eq!(warnings, vec![
+            SceneWarning::MissingMaterial { object: SceneObject::Triangle, index: 1, id: 2.0 },
+            SceneWarning::MissingTexture { object: SceneObject::Triangle, index: 2, id: 2.0 },
+            SceneWarning::MissingTexture { object: SceneObject::Triangle, index: 2, id: -3.0 },
+            SceneWarning::MissingTexture { object: SceneObject::Triangle, index: 2, id: 4.0 },
+        ]);
+        assert_eq!(triangles[0].material_id, 1);
+        assert_eq!(triangles[0].texture_ids, [0.0, -1.0, 1.0, 1.0]);
+        assert_eq!(triangles[1].material_id, 0);
+        assert_eq!(triangles[2].texture_ids, [-1.0; 4]);
+
+        // Without materials the ids are kept for the placeholder material
+        let mut triangles = synthetic_triangles(1);
+        triangles[0].material_id = 2;
+        assert!(validate_scene(&mut triangles, &mut [], 0, 0).is_empty());
+        assert_eq!(triangles[0].material_id, 2);
+    }
+
+    #[test]
+    fn test_validate_config_missing_obj() {
+        let path = std::env::temp_dir().join("raytracer_test_validate_config.toml");
+        std::fs::write(&path, "[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nnear_far = [0.1, 100.0]\nfov = 45.0\n[[materials]]\ncolor = [1.0, 0.0, 0.0]\nattenuation = [0.1, 0.1, 0.1]\nroughness = 0.2\nemission = 0.0\nior = 0.0
+            \n[[spheres]]\nposition = [0.0, 0.0, 0.0]\nradius = 1.0\ntexture_id = [-1, -1, -1]\nmaterial_id = 3\n[3d_model_paths]\nobj_path = \"missing_model.obj\"\n").unwrap();
+
+        let warnings = validate_config(path.to_str().unwrap()).expect("Invalid config");
+        assert_eq!(warnings.len(), 2);
+        assert!(matches!(&warnings[0], SceneWarning::MissingAsset { path, .. } if path.ends_with("missing_model.obj")));
+        assert_eq!(warnings[1], SceneWarning::MissingMaterial { object: SceneObject::Sphere, index: 0, id: 3.0 });
+        assert!(validate_config("missing_config.toml").is_err());
+    }
+
+    #[test]
+    fn test_validate_config_valid_assets() {
+        let mut config = Config::default();
+        config.model_paths.obj_path = Some("../scene/src/test_files/cube_triangulated.obj".to_string());
+        config.model_paths.svg_path = Some("../scene/src/test_files/two_triangles.svg".to_string());
+        config.textures = Some(vec![Textureset {
+            diffuse_path: Some("../scene/src/test_files/image.png".to_string()),
+            normal_path: None,
+            roughness_path: Some("procedural:checker".to_string()),
+            diffuse_color_space: ColorSpace::Srgb,
+            filter: TextureFilter::Nearest,
+        }]);
+        config.background_path = Some("../scene/src/test_files/image.exr".to_string());
+        assert_eq!(validate_loaded_config(config), vec![]);
+    }
+
+    #[test]
+    fn test_validate_scene_sphere_missing_material() {
+        let mut spheres = vec![
+            Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, 1, [0, -1, -1, -1]),
+            Sphere::new(Point3::new(2.0, 0.0, 0.0), 1.0, 5, [-1, 3, -1, 2]), // only two materials and one texture
+        ];
+
+        let warnings = validate_scene(&mut [], &mut spheres, 2, 1);
+        assert_eq!(warnings, vec![
+            SceneWarning::MissingMaterial { object: SceneObject::Sphere, index: 1, id: 5.0 },
+            SceneWarning::MissingTexture { object: SceneObject::Sphere, index: 1, id: 3.0 },
+            SceneWarning::MissingTexture { object: SceneObject::Sphere, index: 1, id: 2.0 },
+        ]);
+        assert_eq!(warnings[0].to_string(), "Sphere 1 references missing material 5, using material 0");
+        assert_eq!(spheres[0].material_texture_id, [1.0, 0.0, -1.0, -1.0]);
+        assert_eq!(spheres[1].material_texture_id, [0.0, -1.0, -1.0, -1.0]);
+        assert_eq!(spheres[1].emission_texture_id[0], -1.0);
+
+        // The placeholder of a scene without spheres has no textures to warn about
+        assert!(validate_scene(&mut [], &mut [Sphere::empty()], 1, 0).is_empty());
+    }
+
+    #[test]
+    fn test_collect_emissive_triangles() {
+        let materials = vec![
+            Material::new([1.0, 1.0, 1.0], [0.5, 0.5, 0.5], 0.5, 0.0, 0.0),
+            Material::new([1.0, 0.9, 0.8], [0.5, 0.5, 0.5], 0.0, 5.0, 0.0),
+        ];
+        let mut triangles = synthetic_triangles(5);
+        triangles[1].material_id = 1;
+        triangles[2].material_id = 1;
+        triangles[2].texture_ids[0] = 0.0;  // textured triangles don't emit
+        triangles[3].material_id = 1;
+        triangles[4].material_id = 1;
+        triangles[4].texture_ids[3] = 0.0;  // emission maps aren't sampled directly
+
+        assert_eq!(collect_emissive_triangles(&triangles, &materials), vec![1, 3]);
+        assert!(collect_emissive_triangles(&triangles, &materials[..1]).is_empty());
+        assert!(collect_emissive_triangles(&[], &materials).is_empty());
+    }
+
+    #[test]
+    fn test_surface_area_per_material() {
+        let triangle = |points: [[f32; 3]; 3], material_id: i32| Triangle::new(points, [0.0, 0.0, 1.0], material_id, [-1.0; 4], [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]]);
+        let triangles = vec![
+            triangle([[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]], 0),
+            triangle([[0.0, 0.0, 0.0], [2.0, 0.0, 0.0], [0.0, 2.0, 0.0]], 0),
+            triangle([[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [2.0, 0.0, 0.0]], 1),   // degenerate
+            triangle([[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]], 5),   // unknown material
+            triangle([[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]], -1),
+        ];
+        assert_eq!(surface_area_per_material(&triangles, 3), vec![2.5, 0.0, 0.0]);
+        assert!(surface_area_per_material(&triangles, 0).is_empty());
+    }
+
+    #[test]
+    fn test_setup_tris_objects_config_and_gltf_materials() {
+        let userconfig = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0
+            \n[[materials]]\ncolor = [1.0, 0.0, 0.0]\nattenuation = [0.1, 0.1, 0.1]\nroughness = 0.5\nemission = 0.0\nior = 0.0
+            \n[[materials]]\ncolor = [0.0, 1.0, 0.0]\nattenuation = [0.1, 0.1, 0.1]\nroughness = 0.5\nemission = 0.0\nior = 0.0
+            \n[3d_model_paths]\ngltf_path = \"../scene/src/test_files/cube.gltf\"").unwrap();
+        let mut materials = Vec::new();
+        let mut textures = Vec::new();
+        add_materials_from_config(&mut materials, &userconfig.materials);
+
+        let (triangles, _, _) = setup_tris_objects(userconfig, &mut materials, &mut textures, &mut Vec::new(), &mut no_progress).unwrap();
+
+        // The GLTF material is appended after both config materials
+        let (_, gltf_materials, _) = load_gltf("../scene/src/test_files/cube.gltf".to_string(), 0, 0).unwrap();
+        assert_eq!(materials.len(), 3);
+        assert_eq!(materials[2].albedo, gltf_materials[0].albedo);
+        assert_eq!(materials[2].roughness, gltf_materials[0].roughness);
+        assert!(!triangles.is_empty());
+        assert!(triangles.iter().all(|triangle| triangle.material_id == 2));
+    }
+
+    #[test]
+    fn test_setup_tris_objects_scene_builder_mesh() {
+        let triangle = Triangle::new([[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]], [0.0, 0.0, 1.0], 1, [-1.0; 4], [[0.0; 2]; 3]);
+        let userconfig = SceneBuilder::new()
+            .add_material(Material::new([1.0, 0.0, 0.0], [0.1, 0.1, 0.1], 0.5, 0.0, 0.0))
+            .add_material(Material::new([0.0, 1.0, 0.0], [0.1, 0.1, 0.1], 0.5, 0.0, 0.0))
+            .add_triangle_mesh(vec![triangle; 2])
+            .build()
+            .unwrap();
+        let mut materials = Vec::new();
+        let mut textures = Vec::new();
+        add_materials_from_config(&mut materials, &userconfig.materials);
+
+        let mut stages = Vec::new();
+        let (triangles, triangles_uniform, _) = setup_tris_objects(userconfig, &mut materials, &mut textures, &mut Vec::new(), &mut |stage, progress| stages.push((stage, progress))).unwrap();
+        assert_eq!(stages.first(), Some(&(LoadStage::Models, 0.0)));
+        assert_eq!(stages.last(), Some(&(LoadStage::Models, 1.0)));
+        assert_eq!(triangles.len(), 2);
+        assert_eq!(triangles_uniform.len(), 2);
+        assert!(triangles.iter().all(|triangle| triangle.material_id == 1));
+    }
+
+    #[test]
+    fn test_setup_bvh_progress() {
+        let triangles = synthetic_triangles(16);
+        let mut updates = Vec::new();
+        let (bvh_uniform, bvh_prim_indices) = setup_bvh(&triangles, &[], 1, BvhBuilder::Locb, None, &mut |stage, progress| updates.push((stage, progress))).unwrap();
+        assert!(!bvh_uniform.is_empty());
+        assert_eq!(bvh_prim_indices.len(), triangles.len());
+
+        assert!(updates.iter().all(|(stage, _)| *stage == LoadStage::Bvh));
+        assert_eq!(updates.first().map(|update| update.1), Some(0.0));
+        assert_eq!(updates.last().map(|update| update.1), Some(1.0));
+        assert!(updates.windows(2).all(|pair| pair[0].1 <= pair[1].1), "progress isn't monotonic: {:?}", updates);
+    }
+
+    #[test]
+    fn test_setup_bvh_mixed_primitives() {
+        let triangles = synthetic_triangles(8);
+        let spheres = vec![
+            Sphere::new(Point3::new(0.0, 5.0, 0.0), 1.0, 0, [-1; 4]),
+            Sphere::empty(),
+            Sphere::new(Point3::new(10.0, 0.0, -3.0), 0.5, 0, [-1; 4]),
+        ];
+        let (bvh_uniform, bvh_prim_indices) = setup_bvh(&triangles, &spheres, 1, BvhBuilder::Locb, None, &mut no_progress).unwrap();
+        assert!(!bvh_uniform.is_empty());
+
+        // Every triangle and every non-placeholder sphere ends up in exactly one leaf
+        let mut prims: Vec<(u32, u32)> = bvh_prim_indices.iter().map(|&[kind, index]| (kind as u32, index as u32)).collect();
+        prims.sort();
+        let mut expected: Vec<(u32, u32)> = (0..8).map(|index| (BVH_PRIM_TRIANGLE as u32, index)).collect();
+        expected.extend([(BVH_PRIM_SPHERE as u32, 0), (BVH_PRIM_SPHERE as u32, 2)]);
+        assert_eq!(prims, expected);
+    }
+
+    #[test]
+    fn test_setup_bvh_leaf_size() {
+        let triangles = synthetic_triangles(64);
+        let (bvh_uniform, bvh_prim_indices) = setup_bvh(&triangles, &[], 4, BvhBuilder::Sah, None, &mut no_progress).unwrap();
+
+        // Walk the tree from the root like the shader, every triangle is in exactly one leaf inside the bounds of all its ancestors
+        let mut visited = vec![0; triangles.len()];
+        let mut stack = vec![0];
+        while let Some(node_index) = stack.pop() {
+            let node = &bvh_uniform[node_index];
+            let (min, max) = node.bounds();
+            let contains = |other: &BvhUniform| {
+                let (other_min, other_max) = other.bounds();
+                (0..3).all(|axis| min[axis] <= other_min[axis] && other_max[axis] <= max[axis])
+            };
+            match (node.leaf_primitives(), node.left_child()) {
+                (Some(range), _) => {
+                    assert!((1..=4).contains(&range.len()), "leaf with {} primitives", range.len());
+                    for [kind, index] in &bvh_prim_indices[range] {
+                        assert_eq!(*kind, BVH_PRIM_TRIANGLE);
+                        let triangle = &triangles[*index as usize];
+                        assert!(triangle.points.iter().all(|point| (0..3).all(|axis| min[axis] <= point[axis] && point[axis] <= max[axis])));
+                        visited[*index as usize] += 1;
+                    }
+                }
+                (None, Some(left)) => {
+                    assert!(contains(&bvh_uniform[left]) && contains(&bvh_uniform[left + 1]));
+                    stack.extend([left, left + 1]);
+                }
+                (None, None) => unreachable!(),
             }
         }
-        None => {
-            return create_texture(&device, &config, 1024, 1024, 1);
-        }
-    };
+        assert!(visited.iter().all(|&count| count == 1), "{:?}", visited);
+    }
 
-    // Load background image
-    let background_img = match load_hdr(background_path){
-        Err(error) => {
-            // Handle the error
-            eprintln!("Error loading HDRI file: {:?}", error);
-            std::process::exit(1);
+    #[test]
+    fn test_output_format() {
+        assert_eq!(OutputFormat::from_path("out/frame.exr"), Ok(OutputFormat::Exr));
+        assert_eq!(OutputFormat::from_path("shot.PNG"), Ok(OutputFormat::Png));
+        assert_eq!(OutputFormat::from_extension("ppm"), Ok(OutputFormat::Ppm));
+        assert!(OutputFormat::from_path("shot.jpg").is_err());
+        assert!(OutputFormat::from_path("shot").is_err());
+        assert!(OutputFormat::Exr.is_hdr() && !OutputFormat::Png.is_hdr());
+    }
+
+    #[test]
+    fn test_save_hdr_image_exr() {
+        // 2x1 image with an emissive pixel brighter than 1.0
+        let pixels = [8.5, 4.0, 1.25, 1.0, 0.25, 0.5, 0.0, 0.5];
+        let path = std::env::temp_dir().join("raytracer_test_save_hdr_image.exr");
+        let path = path.to_str().unwrap();
+        save_hdr_image(path, 2, 1, &pixels, OutputFormat::Exr).unwrap();
+
+        let image = load_texture(path).unwrap().into_rgba32f();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(image.dimensions(), (2, 1));
+        assert_eq!(image.get_pixel(0, 0).0, [8.5, 4.0, 1.25, 1.0]);
+        assert_eq!(image.get_pixel(1, 0).0, [0.25, 0.5, 0.0, 0.5]);
+    }
+
+    #[test]
+    fn test_save_hdr_image_ppm() {
+        let pixels = [8.5, 0.5, -1.0, 1.0];
+        let path = std::env::temp_dir().join("raytracer_test_save_hdr_image.ppm");
+        let path = path.to_str().unwrap();
+        save_hdr_image(path, 1, 1, &pixels, OutputFormat::Ppm).unwrap();
+
+        let data = std::fs::read(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(data, b"P6\n1 1\n255\n\xff\x80\x00");
+
+        assert!(save_hdr_image(path, 2, 2, &pixels, OutputFormat::Ppm).is_err());
+    }
+
+    #[test]
+    fn test_unpremultiply_alpha() {
+        let mut image = image::RgbaImage::from_vec(3, 1, vec![
+            64, 32, 0, 128,     // half transparent
+            10, 20, 30, 255,    // opaque
+            0, 0, 0, 0,         // transparent
+        ]).unwrap();
+        unpremultiply_alpha(&mut image);
+        assert_eq!(image.get_pixel(0, 0).0, [128, 64, 0, 128]);
+        assert_eq!(image.get_pixel(1, 0).0, [10, 20, 30, 255]);
+        assert_eq!(image.get_pixel(2, 0).0, [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_is_minimized() {
+        assert!(is_minimized(winit::dpi::PhysicalSize::new(0, 0)));
+        assert!(is_minimized(winit::dpi::PhysicalSize::new(800, 0)));
+        assert!(is_minimized(winit::dpi::PhysicalSize::new(0, 600)));
+        assert!(!is_minimized(winit::dpi::PhysicalSize::new(800, 600)));
+    }
+
+    #[test]
+    fn test_session_window_size() {
+        use winit::dpi::PhysicalSize;
+        assert_eq!(session_window_size(PhysicalSize::new(800, 600), Some([1024, 768])), [800, 600]);
+        assert_eq!(session_window_size(PhysicalSize::new(800, 600), None), [800, 600]);
+        // A minimized window keeps the size of the previous session
+        assert_eq!(session_window_size(PhysicalSize::new(0, 0), Some([1024, 768])), [1024, 768]);
+    }
+
+    #[test]
+    fn test_scaled_window_size() {
+        use winit::dpi::PhysicalSize;
+        // Moving from a 100% to a 150% monitor and back keeps the logical size of 800x600
+        assert_eq!(scaled_window_size(PhysicalSize::new(800, 600), 1.0, 1.5), PhysicalSize::new(1200, 900));
+        assert_eq!(scaled_window_size(PhysicalSize::new(1200, 900), 1.5, 1.0), PhysicalSize::new(800, 600));
+        assert_eq!(scaled_window_size(PhysicalSize::new(801, 601), 1.0, 1.25), PhysicalSize::new(1001, 751));
+        assert_eq!(scaled_window_size(PhysicalSize::new(800, 600), 2.0, 2.0), PhysicalSize::new(800, 600));
+        // A minimized window stays minimized
+        assert!(is_minimized(scaled_window_size(PhysicalSize::new(0, 0), 1.0, 2.0)));
+    }
+
+    #[test]
+    fn test_grab_cursor() {
+        use winit::window::CursorGrabMode;
+        assert_eq!(grab_cursor(|_| Ok::<(), ()>(())), Ok(CursorGrabMode::Locked));
+
+        // Platforms without locked cursors fall back to confining the cursor to the window
+        let mut tried = Vec::new();
+        let mode = grab_cursor(|mode| {
+            tried.push(mode);
+            if mode == CursorGrabMode::Locked { Err("not supported") } else { Ok(()) }
+        });
+        assert_eq!(mode, Ok(CursorGrabMode::Confined));
+        assert_eq!(tried, vec![CursorGrabMode::Locked, CursorGrabMode::Confined]);
+
+        assert_eq!(grab_cursor(Err::<(), _>), Err(CursorGrabMode::Confined));
+    }
+
+    #[test]
+    fn test_clamp_supersample() {
+        assert_eq!(clamp_supersample(800, 600, 1, 8192), 1);
+        assert_eq!(clamp_supersample(800, 600, 4, 8192), 4);
+        // 4x of a 2560 wide window would be 10240 texels wide
+        assert_eq!(clamp_supersample(2560, 1440, 4, 8192), 3);
+        assert_eq!(clamp_supersample(1440, 2560, 4, 8192), 3);
+        // Rendering at the window size is always possible
+        assert_eq!(clamp_supersample(10000, 600, 2, 8192), 1);
+        assert_eq!(clamp_supersample(0, 0, 2, 8192), 2);
+    }
+
+    #[test]
+    fn test_render_mode() {
+        assert_eq!(render_mode(false, false), RenderMode::Trace);
+        // Paused frames skip the compute passes
+        assert_eq!(render_mode(false, true), RenderMode::Present);
+        assert_eq!(render_mode(true, false), RenderMode::Skip);
+        assert_eq!(render_mode(true, true), RenderMode::Skip);
+    }
+
+    #[test]
+    fn test_tile_schedule() {
+        // 100x50 in tiles of 32: 4 columns and 2 rows, cut off at the right and bottom edge
+        let mut schedule = TileSchedule::new(100, 50, 32);
+        assert_eq!(schedule.tile_count(), 8);
+        assert_eq!(schedule.tile(0), Tile { x: 0, y: 0, width: 32, height: 32 });
+        assert_eq!(schedule.tile(3), Tile { x: 96, y: 0, width: 4, height: 32 });
+        assert_eq!(schedule.tile(7), Tile { x: 96, y: 32, width: 4, height: 18 });
+
+        // Every texel is covered by exactly one tile
+        let tiles = schedule.next_tiles(0);
+        assert_eq!(tiles.len(), 8);
+        assert_eq!(tiles.iter().map(|tile| tile.width * tile.height).sum::<u32>(), 100 * 50);
+        assert!(schedule.pass_complete());
+
+        // 3 tiles per frame: the third frame only traces the last 2 tiles of the pass
+        let frames: Vec<Vec<Tile>> = (0..4).map(|_| schedule.next_tiles(3)).collect();
+        assert_eq!(frames.iter().map(Vec::len).collect::<Vec<_>>(), vec![3, 3, 2, 3]);
+        assert_eq!(frames[1][0], schedule.tile(3));
+        assert_eq!(frames[2][1], schedule.tile(7));
+        assert_eq!(frames[3][0], schedule.tile(0));
+        assert!(!schedule.pass_complete());
+        assert_eq!(schedule.progress(), 3.0 / 8.0);
+
+        schedule.restart();
+        assert!(schedule.pass_complete());
+        assert_eq!(schedule.next_tiles(1), vec![schedule.tile(0)]);
+    }
+
+    #[test]
+    fn test_tile_schedule_whole_screen() {
+        // A tile size of 0 or a tile larger than the screen traces everything at once
+        for tile_size in [0, 4096] {
+            let mut schedule = TileSchedule::new(800, 600, tile_size);
+            assert_eq!(schedule.tile_count(), 1);
+            assert_eq!(schedule.next_tiles(2), vec![Tile { x: 0, y: 0, width: 800, height: 600 }]);
+            assert!(schedule.pass_complete());
         }
-        Ok(data) => data,
-    };
+    }
 
-    // Create texture from background image
-    let mut background_texture = create_texture(&device, &config, background_img.dimensions().0, background_img.dimensions().1, 1);
-    background_texture = match load_textures_from_image(&queue, background_texture, &background_img, 0) {
-        Err(error) => {
-            // Handle the error
-            eprintln!("Error loading texture file: {:?}", error);
-            std::process::exit(1);
+    #[test]
+    fn test_add_textures_from_config_exr_diffuse() {
+        let mut textures = Vec::new();
+        let texturesets = Some(vec![Textureset {
+            diffuse_path: Some("../scene/src/test_files/image.exr".to_string()),
+            normal_path: None,
+            roughness_path: None,
+            diffuse_color_space: ColorSpace::Srgb,
+            filter: TextureFilter::Nearest,
+        }]);
+        add_textures_from_config(&mut textures, &texturesets, &mut Vec::new());
+
+        assert_eq!(textures.len(), 1);
+        assert!(matches!(textures[0], DynamicImage::ImageRgba32F(_)));
+        assert_eq!(textures[0].dimensions(), (1024, 512));
+    }
+
+    #[test]
+    fn test_generate_aabbs_matches_serial() {
+        let triangles = synthetic_triangles(5000);
+        let serial = triangles.iter().map(|t| t.aabb()).collect::<Vec<Aabb>>();
+        let parallel = generate_aabbs(&triangles);
+
+        assert_eq!(serial.len(), parallel.len());
+        for (a, b) in serial.iter().zip(parallel.iter()) {
+            assert_eq!(a.min, b.min);
+            assert_eq!(a.max, b.max);
         }
-        Ok(data) => data,
-    };
+    }
 
-    return background_texture;
-}
\ No newline at end of file
+    #[test]
+    fn test_generate_aabbs_empty() {
+        assert!(generate_aabbs::<Triangle>(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_validate_wgsl() {
+        assert!(validate_wgsl("Denoising Shader", include_str!("../../res/shader/denoising.wgsl")).is_ok());
+
+        let broken = "@compute @workgroup_size(1)\nfn main() {\n    let x: f32 = undefined_value;\n}\n";
+        let error = validate_wgsl("Broken Shader", broken).unwrap_err();
+        assert!(error.contains("Broken Shader"), "{}", error);
+        assert!(error.contains("undefined_value"), "{}", error);
+
+        let invalid = "fn f() -> f32 {\n    return 1u;\n}\n";
+        let error = validate_wgsl("Invalid Shader", invalid).unwrap_err();
+        assert!(error.contains("Invalid Shader"), "{}", error);
+    }
+
+    #[test]
+    fn test_denoising_pass_value() {
+        assert_eq!(denoising_pass_value(0, 0, 0), 0);
+        assert_eq!(denoising_pass_value(2, 0, 0), 2);
+        let value = denoising_pass_value(1, 3, 5);
+        assert_eq!(value & 0xff, 1);
+        assert_eq!((value >> 8) & 0xff, 3);
+        assert_eq!((value >> 16) & 0xff, 5);
+    }
+
+    #[test]
+    fn test_benchmark_result() {
+        let result = BenchmarkResult { frames: 10, width: 100, height: 50, samples_per_pixel: 2, max_bounces: 3, average_pass_ms: 4.0, gpu_timed: true };
+        assert_eq!(result.rays_per_frame(), 40_000);
+        assert_eq!(result.rays_per_second(), 10_000_000.0);
+        assert_eq!(result.to_line(), "bench frames=10 width=100 height=50 samples=2 bounces=3 timer=gpu avg_pass_ms=4.0000 rays_per_sec=1.0000e7");
+
+        let result = BenchmarkResult { average_pass_ms: 0.0, gpu_timed: false, ..result };
+        assert_eq!(result.rays_per_second(), 0.0);
+        assert!(result.to_line().contains("timer=cpu"));
+    }
+}
@@ -0,0 +1,85 @@
+//! Reloads the compute shaders from `res/shader` while the application runs, enabled with the `hot-reload` feature.
+//!
+//! Without the feature the shaders are compiled into the binary with `include_str!`.
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+use notify::{EventKind, RecursiveMode, Watcher};
+
+/// Directory of the WGSL files in the source tree, the binary has to run on the machine it was built on.
+pub const SHADER_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../res/shader");
+
+/// Watches the shader directory for changed WGSL files.
+pub struct ShaderWatcher {
+    dir: PathBuf,
+    // Watching stops when the watcher is dropped
+    _watcher: notify::RecommendedWatcher,
+    events: mpsc::Receiver<notify::Result<notify::Event>>,
+}
+
+impl ShaderWatcher {
+    /// Starts watching `dir`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a message if the directory can't be watched, e.g. because it doesn't exist.
+    pub fn new(dir: &Path) -> Result<Self, String> {
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)
+            .map_err(|e| format!("Could not create the shader watcher: {}", e))?;
+        watcher.watch(dir, RecursiveMode::NonRecursive)
+            .map_err(|e| format!("Could not watch the shaders in {}: {}", dir.display(), e))?;
+        Ok(Self { dir: dir.to_path_buf(), _watcher: watcher, events })
+    }
+
+    /// Path of the shader file with the given name.
+    pub fn path(&self, file_name: &str) -> PathBuf {
+        self.dir.join(file_name)
+    }
+
+    /// Returns the names of the WGSL files that were written since the last call, without waiting for changes.
+    ///
+    /// Editors often save a file in several steps (e.g. a new file that replaces the old one), every file is returned once.
+    pub fn changed_shaders(&self) -> Vec<String> {
+        let mut changed: Vec<String> = self.events.try_iter()
+            .filter_map(|event| match event {
+                Ok(event) => Some(event),
+                Err(e) => {
+                    log::warn!("Shader watcher error: {}", e);
+                    None
+                }
+            })
+            .filter(|event| matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)))
+            .flat_map(|event| event.paths)
+            .filter(|path| path.extension().is_some_and(|extension| extension == "wgsl"))
+            .filter_map(|path| path.file_name().map(|name| name.to_string_lossy().into_owned()))
+            .collect();
+        changed.sort();
+        changed.dedup();
+        changed
+    }
+}
+
+/// The shader watcher and the pipeline layouts needed to recreate the compute pipelines.
+pub struct ShaderReload {
+    pub watcher: ShaderWatcher,
+    pub raytracing_pipeline_layout: wgpu::PipelineLayout,
+    pub denoising_pipeline_layout: wgpu::PipelineLayout,
+}
+
+impl ShaderReload {
+    /// Starts watching `SHADER_DIR`, logs a warning and returns `None` if that isn't possible.
+    pub fn new(raytracing_pipeline_layout: wgpu::PipelineLayout, denoising_pipeline_layout: wgpu::PipelineLayout) -> Option<Self> {
+        match ShaderWatcher::new(Path::new(SHADER_DIR)) {
+            Ok(watcher) => {
+                log::info!("Watching the shaders in {} for changes", SHADER_DIR);
+                Some(Self { watcher, raytracing_pipeline_layout, denoising_pipeline_layout })
+            }
+            Err(e) => {
+                log::warn!("{}, shader hot reload is disabled", e);
+                None
+            }
+        }
+    }
+}
@@ -4,6 +4,10 @@
 //!
 //! ## Modules
 //!
+//! - `reference`: A small CPU path tracer that follows the raytracing shader, used as a reference in tests of the renderer.
+//! - `hot_reload`: Watches the WGSL files and recreates the compute pipelines when they are saved, only with the `hot-reload` feature.
+//! - `bvh_cache`: Saves the BVH next to the model and loads it on the next launch while the geometry is unchanged.
+//! - `timing`: The clock and the frame limiter, which can't block the main thread in the browser.
 //! - `state`: This module contains the [`State`](raytracer/src/state.rs) struct. `State` is a central struct in this crate, as it manages the state of the ray tracing application. It encapsulates the rendering pipeline, GPU resources, and other essential components necessary for the ray tracing process.
 //!
 //! ## Usage
@@ -30,62 +34,164 @@
 //!
 //! For more detailed examples and usage, see the examples directory in this crate's repository.
 
-use winit::{event::*, event_loop::{ControlFlow, EventLoop}, keyboard::{Key, NamedKey}};
+use winit::{event::*, event_loop::{ControlFlow, EventLoop}};
 
 mod state;
+mod render_targets;
+mod scene_data;
+pub mod reference;
 pub mod helper;
+pub mod bvh_cache;
+pub mod timing;
+#[cfg(feature = "hot-reload")]
+pub mod hot_reload;
 pub use state::State;
+pub use wgpu_utils::DeviceInfo;
+pub use helper::{LoadStage, OutputFormat, SceneObject, SceneWarning, validate_config};
 
+/// Frames measured by `run_benchmark` if no count is given.
+pub const DEFAULT_BENCHMARK_FRAMES: u32 = 100;
 
-/// Starts the application.
+/// Options for starting the application with `run`.
+///
+/// Fields that aren't set are taken from `Default`:
+///
+/// ```
+/// use raytracing_lib::RunConfig;
+///
+/// let config = RunConfig {
+///     config_path: Some("examples/0-one_sphere/config.toml".to_string()),
+///     window_size: (800, 600),
+///     vsync: false,
+///     ..Default::default()
+/// };
+/// assert_eq!(config.title, "raytracing_lib");
+/// assert!(!config.headless);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunConfig {
+    /// Config file of the scene, `res/Config.toml` if `None` or the built-in default scene if that file doesn't exist.
+    pub config_path: Option<String>,
+    /// Logical size of the window.
+    pub window_size: (u32, u32),
+    /// Title of the window.
+    pub title: String,
+    /// Waits for the display when presenting. Without vsync the frames are presented uncapped if the surface supports it.
+    pub vsync: bool,
+    /// Creates the window hidden, so nothing is shown on screen while the scene is traced.
+    pub headless: bool,
+    /// Exits once this many frames are accumulated, e.g. to render a converged still from the command line.
+    pub frames: Option<u32>,
+    /// Screenshot saved when `frames` is reached, the format is selected by the extension (see `State::save_screenshot`).
+    pub output: Option<String>,
+}
+
+impl Default for RunConfig {
+    fn default() -> Self {
+        Self {
+            config_path: None,
+            window_size: (1200, 800),
+            title: env!("CARGO_PKG_NAME").to_string(),
+            vsync: true,
+            headless: false,
+            frames: None,
+            output: None,
+        }
+    }
+}
+
+impl RunConfig {
+    /// Returns `true` if the frame count given by `CameraUniform::frame_count` reached `frames`, never without a frame limit.
+    pub fn frames_reached(&self, frame_count: u32) -> bool {
+        self.frames.is_some_and(|frames| frame_count >= frames)
+    }
+}
+
+
+/// Starts the application with the options in `config`.
 ///
 /// This function initializes the logger, creates the window, and starts the event loop.
 /// It sets a panic hook for wasm32 targets and initializes the logger accordingly.
-/// For non-wasm32 targets, it uses the `env_logger` crate to initialize the logger.
+/// For non-wasm32 targets, it uses the `env_logger` crate to initialize the logger. The log level can be set with `RUST_LOG`,
+/// by default only the setup messages of the raytracer and warnings are shown. The same messages are shown in the log window of the GUI.
 ///
-/// It creates a new event loop and a window with a specified title and size.
+/// It creates a new event loop and a window with the title and size of `config`, hidden if `config.headless` is set.
 /// The event loop is set to continuously run, even if the OS hasn't dispatched any events.
 ///
-/// A new `State` object is created for the window.
+/// A new `State` object is created for the window and the scene in `config.config_path`.
 /// The event loop is then started, and it handles various window and device events, such as:
-/// - Closing the window when requested by the user or when the escape key is pressed
-/// - Updating and rendering the state when a redraw is requested
+/// - Closing the window when requested by the user or when the exit key (Escape by default, see `[keys]` in the config) is pressed,
+///   the camera and the settings are saved to the session file of the scene first (see `State::save_session`)
+/// - Updating and rendering the state when a redraw is requested, with `config.frames` set the application saves the screenshot
+///   in `config.output` and exits once that many frames are accumulated
 /// - Resizing the state when the window size changes
+/// - Pausing the rendering while the window is unfocused
 /// - Logging when the window scale factor changes
 /// - Processing mouse motion events
 /// - Requesting a redraw before the system goes to idle and limiting the frame rate
+/// - Recreating the state on a new gpu device if the device was lost, e.g. after a driver reset (see `State::recreate`)
 ///
 /// # Errors
 ///
-/// This function will terminate the process if there is an error loading the HDRI file or the texture file.
-pub async fn run(resource_path: Option<&str>) {
-    cfg_if::cfg_if! {
-        if #[cfg(target_arch = "wasm32")] {
-            std::panic::set_hook(Box::new(console_error_panic_hook::hook));
-            console_log::init_with_level(log::Level::Info).expect("Could't initialize logger");
-        } else {
-            env_logger::init();
-        }
+/// This function will terminate the process if the scene can't be set up (see `State::new`), e.g. if there is an error loading the config or a shader,
+/// if `config.output` has an unsupported extension, or if the screenshot can't be saved.
+pub async fn run(config: RunConfig) {
+    init_logger();
+
+    if let Some(Err(e)) = config.output.as_deref().map(OutputFormat::from_path) {
+        log::error!("Fatal: {}", e);
+        std::process::exit(1);
     }
 
     let event_loop = EventLoop::new().unwrap();
-    let title = env!("CARGO_PKG_NAME");
-    let builder = winit::window::WindowBuilder::new();
-    let window = builder
-        .with_title(title)
-        .with_inner_size(winit::dpi::LogicalSize::new(1200.0, 800.0))
-        .build(&event_loop)
-        .unwrap();
-        
+    let window = create_window(&event_loop, &config);
+
     // ControlFlow::Poll continuously runs the event loop, 
     // even if the OS hasn't dispatched any events.
     event_loop.set_control_flow(ControlFlow::Poll);
 
-    let mut state = State::new(window, resource_path).await;
-    let mut last_render_time = instant::Instant::now();
+    let mut state = match State::new(window, config.config_path.as_deref()).await {
+        Ok(state) => state,
+        Err(e) => {
+            log::error!("Fatal: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if !config.vsync {
+        let present_modes = &state.gui_config.present_modes;
+        let preferred = if present_modes.contains(&wgpu::PresentMode::Mailbox) { wgpu::PresentMode::Mailbox } else { wgpu::PresentMode::Immediate };
+        // Applied by the next render, like a present mode selected in the GUI
+        state.gui_config.present_mode = wgpu_utils::select_present_mode(present_modes, preferred);
+    }
+    let mut last_render_time = timing::Instant::now();
+    // Replaced by a new state when the gpu device is lost, see `State::recreate`
+    let mut state = Some(state);
 
     // Start the event loop
     let _ = event_loop.run(move |event, elwt| {
+        if state.as_ref().is_some_and(State::is_device_lost) {
+            cfg_if::cfg_if! {
+                if #[cfg(target_arch = "wasm32")] {
+                    // The browser can't block until the new device is created
+                    log::error!("GPU device lost, reload the page to continue");
+                    elwt.exit();
+                    return;
+                } else {
+                    let lost = state.take().unwrap();
+                    match pollster::block_on(lost.recreate()) {
+                        Ok(recreated) => state = Some(recreated),
+                        Err(e) => {
+                            log::error!("Could not recreate the GPU device: {}", e);
+                            elwt.exit();
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+        let Some(state) = state.as_mut() else {
+            return;
+        };
         match event {
             Event::WindowEvent {
                 ref event,
@@ -95,9 +201,10 @@ pub async fn run(resource_path: Option<&str>) {
                 match event {
                     // Close the window if requested by the user
                     WindowEvent::CloseRequested => {
+                        state.save_session();
                         elwt.exit();
                     }
-                    // Close the window if the escape key is pressed                    
+                    // Close the window if the exit key (Escape by default) is pressed
                     WindowEvent::KeyboardInput {
                         event:
                             KeyEvent {
@@ -106,14 +213,12 @@ pub async fn run(resource_path: Option<&str>) {
                                 ..
                             },
                         ..
-                    } => {
-                        match key {
-                            Key::Named(NamedKey::Escape) => elwt.exit(),
-                            _ => {}
-                        }
+                    } if state.key_bindings().matches(scene::KeyAction::Exit, key) => {
+                        state.save_session();
+                        elwt.exit();
                     }
                     WindowEvent::RedrawRequested => {
-                        let now = instant::Instant::now();
+                        let now = timing::Instant::now();
                         let dt = now - last_render_time;
                         last_render_time = now;
                         state.update(dt);
@@ -126,13 +231,38 @@ pub async fn run(resource_path: Option<&str>) {
                             // We're ignoring timeouts
                             Err(wgpu::SurfaceError::Timeout) => log::warn!("Surface timeout"),
                         }
+                        if config.frames_reached(state.camera_uniform.frame_count()) {
+                            log::info!("Accumulated {} frames", state.camera_uniform.frame_count());
+                            if let Some(path) = &config.output {
+                                match state.save_screenshot(path) {
+                                    Ok(()) => log::info!("Saved screenshot {}", path),
+                                    Err(e) => {
+                                        log::error!("{}", e);
+                                        std::process::exit(1);
+                                    }
+                                }
+                            }
+                            elwt.exit();
+                        }
                     }
                     WindowEvent::Resized(physical_size) => {
                         state.resize(*physical_size);
                     }
-                    WindowEvent::ScaleFactorChanged  { scale_factor, .. } => {
-                        // Log when the window scale factor changes
-                        println!("Window={window_id:?} changed scale to {scale_factor}");
+                    // Stop tracing while the window is in the background, unless a fixed number of frames is rendered
+                    WindowEvent::Focused(focused) if config.frames.is_none() => {
+                        state.set_paused(!focused);
+                        // The cursor would stay grabbed while switching to another window
+                        if !focused {
+                            state.set_cursor_captured(false);
+                        }
+                    }
+                    // The window moved to a monitor with a different DPI, render at the new physical size
+                    WindowEvent::ScaleFactorChanged { scale_factor, inner_size_writer } => {
+                        log::info!("Window={window_id:?} changed scale to {scale_factor}");
+                        let new_size = state.set_scale_factor(*scale_factor);
+                        if inner_size_writer.clone().request_inner_size(new_size).is_err() {
+                            log::warn!("Could not resize the window to {}x{}", new_size.width, new_size.height);
+                        }
                     }
                     _ => {}
                 };
@@ -140,22 +270,100 @@ pub async fn run(resource_path: Option<&str>) {
             Event::DeviceEvent {
                 event: DeviceEvent::MouseMotion{ delta, },
                 ..
-            } => if state.mouse_pressed {
+            } => if state.mouse_pressed || state.is_cursor_captured() {
                 state.camera_controller.process_mouse(delta.0, delta.1)
             }
             // Request a redraw bevore the system goes to idle
             Event::AboutToWait => {
                 // Application update call
-                // Limit frame rate
-                if state.gui_config.frame_limit != 0 {
-                    let frame_time = instant::Instant::now() - last_render_time;
-                    if frame_time < std::time::Duration::from_secs_f32(1.0 / state.gui_config.frame_limit as f32){
-                        std::thread::sleep(std::time::Duration::from_secs_f32(1.0 / state.gui_config.frame_limit as f32) - frame_time);
-                    }
+                // While paused only redraw after events (e.g. a click on the resume button) instead of continuously
+                // Limit frame rate, with vsync (the default present mode) presenting already waits for the display
+                let control_flow = timing::frame_control_flow(state.gui_config.frame_limit, last_render_time);
+                elwt.set_control_flow(if state.is_paused() { ControlFlow::Wait } else { control_flow });
+                if state.is_paused() || control_flow == ControlFlow::Poll {
+                    state.window.request_redraw();
                 }
-                state.window.request_redraw();
             },
             _ => ()
         }
     });
-}
\ No newline at end of file
+}
+
+/// Starts the application with the scene in the config file at `resource_path`, like `run` did before it took a `RunConfig`.
+///
+/// The window and the other options are the defaults of `RunConfig`, which match the previous behaviour.
+#[deprecated(since = "0.2.0", note = "use `run(RunConfig { config_path, ..Default::default() })` instead")]
+pub async fn run_with_path(resource_path: Option<&str>) {
+    run(RunConfig {
+        config_path: resource_path.map(str::to_string),
+        ..Default::default()
+    }).await
+}
+
+/// Runs the raytracing pass of the scene for `frames` frames and prints the result.
+///
+/// The window is only needed for the GPU setup, no events are handled. The result is printed to stdout as a
+/// single line of `key=value` pairs (see `BenchmarkResult::to_line`), so it can be compared by scripts:
+/// `bench frames=100 width=1200 height=800 samples=1 bounces=10 timer=gpu avg_pass_ms=5.1234 rays_per_sec=2.0610e9`
+///
+/// The scene and the window are set up from `config` like in `run`, the frame limit and the screenshot options are ignored.
+pub async fn run_benchmark(config: RunConfig, frames: u32) {
+    init_logger();
+
+    let event_loop = EventLoop::new().unwrap();
+    let window = create_window(&event_loop, &config);
+    let mut state = match State::new(window, config.config_path.as_deref()).await {
+        Ok(state) => state,
+        Err(e) => {
+            log::error!("Fatal: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let result = state.benchmark(frames);
+    println!("{}", result.to_line());
+}
+
+fn init_logger() {
+    cfg_if::cfg_if! {
+        if #[cfg(target_arch = "wasm32")] {
+            std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+            console_log::init_with_level(log::Level::Info).expect("Could't initialize logger");
+        } else {
+            // Setup messages are shown by default, per-frame output needs RUST_LOG=raytracing_lib=trace
+            let logger = env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn,raytracing_lib=info")).build();
+            let max_level = logger.filter();
+            // The messages also go to the log window of the GUI, which is visible without a terminal
+            if log::set_boxed_logger(Box::new(gui::BufferLogger::new(Box::new(logger), gui::log_buffer()))).is_ok() {
+                log::set_max_level(max_level);
+            }
+        }
+    }
+}
+
+fn create_window(event_loop: &EventLoop<()>, config: &RunConfig) -> winit::window::Window {
+    winit::window::WindowBuilder::new()
+        .with_title(&config.title)
+        .with_inner_size(winit::dpi::LogicalSize::new(config.window_size.0, config.window_size.1))
+        .with_visible(!config.headless)
+        .build(event_loop)
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames_reached() {
+        // Without a frame count the application runs until it is closed
+        assert!(!RunConfig::default().frames_reached(u32::MAX));
+
+        let config = RunConfig { frames: Some(64), ..Default::default() };
+        assert!(!config.frames_reached(0));
+        assert!(!config.frames_reached(63));
+        assert!(config.frames_reached(64));
+        // Frames traced while the screenshot is saved don't keep it running
+        assert!(config.frames_reached(65));
+    }
+}
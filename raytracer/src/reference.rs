@@ -0,0 +1,481 @@
+//! Single-threaded CPU path tracer that follows the `color` function of the raytracing shader.
+//!
+//! It renders the same `Triangle`, `Sphere` and `Material` data as the GPU renderer into a small image, so tests
+//! have a reference to compare the renderer and changes of the shading against. It is slow and only meant for tests.
+
+use std::f32::consts::PI;
+
+use glam::Vec3;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use scene::{closest_hit, roughness_to_alpha, sample_cosine_hemisphere, Background, BackgroundMode, Camera, HitPrimitive, Material, Projection, ShaderConfig, Sphere, Triangle};
+
+use crate::helper::collect_emissive_triangles;
+
+/// Reflectance of dielectrics at normal incidence, same as `DIELECTRIC_F0` in the shader.
+const DIELECTRIC_F0: f32 = 0.04;
+/// Smallest GGX alpha, same as `MIN_GGX_ALPHA` in the shader.
+const MIN_GGX_ALPHA: f32 = 0.001;
+/// Weights of the luminance of a linear rgb color, same as in the shader.
+const LUMINANCE: Vec3 = Vec3::new(0.2126, 0.7152, 0.0722);
+
+/// Linear colors rendered by the `ReferenceTracer`, before exposure and tone mapping.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReferenceImage {
+    pub width: u32,
+    pub height: u32,
+    /// Rows from top to bottom.
+    pub pixels: Vec<[f32; 3]>,
+}
+
+impl ReferenceImage {
+    pub fn pixel(&self, x: u32, y: u32) -> [f32; 3] {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    /// Average luminance of all pixels.
+    pub fn mean_luminance(&self) -> f32 {
+        let sum: f32 = self.pixels.iter().map(|&pixel| Vec3::from(pixel).dot(LUMINANCE)).sum();
+        sum / self.pixels.len().max(1) as f32
+    }
+}
+
+/// CPU version of the path tracing of the raytracing shader.
+///
+/// The paths are traced like in `color` in `raygen.wgsl`: the same material model (metallic-roughness, GGX,
+/// dielectrics with absorption), next-event estimation of the emissive triangles with multiple importance sampling
+/// and the same background modes. Settings like the bounces, ray bias and firefly clamp are taken from the `ShaderConfig`.
+///
+/// Not supported: textures (the materials are shaded with their plain albedo and roughness), HDRI backgrounds
+/// (shown as the sky), depth of field and the debug views. Every primitive is tested for every ray, there is no BVH.
+pub struct ReferenceTracer<'a> {
+    triangles: &'a [Triangle],
+    spheres: &'a [Sphere],
+    materials: &'a [Material],
+    background: Background,
+    shader_config: ShaderConfig,
+    emissive_triangles: Vec<u32>,
+}
+
+impl<'a> ReferenceTracer<'a> {
+    /// The material ids of the primitives have to be valid indices into `materials`.
+    pub fn new(triangles: &'a [Triangle], spheres: &'a [Sphere], materials: &'a [Material], background: Background, shader_config: ShaderConfig) -> Self {
+        let emissive_triangles = collect_emissive_triangles(triangles, materials);
+        Self { triangles, spheres, materials, background, shader_config, emissive_triangles }
+    }
+
+    /// Renders the view of the camera with `samples_per_pixel` jittered camera rays per pixel.
+    ///
+    /// The aspect ratio of the image is the one of `projection`. The result only depends on the inputs and the `seed`.
+    pub fn render(&self, camera: &Camera, projection: &Projection, width: u32, height: u32, samples_per_pixel: u32, seed: u64) -> ReferenceImage {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let origin = Vec3::new(camera.position.x, camera.position.y, camera.position.z);
+        let near_far = projection.near_far();
+        let samples = samples_per_pixel.max(1);
+
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let mut color = Vec3::ZERO;
+                for _ in 0..samples {
+                    let screen_x = (x as f32 + rng.gen::<f32>()) / width as f32;
+                    let screen_y = (y as f32 + rng.gen::<f32>()) / height as f32;
+                    let direction = camera.ray_direction(projection, screen_x, screen_y);
+                    let direction = Vec3::new(direction.x, direction.y, direction.z);
+                    color += self.clamp_firefly(self.color(origin, direction, near_far, &mut rng));
+                }
+                pixels.push((color / samples as f32).to_array());
+            }
+        }
+        ReferenceImage { width, height, pixels }
+    }
+
+    // Same as color() in the shader, without the texture branches
+    fn color(&self, origin: Vec3, direction: Vec3, (near, far): (f32, f32), rng: &mut ChaCha8Rng) -> Vec3 {
+        let config = &self.shader_config;
+        let mut ray = Ray { origin, direction };
+        let mut pixel_color = Vec3::ONE;
+        let mut weight = Vec3::ONE;
+        let mut direct_light = Vec3::ZERO;
+        let mut light_sampled = 0.0;
+        let mut bsdf_pdf = 0.0;
+        let mut medium_absorption = Vec3::ZERO;
+
+        let mut depth = 0;
+        while depth <= config.ray_max_bounces {
+            // Camera rays only hit surfaces between the near and far plane
+            let (t_min, t_max) = if depth == 0 {
+                let length = ray.direction.length();
+                (near / length, config.ray_max_ray_distance.min(far / length))
+            } else {
+                (0.0, config.ray_max_ray_distance)
+            };
+            let Some(hit) = closest_hit(ray.origin, ray.direction, self.spheres, self.triangles, t_min, t_max) else {
+                if depth == 0 {
+                    return if config.transparent_background == 1 { Vec3::ZERO } else { self.background_color(ray.direction) };
+                }
+                let background = self.background_color(ray.direction);
+                return pixel_color + (background - pixel_color) * weight + direct_light;
+            };
+            let t = hit.distance;
+            let hit_point = ray.origin + ray.direction * t;
+
+            // Beer-Lambert absorption inside of a dielectric
+            if medium_absorption.cmpgt(Vec3::ZERO).any() {
+                let transmittance = (-medium_absorption * t * ray.direction.length()).exp();
+                pixel_color *= transmittance;
+                weight *= transmittance;
+                medium_absorption = Vec3::ZERO;
+            }
+
+            let (mut normal, material) = match hit.primitive {
+                HitPrimitive::Sphere(index) => {
+                    let sphere = &self.spheres[index];
+                    let center = Vec3::new(sphere.center[0], sphere.center[1], sphere.center[2]);
+                    ((hit_point - center).normalize(), self.material(sphere.material_texture_id[0] as i32))
+                }
+                HitPrimitive::Triangle(index) => {
+                    let triangle = &self.triangles[index];
+                    (interpolate_normal(triangle, hit_point), self.material(triangle.material_id))
+                }
+            };
+            if material.double_sided == 1 && material.ior == 0.0 && normal.dot(ray.direction) > 0.0 {
+                normal = -normal;
+            }
+            let albedo = Vec3::new(material.albedo[0], material.albedo[1], material.albedo[2]);
+            let attenuation = Vec3::new(material.attenuation[0], material.attenuation[1], material.attenuation[2]);
+
+            if material.emission > 0.0 {
+                match hit.primitive {
+                    _ if depth == 0 => pixel_color = albedo * material.emission,
+                    // Spheres aren't sampled as lights, so the full emission is added
+                    HitPrimitive::Sphere(_) => pixel_color += albedo * material.emission * weight,
+                    HitPrimitive::Triangle(index) if config.mis_enabled == 1 => {
+                        let length = ray.direction.length();
+                        let light_pdf = self.emissive_triangle_pdf(&self.triangles[index], ray.direction / length, t * length);
+                        let mis_weight = (1.0 - light_sampled) + light_sampled * power_heuristic(bsdf_pdf, light_pdf);
+                        pixel_color += albedo * material.emission * weight * mis_weight;
+                    }
+                    HitPrimitive::Triangle(_) => pixel_color += albedo * material.emission * weight * (1.0 - light_sampled),
+                }
+                return pixel_color + direct_light;
+            }
+
+            // Metallic-roughness: metals reflect with the Fresnel of their albedo, dielectrics reflect an
+            // untinted part with the Fresnel of F0 = 0.04 and scatter the rest with their albedo
+            let mut specular = false;
+            let mut clearcoat = false;
+            if material.ior == 0.0 {
+                let cos_theta = normal.dot(ray.direction.normalize()).abs();
+                // The clearcoat reflects untinted on top of the base, without a coat no random number is drawn
+                if material.clearcoat > 0.0 && rng.gen::<f32>() < material.clearcoat * fresnel_schlick(Vec3::splat(DIELECTRIC_F0), cos_theta).x {
+                    specular = true;
+                    clearcoat = true;
+                } else if rng.gen::<f32>() < material.metallic {
+                    pixel_color *= fresnel_schlick(albedo, cos_theta);
+                    specular = true;
+                } else if rng.gen::<f32>() < fresnel_schlick(Vec3::splat(DIELECTRIC_F0), cos_theta).x {
+                    specular = true;
+                } else {
+                    pixel_color *= albedo;
+                }
+                weight *= attenuation;
+            } else {
+                pixel_color *= albedo;
+            }
+
+            // Only the diffuse part is lit directly
+            let mut light_sample = Vec3::ZERO;
+            light_sampled = 0.0;
+            let facing_normal = if normal.dot(ray.direction) > 0.0 { -normal } else { normal };
+            if material.ior == 0.0 && material.roughness > 0.0 && !specular {
+                light_sample = self.sample_emissive_triangle(hit_point, facing_normal, rng) * material.roughness;
+                light_sampled = material.roughness;
+            }
+
+            let mut ggx_pdf = 0.0;
+            if material.ior > 0.0 {
+                ray = self.dielectric_scatter(&ray, hit_point, normal, material.ior, rng);
+                medium_absorption = if ray.direction.dot(normal) < 0.0 { attenuation } else { Vec3::ZERO };
+            } else if specular {
+                let view = -ray.direction.normalize();
+                let roughness = if clearcoat { material.clearcoat_roughness } else { material.roughness };
+                let alpha = roughness_to_alpha(roughness, config.roughness_linear == 1).max(MIN_GGX_ALPHA);
+                let half_vector = sample_ggx(facing_normal, alpha, rng);
+                let direction = reflect(-view, half_vector);
+                let n_dot_l = facing_normal.dot(direction);
+                if n_dot_l <= 0.0 {
+                    // Reflected below the surface, the path is absorbed
+                    return direct_light;
+                }
+                let n_dot_v = facing_normal.dot(view).max(0.0001);
+                let n_dot_h = facing_normal.dot(half_vector).max(0.0001);
+                let v_dot_h = view.dot(half_vector).max(0.0001);
+                pixel_color *= ggx_sample_weight(n_dot_v, n_dot_l, n_dot_h, v_dot_h, alpha);
+                ray = Ray { origin: hit_point + facing_normal * config.ray_bias, direction };
+                ggx_pdf = ggx_distribution(n_dot_h, alpha) * n_dot_h / (4.0 * v_dot_h);
+            } else if rng.gen::<f32>() < material.roughness {
+                ray = Ray { origin: hit_point + facing_normal * config.ray_bias, direction: sample_cosine_hemisphere(facing_normal, rng.gen(), rng.gen()) };
+            } else {
+                let direction = reflect(ray.direction, normal + vec3_in_unit_sphere(rng) * roughness_to_alpha(material.roughness, config.roughness_linear == 1));
+                ray = Ray { origin: hit_point + normal * config.ray_bias, direction };
+            }
+
+            bsdf_pdf = if ggx_pdf > 0.0 { ggx_pdf } else { facing_normal.dot(ray.direction.normalize()).max(0.0) / PI };
+            if material.ior == 0.0 {
+                weight *= attenuation.x;
+            }
+            direct_light += light_sample * weight;
+            depth += 1;
+        }
+        pixel_color + direct_light
+    }
+
+    // Next-event estimation like sample_emissive_triangle() in the shader
+    fn sample_emissive_triangle(&self, hit_point: Vec3, normal: Vec3, rng: &mut ChaCha8Rng) -> Vec3 {
+        if self.emissive_triangles.is_empty() {
+            return Vec3::ZERO;
+        }
+        let count = self.emissive_triangles.len();
+        let light_index = self.emissive_triangles[rng.gen_range(0..count)] as usize;
+        let light = &self.triangles[light_index];
+
+        // Uniform point on the triangle, samples outside of it are mirrored back in
+        let (mut u, mut v) = (rng.gen::<f32>(), rng.gen::<f32>());
+        if u + v > 1.0 {
+            u = 1.0 - u;
+            v = 1.0 - v;
+        }
+        let [vertex1, vertex2, vertex3] = light.points.map(Vec3::from);
+        let (edge1, edge2) = (vertex2 - vertex1, vertex3 - vertex1);
+        let light_point = vertex1 + edge1 * u + edge2 * v;
+        let light_cross = edge1.cross(edge2);
+        let area = 0.5 * light_cross.length();
+
+        let to_light = light_point - hit_point;
+        let distance_squared = to_light.length_squared();
+        let distance = distance_squared.sqrt();
+        let direction = to_light / distance;
+
+        // Lights emit on both sides
+        let cos_surface = normal.dot(direction);
+        let cos_light = (light_cross / (2.0 * area)).dot(direction).abs();
+        if cos_surface <= 0.0 || cos_light <= 0.0 {
+            return Vec3::ZERO;
+        }
+        if self.is_occluded(hit_point + normal * self.shader_config.ray_bias, direction, distance * 0.999, light_index) {
+            return Vec3::ZERO;
+        }
+
+        let mut mis_weight = 1.0;
+        if self.shader_config.mis_enabled == 1 {
+            let light_pdf = distance_squared / (cos_light * area * count as f32);
+            mis_weight = power_heuristic(light_pdf, cos_surface / PI);
+        }
+        let material = self.material(light.material_id);
+        let albedo = Vec3::new(material.albedo[0], material.albedo[1], material.albedo[2]);
+        albedo * material.emission * cos_surface * cos_light * area * count as f32 * mis_weight / (distance_squared * PI)
+    }
+
+    fn emissive_triangle_pdf(&self, light: &Triangle, direction: Vec3, distance: f32) -> f32 {
+        let [vertex1, vertex2, vertex3] = light.points.map(Vec3::from);
+        let light_cross = (vertex2 - vertex1).cross(vertex3 - vertex1);
+        let area = 0.5 * light_cross.length();
+        let cos_light = (light_cross / (2.0 * area)).dot(direction).abs();
+        if area <= 0.0 || cos_light <= 0.0 {
+            return 0.0;
+        }
+        distance * distance / (cos_light * area * self.emissive_triangles.len() as f32)
+    }
+
+    // Anything except the light triangle closer than max_distance
+    fn is_occluded(&self, origin: Vec3, direction: Vec3, max_distance: f32, light_index: usize) -> bool {
+        closest_hit(origin, direction, self.spheres, self.triangles, 0.0, self.shader_config.ray_max_ray_distance)
+            .is_some_and(|hit| hit.primitive != HitPrimitive::Triangle(light_index) && hit.distance < max_distance)
+    }
+
+    fn dielectric_scatter(&self, ray: &Ray, hit_point: Vec3, normal: Vec3, ior: f32, rng: &mut ChaCha8Rng) -> Ray {
+        let etai_over_etat = if ray.direction.dot(normal) > 0.0 { ior } else { 1.0 / ior };
+        let unit_direction = ray.direction.normalize();
+        let cos_theta = (-unit_direction).dot(normal).min(1.0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        let reflect_prob = schlick(cos_theta, etai_over_etat);
+        let cannot_refract = etai_over_etat * sin_theta > 1.0;
+
+        let direction = if rng.gen::<f32>() - 0.5 < reflect_prob || cannot_refract {
+            reflect(unit_direction, normal)
+        } else {
+            refract(unit_direction, normal, etai_over_etat)
+        };
+        // The origin is moved to the side of the surface the ray leaves to
+        let bias = if direction.dot(normal) >= 0.0 { self.shader_config.ray_bias } else { -self.shader_config.ray_bias };
+        Ray { origin: hit_point + normal * bias, direction }
+    }
+
+    fn background_color(&self, direction: Vec3) -> Vec3 {
+        let background = &self.background;
+        let color = match background.mode() {
+            BackgroundMode::Color => Vec3::from(background.color),
+            BackgroundMode::Gradient => Vec3::from(background.gradient().color(direction.normalize().y)),
+            // The HDRI isn't loaded, the shader shows the sky without one as well
+            BackgroundMode::Sky | BackgroundMode::Texture => {
+                let t = 0.5 * (direction.normalize().y + 1.0);
+                Vec3::ONE.lerp(Vec3::new(0.5, 0.7, 1.0), t)
+            }
+        };
+        color * background.intensity
+    }
+
+    fn clamp_firefly(&self, color: Vec3) -> Vec3 {
+        let luminance = color.dot(LUMINANCE);
+        if luminance > self.shader_config.firefly_clamp {
+            color * (self.shader_config.firefly_clamp / luminance)
+        } else {
+            color
+        }
+    }
+
+    fn material(&self, material_id: i32) -> Material {
+        self.materials[material_id as usize]
+    }
+}
+
+struct Ray {
+    origin: Vec3,
+    direction: Vec3,
+}
+
+// Vertex normals interpolated at the hit point, the face normal if they cancel out
+fn interpolate_normal(triangle: &Triangle, hit_point: Vec3) -> Vec3 {
+    let [a, b, c] = triangle.points.map(Vec3::from);
+    let (v0, v1, v2) = (b - a, c - a, hit_point - a);
+    let (d00, d01, d11) = (v0.dot(v0), v0.dot(v1), v1.dot(v1));
+    let (d20, d21) = (v2.dot(v0), v2.dot(v1));
+    let inv_denom = 1.0 / (d00 * d11 - d01 * d01);
+    let u = (d11 * d20 - d01 * d21) * inv_denom;
+    let v = (d00 * d21 - d01 * d20) * inv_denom;
+
+    let [n1, n2, n3] = triangle.vertex_normals.map(Vec3::from);
+    let normal = n1 * (1.0 - u - v) + n2 * u + n3 * v;
+    if normal.length_squared() < 1e-12 {
+        Vec3::from(triangle.normal).normalize()
+    } else {
+        normal.normalize()
+    }
+}
+
+// Same distribution as rngNextVec3InUnitSphere() in the shader
+fn vec3_in_unit_sphere(rng: &mut ChaCha8Rng) -> Vec3 {
+    let r = rng.gen::<f32>().powf(0.33333);
+    let theta = PI * rng.gen::<f32>();
+    let phi = 2.0 * PI * rng.gen::<f32>();
+    Vec3::new(r * theta.sin() * phi.cos(), r * theta.sin() * phi.sin(), r * theta.cos())
+}
+
+fn sample_ggx(normal: Vec3, alpha: f32, rng: &mut ChaCha8Rng) -> Vec3 {
+    let (u1, u2) = (rng.gen::<f32>(), rng.gen::<f32>());
+    let cos_theta = ((1.0 - u1) / (1.0 + (alpha * alpha - 1.0) * u1)).sqrt();
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * u2;
+    let (tangent, bitangent) = normal.any_orthonormal_pair();
+    (tangent * sin_theta * phi.cos() + bitangent * sin_theta * phi.sin() + normal * cos_theta).normalize()
+}
+
+fn ggx_distribution(n_dot_h: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    let denominator = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    a2 / (PI * denominator * denominator)
+}
+
+fn smith_g1_ggx(n_dot_x: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    2.0 * n_dot_x / (n_dot_x + (a2 + (1.0 - a2) * n_dot_x * n_dot_x).sqrt())
+}
+
+fn ggx_sample_weight(n_dot_v: f32, n_dot_l: f32, n_dot_h: f32, v_dot_h: f32, alpha: f32) -> f32 {
+    smith_g1_ggx(n_dot_v, alpha) * smith_g1_ggx(n_dot_l, alpha) * v_dot_h / (n_dot_h * n_dot_v)
+}
+
+fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+    let sum = pdf * pdf + other_pdf * other_pdf;
+    if sum <= 0.0 {
+        0.0
+    } else {
+        pdf * pdf / sum
+    }
+}
+
+fn fresnel_schlick(f0: Vec3, cos_theta: f32) -> Vec3 {
+    f0 + (Vec3::ONE - f0) * (1.0 - cos_theta.clamp(0.0, 1.0)).powi(5)
+}
+
+fn schlick(cosine: f32, ref_idx: f32) -> f32 {
+    let r0 = ((1.0 - ref_idx) / (1.0 + ref_idx)).powi(2);
+    r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
+}
+
+fn reflect(v: Vec3, n: Vec3) -> Vec3 {
+    v - 2.0 * v.dot(n) * n
+}
+
+fn refract(direction: Vec3, normal: Vec3, etai_over_etat: f32) -> Vec3 {
+    let cos_theta = (-direction).dot(normal);
+    let r_out_parallel = etai_over_etat * (direction + cos_theta * normal);
+    let r_out_perp = -(1.0 - r_out_parallel.length_squared()).sqrt() * normal;
+    r_out_parallel + r_out_perp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Deg, Point3, Vector3};
+    use scene::{presets, Config};
+
+    /// Mean luminance of `test_reference_cornell_box`, a change means that the shading of the reference changed.
+    const CORNELL_BOX_MEAN_LUMINANCE: f32 = 0.28066;
+
+    fn camera(config: &Config, width: u32, height: u32) -> (Camera, Projection) {
+        let target = config.camera_target.expect("Missing camera target");
+        let camera = Camera::look_at(config.camera_position.into(), target.into(), Vector3::unit_y());
+        let [near, far] = config.camera_near_far;
+        (camera, Projection::new(width, height, Deg(config.camera_fov), near, far))
+    }
+
+    #[test]
+    fn test_reference_cornell_box() {
+        let scene = presets::cornell_box().build().expect("Invalid scene");
+        let triangles = scene.meshes.clone().expect("Missing triangles");
+        let materials = scene.materials.clone().expect("Missing materials");
+        let background = scene.background.expect("Missing background");
+        let tracer = ReferenceTracer::new(&triangles, &[], &materials, background, ShaderConfig::default());
+
+        let (camera, projection) = camera(&scene, 16, 16);
+        let image = tracer.render(&camera, &projection, 16, 16, 16, 1);
+        assert_eq!(image.pixels.len(), 16 * 16);
+        assert!(image.pixels.iter().flatten().all(|value| value.is_finite() && *value >= 0.0));
+        assert_eq!(image, tracer.render(&camera, &projection, 16, 16, 16, 1));
+
+        let mean = image.mean_luminance();
+        assert!((mean - CORNELL_BOX_MEAN_LUMINANCE).abs() < CORNELL_BOX_MEAN_LUMINANCE * 0.01, "{}", mean);
+    }
+
+    #[test]
+    fn test_reference_background_and_light() {
+        // A light filling the left half of the view in front of a colored background
+        let materials = [Material::new([1.0, 0.5, 0.25], [0.0; 3], 1.0, 2.0, 0.0)];
+        let spheres = [Sphere::new(Point3::new(-101.0, 0.0, 0.0), 100.0, 0, [-1; 4])];
+        let background = Background::default().with_color([0.2, 0.4, 0.6]);
+        let tracer = ReferenceTracer::new(&[], &spheres, &materials, background, ShaderConfig::default());
+
+        let camera = Camera::look_at(Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, -1.0), Vector3::unit_y());
+        let projection = Projection::new(4, 4, Deg(10.0), 0.1, 1000.0);
+        let image = tracer.render(&camera, &projection, 4, 4, 4, 7);
+        let intensity = background.intensity;
+        assert_eq!(image.pixel(3, 0), [0.2 * intensity, 0.4 * intensity, 0.6 * intensity]);
+
+        // Looking straight at the light shows its emission
+        let camera = Camera::look_at(Point3::new(0.0, 0.0, 0.0), Point3::new(-1.0, 0.0, 0.0), Vector3::unit_y());
+        let image = tracer.render(&camera, &projection, 4, 4, 4, 7);
+        assert!(image.pixels.iter().all(|&pixel| pixel == [2.0, 1.0, 0.5]));
+    }
+}
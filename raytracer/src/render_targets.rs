@@ -0,0 +1,266 @@
+use wgpu::SurfaceConfiguration;
+use wgpu_utils::{BindGroupDescriptor, BindingResourceTemplate, BufferType, create_color_texture};
+
+/// The storage textures with the size of the window (times the supersampling factor), written by the raytracing and denoising passes.
+///
+/// They have to be recreated together with the bind groups that reference them whenever the window is resized,
+/// otherwise the passes would be dispatched for the new size but write into textures of the old size.
+pub struct RenderTargets {
+    pub color: wgpu::Texture,
+    pub color_view: wgpu::TextureView,
+    pub moment_view: wgpu::TextureView,
+    pub gbuffer_view: wgpu::TextureView,
+    pub hdr: wgpu::Texture,
+    pub hdr_view: wgpu::TextureView,
+    pub denoising_view: wgpu::TextureView,
+    pub atrous: wgpu::Texture,
+    pub atrous_view: wgpu::TextureView,
+    /// Format of the color, denoising and À-Trous textures, the format of the surface.
+    pub format: wgpu::TextureFormat,
+}
+
+impl RenderTargets {
+    /// Creates the textures for the size of `config`, `color` is the color texture created by `setup_gpu`.
+    pub fn new(color: wgpu::Texture, device: &wgpu::Device, config: &SurfaceConfiguration, denoising_history_length: u32) -> Self {
+        let color_view = color.create_view(&wgpu::TextureViewDescriptor::default());
+
+        // Texture to accumulate the per pixel sample moments for adaptive sampling
+        // Layer 0 holds the mean color and sample count, layer 1 the luminance moments
+        let moment_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Moment Buffer"),
+            view_formats: &[],
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 2,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba32Float,
+            usage: wgpu::TextureUsages::STORAGE_BINDING,
+        });
+        let moment_view = moment_texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+
+        // G-buffer with the first hit of each pixel, used by the edge-avoiding À-Trous denoiser
+        // Holds the normal (xyz) and the hit distance (w)
+        let gbuffer_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("G-Buffer"),
+            view_formats: &[],
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba32Float,
+            usage: wgpu::TextureUsages::STORAGE_BINDING,
+        });
+        let gbuffer_view = gbuffer_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        // Linear color of the raytracing pass before it is clamped to the 8 bit color buffer, read back for HDR file output
+        let hdr = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("HDR Buffer"),
+            view_formats: &[],
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba32Float,
+            usage: wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+        });
+        let hdr_view = hdr.create_view(&wgpu::TextureViewDescriptor::default());
+
+        // Define Texture to store the temporal denoising result to use it in the next frames again for temporal denoising
+        // Every layer holds one frame of the history, the layer of the current frame rotates each frame
+        let denoising_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Denoising Buffer"),
+            view_formats: &[config.format], // Use the same format as the color buffer
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: denoising_history_length,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format, // Use the same format as the color buffer
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+        });
+        // Create a view for the denoising texture, an array view even with a single layer
+        let denoising_view = denoising_texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+
+        // Result of an À-Trous iteration, copied back into the color buffer before the next iteration
+        let atrous = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("À-Trous Buffer"),
+            view_formats: &[config.format],
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+        });
+        let atrous_view = atrous.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self { color, color_view, moment_view, gbuffer_view, hdr, hdr_view, denoising_view, atrous, atrous_view, format: config.format }
+    }
+
+    /// Surface configuration with the size of the textures, `supersample` times the size of the window in each direction.
+    pub fn target_config(config: &SurfaceConfiguration, supersample: u32) -> SurfaceConfiguration {
+        SurfaceConfiguration {
+            width: config.width * supersample,
+            height: config.height * supersample,
+            ..config.clone()
+        }
+    }
+
+    /// Recreates all textures for the current size of `config` and the supersampling factor.
+    pub fn resize(&mut self, device: &wgpu::Device, config: &SurfaceConfiguration, supersample: u32, denoising_history_length: u32) {
+        let config = Self::target_config(config, supersample);
+        *self = Self::new(create_color_texture(device, &config), device, &config, denoising_history_length);
+    }
+
+    /// Width and height of the textures.
+    pub fn size(&self) -> (u32, u32) {
+        (self.color.width(), self.color.height())
+    }
+
+    /// Bindings of the raytracing pass: color, moment, g-buffer and HDR buffer, followed by the tile traced by a dispatch.
+    pub fn raytracing_bind_group_descriptor<'a>(&'a self, tile_buffer: &'a wgpu::Buffer) -> BindGroupDescriptor<'a> {
+        BindGroupDescriptor::new(
+            Some("raytracing"),
+            wgpu::ShaderStages::COMPUTE,
+            vec![
+                BufferType::with_storage_format(
+                    BindingResourceTemplate::StorageTexture(
+                        wgpu::BindingResource::TextureView(&self.color_view)
+                    ),
+                    wgpu::TextureViewDimension::D2,
+                    self.format
+                ),
+                BufferType::with_storage_format(
+                    BindingResourceTemplate::StorageTexture(
+                        wgpu::BindingResource::TextureView(&self.moment_view)
+                    ),
+                    wgpu::TextureViewDimension::D2Array,
+                    wgpu::TextureFormat::Rgba32Float
+                ),
+                BufferType::with_storage_format(
+                    BindingResourceTemplate::StorageTexture(
+                        wgpu::BindingResource::TextureView(&self.gbuffer_view)
+                    ),
+                    wgpu::TextureViewDimension::D2,
+                    wgpu::TextureFormat::Rgba32Float
+                ),
+                BufferType::with_storage_format(
+                    BindingResourceTemplate::StorageTexture(
+                        wgpu::BindingResource::TextureView(&self.hdr_view)
+                    ),
+                    wgpu::TextureViewDimension::D2,
+                    wgpu::TextureFormat::Rgba32Float
+                ),
+                BufferType::new(
+                    BindingResourceTemplate::BufferUniform(
+                        tile_buffer.as_entire_binding()
+                    )
+                )
+            ]
+        )
+    }
+
+    /// Bindings of the denoising pass, the buffers hold the current and the previous camera and the pass number.
+    pub fn denoising_bind_group_descriptor<'a>(&'a self, camera_buffer: &'a wgpu::Buffer, denoising_camera_buffer: &'a wgpu::Buffer, denoising_pass_buffer: &'a wgpu::Buffer) -> BindGroupDescriptor<'a> {
+        BindGroupDescriptor::new(
+            Some("denoising"),
+            wgpu::ShaderStages::COMPUTE,
+            vec![
+                BufferType::with_storage_format(
+                    BindingResourceTemplate::StorageTexture(
+                        wgpu::BindingResource::TextureView(&self.color_view),
+                    ),
+                    wgpu::TextureViewDimension::D2,
+                    self.format
+                ),
+                BufferType::with_storage_format(
+                    BindingResourceTemplate::StorageTexture(
+                        wgpu::BindingResource::TextureView(&self.denoising_view),
+                    ),
+                    wgpu::TextureViewDimension::D2Array,
+                    self.format
+                ),
+                BufferType::new(
+                    BindingResourceTemplate::BufferUniform(
+                        camera_buffer.as_entire_binding()
+                    )
+                ),
+                BufferType::new(
+                    BindingResourceTemplate::BufferUniform(
+                        denoising_camera_buffer.as_entire_binding()
+                    ),
+                ),
+                BufferType::new(
+                    BindingResourceTemplate::BufferUniform(
+                        denoising_pass_buffer.as_entire_binding()
+                    )
+                ),
+                BufferType::with_storage_format(
+                    BindingResourceTemplate::StorageTexture(
+                        wgpu::BindingResource::TextureView(&self.gbuffer_view),
+                    ),
+                    wgpu::TextureViewDimension::D2,
+                    wgpu::TextureFormat::Rgba32Float
+                ),
+                BufferType::with_storage_format(
+                    BindingResourceTemplate::StorageTexture(
+                        wgpu::BindingResource::TextureView(&self.atrous_view),
+                    ),
+                    wgpu::TextureViewDimension::D2,
+                    self.format
+                )
+            ]
+        )
+    }
+
+    /// Bindings of the pass that draws the color texture to the screen.
+    pub fn screen_bind_group_descriptor<'a>(&'a self, sampler: &'a wgpu::Sampler) -> BindGroupDescriptor<'a> {
+        BindGroupDescriptor::new(
+            Some("screen_transfer"),
+            wgpu::ShaderStages::FRAGMENT,
+            vec![
+                BufferType::new(
+                    BindingResourceTemplate::Sampler(
+                        wgpu::BindingResource::Sampler(sampler)
+                    )
+                ),
+                BufferType::with_view_dimension(
+                    BindingResourceTemplate::TextureView(
+                        wgpu::BindingResource::TextureView(&self.color_view)
+                    ),
+                    wgpu::TextureViewDimension::D2
+                )
+            ]
+        )
+    }
+}
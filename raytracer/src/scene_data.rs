@@ -0,0 +1,70 @@
+use image::DynamicImage;
+use scene::{Config, Material, Sphere, TextureFilter, Triangle};
+
+use crate::helper::{add_materials_from_config, add_placeholder_material, add_textures_from_config, has_triangles, load_background,
+    setup_spheres, setup_tris_objects, validate_scene, LoadStage};
+
+/// The scene of a config as it was read from disk, before anything is uploaded to the gpu.
+///
+/// `State` keeps it to set the scene up on a new device after the device was lost, so the models and textures
+/// don't have to be loaded again and files that changed since the start don't end up in the recovered scene.
+pub struct SceneData {
+    /// The config the scene was loaded from.
+    pub config: Config,
+    /// Triangles of the models and the meshes built in code, with ids validated against the loaded materials and textures.
+    pub triangles: Vec<Triangle>,
+    /// Set if the scene has no triangles and `triangles` only holds the placeholder that keeps the gpu buffer from being empty.
+    pub placeholder_triangle: bool,
+    /// Spheres of the config, or the placeholder of a scene without spheres.
+    pub spheres: Vec<Sphere>,
+    /// Materials of the config followed by the ones of the models, or the placeholder material of a scene without any.
+    pub materials: Vec<Material>,
+    /// Decoded textures of the config followed by the ones of the glTF model, in the order of the texture ids.
+    pub textures: Vec<DynamicImage>,
+    /// Scaling filter of each config texture, see `add_textures_from_config`.
+    pub texture_filters: Vec<TextureFilter>,
+    /// The HDRI background, see `load_background`.
+    pub background: Option<DynamicImage>,
+}
+
+impl SceneData {
+    /// Loads the materials, textures, models and background of `userconfig`.
+    ///
+    /// Missing textures and invalid ids are replaced and logged, the scene is still shown.
+    ///
+    /// # Errors
+    ///
+    /// Returns a message if a model file can't be loaded.
+    pub fn load(userconfig: Config, progress: &mut dyn FnMut(LoadStage, f32)) -> Result<Self, String> {
+        let mut materials: Vec<Material> = Vec::new();
+        let mut textures: Vec<DynamicImage> = Vec::new();
+        let mut scene_warnings = Vec::new();
+
+        add_materials_from_config(&mut materials, &userconfig.materials);
+        // Textures added after the config textures, e.g. from gltf models, use the default filter
+        let texture_filters = add_textures_from_config(&mut textures, &userconfig.textures, &mut scene_warnings);
+
+        let placeholder_triangle = !has_triangles(&userconfig);
+        let (triangles, _, config) = setup_tris_objects(userconfig, &mut materials, &mut textures, &mut scene_warnings, progress)
+            .map_err(|e| e.to_string())?;
+        add_placeholder_material(&mut materials);
+
+        let mut spheres = setup_spheres(&config);
+        scene_warnings.extend(validate_scene(&mut [], &mut spheres, materials.len(), textures.len()));
+        for warning in scene_warnings {
+            log::warn!("{}", warning);
+        }
+
+        let background = load_background(&config);
+        Ok(Self {
+            config,
+            triangles,
+            placeholder_triangle,
+            spheres,
+            materials,
+            textures,
+            texture_filters,
+            background,
+        })
+    }
+}
@@ -1,16 +1,34 @@
 use std::collections::VecDeque;
-use image::DynamicImage;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use winit::{event::*, window::Window};
 use egui_wgpu::ScreenDescriptor;
 
-use wgpu_utils::{BufferInitDescriptor, BindGroupDescriptor, BufferType, BindingResourceTemplate, setup_gpu};
+use wgpu_utils::{create_color_texture, BufferInitDescriptor, BindGroupDescriptor, BufferType, BindingResourceTemplate, DeviceInfo, GpuTimer, setup_gpu};
 
 use gui::{EguiRenderer, gui, GuiConfig};
 
-use scene::{Camera, CameraUniform, CameraController, Projection, Background, Material, ShaderConfig, Sphere};
+use scene::{sample_offsets, SAMPLE_OFFSET_COUNT, raycast, Camera, DebugView, JitterMode, CameraAnimation, CameraUniform, CameraController, CameraPath, Projection, Background, Config, KeyAction, KeyBindings, Material, SceneSource, SessionState, ShaderConfig, Sphere, Triangle, TriangleUniform};
 
-use crate::helper::{add_materials_from_config, add_textures_from_config, setup_bvh, setup_hdri, setup_textures, setup_tris_objects};
-use crate::helper::setup_camera;
+use crate::helper::{BenchmarkResult, OutputFormat, save_hdr_image, is_minimized, scaled_window_size, session_window_size, clamp_supersample, render_mode, RenderMode, Tile, TileSchedule, unpremultiply_alpha, collect_emissive_triangles, surface_area_per_material, create_compute_pipeline, create_shader_module, denoising_pass_value, grab_cursor, setup_bvh, setup_hdri, setup_blue_noise, setup_textures, validate_scene};
+use crate::helper::{setup_camera, no_progress, LoadStage};
+use crate::bvh_cache;
+use crate::render_targets::RenderTargets;
+use crate::scene_data::SceneData;
+use crate::timing::Instant;
+#[cfg(feature = "hot-reload")]
+use crate::hot_reload::ShaderReload;
+
+/// Maximum time between the two clicks of a double click.
+const DOUBLE_CLICK_TIME: std::time::Duration = std::time::Duration::from_millis(400);
+/// Maximum cursor movement in pixels between the two clicks of a double click.
+const DOUBLE_CLICK_DISTANCE: f64 = 4.0;
+/// Distance the camera stops in front of a double clicked surface.
+const FLY_TO_STANDOFF: f32 = 1.0;
+const FLY_TO_DURATION: std::time::Duration = std::time::Duration::from_millis(300);
+
+/// Message of the device lost callback when the device is dropped, see `State::from_scene`.
+const DEVICE_DROPPED_MESSAGE: &str = "Device dropped.";
 
 pub struct State<'a>{
     pub window: Window,
@@ -18,20 +36,48 @@ pub struct State<'a>{
     device: wgpu::Device,
     queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
+    // Adapter, limits and features captured when the device was created
+    device_info: DeviceInfo,
+    // Set by the device lost callback, the state has to be replaced with `recreate`
+    device_lost: Arc<AtomicBool>,
+    // The loaded scene, kept to set up the scene on a new device after the device was lost
+    scene: SceneData,
     pub size: winit::dpi::PhysicalSize<u32>,
+    // Scale factor of the monitor the window is on, `size` is converted with it when the window moves to another monitor
+    scale_factor: f64,
+    // Storage textures with the size of the window, recreated on resize together with the bind groups using them
+    render_targets: RenderTargets,
+    // Requested supersampling factor, the factor in use is in shader_config.supersample and may be smaller
+    supersample: u32,
     //Antialiasing Sample Textures
     denoising_camera_buffer: wgpu::Buffer,
     denoising_pass_buffer: wgpu::Buffer,
     denoising_bind_group: wgpu::BindGroup,
+    denoising_bind_group_layout: wgpu::BindGroupLayout,
+    denoising_history_length: u32,
+    denoising_history_index: u32,
     denoising_pipeline: wgpu::ComputePipeline,
     //Raytracing
     shader_config: ShaderConfig,
     shader_config_buffer: wgpu::Buffer,
     shader_config_bind_group: wgpu::BindGroup,
     ray_tracing_pipeline: wgpu::ComputePipeline,
+    // Recreates the compute pipelines when their shaders change on disk
+    #[cfg(feature = "hot-reload")]
+    shader_reload: Option<ShaderReload>,
     raytracing_bind_group: wgpu::BindGroup,
+    raytracing_bind_group_layout: wgpu::BindGroupLayout,
+    // Offset and size of the tile traced by the next dispatch of the raytracing pass
+    tile_buffer: wgpu::Buffer,
+    // Tiles of the render textures that are traced next, recreated on resize
+    tile_schedule: TileSchedule,
+    tile_size: u32,
+    tiles_per_frame: u32,
     screen_render_pipeline: wgpu::RenderPipeline,
     screen_bind_group: wgpu::BindGroup,
+    screen_bind_group_layout: wgpu::BindGroupLayout,
+    screen_sampler: wgpu::Sampler,
+    clear_color: wgpu::Color,
     //Camera
     camera: Camera,
     projection: Projection,
@@ -40,15 +86,39 @@ pub struct State<'a>{
     camera_buffer: wgpu::Buffer,
     camera_bind_group: wgpu::BindGroup,
     pub mouse_pressed: bool,
+    // The cursor is grabbed and hidden, all mouse motion turns the camera without holding a button
+    cursor_captured: bool,
+    camera_path: Option<CameraPath>,
+    camera_path_time: f32,
+    pub camera_path_playing: bool,
+    camera_animation: Option<CameraAnimation>,
+    cursor_position: winit::dpi::PhysicalPosition<f64>,
+    last_click: Option<(Instant, winit::dpi::PhysicalPosition<f64>)>,
+    // Session file of the config file scene, written by `save_session`
+    session_path: Option<std::path::PathBuf>,
     //Objects
+    // CPU copies of the scene geometry on the gpu, used to pick the surface under the cursor
+    spheres: Vec<Sphere>,
+    triangles: Vec<Triangle>,
     object_bind_group: wgpu::BindGroup,
     bvh_bind_group: wgpu::BindGroup,
     //Textures
     texture_bind_group: wgpu::BindGroup,
+    //Materials
+    materials: Vec<Material>,
+    material_buffer: wgpu::Buffer,
+    //Background
+    background: Background,
+    background_buffer: wgpu::Buffer,
+    reset_history: bool,
     //GUI
     pub egui: gui::EguiRenderer,
     pub gui_config: GuiConfig,
     fps: VecDeque<f32>,
+    // Set while the window has a zero size, rendering is skipped until it is restored
+    minimized: bool,
+    // Set while the window is unfocused or paused in the GUI, the last frame is shown without tracing new samples
+    paused: bool,
 }
 
 impl<'a> State<'a>{  
@@ -57,13 +127,19 @@ impl<'a> State<'a>{
     /// This function initializes the gpu, sets up the camera and objects, sets up the render pipelines for raytracing, denoising and screen rendering, and initializes the GUI.
     /// # Arguments
     /// * `window` - A `Window` instance representing the window in which the state will be rendered.
+    /// * `scene` - The scene to render, either a config file path (`None` uses the default config) or a `Config`, e.g. built with the `SceneBuilder`.
     /// # Returns
     /// * `Self` - A new `State` instance.
+    /// # Errors
+    /// Returns a message if the config can't be loaded, no gpu device can be created or a shader doesn't compile.
     /// # Asynchronous
     /// This function is asynchronous and must be awaited.
     /// 
     /// # Gpu Setup
-    /// The gpu setup involves creating an instance that serves as a handle to our GPU. It also sets up the surface, config, color buffer view, userconfig, and size.
+    /// The gpu setup involves creating an instance that serves as a handle to our GPU. It also sets up the surface, config, color buffer texture, userconfig, and size.
+    /// # Session
+    /// For a config file scene, the camera pose, shader settings and window size saved by `save_session` are restored
+    /// from the session file next to the config (see `SessionState::path_for`) and override the values of the config.
     /// # Camera Setup
     /// The camera setup involves creating a camera, projection, camera controller, and camera uniform. It also creates a buffer to hold the camera data and a bind group for the camera.
     /// # Object Setup
@@ -82,35 +158,108 @@ impl<'a> State<'a>{
     /// The denoising setup involves creating a denoising buffer and a bind group for it. It also passes camera info to the denoising shader and creates a buffer to hold the camera data for denoising. It also creates a buffer to hold the denoising pass number, a view for the denoising texture, a bind group descriptor for the denoising step, and a pipeline layout for denoising. Finally, it loads the denoising shader and creates a denoising pipeline.
     /// # Screen rendering Setup
     /// The screen rendering setup involves creating a sampler for transferring color data from render to screen texture. It also creates a bind group layout for the shader and a bind group for the screen rendering pipeline. It loads the screen shader and creates a screen pipeline layout.
-    pub async fn new(window: Window, config_path: Option<&str>) -> Self {
-        //---------Setup Hardware---------
-        let config_path: &str = match config_path {
-            Some(path) => {
-                println!("Using config file: {}", path);
-                path
+    pub async fn new(window: Window, scene: impl Into<SceneSource>) -> Result<Self, String> {
+        Self::new_with_progress(window, scene, &mut no_progress).await
+    }
+
+    /// Same as `new`, but reports the progress of the slow setup steps (model loading, texture upload and BVH build),
+    /// e.g. to draw a progress bar while a large scene loads.
+    ///
+    /// `progress` is called with the current `LoadStage` and the progress of that stage between 0.0 and 1.0.
+    pub async fn new_with_progress(window: Window, scene: impl Into<SceneSource>, progress: &mut dyn FnMut(LoadStage, f32)) -> Result<Self, String> {
+        //---------Load Scene---------
+        let scene = scene.into();
+        match &scene {
+            SceneSource::File(path) => log::info!("Using config file: {}", path),
+            SceneSource::Scene(_) => log::info!("Using scene built in code"),
+        }
+        // Scenes built in code and the default scene used without a config file have no file to put the session next to
+        let session_path = match &scene {
+            SceneSource::File(path) if std::path::Path::new(path).exists() => Some(SessionState::path_for(path)),
+            _ => None,
+        };
+        let userconfig = scene.load().map_err(|e| format!("Error loading config: {}", e))?;
+        let session = session_path.as_ref().filter(|path| path.exists()).and_then(|path| match SessionState::load(path) {
+            Ok(session) => {
+                log::info!("Restoring session: {}", path.display());
+                Some(session)
             }
-            None => {
-                println!("Using default config");
-                "res/config.toml"
+            Err(e) => {
+                log::warn!("{}, using the config", e);
+                None
             }
-        };
+        });
+        // The surface is configured with the restored size if the window could be resized right away, otherwise by the Resized event
+        if let Some(session) = session.as_ref().filter(|session| !is_minimized(session.window_size.into())) {
+            let [width, height] = session.window_size;
+            let _ = window.request_inner_size(winit::dpi::PhysicalSize::new(width, height));
+        }
+        Self::from_config(window, userconfig, session_path, session, progress).await
+    }
+
+    /// Loads the scene of a config and sets it up on the gpu, the camera and shader settings of `session` override the config.
+    async fn from_config(window: Window, userconfig: Config, session_path: Option<std::path::PathBuf>, session: Option<SessionState>, progress: &mut dyn FnMut(LoadStage, f32)) -> Result<Self, String> {
+        let scene = SceneData::load(userconfig, progress)?;
+        Self::from_scene(window, scene, session_path, session, progress).await
+    }
+
+    /// Sets up the gpu and uploads a loaded scene, the camera and shader settings of `session` override the config.
+    async fn from_scene(window: Window, scene: SceneData, session_path: Option<std::path::PathBuf>, session: Option<SessionState>, progress: &mut dyn FnMut(LoadStage, f32)) -> Result<Self, String> {
+        //---------Setup Hardware---------
 
         let (window,
             device, 
             queue, 
             surface, 
             config, 
-            color_buffer_view, 
+            present_modes,
+            color_texture, 
             userconfig, 
-            size) = setup_gpu(window, config_path).await;
-        println!("Hardware initialized");
+            size,
+            device_info) = setup_gpu(window, scene.config.clone()).await?;
+        log::info!("Hardware initialized");
+        log::debug!("{}", device_info);
+
+        // A driver reset (e.g. the TDR timeout on Windows) loses the device, the event loop then recreates the state
+        let device_lost = Arc::new(AtomicBool::new(false));
+        let lost = device_lost.clone();
+        device.set_device_lost_callback(move |reason, message| {
+            // wgpu also calls this with `Unknown` when the device is dropped, e.g. on exit, which isn't a loss
+            if message != DEVICE_DROPPED_MESSAGE {
+                log::error!("GPU device lost ({:?}): {}", reason, message);
+                lost.store(true, Ordering::Relaxed);
+            }
+        });
+        // wgpu panics on uncaptured errors by default, a new device can continue after the gpu ran out of memory
+        // and calls on a device that is already lost fail validation
+        let lost = device_lost.clone();
+        device.on_uncaptured_error(Box::new(move |error| match error {
+            wgpu::Error::OutOfMemory { .. } => {
+                log::error!("GPU out of memory: {}", error);
+                lost.store(true, Ordering::Relaxed);
+            }
+            wgpu::Error::Validation { .. } if lost.load(Ordering::Relaxed) => log::error!("GPU device lost: {}", error),
+            wgpu::Error::Validation { .. } => panic!("wgpu error: {}", error),
+        }));
 
         //-------------Camera-------------
         // Create a camera with configured settings
-        let (camera, 
-            projection, 
+        let (mut camera, 
+            mut projection, 
             camera_controller, 
-            camera_uniform) = setup_camera(&config, &userconfig);
+            mut camera_uniform) = setup_camera(&config, &userconfig);
+        if let Some(session) = &session {
+            session.apply_camera(&mut camera, &mut projection);
+            camera_uniform.update_view_proj(&camera, &projection);
+        }
+
+        // Play the camera path from the config right away if one is defined
+        let camera_path = userconfig.camera_path.clone();
+        let camera_path_playing = camera_path.is_some();
+
+        // The surface expects premultiplied colors, see the screen pipeline blend state
+        let [r, g, b, a] = userconfig.render_clear_color.map(|c| c as f64);
+        let clear_color = wgpu::Color { r: r * a, g: g * a, b: b * a, a };
 
         // Create a buffer to hold the camera data
         let camera_descriptor = BufferInitDescriptor::new(Some("Camera Buffer"), wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC);
@@ -128,43 +277,55 @@ impl<'a> State<'a>{
         );
         let camera_bind_group = camera_bind_group_descriptor.generate_bind_group(&device);
         let camera_bind_group_layout = camera_bind_group_descriptor.layout.unwrap();
-        println!("Camera ready");
+        log::info!("Camera ready");
 
         //============== Load Render Objects ==============
-        //---------- Load Materials and Textures fromc config ----
-        let mut materials: Vec<Material> = Vec::new();
-        let mut textures: Vec<DynamicImage> = Vec::new();
-
-        add_materials_from_config(&mut materials, &userconfig.materials);
-        add_textures_from_config(&mut textures, &userconfig.textures);
-
-
-        //---------- Load Triangles(Vertecies) ----------
-        let (triangles, 
-            triangles_uniform, 
-            userconfig) = setup_tris_objects(userconfig, &mut materials, &mut textures);
+        // The loaded scene stays unchanged for `recreate`, the ids of textures the gpu can't hold are reset on the copies
+        let materials = scene.materials.clone();
+        let mut triangles = scene.triangles.clone();
+        let mut spheres = scene.spheres.clone();
+
+        //------Textures------
+        // Create 3D textures with textures from config and glft
+        // With one array layer per texture, or if the textures don't fit into an atlas, the textures beyond the layer limit
+        // of the GPU are dropped and the triangles and spheres referencing them are shown without texture
+        let loaded_texture_count = scene.textures.len();
+        let (textures_buffer, texture_regions) = setup_textures(scene.textures.clone(), &scene.texture_filters, &device, &queue, &config, userconfig.render_texture_array_layers, progress);
+        let texture_count = loaded_texture_count.min(texture_regions.len());
+        if texture_count < loaded_texture_count {
+            for warning in validate_scene(&mut triangles, &mut spheres, materials.len(), texture_count) {
+                log::warn!("{}", warning);
+            }
+        }
+        let triangles_uniform: Vec<TriangleUniform> = if scene.placeholder_triangle {
+            vec![TriangleUniform::empty()]
+        } else {
+            triangles.iter().map(|triangle| TriangleUniform::new(*triangle)).collect()
+        };
 
         // Create a buffer to hold the vertex data of the triangles
         let vertex_buffer_descriptor = BufferInitDescriptor::new(Some("Vertex Buffer"), wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST);
         let vertex_buffer = vertex_buffer_descriptor.create_new_buffer(&device, &triangles_uniform);
 
-        // --------- Load Spheres ---------
-        // Load spheres amd store them as gpu compatible vector
-        let default_sphere = Vec::from([Sphere::empty()]);
-        let spheres: &Vec<Sphere> = 
-            match &userconfig.spheres {
-                Some(userspheres) => {
-                    userspheres
-                }
-                None => {
-                    &default_sphere
-                }
-            };
-
+        // --------- Spheres ---------
         // Create a buffer to hold the sphere data
         let sphere_buffer_descriptor = BufferInitDescriptor::new(Some("Sphere Buffer"), wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST);
         let sphere_buffer = sphere_buffer_descriptor.create_new_buffer(&device, &spheres);
 
+        // --------- Emissive Triangles ---------
+        // Indices of the triangles that are sampled as area lights
+        let mut emissive_triangles = collect_emissive_triangles(&triangles, &materials);
+        log::info!("Emissive triangles: {}", emissive_triangles.len());
+        for (material_id, area) in surface_area_per_material(&triangles, materials.len()).iter().enumerate() {
+            log::debug!("Material {}: triangle surface area {}", material_id, area);
+        }
+        if emissive_triangles.is_empty() {
+            // The buffer can't be empty, u32::MAX tells the shader that there are no area lights
+            emissive_triangles.push(u32::MAX);
+        }
+        let emissive_buffer_descriptor = BufferInitDescriptor::new(Some("Emissive Triangle Buffer"), wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST);
+        let emissive_buffer = emissive_buffer_descriptor.create_new_buffer(&device, &emissive_triangles);
+
         // ------ Combined Bind Group ---------
         // Create a bind group for all primitive objects
         let mut object_bind_group_descriptor = BindGroupDescriptor::new(
@@ -180,6 +341,11 @@ impl<'a> State<'a>{
                     BindingResourceTemplate::BufferStorage(
                         sphere_buffer.as_entire_binding()
                     )
+                ),
+                BufferType::new(
+                    BindingResourceTemplate::BufferStorage(
+                        emissive_buffer.as_entire_binding()
+                    )
                 )
             ]
         );
@@ -187,19 +353,18 @@ impl<'a> State<'a>{
         // Generate the object bind group & layout
         let object_bind_group = object_bind_group_descriptor.generate_bind_group(&device);
         let object_bind_group_layout = object_bind_group_descriptor.layout.unwrap();
-        println!("Meshes ready");
+        log::info!("Meshes ready");
 
         //-------------BVH---------------
-        //-This only works for triangles-
-
-        // Create a bvh for the triangles
-        let (bvh_uniform, bvh_prim_indices) = setup_bvh(&triangles);
+        // Create a bvh for the triangles and spheres
+        let bvh_cache_path = if userconfig.bvh_cache { bvh_cache::cache_path(&userconfig.model_paths) } else { None };
+        let (bvh_uniform, bvh_prim_indices) = setup_bvh(&triangles, &spheres, userconfig.bvh_leaf_size, userconfig.bvh_builder, bvh_cache_path.as_deref(), progress)?;
         
         // Store bvh nodes in a buffer as a array
         let bvh_descriptor = BufferInitDescriptor::new(Some("BVH Buffer"), wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST);
         let bvh_buffer = bvh_descriptor.create_new_buffer(&device, &bvh_uniform);
 
-        // Store prim indices of the bvh nodes in a buffer as a array (these are needed for a tree traversal on the gpu)
+        // Store kind and index of the primitives in the bvh leaves in a buffer as a array (these are needed for a tree traversal on the gpu)
         let bvh_indices_descriptor = BufferInitDescriptor::new(Some("BVH Prim Indices Buffer"), wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST);
         let bvh_prim_indices_buffer = bvh_indices_descriptor.create_new_buffer(&device, &bvh_prim_indices);
 
@@ -224,13 +389,11 @@ impl<'a> State<'a>{
         // Generate the bvh bind group & layout
         let bvh_bind_group = bvh_bind_group_descriptor.generate_bind_group(&device);
         let bvh_bind_goup_layout = bvh_bind_group_descriptor.layout.unwrap();
-        println!("BVH ready");
+        log::info!("BVH ready");
 
-        //------Textures & Materials------
-        // Create 3D textures with textures from config and glft or background hdri 
-        
-        let textures_buffer = setup_textures(textures, &device, &queue, &config);
-        let background_texture = setup_hdri(&userconfig, &device, &queue, &config);
+        //------Materials & Background------
+        let background_texture = setup_hdri(scene.background.as_ref(), &device, &queue, &config);
+        let background_texture_loaded = userconfig.background_path.as_deref().is_some_and(|path| !path.is_empty());
 
         // Create a buffer to hold the material data from config and glft
         let material_descriptor = BufferInitDescriptor::new(Some("Material Buffer"), wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST);
@@ -247,7 +410,16 @@ impl<'a> State<'a>{
         let background_descriptor = BufferInitDescriptor::new(Some("Background Buffer"), wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST);
         let background_buffer = background_descriptor.create_new_buffer(&device, &[background]);
 
-        println!("Background: {:?}", background);
+        // Create a buffer with the atlas page or array layer of every texture
+        let texture_region_descriptor = BufferInitDescriptor::new(Some("Texture Region Buffer"), wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST);
+        let texture_region_buffer = texture_region_descriptor.create_new_buffer(&device, &texture_regions);
+
+        log::debug!("Background: {:?}", background);
+
+        // Sub-pixel offsets and blue noise for the sampling patterns of the camera rays
+        let sample_offset_descriptor = BufferInitDescriptor::new(Some("Sample Offset Buffer"), wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST);
+        let sample_offset_buffer = sample_offset_descriptor.create_new_buffer(&device, &sample_offsets(SAMPLE_OFFSET_COUNT));
+        let blue_noise_texture = setup_blue_noise(&device, &queue, &config);
 
         // Create a sampler for all textures
         let texture_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
@@ -261,10 +433,34 @@ impl<'a> State<'a>{
             anisotropy_clamp: 1,
             ..Default::default()
         });
+        // Materials pick one of the samplers by their wrap mode
+        let texture_sampler_clamp = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Sampler Clamp"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            anisotropy_clamp: 1,
+            ..Default::default()
+        });
+        let texture_sampler_mirror = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Sampler Mirror"),
+            address_mode_u: wgpu::AddressMode::MirrorRepeat,
+            address_mode_v: wgpu::AddressMode::MirrorRepeat,
+            address_mode_w: wgpu::AddressMode::MirrorRepeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            anisotropy_clamp: 1,
+            ..Default::default()
+        });
 
         // Create a bind group for the textures, materials and background
         let textures_view = textures_buffer.create_view(&wgpu::TextureViewDescriptor::default());
         let background_texture_view = background_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let blue_noise_view = blue_noise_texture.create_view(&wgpu::TextureViewDescriptor::default());
         let mut texture_bind_group_descriptor = BindGroupDescriptor::new(
             Some("textures_and_materials"),
             wgpu::ShaderStages::COMPUTE,
@@ -295,6 +491,32 @@ impl<'a> State<'a>{
                         wgpu::BindingResource::TextureView(&background_texture_view)
                     ),
                     wgpu::TextureViewDimension::D2,
+                ),
+                BufferType::new(
+                    BindingResourceTemplate::Sampler(
+                        wgpu::BindingResource::Sampler(&texture_sampler_clamp)
+                    )
+                ),
+                BufferType::new(
+                    BindingResourceTemplate::Sampler(
+                        wgpu::BindingResource::Sampler(&texture_sampler_mirror)
+                    )
+                ),
+                BufferType::new(
+                    BindingResourceTemplate::BufferStorage(
+                        texture_region_buffer.as_entire_binding()
+                    )
+                ),
+                BufferType::with_view_dimension(
+                    BindingResourceTemplate::TextureView(
+                        wgpu::BindingResource::TextureView(&blue_noise_view)
+                    ),
+                    wgpu::TextureViewDimension::D2,
+                ),
+                BufferType::new(
+                    BindingResourceTemplate::BufferUniform(
+                        sample_offset_buffer.as_entire_binding()
+                    )
                 )
             ]
         );
@@ -302,21 +524,29 @@ impl<'a> State<'a>{
         // Generate the texture bind group & layout
         let texture_bind_group = texture_bind_group_descriptor.generate_bind_group(&device);
         let texture_bind_group_layout = texture_bind_group_descriptor.layout.unwrap();
-        println!("Textures ready");
+        log::info!("Textures ready");
 
         //============= Shader&Pipeline Setup =============
 
         //--------Shader config-----------
-        // Initialize shader config
-        let shader_config = ShaderConfig::default();
+        // Initialize shader config with the settings from the config (defaults for missing values)
+        let mut shader_config = userconfig.shader_config;
+        // The settings of the last session win over the config, except the background transparency of the [render] section
+        if let Some(session) = &session {
+            shader_config = ShaderConfig { transparent_background: shader_config.transparent_background, ..session.shader_config };
+        }
+        // The render textures can't be larger than the GPU allows, the shader is told the factor that is actually used
+        let supersample = shader_config.supersample_factor();
+        shader_config.supersample = clamp_supersample(config.width, config.height, supersample, device.limits().max_texture_dimension_2d);
         // Create a buffer to hold the shader config data
         let shader_config_descriptor = BufferInitDescriptor::new(Some("Shader Config Buffer"), wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST);
         let shader_config_buffer =  shader_config_descriptor.create_new_buffer(&device, &[shader_config]);
 
         // Create a bind group for pasing the shader config to the shader
+        // The screen shader reads the exposure
         let mut shader_config_bind_group_descriptor = BindGroupDescriptor::new(
             Some("shader_config"),
-            wgpu::ShaderStages::COMPUTE,
+            wgpu::ShaderStages::COMPUTE | wgpu::ShaderStages::FRAGMENT,
             vec![
                 BufferType::new(
                     BindingResourceTemplate::BufferUniform(
@@ -328,28 +558,27 @@ impl<'a> State<'a>{
         // Generate the shader config bind group & layout
         let shader_config_bind_group = shader_config_bind_group_descriptor.generate_bind_group(&device);
         let shader_config_bind_group_layout = shader_config_bind_group_descriptor.layout.unwrap();
-        println!("Shader config ready");
+        log::info!("Shader config ready");
 
         //----------Raytracing-------------
-        // Load the ray tracing shader
-        let ray_generation_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Ray Generation Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("../../res/shader/raygen.wgsl").into()), 
-        });
+        // Textures with the size of the window times the supersampling factor, recreated on resize
+        let denoising_history_length = shader_config.denoise_history_layers();
+        let render_target_config = RenderTargets::target_config(&config, shader_config.supersample);
+        let color_texture = if shader_config.supersample > 1 { create_color_texture(&device, &render_target_config) } else { color_texture };
+        let render_targets = RenderTargets::new(color_texture, &device, &render_target_config, denoising_history_length);
+
+        // The raytracing pass is split into tiles, each one is dispatched with its own submit
+        let tile_size = userconfig.render_tile_size;
+        let tiles_per_frame = userconfig.render_tiles_per_frame;
+        let tile_schedule = TileSchedule::new(render_targets.size().0, render_targets.size().1, tile_size);
+        let tile_buffer_descriptor = BufferInitDescriptor::new(Some("Tile Buffer"), wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST);
+        let tile_buffer = tile_buffer_descriptor.create_new_buffer(&device, &[tile_schedule.tile(0).to_uniform()]);
+        if tile_schedule.tile_count() > 1 {
+            log::info!("Raytracing in {} tiles of {}x{}", tile_schedule.tile_count(), tile_size, tile_size);
+        }
 
         // Create the bind group layout for the shader
-        let mut raytracing_bind_group_descriptior = BindGroupDescriptor::new(
-            Some("raytracing"),
-            wgpu::ShaderStages::COMPUTE,
-            vec![
-                BufferType::with_view_dimension(
-                    BindingResourceTemplate::StorageTexture(
-                        wgpu::BindingResource::TextureView(&color_buffer_view)
-                    ),
-                    wgpu::TextureViewDimension::D2
-                )
-            ]
-        );
+        let mut raytracing_bind_group_descriptior = render_targets.raytracing_bind_group_descriptor(&tile_buffer);
 
         // Generate the raytracing bind group & layout
         let raytracing_bind_group = raytracing_bind_group_descriptior.generate_bind_group(&device);
@@ -369,44 +598,11 @@ impl<'a> State<'a>{
             ],
             push_constant_ranges: &[],
         });
-        // Create the ray tracing pipeline
-        let ray_tracing_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: Some("Ray Tracing Pipeline"),
-            layout: Some(&raytracing_pipeline_layout),
-            module: &ray_generation_shader,
-            entry_point: "main",
-            }
-        );
-        println!("Raytracing shader&pipeline ready");
+        // Load the ray tracing shader and create the ray tracing pipeline
+        let ray_tracing_pipeline = create_compute_pipeline(&device, &raytracing_pipeline_layout, "Ray Tracing Pipeline", "Ray Generation Shader", include_str!("../../res/shader/raygen.wgsl"))?;
+        log::info!("Raytracing shader&pipeline ready");
 
         //--------Denoising pass----------
-        // Load the denoising shader
-        let denoising_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Denoising Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("../../res/shader/denoising.wgsl").into()),
-        });
-
-        // Define Texture to store the temporal denoising result to use it in the next frame again for temporal denoising
-        let denoising_texture = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("Denoising Buffer"),
-            view_formats: &[config.format], // Use the same format as the color buffer
-            size: wgpu::Extent3d {
-                width: config.width,
-                height: config.height,
-                depth_or_array_layers: 1,
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: config.format, // Use the same format as the color buffer
-            usage: wgpu::TextureUsages::TEXTURE_BINDING
-                | wgpu::TextureUsages::COPY_DST
-                | wgpu::TextureUsages::STORAGE_BINDING
-                | wgpu::TextureUsages::COPY_SRC,
-        });        
-        // Create a view for the denoising texture
-        let denoising_texture_view = denoising_texture.create_view(&wgpu::TextureViewDescriptor::default());
-
         // ~~~Pass camera info to denoising shader~~~
         let denoising_camera: Camera = camera.clone();
         let mut denoising_camera_uniform = CameraUniform::new();
@@ -421,39 +617,7 @@ impl<'a> State<'a>{
         let denoising_pass_buffer = denoising_pass_buffer_descriptor.create_new_buffer(&device, &[0u32]);
 
         // Create a bind group descriptor for denoising step
-        let mut denoising_bind_group_descriptor = BindGroupDescriptor::new(
-            Some("denoising"),
-            wgpu::ShaderStages::COMPUTE,
-            vec![
-                BufferType::with_view_dimension(
-                    BindingResourceTemplate::StorageTexture(
-                        wgpu::BindingResource::TextureView(&color_buffer_view),
-                    ),
-                    wgpu::TextureViewDimension::D2
-                ),
-                BufferType::with_view_dimension(
-                    BindingResourceTemplate::StorageTexture(
-                        wgpu::BindingResource::TextureView(&denoising_texture_view),
-                    ),
-                    wgpu::TextureViewDimension::D2
-                ),
-                BufferType::new(
-                    BindingResourceTemplate::BufferUniform(
-                        camera_buffer.as_entire_binding()
-                    )
-                ),
-                BufferType::new(
-                    BindingResourceTemplate::BufferUniform(
-                        denoising_camera_buffer.as_entire_binding()
-                    ),
-                ),
-                BufferType::new(
-                    BindingResourceTemplate::BufferUniform(
-                        denoising_pass_buffer.as_entire_binding()
-                    )
-                )
-            ]
-        );
+        let mut denoising_bind_group_descriptor = render_targets.denoising_bind_group_descriptor(&camera_buffer, &denoising_camera_buffer, &denoising_pass_buffer);
         // Generate the denoising bind group & layout
         let denoising_bind_group = denoising_bind_group_descriptor.generate_bind_group(&device);
         let denoising_bind_group_layout = denoising_bind_group_descriptor.layout.unwrap();
@@ -467,26 +631,20 @@ impl<'a> State<'a>{
             push_constant_ranges: &[],
         });
 
-        // Create the denoising pipeline
-        let denoising_pipeline = device.create_compute_pipeline(
-            &wgpu::ComputePipelineDescriptor {
-                label: Some("Denoising Pipeline"),
-                layout: Some(&denoising_pipeline_layout),
-                module: &denoising_shader,
-                entry_point: "main",
-            }
-        );
-        println!("Denoising shader&pipeline ready");
+        // Load the denoising shader and create the denoising pipeline
+        let denoising_pipeline = create_compute_pipeline(&device, &denoising_pipeline_layout, "Denoising Pipeline", "Denoising Shader", include_str!("../../res/shader/denoising.wgsl"))?;
+        log::info!("Denoising shader&pipeline ready");
+
+        // The shaders in res/shader replace the compiled in ones whenever they are saved
+        #[cfg(feature = "hot-reload")]
+        let shader_reload = ShaderReload::new(raytracing_pipeline_layout, denoising_pipeline_layout);
 
         //----------Transfer to screen-------------
         // Load the screen transfer shader
-        let screen_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Screen Transfer Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("../../res/shader/screen-shader.wgsl").into()),
-        });
+        let screen_shader = create_shader_module(&device, "Screen Transfer Shader", include_str!("../../res/shader/screen-shader.wgsl"))?;
 
         // Create a Sampler for trasfering color data from rendered texture to screen texture
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        let screen_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             label: Some("Sampler"),
             address_mode_u: wgpu::AddressMode::Repeat,
             address_mode_v: wgpu::AddressMode::Repeat,
@@ -499,23 +657,7 @@ impl<'a> State<'a>{
         });
 
         // Create a bind group layout for the shader
-        let mut screen_bind_group_descriptor = BindGroupDescriptor::new(
-            Some("screen_transfer"),
-            wgpu::ShaderStages::FRAGMENT,
-            vec![
-                BufferType::new(
-                    BindingResourceTemplate::Sampler(
-                        wgpu::BindingResource::Sampler(&sampler)
-                    )
-                ),
-                BufferType::with_view_dimension(
-                    BindingResourceTemplate::TextureView(
-                        wgpu::BindingResource::TextureView(&color_buffer_view)
-                    ),
-                    wgpu::TextureViewDimension::D2
-                )
-            ]
-        );
+        let mut screen_bind_group_descriptor = render_targets.screen_bind_group_descriptor(&screen_sampler);
 
         // Generate the screen bind group & layout
         let screen_bind_group = screen_bind_group_descriptor.generate_bind_group(&device);
@@ -525,7 +667,7 @@ impl<'a> State<'a>{
         let screen_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Screen Transfer Pipeline Layout"),
-                bind_group_layouts: &[&screen_bind_group_layout],
+                bind_group_layouts: &[&screen_bind_group_layout, &shader_config_bind_group_layout],
                 push_constant_ranges: &[],
             });
         
@@ -543,7 +685,8 @@ impl<'a> State<'a>{
                 targets: &[
                     Some(wgpu::ColorTargetState {
                     format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
+                    // The color buffer holds premultiplied colors, so a transparent background shows the clear color
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
                     write_mask: wgpu::ColorWrites::ALL,
                     })
                 ],
@@ -570,7 +713,7 @@ impl<'a> State<'a>{
             // Used with a multiview render pass, this indicates how many array layers the attachments will have.
             multiview: None,
         });
-        println!("Screen transfer shader&pipeline ready");
+        log::info!("Screen transfer shader&pipeline ready");
 
 
         //=============== GUI config (not directly in contact with wgpu) ===============
@@ -583,25 +726,53 @@ impl<'a> State<'a>{
         );
 
         let fps: VecDeque<f32> = VecDeque::with_capacity(100);
+        let scale_factor = window.scale_factor();
+
+        // Only the present modes supported by the surface are offered in the GUI, the same goes for the HDRI background
+        let gui_config = GuiConfig {
+            present_mode: config.present_mode,
+            present_modes,
+            background_texture_loaded,
+            device_info: device_info.to_string(),
+            key_bindings: camera_controller.key_bindings().clone(),
+            ..GuiConfig::default()
+        };
         
-        Self {
+        Ok(Self {
             surface,
             device,
             queue,
             config,
+            device_info,
             window,
             size,
+            scale_factor,
+            render_targets,
+            supersample,
             denoising_camera_buffer,
             denoising_pass_buffer,
             denoising_bind_group,
+            denoising_bind_group_layout,
+            denoising_history_length,
+            denoising_history_index: 0,
             denoising_pipeline,
             shader_config,
             shader_config_buffer,
             shader_config_bind_group,
             ray_tracing_pipeline,
+            #[cfg(feature = "hot-reload")]
+            shader_reload,
             raytracing_bind_group,
+            raytracing_bind_group_layout,
+            tile_buffer,
+            tile_schedule,
+            tile_size,
+            tiles_per_frame,
             screen_render_pipeline,
             screen_bind_group,
+            screen_bind_group_layout,
+            screen_sampler,
+            clear_color,
             camera,
             projection,
             camera_controller,
@@ -609,33 +780,258 @@ impl<'a> State<'a>{
             camera_bind_group,
             camera_uniform,
             mouse_pressed: false,
+            cursor_captured: false,
+            camera_path,
+            camera_path_time: 0.0,
+            camera_path_playing,
+            camera_animation: None,
+            cursor_position: winit::dpi::PhysicalPosition::new(0.0, 0.0),
+            last_click: None,
+            session_path,
+            device_lost,
+            scene,
+            spheres,
+            triangles,
             object_bind_group,
             bvh_bind_group,
             texture_bind_group,
+            materials,
+            material_buffer,
+            background,
+            background_buffer,
+            reset_history: false,
             egui,
-            gui_config: GuiConfig::default(),
+            gui_config,
             fps,
-        }
+            minimized: false,
+            paused: false,
+        })
     }
 
     /// Resizes the application window and updates the configuration.
     ///
     /// This function takes a new size as input and checks if the width and height are greater than 0.
-    /// If they are, it resizes the projection, updates the size and configuration, and reconfigures the surface.
+    /// If they are, it resizes the projection, updates the size and configuration, reconfigures the surface
+    /// and recreates the storage textures of the compute passes with their bind groups.
+    /// A size of 0 (e.g. a minimized window) marks the state as minimized, which pauses rendering until the window is restored.
     ///
     /// # Arguments
     ///
     /// * `new_size` - A `PhysicalSize<u32>` object representing the new size of the window.
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
-        if new_size.width > 0 && new_size.height > 0 {
+        self.minimized = is_minimized(new_size);
+        if !self.minimized {
             self.projection.resize(new_size.width, new_size.height);
             self.size = new_size;
             self.config.width = new_size.width;
             self.config.height = new_size.height;
             self.surface.configure(&self.device, &self.config);
+            self.recreate_render_targets();
         }
     }
 
+    /// Resolution of the traced image, the window size times the supersampling factor.
+    pub fn render_size(&self) -> (u32, u32) {
+        self.render_targets.size()
+    }
+
+    /// Resizes to the physical size of the window at the new scale factor, e.g. after it moved to a monitor with a different DPI.
+    ///
+    /// The logical size of the window stays the same. Returns the new physical size, which the window has to be set to as well.
+    /// The GUI doesn't need an update, it reads the scale factor from the window for every frame.
+    pub fn set_scale_factor(&mut self, scale_factor: f64) -> winit::dpi::PhysicalSize<u32> {
+        let new_size = scaled_window_size(self.size, self.scale_factor, scale_factor);
+        self.scale_factor = scale_factor;
+        self.resize(new_size);
+        new_size
+    }
+
+    /// Renders at `factor` times the window resolution in each direction, the screen pass averages the texels of every pixel.
+    ///
+    /// Screenshots and saved images have the full supersampled resolution. The factor is clamped to
+    /// `1..=ShaderConfig::MAX_SUPERSAMPLE` and to the largest texture size of the GPU.
+    pub fn set_supersample(&mut self, factor: u32) {
+        self.supersample = factor.clamp(1, ShaderConfig::MAX_SUPERSAMPLE);
+        self.recreate_render_targets();
+        log::info!("Supersampling {}x, rendering at {}x{}", self.shader_config.supersample, self.render_targets.size().0, self.render_targets.size().1);
+    }
+
+    /// Recreates the storage textures for the current surface size and the bind groups that reference them.
+    ///
+    /// The history of the old size can't be reused, so the accumulation starts over.
+    fn recreate_render_targets(&mut self) {
+        let max_dimension = self.device.limits().max_texture_dimension_2d;
+        self.shader_config.supersample = clamp_supersample(self.config.width, self.config.height, self.supersample, max_dimension);
+        if self.shader_config.supersample < self.supersample {
+            log::warn!("Supersampling {}x exceeds the maximum texture size of {}, using {}x", self.supersample, max_dimension, self.shader_config.supersample);
+        }
+        self.render_targets.resize(&self.device, &self.config, self.shader_config.supersample, self.denoising_history_length);
+        self.raytracing_bind_group = self.render_targets.raytracing_bind_group_descriptor(&self.tile_buffer)
+            .generate_bind_group_with_layout(&self.device, &self.raytracing_bind_group_layout);
+        self.tile_schedule = TileSchedule::new(self.render_targets.size().0, self.render_targets.size().1, self.tile_size);
+        self.denoising_bind_group = self.render_targets.denoising_bind_group_descriptor(&self.camera_buffer, &self.denoising_camera_buffer, &self.denoising_pass_buffer)
+            .generate_bind_group_with_layout(&self.device, &self.denoising_bind_group_layout);
+        self.screen_bind_group = self.render_targets.screen_bind_group_descriptor(&self.screen_sampler)
+            .generate_bind_group_with_layout(&self.device, &self.screen_bind_group_layout);
+        self.denoising_history_index = 0;
+        self.reset_history = true;
+    }
+
+    /// Triangles of the loaded scene in world space, from the 3D models and the meshes added in code, e.g. to export them.
+    ///
+    /// A scene without triangles returns an empty slice, the placeholder that keeps the gpu buffer from being empty is left out.
+    pub fn triangles(&self) -> &[Triangle] {
+        if self.scene.placeholder_triangle {
+            &[]
+        } else {
+            &self.triangles
+        }
+    }
+
+    /// Spheres of the loaded scene, without the placeholder of a scene without spheres.
+    pub fn spheres(&self) -> &[Sphere] {
+        match self.spheres.as_slice() {
+            [sphere] if sphere.radius[0] == 0.0 => &[],
+            spheres => spheres,
+        }
+    }
+
+    /// Materials of the config followed by the ones of the 3D models, indexed by the material ids of the primitives.
+    ///
+    /// Includes the edits made in the GUI. A scene without materials has a single default material.
+    pub fn materials(&self) -> &[Material] {
+        &self.materials
+    }
+
+    /// Writes the camera pose, the shader and denoising settings and the window size to the session file of the scene,
+    /// they are restored when the scene is loaded again. Scenes built in code have no session file.
+    ///
+    /// While the window is minimized the window size of the previous session is kept.
+    pub fn save_session(&self) {
+        let Some(path) = &self.session_path else {
+            return;
+        };
+        let previous_size = SessionState::load(path).ok().map(|previous| previous.window_size);
+        let window_size = session_window_size(self.size, previous_size);
+        let session = SessionState::new(&self.camera, &self.projection, window_size, self.shader_config);
+        match session.save(path) {
+            Ok(()) => log::info!("Saved session: {}", path.display()),
+            Err(e) => log::warn!("Could not save session {}: {}", path.display(), e),
+        }
+    }
+
+    /// Keyboard controls of the config, see `[keys]`.
+    pub fn key_bindings(&self) -> &KeyBindings {
+        self.camera_controller.key_bindings()
+    }
+
+    /// Adapter name, backend, key limits and enabled features of the gpu, e.g. for bug reports.
+    pub fn device_info(&self) -> DeviceInfo {
+        self.device_info.clone()
+    }
+
+    /// Returns `true` after the gpu device was lost, e.g. because the driver was reset. The state can't render anymore
+    /// and has to be replaced with `recreate`.
+    pub fn is_device_lost(&self) -> bool {
+        self.device_lost.load(Ordering::Relaxed)
+    }
+
+    /// Destroys the gpu device like a driver reset would, the device lost callback marks the state as lost
+    /// (see `is_device_lost`). Used to test `recreate`.
+    #[cfg(feature = "test-hooks")]
+    pub fn destroy_device(&self) {
+        self.device.destroy();
+        self.device.poll(wgpu::Maintain::Wait);
+    }
+
+    /// Submits the commands to the queue, unless the device lost callback marked the state as lost.
+    ///
+    /// wgpu panics if a submission to a lost device fails, the commands are dropped instead so the event loop can `recreate` the state.
+    fn submit(&self, commands: wgpu::CommandBuffer) {
+        if !self.is_device_lost() {
+            self.queue.submit(std::iter::once(commands));
+        }
+    }
+
+    /// Creates a new device and sets up the scene on it again, to continue after the device was lost (see `is_device_lost`).
+    ///
+    /// The scene is uploaded from the data loaded at the start, models and textures aren't read from disk again.
+    /// The camera, the shader settings, the materials edited in the GUI and the frame limit are kept.
+    ///
+    /// # Errors
+    ///
+    /// Returns a message if the scene can't be set up again, e.g. because no new gpu device can be created.
+    pub async fn recreate(self) -> Result<Self, String> {
+        log::warn!("Recreating the GPU device");
+        let session = SessionState::new(&self.camera, &self.projection, [self.size.width, self.size.height], self.shader_config);
+        let (frame_limit, frame_limit_unlimited, present_mode) = (self.gui_config.frame_limit, self.gui_config.frame_limit_unlimited, self.gui_config.present_mode);
+        let (window, mut scene, session_path, materials) = {
+            // Everything on the old device is dropped before the new one is created
+            let state = self;
+            let State { window, scene, session_path, materials, .. } = state;
+            (window, scene, session_path, materials)
+        };
+        // The GUI only edits the materials of the loaded scene, so the new device gets the edited ones
+        if materials.len() == scene.materials.len() {
+            scene.materials = materials;
+        } else {
+            log::warn!("Expected {} materials, but the GUI has {}. Using the materials of the config.", scene.materials.len(), materials.len());
+        }
+
+        let mut state = Self::from_scene(window, scene, session_path, Some(session), &mut no_progress).await?;
+        state.gui_config.frame_limit = frame_limit;
+        state.gui_config.frame_limit_unlimited = frame_limit_unlimited;
+        // Applied by the next render like a present mode selected in the GUI, the new surface may not support the old one
+        state.gui_config.present_mode = wgpu_utils::select_present_mode(&state.gui_config.present_modes, present_mode);
+        Ok(state)
+    }
+
+    /// Returns `true` while the window is minimized and rendering is paused.
+    pub fn is_minimized(&self) -> bool {
+        self.minimized
+    }
+
+    /// Returns `true` while rendering is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Pauses or resumes tracing new frames, e.g. when the window loses or regains the focus.
+    ///
+    /// While paused `render` only presents the last frame and the GUI, so the GPU is mostly idle.
+    pub fn set_paused(&mut self, paused: bool) {
+        if paused != self.paused {
+            log::info!("{} rendering", if paused { "Pausing" } else { "Resuming" });
+        }
+        self.paused = paused;
+        self.gui_config.paused = paused;
+    }
+
+    /// Returns `true` while the cursor is captured for mouse-look, see `set_cursor_captured`.
+    pub fn is_cursor_captured(&self) -> bool {
+        self.cursor_captured
+    }
+
+    /// Grabs and hides the cursor, so all mouse motion turns the camera like in a first person game, or releases it.
+    ///
+    /// Falls back to confining the cursor to the window where locking it isn't supported.
+    /// If the cursor can't be grabbed at all, mouse-look stays captured with a visible cursor.
+    pub fn set_cursor_captured(&mut self, captured: bool) {
+        if captured == self.cursor_captured {
+            return;
+        }
+        if captured {
+            match grab_cursor(|mode| self.window.set_cursor_grab(mode)) {
+                Ok(mode) => log::info!("Cursor captured ({:?}), press Escape or right click to release it", mode),
+                Err(error) => log::warn!("Could not grab the cursor: {}", error),
+            }
+        } else if let Err(error) = self.window.set_cursor_grab(winit::window::CursorGrabMode::None) {
+            log::warn!("Could not release the cursor: {}", error);
+        }
+        self.window.set_cursor_visible(!captured);
+        self.cursor_captured = captured;
+    }
+
     /// Handles input events for the application.
     ///
     /// This function takes a window event as input and processes it.
@@ -657,6 +1053,63 @@ impl<'a> State<'a>{
         }
         // Camera updates
         match event {
+            // Toggle the camera path playback
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key: key,
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } if self.camera_controller.key_bindings().matches(KeyAction::CameraPath, key) && self.camera_path.is_some() => {
+                self.camera_path_playing = !self.camera_path_playing;
+                log::info!("Camera path playback: {}", self.camera_path_playing);
+                true
+            }
+            // Step the exposure
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key: key,
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } if matches!(self.camera_controller.key_bindings().action(key), Some(KeyAction::ExposureUp | KeyAction::ExposureDown)) => {
+                let up = self.camera_controller.key_bindings().action(key) == Some(KeyAction::ExposureUp);
+                self.shader_config.step_exposure(if up { 1.0 } else { -1.0 });
+                log::info!("Exposure: {:+.1} EV", self.shader_config.exposure);
+                true
+            }
+            // Release the captured cursor, the exit key only closes the window while the cursor is free
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key: key,
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } if self.cursor_captured && self.camera_controller.key_bindings().matches(KeyAction::Exit, key) => {
+                self.set_cursor_captured(false);
+                true
+            }
+            // Cycle the debug views
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key: key,
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } if self.camera_controller.key_bindings().matches(KeyAction::DebugView, key) => {
+                let debug_view = DebugView::from_u32(self.shader_config.debug_view).next();
+                log::info!("Debug view: {}", debug_view.name());
+                self.set_debug_view(debug_view);
+                true
+            }
             WindowEvent::KeyboardInput {
                 event:
                     KeyEvent {
@@ -676,26 +1129,156 @@ impl<'a> State<'a>{
                 ..
             } => {
                 self.mouse_pressed = *state == ElementState::Pressed;
+                if self.mouse_pressed {
+                    self.handle_click();
+                }
                 true
             }
+            WindowEvent::MouseInput {
+                button: MouseButton::Right,
+                state: ElementState::Pressed,
+                ..
+            } => {
+                self.set_cursor_captured(!self.cursor_captured);
+                true
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor_position = *position;
+                false
+            }
             _ => false,
         }
     }
 
+    /// Switches the output of the raytracing shader.
+    ///
+    /// The temporal history is reset, so the new view isn't blended with the frames of the previous one.
+    pub fn set_debug_view(&mut self, debug_view: DebugView) {
+        if self.shader_config.debug_view != debug_view as u32 {
+            self.shader_config.debug_view = debug_view as u32;
+            self.reset_history = true;
+        }
+    }
+
+    /// Shader settings of the next frame, including the changes made in the GUI.
+    pub fn shader_config(&self) -> ShaderConfig {
+        self.shader_config
+    }
+
+    /// Replaces the shader settings, e.g. to compare renders with and without a feature.
+    ///
+    /// The temporal history is reset, so the new settings aren't blended with the frames of the previous ones.
+    pub fn set_shader_config(&mut self, shader_config: ShaderConfig) {
+        self.shader_config = shader_config;
+        self.reset_history = true;
+    }
+
+    /// Starts flying the camera to the surface under the cursor if this click completes a double click.
+    fn handle_click(&mut self) {
+        let now = Instant::now();
+        let is_double_click = self.last_click.is_some_and(|(time, position)| {
+            now.duration_since(time) <= DOUBLE_CLICK_TIME
+                && (position.x - self.cursor_position.x).abs() <= DOUBLE_CLICK_DISTANCE
+                && (position.y - self.cursor_position.y).abs() <= DOUBLE_CLICK_DISTANCE
+        });
+        if !is_double_click {
+            self.last_click = Some((now, self.cursor_position));
+            return;
+        }
+        // A third click starts a new double click
+        self.last_click = None;
+
+        let screen_x = self.cursor_position.x as f32 / self.size.width as f32;
+        let screen_y = self.cursor_position.y as f32 / self.size.height as f32;
+        let direction = self.camera.ray_direction(&self.projection, screen_x, screen_y);
+        let origin = glam::Vec3::new(self.camera.position.x, self.camera.position.y, self.camera.position.z);
+        let hit = raycast(origin, glam::Vec3::new(direction.x, direction.y, direction.z), &self.spheres, &self.triangles, self.shader_config.ray_max_ray_distance);
+
+        // Clicking empty space does nothing
+        if let Some(distance) = hit {
+            let target = self.camera.position + direction * (distance - FLY_TO_STANDOFF).max(0.0);
+            log::info!("Flying to {:?}, {:.2} units away", target, distance);
+            self.camera_animation = Some(CameraAnimation::new(self.camera.position, target, FLY_TO_DURATION));
+        }
+    }
+
+    /// Recreates the compute pipelines of the shaders that were saved since the last frame.
+    ///
+    /// A shader that doesn't compile is reported in the log and the old pipeline is kept, so the application keeps running.
+    #[cfg(feature = "hot-reload")]
+    fn reload_changed_shaders(&mut self) {
+        let Some(reload) = &self.shader_reload else {
+            return;
+        };
+        for file in reload.watcher.changed_shaders() {
+            let (label, shader_name, layout) = match file.as_str() {
+                "raygen.wgsl" => ("Ray Tracing Pipeline", "Ray Generation Shader", &reload.raytracing_pipeline_layout),
+                "denoising.wgsl" => ("Denoising Pipeline", "Denoising Shader", &reload.denoising_pipeline_layout),
+                _ => {
+                    log::warn!("Shader {} changed, only the compute shaders are reloaded, restart to apply it", file);
+                    continue;
+                }
+            };
+            let pipeline = std::fs::read_to_string(reload.watcher.path(&file))
+                .map_err(|e| format!("Could not read shader {}: {}", file, e))
+                .and_then(|source| create_compute_pipeline(&self.device, layout, label, shader_name, &source));
+            match pipeline {
+                Ok(pipeline) if file == "raygen.wgsl" => self.ray_tracing_pipeline = pipeline,
+                Ok(pipeline) => self.denoising_pipeline = pipeline,
+                Err(e) => {
+                    log::error!("{}", e);
+                    continue;
+                }
+            }
+            log::info!("Reloaded shader {}", file);
+            self.reset_history = true;
+        }
+    }
+
     /// Updates the state of the application.
     ///
     /// This function takes a duration as input and updates the camera, shader configuration, and render texture size.
+    /// If a camera path is playing, the camera is set from the path instead of the camera controller.
     /// It also calculates and stores the frames per second.
+    /// With the `hot-reload` feature the compute pipelines of the shaders changed on disk are recreated first.
     ///
     /// # Arguments
     ///
     /// * `dt` - A `Duration` object representing the time since the last update.
     pub fn update(&mut self, dt: std::time::Duration) {
+        #[cfg(feature = "hot-reload")]
+        self.reload_changed_shaders();
+
         // Update the camera
-        self.camera_controller.update_camera(&mut self.camera, dt);
+        match (&self.camera_path, self.camera_path_playing) {
+            (Some(camera_path), true) => {
+                self.camera_path_time += dt.as_secs_f32();
+                if let Some(camera) = camera_path.camera_at(self.camera_path_time) {
+                    self.camera = camera;
+                }
+            }
+            _ => {
+                self.camera_controller.update_camera(&mut self.camera, &mut self.projection, dt);
+                // The fly-to animation overrides the movement keys until it is done
+                if let Some(animation) = &mut self.camera_animation {
+                    if animation.update_camera(&mut self.camera, dt) {
+                        self.camera_animation = None;
+                    }
+                }
+            }
+        }
+        let previous_camera_uniform = self.camera_uniform;
         self.camera_uniform.update_view_proj(&self.camera, &self.projection);
         self.camera_uniform.update_frame();
 
+        // Jittered rays blur the image while the camera moves, they are only needed for still images
+        let camera_moving = self.camera_uniform.view_changed(&previous_camera_uniform);
+        self.shader_config.jitter_enabled = JitterMode::from_u32(self.shader_config.jitter_mode).enabled(camera_moving) as i32;
+        // A pass spread over several frames would mix tiles traced from the old and the new view
+        if camera_moving {
+            self.tile_schedule.restart();
+        }
+
         self.queue.write_buffer(
             &self.camera_buffer,
             0,
@@ -717,7 +1300,8 @@ impl<'a> State<'a>{
         // );
 
         // ---------FPS---------
-        // println!("FPS: {}", 1.0 / dt.as_secs_f32());
+        // Per frame, so only at trace level (RUST_LOG=raytracing_lib=trace)
+        log::trace!("FPS: {}", 1.0 / dt.as_secs_f32());
 
         // If fps is empty fill with the first value
         if self.fps.is_empty() {
@@ -739,11 +1323,18 @@ impl<'a> State<'a>{
     /// 4. Render pass: This pass renders the final image to the screen.
     ///
     /// Each pass is performed by dispatching workgroups to the GPU. The number of workgroups is determined by the size of the output image.
+    /// While paused the first three passes are skipped and the last frame is presented again, while minimized nothing is rendered.
     ///
     /// # Returns
     ///
     /// A `Result` that is `Ok` if the rendering was successful, or `Err` if there was an error with the surface.
     pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        // Don't touch the surface while minimized, acquiring a zero sized texture can fail or panic
+        let mode = render_mode(self.minimized, self.paused);
+        if mode == RenderMode::Skip {
+            return Ok(());
+        }
+
         // Get the current output texture from the surface
         let output = self.surface.get_current_texture()?;
         
@@ -752,105 +1343,22 @@ impl<'a> State<'a>{
         .texture
         .create_view(&wgpu::TextureViewDescriptor::default());
     
-        // Create a command encoder
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render Encoder"),
-            });
-
-        //----------Raytracing pass----------
-        {
-            // Start a compute pass for ray tracing
-            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("Ray Tracing Pass"),
-                timestamp_writes: None,
-            });
-    
-            // Set ray tracing pipeline and bind group
-            compute_pass.set_pipeline(&self.ray_tracing_pipeline);
-            compute_pass.set_bind_group(0, &self.shader_config_bind_group, &[]);
-            compute_pass.set_bind_group(1, &self.raytracing_bind_group, &[]);
-            compute_pass.set_bind_group(2, &self.camera_bind_group, &[]);
-            compute_pass.set_bind_group(3, &self.object_bind_group, &[]);
-            compute_pass.set_bind_group(4, &self.texture_bind_group, &[]);
-            compute_pass.set_bind_group(5, &self.bvh_bind_group, &[]);
-    
-            // Dispatch workgroups for ray tracing (adjust dimensions as needed)
-            compute_pass.dispatch_workgroups(
-                (self.config.width + 7) / 8,
-                (self.config.height + 7) / 8,
-                1
-            );
-        }
-
-
-        //----------1. Denoising pass----------
-        {
-            self.queue.write_buffer(
-                &self.denoising_pass_buffer,
-                0,
-                bytemuck::cast_slice(&[0u32]),
-            );
-
-            let mut denoise_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("1. Denoising Pass"),
-                timestamp_writes: None,
-            });
-    
-            // Set denoising pipeline and bind group
-            denoise_pass.set_pipeline(&self.denoising_pipeline);
-            denoise_pass.set_bind_group(0, &self.denoising_bind_group, &[]);
-            denoise_pass.set_bind_group(1, &self.shader_config_bind_group, &[]);
-    
-            // Dispatch workgroups for denoising (adjust dimensions as needed)
-            denoise_pass.dispatch_workgroups(
-                (self.config.width + 7) / 8,
-                (self.config.height + 7) / 8,
-                1
-            );
-        }
-
-        // Submit the command encoder for the 1st pass
-        self.queue.submit(std::iter::once(encoder.finish()));
-
-        // Create a new command encoder for the 2nd denoising pass
-        let mut encoder2 = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("Render Encoder 2"),
-        });
-
-        //----------2. Denoising pass----------
-        // Set denoising pass number to 1
-        self.queue.write_buffer(
-            &self.denoising_pass_buffer,
-            0,
-            bytemuck::cast_slice(&[1u32]),
-        );
-
-        // Perform 2. denoising pass
-        {
-            let mut denoise_pass = encoder2.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("2. Denoising Pass"),
-                timestamp_writes: None,
-            });
-    
-            // Set denoising pipeline and bind group
-            denoise_pass.set_pipeline(&self.denoising_pipeline);
-            denoise_pass.set_bind_group(0, &self.denoising_bind_group, &[]);
-            denoise_pass.set_bind_group(1, &self.shader_config_bind_group, &[]);
-    
-            // Dispatch workgroups for denoising (adjust dimensions as needed)
-            denoise_pass.dispatch_workgroups(
-                (self.config.width + 7) / 8,
-                (self.config.height + 7) / 8,
-                1
-            );
+        // Trace the scene and denoise the result in the color buffer, while paused the color buffer still holds the last frame
+        if mode == RenderMode::Trace {
+            let reset_history = std::mem::take(&mut self.reset_history);
+            if self.dispatch_raytracing_and_denoising(reset_history, self.tiles_per_frame) {
+                self.queue.write_buffer(
+                    &self.denoising_camera_buffer,
+                    0,
+                    bytemuck::cast_slice(&[self.camera_uniform]),
+                );
+            } else {
+                // The history is reset once the pass is complete and denoised
+                self.reset_history |= reset_history;
+            }
         }
 
-        // Submit the command encoder for the 1st pass
-        self.queue.submit(std::iter::once(encoder2.finish()));
-
-        // Create a new command encoder for the 2nd denoising pass
+        // Create a new command encoder for the render pass
         let mut encoder3 = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Render Encoder 3"),
         });
@@ -864,12 +1372,7 @@ impl<'a> State<'a>{
                     view: &view,
                     resolve_target: None,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.2,
-                            b: 0.3,
-                            a: 1.0,
-                        }),
+                        load: wgpu::LoadOp::Clear(self.clear_color),
                         store: wgpu::StoreOp::Store,
                     },
                 })],
@@ -881,15 +1384,11 @@ impl<'a> State<'a>{
             // Set the screen rendering pipeline and bind group
             render_pass.set_pipeline(&self.screen_render_pipeline);
             render_pass.set_bind_group(0, &self.screen_bind_group, &[]);
+            render_pass.set_bind_group(1, &self.shader_config_bind_group, &[]);
     
             // Draw using the render pass (adjust the range as needed)
             render_pass.draw(0..6, 0..1);
         }
-        self.queue.write_buffer(
-            &self.denoising_camera_buffer,
-            0,
-            bytemuck::cast_slice(&[self.camera_uniform]),
-        );
     
         // Draw the GUI ontop of the render pass
         let screen_descriptor = ScreenDescriptor {
@@ -897,6 +1396,10 @@ impl<'a> State<'a>{
             pixels_per_point: self.window.scale_factor() as f32,
         };
 
+        let mut changed_materials = Vec::new();
+        let mut background_changed = false;
+        let debug_view = self.shader_config.debug_view;
+        let supersample = self.shader_config.supersample;
         self.egui.draw(
             &self.device,
             &self.queue,
@@ -904,12 +1407,431 @@ impl<'a> State<'a>{
             &self.window,
             &view,
             screen_descriptor,
-            |ui| gui(ui, &self.fps, &mut self.gui_config, &mut self.shader_config),
+            |ui| (changed_materials, background_changed) = gui(ui, &self.fps, &mut self.gui_config, &mut self.shader_config, &mut self.materials, &mut self.background),
         );
 
-        self.queue.submit(std::iter::once(encoder3.finish()));
+        // Upload only the edited materials and restart the temporal accumulation
+        for index in changed_materials {
+            self.queue.write_buffer(
+                &self.material_buffer,
+                (index * std::mem::size_of::<Material>()) as u64,
+                bytemuck::cast_slice(&[self.materials[index]]),
+            );
+            self.reset_history = true;
+        }
+        // A debug view selected in the GUI starts a new accumulation as well
+        if self.shader_config.debug_view != debug_view {
+            self.reset_history = true;
+        }
+        // Upload the background edited in the GUI
+        if background_changed {
+            self.queue.write_buffer(&self.background_buffer, 0, bytemuck::cast_slice(&[self.background]));
+            self.reset_history = true;
+        }
+
+        self.submit(encoder3.finish());
         output.present();
+
+        // Apply the pause button of the GUI
+        if self.gui_config.paused != self.paused {
+            self.set_paused(self.gui_config.paused);
+        }
+
+        // Apply a supersampling factor selected in the GUI, the GUI shows the factor in use
+        if self.shader_config.supersample != supersample {
+            self.set_supersample(self.shader_config.supersample);
+        }
+
+        // Apply a present mode selected in the GUI from the next frame on
+        if self.gui_config.present_mode != self.config.present_mode {
+            log::info!("Switching present mode to {:?}", self.gui_config.present_mode);
+            self.config.present_mode = self.gui_config.present_mode;
+            self.surface.configure(&self.device, &self.config);
+        }
     
         Ok(())
-    }    
+    }
+
+    /// Runs the raytracing pass on up to `max_tiles` tiles and both denoising passes on the color buffer once all tiles are traced.
+    ///
+    /// Every tile and the denoising passes are submitted directly, so the color buffer holds the denoised frame afterwards.
+    /// If `reset_history` is set, the temporal denoiser starts over instead of blending with the previous frames.
+    /// A `max_tiles` of 0 traces all remaining tiles of the screen.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the last tile of the screen was traced and the frame was denoised.
+    fn dispatch_raytracing_and_denoising(&mut self, reset_history: bool, max_tiles: u32) -> bool {
+        //----------Raytracing pass----------
+        // Separate submits keep the GPU from running into the driver timeout on large scenes
+        for tile in self.tile_schedule.next_tiles(max_tiles) {
+            self.dispatch_raytracing_tile(tile);
+        }
+        if !self.tile_schedule.pass_complete() {
+            return false;
+        }
+
+        // The oldest frame of the temporal history is replaced by the current one
+        self.denoising_history_index = (self.denoising_history_index + 1) % self.denoising_history_length;
+
+        //----------1. Denoising pass----------
+        let encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Render Encoder"),
+        });
+        let first_pass: u32 = if reset_history { 2 } else { 0 };
+        self.dispatch_denoising(encoder, first_pass, self.shader_config.first_pass, "1. Denoising Pass");
+
+        //----------2. Denoising pass----------
+        let encoder2 = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Render Encoder 2"),
+        });
+        self.dispatch_denoising(encoder2, 1, self.shader_config.second_pass, "2. Denoising Pass");
+        true
+    }
+
+    /// Traces a tile of the render textures and submits it on its own.
+    fn dispatch_raytracing_tile(&self, tile: Tile) {
+        self.queue.write_buffer(&self.tile_buffer, 0, bytemuck::cast_slice(&[tile.to_uniform()]));
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Render Encoder Tile"),
+        });
+        self.record_raytracing_pass(&mut encoder, tile, None);
+        self.submit(encoder.finish());
+    }
+
+    /// Records the raytracing compute pass of a tile, optionally with timestamp writes to measure it.
+    ///
+    /// The tile has to be written to the tile buffer before the pass is submitted.
+    fn record_raytracing_pass(&self, encoder: &mut wgpu::CommandEncoder, tile: Tile, timestamp_writes: Option<wgpu::ComputePassTimestampWrites>) {
+        // Start a compute pass for ray tracing
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Ray Tracing Pass"),
+            timestamp_writes,
+        });
+
+        // Set ray tracing pipeline and bind group
+        compute_pass.set_pipeline(&self.ray_tracing_pipeline);
+        compute_pass.set_bind_group(0, &self.shader_config_bind_group, &[]);
+        compute_pass.set_bind_group(1, &self.raytracing_bind_group, &[]);
+        compute_pass.set_bind_group(2, &self.camera_bind_group, &[]);
+        compute_pass.set_bind_group(3, &self.object_bind_group, &[]);
+        compute_pass.set_bind_group(4, &self.texture_bind_group, &[]);
+        compute_pass.set_bind_group(5, &self.bvh_bind_group, &[]);
+
+        // Dispatch workgroups for ray tracing, one thread per texel of the tile
+        compute_pass.dispatch_workgroups(
+            (tile.width + 7) / 8,
+            (tile.height + 7) / 8,
+            1
+        );
+    }
+
+    /// Runs one denoising pass and submits it together with the commands already recorded in `encoder`.
+    ///
+    /// The À-Trous filter is dispatched `atrous_iterations` times with a growing stride. Each iteration writes
+    /// into the À-Trous buffer, which is copied back into the color buffer for the next iteration.
+    fn dispatch_denoising(&self, encoder: wgpu::CommandEncoder, pass: u32, pass_mode: i32, label: &str) {
+        let iterations = self.shader_config.denoising_iterations(pass_mode);
+        let mut first_encoder = Some(encoder);
+        for iteration in 0..iterations {
+            let mut encoder = first_encoder.take().unwrap_or_else(|| {
+                self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Render Encoder À-Trous"),
+                })
+            });
+
+            // The iteration and the history layer are passed in the upper bits of the pass number
+            self.queue.write_buffer(
+                &self.denoising_pass_buffer,
+                0,
+                bytemuck::cast_slice(&[denoising_pass_value(pass, iteration, self.denoising_history_index)]),
+            );
+
+            {
+                let mut denoise_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some(label),
+                    timestamp_writes: None,
+                });
+
+                // Set denoising pipeline and bind group
+                denoise_pass.set_pipeline(&self.denoising_pipeline);
+                denoise_pass.set_bind_group(0, &self.denoising_bind_group, &[]);
+                denoise_pass.set_bind_group(1, &self.shader_config_bind_group, &[]);
+
+                // Dispatch workgroups for denoising, one thread per texel of the render textures
+                let (width, height) = self.render_targets.size();
+                denoise_pass.dispatch_workgroups(
+                    (width + 7) / 8,
+                    (height + 7) / 8,
+                    1
+                );
+            }
+
+            if pass_mode == ShaderConfig::ATROUS_PASS {
+                encoder.copy_texture_to_texture(
+                    self.render_targets.atrous.as_image_copy(),
+                    self.render_targets.color.as_image_copy(),
+                    self.render_targets.atrous.size(),
+                );
+            }
+
+            self.submit(encoder.finish());
+        }
+    }
+
+    /// Copies the color buffer from the GPU into an image.
+    ///
+    /// The color buffer holds premultiplied colors, the returned image uses straight alpha like PNG files.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `RgbaImage` of the current color buffer, or an error message if the buffer couldn't be read.
+    fn read_color_buffer(&self) -> Result<image::RgbaImage, String> {
+        let pixels = self.read_texture(&self.render_targets.color, 4)?;
+        let mut image = image::RgbaImage::from_raw(self.render_targets.color.width(), self.render_targets.color.height(), pixels)
+            .ok_or("Color buffer size doesn't match the image size".to_string())?;
+        unpremultiply_alpha(&mut image);
+        Ok(image)
+    }
+
+    /// Copies the linear, unclamped color of the last raytracing pass from the GPU.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the premultiplied RGBA values row by row, or an error message if the buffer couldn't be read.
+    fn read_hdr_buffer(&self) -> Result<Vec<f32>, String> {
+        let bytes = self.read_texture(&self.render_targets.hdr, 16)?;
+        Ok(bytes.chunks_exact(4).map(|value| f32::from_le_bytes([value[0], value[1], value[2], value[3]])).collect())
+    }
+
+    /// Copies a 2D texture with `bytes_per_pixel` bytes per texel from the GPU, returns the rows without padding.
+    fn read_texture(&self, texture: &wgpu::Texture, bytes_per_pixel: u32) -> Result<Vec<u8>, String> {
+        let width = texture.width();
+        let height = texture.height();
+
+        // Rows of a texture to buffer copy have to be aligned to 256 bytes
+        let unpadded_bytes_per_row = width * bytes_per_pixel;
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = (unpadded_bytes_per_row + align - 1) / align * align;
+
+        let output_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Texture Readback"),
+            size: (padded_bytes_per_row * height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Readback Encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &output_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.submit(encoder.finish());
+
+        // Wait for the GPU to finish the copy and map the buffer
+        let buffer_slice = output_buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver.recv()
+            .map_err(|e| format!("Could not read texture: {}", e))?
+            .map_err(|e| format!("Could not map texture: {}", e))?;
+
+        // Remove the row padding
+        let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+        {
+            let data = buffer_slice.get_mapped_range();
+            for row in data.chunks(padded_bytes_per_row as usize) {
+                pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+            }
+        }
+        output_buffer.unmap();
+        Ok(pixels)
+    }
+
+    /// Saves the current content of the color buffer as a PNG file, or the linear color of the last frame as an EXR or PPM file.
+    ///
+    /// The format is selected by the extension of `path`, see `OutputFormat`.
+    /// With a transparent clear color (`[render] clear_color` with alpha 0) the background is transparent in the saved image.
+    pub fn save_screenshot(&self, path: &str) -> Result<(), String> {
+        let format = OutputFormat::from_path(path)?;
+        if format.is_hdr() {
+            let pixels = self.read_hdr_buffer()?;
+            return save_hdr_image(path, self.render_targets.hdr.width(), self.render_targets.hdr.height(), &pixels, format);
+        }
+        let image = self.read_color_buffer()?;
+        image.save(path).map_err(|e| format!("Could not save screenshot {}: {}", path, e))
+    }
+
+    /// Renders the camera path from the config into a numbered image sequence.
+    ///
+    /// The path is stepped with a fixed time step of `1 / fps`, independent of the real frame time.
+    /// Every frame is traced `samples_per_frame` times so the temporal denoiser can converge before it is saved.
+    /// The files are named `frame_00000.png`, `frame_00001.png`, ... and can be turned into a video with ffmpeg:
+    /// `ffmpeg -framerate 30 -i frame_%05d.png video.mp4`
+    ///
+    /// EXR and PPM frames are written from the linear color without denoising instead, averaged over the
+    /// `samples_per_frame` passes, so EXR frames keep values above 1.0 for compositing.
+    ///
+    /// # Arguments
+    ///
+    /// * `output_dir` - The directory the frames are written to. It is created if it doesn't exist.
+    /// * `fps` - The frame rate of the video.
+    /// * `samples_per_frame` - How often every frame is traced before it is saved.
+    /// * `extension` - The file extension selecting the format of the frames: "png", "exr" or "ppm".
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the number of written frames, or an error message if there is no camera path, the format is unknown or a frame couldn't be written.
+    pub fn render_to_file(&mut self, output_dir: &str, fps: f32, samples_per_frame: u32, extension: &str) -> Result<usize, String> {
+        let format = OutputFormat::from_extension(extension)?;
+        let camera_path = self.camera_path.clone().ok_or("No camera path defined in config")?;
+        if fps <= 0.0 {
+            return Err("fps has to be greater than 0".to_string());
+        }
+        std::fs::create_dir_all(output_dir)
+            .map_err(|e| format!("Could not create output directory: {}", e))?;
+
+        let start_time = camera_path.keyframes().first().map(|k| k.time).unwrap_or(0.0);
+        // Every traced frame covers all tiles, even if the interactive rendering stopped in the middle of the screen
+        self.tile_schedule.restart();
+        let frame_count = (camera_path.duration() * fps).floor() as usize + 1;
+
+        for frame in 0..frame_count {
+            if let Some(camera) = camera_path.camera_at(start_time + frame as f32 / fps) {
+                self.camera = camera;
+            }
+
+            let file_path = format!("{}/frame_{:05}.{}", output_dir, frame, format.extension());
+            if format.is_hdr() {
+                let pixels = self.render_linear(samples_per_frame)?;
+                save_hdr_image(&file_path, self.render_targets.hdr.width(), self.render_targets.hdr.height(), &pixels, format)?;
+            } else {
+                for _ in 0..samples_per_frame.max(1) {
+                    self.trace_full_frame();
+                }
+                let image = self.read_color_buffer()?;
+                image.save(&file_path)
+                    .map_err(|e| format!("Could not save frame {}: {}", file_path, e))?;
+            }
+            log::info!("Saved frame {}/{}", frame + 1, frame_count);
+        }
+
+        Ok(frame_count)
+    }
+
+    /// Traces the current view `samples` times and returns the mean linear color before exposure and tone mapping.
+    ///
+    /// The result has four values (RGBA) per pixel of the render resolution, rows from top to bottom, and isn't denoised.
+    /// It is used for the EXR and PPM frames of `render_to_file` and to compare the renderer with the `reference` tracer.
+    ///
+    /// # Errors
+    ///
+    /// Returns a message if the traced image can't be read back from the GPU.
+    pub fn render_linear(&mut self, samples: u32) -> Result<Vec<f32>, String> {
+        self.tile_schedule.restart();
+        let mut sum: Vec<f32> = Vec::new();
+        for _ in 0..samples.max(1) {
+            self.trace_full_frame();
+            let pixels = self.read_hdr_buffer()?;
+            if sum.is_empty() {
+                sum = pixels;
+            } else {
+                sum.iter_mut().zip(pixels).for_each(|(sum, value)| *sum += value);
+            }
+        }
+        let samples = samples.max(1) as f32;
+        sum.iter_mut().for_each(|value| *value /= samples);
+        Ok(sum)
+    }
+
+    /// Traces and denoises a whole frame with the current camera, without presenting it.
+    fn trace_full_frame(&mut self) {
+        self.camera_uniform.update_view_proj(&self.camera, &self.projection);
+        self.camera_uniform.update_frame();
+        self.queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[self.camera_uniform]));
+        self.queue.write_buffer(&self.shader_config_buffer, 0, bytemuck::cast_slice(&[self.shader_config]));
+
+        self.dispatch_raytracing_and_denoising(false, 0);
+
+        self.queue.write_buffer(&self.denoising_camera_buffer, 0, bytemuck::cast_slice(&[self.camera_uniform]));
+    }
+
+    /// Measures the raytracing pass over `frames` frames with the current camera and shader settings.
+    ///
+    /// Only the raytracing pass is run, without denoising or presenting. Every frame is submitted and awaited on its own,
+    /// so the frames don't overlap. The pass is timed with timestamp queries if the GPU supports them, otherwise the
+    /// CPU time from submitting until the GPU is done is used, which includes the submission overhead.
+    /// The result is logged and can be printed as a single line with `BenchmarkResult::to_line`.
+    pub fn benchmark(&mut self, frames: u32) -> BenchmarkResult {
+        let timer = GpuTimer::new(&self.device, &self.queue);
+        if timer.is_none() {
+            log::warn!("Timestamp queries aren't supported, the benchmark uses the CPU time of each frame");
+        }
+        self.queue.write_buffer(&self.shader_config_buffer, 0, bytemuck::cast_slice(&[self.shader_config]));
+        // The whole screen is measured in a single dispatch, independent of the tile size
+        let tile = TileSchedule::new(self.render_targets.size().0, self.render_targets.size().1, 0).tile(0);
+        self.queue.write_buffer(&self.tile_buffer, 0, bytemuck::cast_slice(&[tile.to_uniform()]));
+
+        let frames = frames.max(1);
+        let mut total_ms = 0.0;
+        for _ in 0..frames {
+            // The camera stays fixed, only the random seed changes
+            self.camera_uniform.update_view_proj(&self.camera, &self.projection);
+            self.camera_uniform.update_frame();
+            self.queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[self.camera_uniform]));
+
+            let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Benchmark Encoder"),
+            });
+            self.record_raytracing_pass(&mut encoder, tile, timer.as_ref().map(GpuTimer::compute_pass_timestamp_writes));
+            if let Some(timer) = &timer {
+                timer.resolve(&mut encoder);
+            }
+
+            let start = Instant::now();
+            self.submit(encoder.finish());
+            let frame_ms = match &timer {
+                Some(timer) => timer.read_ms(&self.device).unwrap_or(0.0),
+                None => {
+                    self.device.poll(wgpu::Maintain::Wait);
+                    start.elapsed().as_secs_f64() * 1000.0
+                }
+            };
+            total_ms += frame_ms;
+        }
+
+        let result = BenchmarkResult {
+            frames,
+            width: self.render_targets.size().0,
+            height: self.render_targets.size().1,
+            samples_per_pixel: self.shader_config.ray_samples_per_pixel.max(0) as u32,
+            max_bounces: self.shader_config.ray_max_bounces.max(0) as u32,
+            average_pass_ms: total_ms / frames as f64,
+            gpu_timed: timer.is_some(),
+        };
+        log::info!("Benchmark: {:.3} ms per frame, {:.3e} rays/s", result.average_pass_ms, result.rays_per_second());
+        result
+    }
 }
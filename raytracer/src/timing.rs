@@ -0,0 +1,61 @@
+//! Clock and frame limiter that work on native targets and in the browser.
+//!
+//! `std::time::Instant::now` panics on wasm32, the `instant` crate uses `performance.now()` there instead.
+
+use std::time::Duration;
+
+use winit::event_loop::ControlFlow;
+
+/// Monotonic clock, `std::time::Instant` on native targets and `performance.now()` in the browser.
+pub use instant::Instant;
+
+/// Returns how long to wait after a frame that took `frame_time` to stay at `frame_limit` frames per second.
+///
+/// A `frame_limit` of 0 doesn't limit the frame rate and never waits.
+pub fn frame_wait(frame_limit: u32, frame_time: Duration) -> Duration {
+    if frame_limit == 0 {
+        return Duration::ZERO;
+    }
+    (Duration::from_secs(1) / frame_limit).saturating_sub(frame_time)
+}
+
+/// Control flow of the event loop that limits the frame rate to `frame_limit` frames per second, counted from the start of the last frame.
+///
+/// Until the next frame is due the event loop waits with `ControlFlow::WaitUntil` instead of polling, so it doesn't
+/// spin in the browser, where the main thread can't sleep either.
+///
+/// # Returns
+///
+/// `ControlFlow::Poll` if the next frame should be rendered now.
+pub fn frame_control_flow(frame_limit: u32, last_render_time: Instant) -> ControlFlow {
+    let wait = frame_wait(frame_limit, last_render_time.elapsed());
+    if wait.is_zero() {
+        ControlFlow::Poll
+    } else {
+        ControlFlow::wait_duration(wait)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_wait() {
+        // 50 fps leave 20 ms per frame
+        assert_eq!(frame_wait(50, Duration::from_millis(5)), Duration::from_millis(15));
+        assert_eq!(frame_wait(50, Duration::ZERO), Duration::from_millis(20));
+        // Slow frames and an unlimited frame rate don't wait
+        assert_eq!(frame_wait(50, Duration::from_millis(30)), Duration::ZERO);
+        assert_eq!(frame_wait(0, Duration::ZERO), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_frame_control_flow() {
+        // Within the frame time of 20 ms the event loop waits for the next frame
+        assert!(matches!(frame_control_flow(50, Instant::now()), ControlFlow::WaitUntil(_)));
+        assert_eq!(frame_control_flow(50, Instant::now() - Duration::from_millis(30)), ControlFlow::Poll);
+        // Without a limit the next frame is rendered right away
+        assert_eq!(frame_control_flow(0, Instant::now()), ControlFlow::Poll);
+    }
+}
@@ -0,0 +1,32 @@
+//! Shared setup of the GPU tests.
+//!
+//! The window and the GPU need the main thread on some platforms, so these tests have their own `main` instead of
+//! the test harness (`harness = false` in `Cargo.toml`) and are skipped on machines without a display or a GPU adapter.
+
+use winit::{dpi::PhysicalSize, event_loop::EventLoop, window::{Window, WindowBuilder}};
+
+/// Runs `test` with a small hidden window and reports the result like the test harness.
+///
+/// Prints why the test is skipped if there is no display or no GPU adapter.
+pub fn run_gpu_test(name: &str, test: impl FnOnce(Window)) {
+    let event_loop = match EventLoop::new() {
+        Ok(event_loop) => event_loop,
+        Err(error) => {
+            println!("test {} ... ignored, no display: {}", name, error);
+            return;
+        }
+    };
+    let instance = wgpu::Instance::default();
+    if pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default())).is_none() {
+        println!("test {} ... ignored, no GPU adapter", name);
+        return;
+    }
+
+    let window = WindowBuilder::new()
+        .with_visible(false)
+        .with_inner_size(PhysicalSize::new(64, 64))
+        .build(&event_loop)
+        .unwrap();
+    test(window);
+    println!("test {} ... ok", name);
+}
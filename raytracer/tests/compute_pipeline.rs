@@ -0,0 +1,28 @@
+//! Creates compute pipelines from valid and invalid shaders and checks that the errors of the device are returned instead of panicking.
+
+mod common;
+
+use raytracing_lib::helper::create_compute_pipeline;
+
+fn main() {
+    common::run_gpu_test("compute_pipeline", |_window| {
+        let instance = wgpu::Instance::default();
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default())).expect("No GPU adapter");
+        let (device, _queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None)).unwrap();
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Test Pipeline Layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+
+        let source = "@compute @workgroup_size(8, 8, 1)\nfn main(@builtin(global_invocation_id) id: vec3<u32>) {\n    let x = id.x + 1u;\n}\n";
+        assert!(create_compute_pipeline(&device, &layout, "Test Pipeline", "Test Shader", source).is_ok());
+
+        // A shader with a binding that isn't in the layout is rejected by the device instead of panicking
+        let unbound = "@group(0) @binding(0) var<uniform> value: u32;\n@compute @workgroup_size(1)\nfn main() {\n    let x = value;\n}\n";
+        let error = create_compute_pipeline(&device, &layout, "Test Pipeline", "Unbound Shader", unbound).unwrap_err();
+        assert!(error.contains("Test Pipeline"), "{}", error);
+        let error = create_compute_pipeline(&device, &layout, "Test Pipeline", "Broken Shader", "fn main() {").unwrap_err();
+        assert!(error.contains("Broken Shader"), "{}", error);
+    });
+}
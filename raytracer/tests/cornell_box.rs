@@ -0,0 +1,50 @@
+//! Renders the Cornell box until it converges and compares the mean luminance with the CPU reference tracer,
+//! with and without multiple importance sampling of the light.
+
+mod common;
+
+use cgmath::{Deg, Vector3};
+use raytracing_lib::reference::ReferenceTracer;
+use raytracing_lib::State;
+use scene::{presets, Camera, Projection, ShaderConfig};
+
+/// Frames averaged on the GPU, enough for the mean of a small window to settle.
+const GPU_SAMPLES: u32 = 256;
+/// Samples per pixel of the reference image.
+const REFERENCE_SAMPLES: u32 = 32;
+/// Allowed relative difference of the mean luminance, from the Monte Carlo noise of the 32 reference samples per pixel
+/// and the different sampling patterns of the GPU and the reference tracer.
+const TOLERANCE: f32 = 0.05;
+
+/// Average luminance of RGBA values, with the weights of the shader.
+fn mean_luminance(pixels: &[f32]) -> f32 {
+    let sum: f32 = pixels.chunks_exact(4).map(|pixel| 0.2126 * pixel[0] + 0.7152 * pixel[1] + 0.0722 * pixel[2]).sum();
+    sum / (pixels.len() / 4).max(1) as f32
+}
+
+fn main() {
+    common::run_gpu_test("cornell_box", |window| {
+        let scene = presets::cornell_box().build().unwrap();
+        let size = window.inner_size();
+        let mut state = pollster::block_on(State::new(window, scene.clone())).expect("Could not set up the Cornell box");
+
+        let triangles = scene.meshes.clone().expect("Missing triangles");
+        let materials = scene.materials.clone().expect("Missing materials");
+        let background = scene.background.expect("Missing background");
+        let tracer = ReferenceTracer::new(&triangles, &[], &materials, background, state.shader_config());
+        let target = scene.camera_target.expect("Missing camera target");
+        let camera = Camera::look_at(scene.camera_position.into(), target.into(), Vector3::unit_y());
+        let [near, far] = scene.camera_near_far;
+        let projection = Projection::new(size.width, size.height, Deg(scene.camera_fov), near, far);
+        let reference = tracer.render(&camera, &projection, size.width, size.height, REFERENCE_SAMPLES, 1).mean_luminance();
+
+        for mis_enabled in [1, 0] {
+            state.set_shader_config(ShaderConfig { mis_enabled, ..state.shader_config() });
+            let pixels = state.render_linear(GPU_SAMPLES).expect("Could not read the rendered image");
+            assert_eq!(pixels.len(), (size.width * size.height * 4) as usize);
+            let mean = mean_luminance(&pixels);
+            assert!((mean - reference).abs() < reference * TOLERANCE,
+                    "mis_enabled = {}: mean luminance {} differs from the reference {}", mis_enabled, mean, reference);
+        }
+    });
+}
@@ -0,0 +1,29 @@
+//! Loses the gpu device of the Cornell box and checks that `recreate` sets up the scene again from the retained scene data.
+
+mod common;
+
+use raytracing_lib::State;
+use scene::presets;
+
+fn main() {
+    common::run_gpu_test("device_lost", |window| {
+        let state = pollster::block_on(State::new(window, presets::cornell_box().build().unwrap())).expect("Could not set up the scene");
+        assert!(!state.is_device_lost());
+        let triangle_count = state.triangles().len();
+        let sphere_count = state.spheres().len();
+        let materials = state.materials().to_vec();
+        let camera_uniform = state.camera_uniform;
+
+        state.destroy_device();
+        assert!(state.is_device_lost());
+
+        let state = pollster::block_on(state.recreate()).expect("Could not recreate the state");
+        assert!(!state.is_device_lost());
+        assert_eq!(state.triangles().len(), triangle_count);
+        assert_eq!(state.spheres().len(), sphere_count);
+        assert_eq!(state.materials().len(), materials.len());
+        assert!(state.materials().iter().zip(&materials).all(|(material, old)| material.albedo == old.albedo && material.roughness == old.roughness));
+        // The camera stays where it was
+        assert!(!state.camera_uniform.view_changed(&camera_uniform));
+    });
+}
@@ -0,0 +1,22 @@
+//! Renders a scene without models, spheres, materials and textures, which only shows the background.
+
+mod common;
+
+use std::time::Duration;
+
+use raytracing_lib::State;
+use scene::SceneBuilder;
+
+fn main() {
+    common::run_gpu_test("empty_scene", |window| {
+        let scene = SceneBuilder::new().build().unwrap();
+        let mut state = pollster::block_on(State::new(window, scene)).expect("Could not set up the empty scene");
+        // The placeholders that keep the gpu buffers from being empty aren't part of the scene
+        assert!(state.triangles().is_empty());
+        assert!(state.spheres().is_empty());
+
+        // wgpu panics on validation errors, e.g. an empty storage buffer
+        state.update(Duration::from_millis(16));
+        state.render().expect("Could not render the empty scene");
+    });
+}
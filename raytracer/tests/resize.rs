@@ -0,0 +1,27 @@
+//! Resizes the `State` of the Cornell box and checks that the storage textures of the compute passes have the new size.
+
+mod common;
+
+use raytracing_lib::State;
+use scene::presets;
+use winit::dpi::PhysicalSize;
+
+fn main() {
+    common::run_gpu_test("resize", |window| {
+        let mut state = pollster::block_on(State::new(window, presets::cornell_box().build().unwrap())).expect("Could not set up the scene");
+        assert_eq!(state.render_size(), (state.size.width, state.size.height));
+
+        state.resize(PhysicalSize::new(40, 24));
+        assert_eq!(state.size, PhysicalSize::new(40, 24));
+        assert_eq!(state.render_size(), (40, 24));
+        // The traced image is read back from the recreated texture, wgpu panics if a bind group still references the old one
+        let pixels = state.render_linear(1).expect("Could not trace after the resize");
+        assert_eq!(pixels.len(), 40 * 24 * 4);
+        state.render().expect("Could not render after the resize");
+
+        // A minimized window keeps the textures of the last size
+        state.resize(PhysicalSize::new(0, 0));
+        assert!(state.is_minimized());
+        assert_eq!(state.render_size(), (40, 24));
+    });
+}
@@ -0,0 +1,26 @@
+//! Loads the Cornell box with an extra sphere and checks that the primitives of the scene can be read back from the `State`.
+
+mod common;
+
+use cgmath::Point3;
+use raytracing_lib::State;
+use scene::{presets, Material, Sphere};
+
+fn main() {
+    common::run_gpu_test("scene_accessors", |window| {
+        let scene = presets::cornell_box()
+            .add_material(Material::glass())
+            .add_sphere(Sphere::new(Point3::new(0.0, 0.5, 0.0), 0.3, 4, [-1; 4]))
+            .build()
+            .unwrap();
+        let triangle_count = scene.meshes.as_ref().map_or(0, Vec::len);
+        let material_count = scene.materials.as_ref().map_or(0, Vec::len);
+        let state = pollster::block_on(State::new(window, scene)).expect("Could not set up the scene");
+
+        assert_eq!(state.triangles().len(), triangle_count);
+        assert_eq!(state.spheres().len(), 1);
+        assert_eq!(state.spheres()[0].radius[0], 0.3);
+        assert_eq!(state.materials().len(), material_count);
+        assert!(state.triangles().iter().all(|triangle| (triangle.material_id as usize) < state.materials().len()));
+    });
+}
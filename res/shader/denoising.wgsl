@@ -1,6 +1,7 @@
 // Bindings
 @group(0) @binding(0) var color_buffer: texture_storage_2d<rgba8unorm, read_write>;
-@group(0) @binding(1) var temporal_buffer: texture_storage_2d<rgba8unorm, read_write>;
+// History of the last frames for temporal denoising, one layer per frame
+@group(0) @binding(1) var temporal_buffer: texture_storage_2d_array<rgba8unorm, read_write>;
 
 struct Camera {
     current_frame_counter: f32,
@@ -9,12 +10,26 @@ struct Camera {
 }
 @group(0) @binding(2) var<uniform> current_camera: Camera;
 @group(0) @binding(3) var<uniform> lastframe_camera: Camera;
+// Low byte: 0u = first pass, 1u = second pass, 2u = first pass with reset temporal history (e.g. after a material edit)
+// Second byte: iteration of the À-Trous filter, the filter stride is 2^iteration
+// Third byte: layer of the temporal history that holds the current frame
 @group(0) @binding(4) var<uniform> current_denoising_pass: u32;
+// First hit of each pixel written by the raytracer: normal (xyz), hit distance (w)
+@group(0) @binding(5) var gbuffer: texture_storage_2d<rgba32float, read_write>;
+// The À-Trous filter reads its neighbours from color_buffer, so its result is written here and copied back afterwards
+@group(0) @binding(6) var atrous_buffer: texture_storage_2d<rgba8unorm, read_write>;
+
+// Value of first_pass/second_pass that selects the À-Trous filter
+const ATROUS_PASS: i32 = 6;
+
+// Layer of the temporal history written this frame, the other layers hold the previous frames
+var<private> history_index: i32 = 0;
 
 struct Shaderconfig  {
     max_bounces: i32,
     samples: i32,
     max_ray_distance: f32,
+    ray_bias: f32,      // Offset of secondary ray origins along the normal
     
     focus_distance: f32,
     aperture: f32,
@@ -24,10 +39,28 @@ struct Shaderconfig  {
     focus_viewer_visible: i32,
     debug_bvh_bounding_visible: i32,
     debug_bvh_bounding_color_visible: i32,
+    debug_view: u32,
+
+    //adaptive sampling
+    adaptive_sampling_enabled: i32,
+    adaptive_variance_threshold: f32,
+
+    //light sampling
+    mis_enabled: i32,
+    firefly_clamp: f32,
+
+    //materials
+    roughness_linear: i32,
+
+    //anti-aliasing
+    sampling_pattern: u32,
+    jitter_mode: u32,
+    jitter_enabled: i32,
 
     //denoising
     first_pass: i32,
     second_pass: i32,
+    denoise_history_length: i32,   //Only used when the denoising buffers are created
 
     //temporal basic
     temporal_basic_low_threshold: f32,
@@ -53,6 +86,18 @@ struct Shaderconfig  {
     spatial_den_cormpare_radius: i32,
     spatial_den_patch_radius: i32,
     spatial_den_significant_weight: f32,    
+    //spatial a-trous wavelet
+    atrous_iterations: i32,
+    atrous_color_phi: f32,
+    atrous_normal_phi: f32,
+    atrous_depth_phi: f32,
+
+    //display
+    exposure: f32,      //Only used in the screen shader
+    supersample: u32,   //Only used in the screen shader
+
+    // Background is transparent (alpha 0) instead of showing the background color
+    transparent_background: i32,
 }
 @group(1) @binding(0) var<uniform> config: Shaderconfig;
 
@@ -61,9 +106,11 @@ fn main(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
     let screen_pos: vec2<u32> = vec2<u32>(GlobalInvocationID.xy);
     let screen_size: vec2<u32> = vec2<u32>(textureDimensions(color_buffer));
 
+    history_index = i32((current_denoising_pass >> 16u) & 0xffu);
+
     // Sample the central pixel
     let centralColor: vec4<f32> = textureLoad(color_buffer, vec2<i32>(screen_pos));
-    let previousColor: vec4<f32> = textureLoad(temporal_buffer, vec2<i32>(screen_pos));
+    let previousColor: vec4<f32> = history_average(vec2<i32>(screen_pos));
 
     // Calculate relative movement between frames
     let relative_movement: vec4<f32> = calculate_relative_movement(current_camera, lastframe_camera);
@@ -75,7 +122,10 @@ fn main(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
     // Combine denoised results based on regions (you can modify this logic)
     var final_color: vec4<f32> = vec4<f32>(0.0);
 
-    if current_denoising_pass == 0u {
+    let denoising_pass = current_denoising_pass & 0xffu;
+    let atrous_step = (current_denoising_pass >> 8u) & 0xffu;
+
+    if denoising_pass == 0u || denoising_pass == 2u {
         //----------Intended for temporal Denoising----------//
         //final_color = adaptive_temporal_denoising(centralColor, screen_pos, previousColor, relative_movement, relative_direction);
 
@@ -89,10 +139,26 @@ fn main(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
             final_color = temporal_denoising(centralColor, screen_pos, previousColor);
         } else if config.first_pass == 4 {
             final_color = adaptive_temporal_denoising(centralColor, screen_pos, previousColor, relative_movement, relative_direction);
+        } else if config.first_pass == ATROUS_PASS {
+            final_color = atrous_denoising(centralColor, screen_pos, screen_size, atrous_step);
         } else {
             final_color = centralColor;
         }
-        textureStore(temporal_buffer, vec2<i32>(screen_pos), final_color);
+        // Drop the accumulated history so that the old frames don't bleed into the new result
+        if denoising_pass == 2u && (config.first_pass == 3 || config.first_pass == 4) {
+            final_color = centralColor;
+        }
+        if denoising_pass == 2u {
+            for (var layer: i32 = 0; layer < i32(textureNumLayers(temporal_buffer)); layer = layer + 1) {
+                textureStore(temporal_buffer, vec2<i32>(screen_pos), layer, final_color);
+            }
+        } else {
+            textureStore(temporal_buffer, vec2<i32>(screen_pos), history_index, final_color);
+        }
+        if config.first_pass == ATROUS_PASS {
+            textureStore(atrous_buffer, vec2<i32>(screen_pos), final_color);
+            return;
+        }
     } else {
         //----------Intended for spacial Denoising----------//
         // final_color = non_local_means_denoising(centralColor, screen_pos);
@@ -107,6 +173,9 @@ fn main(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
             final_color = temporal_denoising(centralColor, screen_pos, previousColor);
         } else if config.second_pass == 4 {
             final_color = adaptive_temporal_denoising(centralColor, screen_pos, previousColor, relative_movement, relative_direction);
+        } else if config.second_pass == ATROUS_PASS {
+            textureStore(atrous_buffer, vec2<i32>(screen_pos), atrous_denoising(centralColor, screen_pos, screen_size, atrous_step));
+            return;
         } else {
             final_color = centralColor;
         }
@@ -166,6 +235,17 @@ fn calculate_relative_direction(
 }
 
 
+// Average of the frames in the temporal history, before the current frame is stored
+fn history_average(pos: vec2<i32>) -> vec4<f32> {
+    let layers: i32 = i32(textureNumLayers(temporal_buffer));
+    var sum: vec4<f32> = vec4<f32>(0.0);
+    for (var layer: i32 = 0; layer < layers; layer = layer + 1) {
+        sum += textureLoad(temporal_buffer, pos, layer);
+    }
+    return sum / f32(layers);
+}
+
+
 //---------Denoising Functions---------//
 fn spacial_denoising(centralColor: vec4<f32>, screen_pos: vec2<u32>) -> vec4<f32>{
     
@@ -210,7 +290,7 @@ fn bilateral_denoising(centralColor: vec4<f32>, screen_pos: vec2<u32>) -> vec4<f
              let neighborPos: vec2<i32> = vec2<i32>(screen_pos) + offset;
             
              // Sample the color of the neighboring pixel
-             let neighborColor: vec4<f32> = textureLoad(temporal_buffer, neighborPos);
+             let neighborColor: vec4<f32> = textureLoad(temporal_buffer, neighborPos, history_index);
             
              // Calculate the spatial and color weights
              let spatialDist: f32 = length(vec2<f32>(offset));
@@ -269,6 +349,53 @@ fn non_local_means_denoising(centralColor: vec4<f32>, screen_pos: vec2<u32>) ->
      return denoisedColor;
 }
 
+// Edge-avoiding À-Trous wavelet filter (Dammertz et al. 2010).
+// One iteration of a 5x5 B3-spline kernel whose taps are spread 2^step pixels apart. Neighbours are weighted
+// by their color, normal and depth difference to the central pixel, so edges and silhouettes stay sharp.
+fn atrous_denoising(centralColor: vec4<f32>, screen_pos: vec2<u32>, screen_size: vec2<u32>, step: u32) -> vec4<f32> {
+    var kernel = array<f32, 5>(1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0);
+    let stride: i32 = i32(1u << step);
+    let max_pos = vec2<i32>(screen_size) - vec2<i32>(1);
+
+    let central_gbuffer: vec4<f32> = textureLoad(gbuffer, vec2<i32>(screen_pos));
+    // The color weight gets stricter every iteration, as the image gets smoother
+    let color_phi: f32 = max(config.atrous_color_phi / f32(1u << step), 1e-6);
+    let depth_phi: f32 = max(config.atrous_depth_phi * central_gbuffer.w * f32(stride), 1e-4);
+
+    var weightedSum: vec4<f32> = vec4<f32>(0.0);
+    var totalWeight: f32 = 0.0;
+
+    for (var dx: i32 = -2; dx <= 2; dx = dx + 1) {
+        for (var dy: i32 = -2; dy <= 2; dy = dy + 1) {
+            let neighborPos: vec2<i32> = clamp(vec2<i32>(screen_pos) + vec2<i32>(dx, dy) * stride, vec2<i32>(0), max_pos);
+            let neighborColor: vec4<f32> = textureLoad(color_buffer, neighborPos);
+            let neighbor_gbuffer: vec4<f32> = textureLoad(gbuffer, neighborPos);
+
+            let colorDiff: vec4<f32> = centralColor - neighborColor;
+            let colorWeight: f32 = exp(-dot(colorDiff, colorDiff) / color_phi);
+
+            // Background pixels have no normal, they only blend with other background pixels
+            var normalWeight: f32 = 1.0;
+            let central_has_normal = dot(central_gbuffer.xyz, central_gbuffer.xyz) > 0.0;
+            let neighbor_has_normal = dot(neighbor_gbuffer.xyz, neighbor_gbuffer.xyz) > 0.0;
+            if (central_has_normal && neighbor_has_normal) {
+                normalWeight = pow(max(dot(central_gbuffer.xyz, neighbor_gbuffer.xyz), 0.0), config.atrous_normal_phi);
+            } else if (central_has_normal != neighbor_has_normal) {
+                normalWeight = 0.0;
+            }
+
+            let depthWeight: f32 = exp(-abs(central_gbuffer.w - neighbor_gbuffer.w) / depth_phi);
+
+            let weight: f32 = kernel[dx + 2] * kernel[dy + 2] * colorWeight * normalWeight * depthWeight;
+            weightedSum += neighborColor * weight;
+            totalWeight += weight;
+        }
+    }
+
+    // The central pixel always has a weight above zero
+    return weightedSum / totalWeight;
+}
+
 fn temporal_denoising(centralColor: vec4<f32>, screen_pos: vec2<u32>, previousColor: vec4<f32>) -> vec4<f32> {
     // Calculate the color difference between centralColor and previousColor
     let colorDifference: f32 = length(centralColor.rgb - previousColor.rgb);
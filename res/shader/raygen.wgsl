@@ -2,6 +2,7 @@ struct Shaderconfig  {
     max_bounces: i32,
     samples: i32,
     max_ray_distance: f32,
+    ray_bias: f32,      // Offset of secondary ray origins along the normal
     
     focus_distance: f32,
     aperture: f32,
@@ -11,10 +12,28 @@ struct Shaderconfig  {
     focus_viewer_visible: i32,
     debug_bvh_bounding_visible: i32,
     debug_bvh_bounding_color_visible: i32,
+    debug_view: u32,
+
+    //adaptive sampling
+    adaptive_sampling_enabled: i32,
+    adaptive_variance_threshold: f32,
+
+    //light sampling
+    mis_enabled: i32,
+    firefly_clamp: f32,
+
+    //materials
+    roughness_linear: i32,
+
+    //anti-aliasing
+    sampling_pattern: u32,
+    jitter_mode: u32,
+    jitter_enabled: i32,
 
     //denoising
     first_pass: i32,
     second_pass: i32,
+    denoise_history_length: i32,   //Only used when the denoising buffers are created
 
     //temporal basic                            //Not used in this shader |
     temporal_basic_low_threshold: f32,          //                        v
@@ -40,16 +59,39 @@ struct Shaderconfig  {
     spatial_den_cormpare_radius: i32,
     spatial_den_patch_radius: i32,              //                        ^
     spatial_den_significant_weight: f32,        //Not used in this shader |
+    //spatial a-trous wavelet
+    atrous_iterations: i32,
+    atrous_color_phi: f32,
+    atrous_normal_phi: f32,
+    atrous_depth_phi: f32,
+
+    //display
+    exposure: f32,      //Only used in the screen shader
+    supersample: u32,   //Only used in the screen shader
+
+    // Background is transparent (alpha 0) instead of showing the background color
+    transparent_background: i32,
 }
 @group(0) @binding(0) var<uniform> config: Shaderconfig;
 
 @group(1) @binding(0) var color_buffer: texture_storage_2d<rgba8unorm, read_write>;// Only needs to be write, but helps with bindgroup generation
+// Per pixel sample moments for adaptive sampling
+// layer 0: mean color (rgb), sample count (a)
+// layer 1: mean luminance (x), mean squared luminance (y), camera key (z), mean alpha (w)
+@group(1) @binding(1) var moment_buffer: texture_storage_2d_array<rgba32float, read_write>;
+// First hit of each pixel for the edge-avoiding denoiser: normal (xyz), hit distance (w)
+@group(1) @binding(2) var gbuffer: texture_storage_2d<rgba32float, read_write>;
+// Linear color of the pixel, the same as color_buffer but not clamped to 0..1, for HDR file output
+@group(1) @binding(3) var hdr_buffer: texture_storage_2d<rgba32float, read_write>;
+// Tile of the screen traced by this dispatch: offset (xy), size (zw)
+@group(1) @binding(4) var<uniform> tile: vec4<u32>;
 
 // Camera
 struct Camera {
-    frame: vec4<f32>,
+    frame: vec4<f32>, // frame counter, vertical fov in degrees, near plane, far plane
     view_pos: vec4<f32>, // 4. is a frame counter
     view_proj: mat4x4<f32>,
+    projection: vec4<f32>, // aspect ratio (width / height), unused, unused, unused
 }
 @group(2) @binding(0) var<uniform> camera: Camera;
 
@@ -59,19 +101,57 @@ struct Material {
     roughness: f32,
     emission: f32,
     ior: f32,
-    _padding: f32,
+    wrap_mode: u32, // 0 = repeat, 1 = clamp to edge, 2 = mirror repeat
+    metallic: f32,  // 0 = dielectric, 1 = metal
+    alpha_mode: u32, // 0 = opaque, 1 = mask, 2 = blend, uses the alpha of the diffuse texture
+    alpha_cutoff: f32, // alpha below which a masked surface lets the rays pass
+    double_sided: u32, // 1 = the normal is flipped to face the ray, so the back is shaded like the front
+    clearcoat: f32, // strength of the untinted glossy layer on top of opaque materials, 0 = no coat
+    clearcoat_roughness: f32, // perceptual roughness of the clearcoat
+    _padding: vec2<f32>,
 }
+// Values of material.alpha_mode, see AlphaMode on the CPU side
+const ALPHA_MODE_OPAQUE: u32 = 0u;
+const ALPHA_MODE_MASK: u32 = 1u;
+const ALPHA_MODE_BLEND: u32 = 2u;
 
 struct Background {
-    material_ids: vec4<f32>, //material_id, texture_id_diffuse
-    intensity: vec4<f32>,
+    material_ids: vec4<f32>, //material_id, texture_id_diffuse, mode
+    intensity: vec4<f32>, //intensity, color rgb
+    gradient_top: vec4<f32>, //rgb, gradient mode only
+    gradient_horizon: vec4<f32>,
+    gradient_bottom: vec4<f32>,
 }
+// Values of background.material_ids.z, see BackgroundMode on the CPU side
+const BACKGROUND_SKY: u32 = 0u;
+const BACKGROUND_TEXTURE: u32 = 1u;
+const BACKGROUND_COLOR: u32 = 2u;
+const BACKGROUND_GRADIENT: u32 = 3u;
 
 @group(4) @binding(0) var texture_sampler: sampler;
 @group(4) @binding(1) var textures: texture_2d_array<f32>;
 @group(4) @binding(2) var<storage> materials: array<Material>;
 @group(4) @binding(3) var<storage> background: Background;
 @group(4) @binding(4) var background_texture: texture_2d<f32>;
+// The address mode is fixed per sampler, so there is one sampler per wrap mode (texture_sampler repeats)
+@group(4) @binding(5) var texture_sampler_clamp: sampler;
+@group(4) @binding(6) var texture_sampler_mirror: sampler;
+// Where a texture is found, indexed by texture id. Packed textures share atlas pages and are wrapped in the shader
+struct TextureRegion {
+    uv_rect: vec4<f32>, // offset xy, size zw on the page
+    layer: vec4<f32>,   // page or array layer, 1.0 if packed into an atlas
+}
+@group(4) @binding(7) var<storage> texture_regions: array<TextureRegion>;
+// Sub-pixel sampling patterns, see SamplingPattern on the CPU side
+const SAMPLING_RANDOM: u32 = 0u;
+const SAMPLING_HALTON: u32 = 1u;
+const SAMPLING_SOBOL: u32 = 2u;
+const SAMPLING_BLUE_NOISE: u32 = 3u;
+const SAMPLE_OFFSET_COUNT: u32 = 64u;
+// Tileable blue noise, two independent values in r and g
+@group(4) @binding(8) var blue_noise: texture_2d<f32>;
+// Precomputed offsets per sample: halton base 2 and 3 (xy), sobol (zw)
+@group(4) @binding(9) var<uniform> sample_offsets: array<vec4<f32>, SAMPLE_OFFSET_COUNT>;
 
 
 // Triangles
@@ -83,6 +163,11 @@ struct Triangle {
     tex_coords1: vec4<f32>,
     tex_coords2: vec4<f32>,
     material_texture_ids: vec4<f32>, //material_id, texture_id_diffuse, texture_id_roughness, texture_id_normal
+    tangent: vec4<f32>, //tangent, bitangent sign
+    vertex_normal1: vec4<f32>,
+    vertex_normal2: vec4<f32>,
+    vertex_normal3: vec4<f32>,
+    emission_texture_id: vec4<f32>, //texture_id_emission
 }
 @group(3) @binding(0) var<storage> triangles : array<Triangle>;
 
@@ -90,8 +175,13 @@ struct Sphere {
     center: vec4<f32>,
     radius: vec4<f32>,
     material_texture_ids: vec4<f32>, //material_id, texture_id_diffuse, texture_id_roughness, texture_id_normal
+    emission_texture_id: vec4<f32>, //texture_id_emission
 }
 @group(3) @binding(1) var<storage> spheres : array<Sphere>;
+// Indices of the emissive triangles, sampled directly as area lights
+@group(3) @binding(2) var<storage> emissive_triangles : array<u32>;
+// Only entry of emissive_triangles if the scene has no emissive triangles
+const NO_EMISSIVE_TRIANGLES: u32 = 0xffffffffu;
 
 struct Ray {
     origin: vec3<f32>,
@@ -110,7 +200,10 @@ struct BVHNodes {
 }
 
 @group(5) @binding(0) var<storage> bvh: array<BVHNodes>;
-@group(5) @binding(1) var<storage> bvh_prim_indices: array<f32>;
+// Kind (BVH_PRIM_TRIANGLE or BVH_PRIM_SPHERE) and index in the triangle or sphere buffer of the primitives in the leaves
+@group(5) @binding(1) var<storage> bvh_prim_indices: array<vec2<f32>>;
+const BVH_PRIM_TRIANGLE: f32 = 0.0;
+const BVH_PRIM_SPHERE: f32 = 1.0;
 
 
 var<private> seed: f32;
@@ -127,43 +220,169 @@ var<private> sample_count: i32 = 0;
 
 // Initialize pixel_color to zero
 var<private> pixel_color: vec3<f32> = vec3<f32>(0.0, 0.0, 0.0);
+// Coverage of the pixel, only below 1.0 with a transparent background
+var<private> pixel_alpha: f32 = 0.0;
+
+// Samples a pixel needs before adaptive sampling may stop sampling it
+const ADAPTIVE_MIN_SAMPLES: f32 = 16.0;
+
+// Width of the focus viewer band, relative to the focus distance
+const FOCUS_BAND_WIDTH: f32 = 0.01;
+// Reflectance of dielectrics at normal incidence (ior 1.5)
+const DIELECTRIC_F0: f32 = 0.04;
+// Smallest GGX alpha, a perfect mirror has no finite pdf
+const MIN_GGX_ALPHA: f32 = 0.001;
+// Values of config.debug_view, see DebugView on the CPU side
+const DEBUG_VIEW_BEAUTY: u32 = 0u;
+const DEBUG_VIEW_ALBEDO: u32 = 1u;
+const DEBUG_VIEW_NORMAL: u32 = 2u;
+const DEBUG_VIEW_DEPTH: u32 = 3u;
+const DEBUG_VIEW_BVH_HEATMAP: u32 = 4u;
+// Number of visited BVH nodes shown as red in the heatmap
+const DEBUG_HEATMAP_MAX_NODES: f32 = 128.0;
+// Number of samples of the current pixel whose first hit lies on the focal plane
+var<private> focus_plane_hits: f32 = 0.0;
+// First hit of the last traced sample of the current pixel, w is negative if no sample was traced
+var<private> first_hit: vec4<f32> = vec4<f32>(0.0, 0.0, 0.0, -1.0);
 
 // Main ray tracing function
 @compute @workgroup_size(8, 8, 1)
 fn main(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
     // Get the screen size
     let screen_size: vec2<u32> = vec2<u32>(textureDimensions(color_buffer));
+    // Skip the threads of the last workgroups that are outside the tile
+    if (any(GlobalInvocationID.xy >= tile.zw)) {
+        return;
+    }
     // Calculate screen position
-    let screen_pos: vec2<u32> = vec2<u32>(GlobalInvocationID.xy);
+    let screen_pos: vec2<u32> = vec2<u32>(GlobalInvocationID.xy) + tile.xy;
 
     // Start rand seed
     seed = f32(initRng(screen_pos, screen_size, u32(camera.frame[0])));
 
+    let debug_view = config.debug_view != DEBUG_VIEW_BEAUTY || config.debug_random_color_visible == 1 || config.focus_viewer_visible == 1 || config.debug_bvh_bounding_visible == 1 || config.debug_bvh_bounding_color_visible == 1;
+    if (config.adaptive_sampling_enabled == 1 && !debug_view) {
+        let sampled_color = adaptive_sampling(screen_pos, screen_size);
+        textureStore(color_buffer, vec2<i32>(screen_pos), sampled_color);
+        textureStore(hdr_buffer, vec2<i32>(screen_pos), sampled_color);
+        // Converged pixels keep the G-buffer of the frame that traced them last
+        if (first_hit.w >= 0.0) {
+            textureStore(gbuffer, vec2<i32>(screen_pos), first_hit);
+        }
+        return;
+    }
+    // Drop the accumulated samples, so they don't get reused when adaptive sampling is turned on again
+    textureStore(moment_buffer, vec2<i32>(screen_pos), 0, vec4<f32>(0.0));
+
     // Multiple Samples as Antialiasing (MSAA)
     for (var color_samples = 0; color_samples < config.samples; color_samples += 1) {
         // Calculate Ray
-        var ray = calc_ray(screen_pos, screen_size);
+        var ray = calc_ray(screen_pos, screen_size, sample_index(color_samples));
 
         // Debugging options, Focus viewer is toggled in color() function
-        if (config.debug_random_color_visible == 1) {
+        if (config.debug_view != DEBUG_VIEW_BEAUTY) {
+            pixel_color += debug_view_color(ray);
+            pixel_alpha += 1.0;
+        } else if (config.debug_random_color_visible == 1) {
             pixel_color += debug_rand_color();
+            pixel_alpha += 1.0;
         } else if (config.debug_bvh_bounding_visible == 1) {
             pixel_color += debug_bvh_bounding(ray);
             pixel_color += color(ray).xyz * 0.2;
+            pixel_alpha += 1.0;
         } else if (config.debug_bvh_bounding_color_visible == 1) {
             debug_bvh_bounding_color(ray);
             pixel_color += color(ray).xyz * 0.4;
+            pixel_alpha += 1.0;
         } else {
             // Normal color calculation
-            pixel_color += color(ray).xyz;
+            let sample_color = color(ray);
+            pixel_color += clamp_firefly(sample_color.xyz);
+            pixel_alpha += sample_color.w;
         }
 
     }
     // Weighted average of pixel colors
     pixel_color /= f32(config.samples);
+    pixel_alpha /= f32(config.samples);
+
+    // Tint the parts of the scene that are in focus
+    if (config.focus_viewer_visible == 1) {
+        pixel_color = mix(pixel_color, vec3<f32>(1.0, 0.0, 0.0), 0.6 * focus_plane_hits / f32(config.samples));
+    }
 
-    // Store the pixel color in the color buffer
-    textureStore(color_buffer, vec2<i32>(screen_pos), vec4<f32>(pixel_color, 1.0));
+    // Store the pixel color in the color buffer (premultiplied alpha)
+    textureStore(color_buffer, vec2<i32>(screen_pos), vec4<f32>(pixel_color, pixel_alpha));
+    textureStore(hdr_buffer, vec2<i32>(screen_pos), vec4<f32>(pixel_color, pixel_alpha));
+    if (first_hit.w >= 0.0) {
+        textureStore(gbuffer, vec2<i32>(screen_pos), first_hit);
+    }
+}
+
+// Accumulates the samples of a pixel over multiple frames and stops sampling once the pixel has converged.
+// A pixel is converged if the variance of its mean luminance (sample variance / sample count) is below
+// config.adaptive_variance_threshold after at least ADAPTIVE_MIN_SAMPLES samples.
+// The accumulation restarts whenever the camera changes.
+fn adaptive_sampling(screen_pos: vec2<u32>, screen_size: vec2<u32>) -> vec4<f32> {
+    let pos = vec2<i32>(screen_pos);
+    var mean = textureLoad(moment_buffer, pos, 0);
+    var moments = textureLoad(moment_buffer, pos, 1);
+
+    let key = camera_key();
+    if (moments.z != key) {
+        mean = vec4<f32>(0.0);
+        moments = vec4<f32>(0.0, 0.0, key, 0.0);
+    }
+
+    var n = mean.w;
+    if (n >= ADAPTIVE_MIN_SAMPLES) {
+        let variance = max(moments.y - moments.x * moments.x, 0.0) / n;
+        if (variance < config.adaptive_variance_threshold) {
+            return vec4<f32>(mean.xyz, moments.w);
+        }
+    }
+
+    for (var color_samples = 0; color_samples < config.samples; color_samples += 1) {
+        let sample = color(calc_ray(screen_pos, screen_size, sample_index(color_samples)));
+        let sample_color = clamp_firefly(sample.xyz);
+        let sample_luminance = dot(sample_color, vec3<f32>(0.2126, 0.7152, 0.0722));
+
+        // Running mean, stays stable for large sample counts
+        n += 1.0;
+        mean = vec4<f32>(mean.xyz + (sample_color - mean.xyz) / n, n);
+        moments.x += (sample_luminance - moments.x) / n;
+        moments.y += (sample_luminance * sample_luminance - moments.y) / n;
+        moments.w += (sample.w - moments.w) / n;
+    }
+
+    textureStore(moment_buffer, pos, 0, mean);
+    textureStore(moment_buffer, pos, 1, moments);
+    return vec4<f32>(mean.xyz, moments.w);
+}
+
+// Scales a sample down to a luminance of at most config.firefly_clamp, keeping its hue.
+// Clamping removes the energy of rare bright paths, so it trades fireflies for a darker, biased image.
+fn clamp_firefly(sample_color: vec3<f32>) -> vec3<f32> {
+    let luminance = dot(sample_color, vec3<f32>(0.2126, 0.7152, 0.0722));
+    if (luminance > config.firefly_clamp) {
+        return sample_color * (config.firefly_clamp / luminance);
+    }
+    return sample_color;
+}
+
+// Viewing direction of the camera, same as in calc_ray()
+fn camera_forward() -> vec3<f32> {
+    return normalize(normalize(camera.view_proj * vec4<f32>(0.0, 0.0, -1.0, 0.0)).xyz);
+}
+
+// Value that changes whenever the camera moves, rotates or changes its field of view
+fn camera_key() -> f32 {
+    let m = camera.view_proj;
+    return dot(m[0], vec4<f32>(1.0, 2.0, 3.0, 4.0))
+         + dot(m[1], vec4<f32>(5.0, 6.0, 7.0, 8.0))
+         + dot(m[2], vec4<f32>(9.0, 10.0, 11.0, 12.0))
+         + dot(m[3], vec4<f32>(13.0, 14.0, 15.0, 16.0))
+         + dot(camera.view_pos.xyz, vec3<f32>(17.0, 18.0, 19.0));
 }
 
 fn intersectPrimitive(ray: Ray, prim_index: i32) -> f32 {
@@ -175,6 +394,57 @@ fn intersectPrimitive(ray: Ray, prim_index: i32) -> f32 {
     return -1.0;
 }
 
+// Ray parameters of the near and far plane for a camera ray, the far plane is limited to config.max_ray_distance
+fn camera_ray_range(ray: Ray) -> vec2<f32> {
+    let ray_length = length(ray.direction);
+    return vec2<f32>(camera.frame[2] / ray_length, min(config.max_ray_distance, camera.frame[3] / ray_length));
+}
+
+// Value of the first hit shown by the debug views: albedo, normal (mapped to 0..1), depth (white at the near plane,
+// black at the far plane) or the number of visited BVH nodes as a heatmap
+fn debug_view_color(ray: Ray) -> vec3<f32> {
+    let range = camera_ray_range(ray);
+    let hit = intersectBVH(ray, range.x, range.y);
+    if (config.debug_view == DEBUG_VIEW_BVH_HEATMAP) {
+        let heat = clamp(hit.z / DEBUG_HEATMAP_MAX_NODES, 0.0, 1.0);
+        let cold_to_warm = mix(vec3<f32>(0.0, 0.0, 1.0), vec3<f32>(0.0, 1.0, 0.0), clamp(heat * 2.0, 0.0, 1.0));
+        return mix(cold_to_warm, vec3<f32>(1.0, 0.0, 0.0), clamp(heat * 2.0 - 1.0, 0.0, 1.0));
+    }
+    if (hit.x < 0.0) {
+        return vec3<f32>(0.0);
+    }
+    if (config.debug_view == DEBUG_VIEW_DEPTH) {
+        let distance = hit.y * length(ray.direction);
+        return vec3<f32>(1.0 - clamp((distance - camera.frame[2]) / (camera.frame[3] - camera.frame[2]), 0.0, 1.0));
+    }
+
+    let hit_point = ray.origin + ray.direction * hit.y;
+    var normal: vec3<f32>;
+    var material: Material;
+    var texture_id_diffuse: i32;
+    var uv: vec2<f32>;
+    if (hit.w == BVH_PRIM_SPHERE) {
+        let sphere = spheres[i32(hit.x)];
+        normal = normalize(hit_point - sphere.center.xyz);
+        material = materials[i32(sphere.material_texture_ids[0])];
+        texture_id_diffuse = i32(sphere.material_texture_ids[1]);
+        uv = sphereUVMapping(hit_point, sphere);
+    } else {
+        let tris = triangles[i32(hit.x)];
+        normal = interpolate_normal(tris, hit_point);
+        material = materials[i32(tris.material_texture_ids[0])];
+        texture_id_diffuse = i32(tris.material_texture_ids[1]);
+        uv = tex_coord(tris.vertex1.xyz, tris.vertex2.xyz, tris.vertex3.xyz, tris.tex_coords1.xy, tris.tex_coords1.zw, tris.tex_coords2.xy, hit_point);
+    }
+    if (config.debug_view == DEBUG_VIEW_NORMAL) {
+        return normal * 0.5 + 0.5;
+    }
+    if (texture_id_diffuse > -1) {
+        return get_texture_color(texture_id_diffuse, uv, material.wrap_mode);
+    }
+    return material.albedo.xyz;
+}
+
 fn debug_rand_color() -> vec3<f32> {
     return vec3<f32>(rngNextFloat(), rngNextFloat(), rngNextFloat());
 }
@@ -182,14 +452,14 @@ fn debug_rand_color() -> vec3<f32> {
 fn debug_bvh_bounding(ray: Ray) -> vec3<f32> {
     // draws all the bounding boxes
     // Optimized O(logn) complexity
-    var hit_bvh: vec3<f32> = intersectBVH(ray);
+    var hit_bvh: vec4<f32> = intersectBVH(ray, 0.0, config.max_ray_distance);
     return vec3<f32>(0.1*hit_bvh.z, 0.0, 0.0); // Adjust Scaling factor to make the bounding boxes more visible
 }
 
 fn debug_bvh_bounding_color(ray: Ray) {
     // draws all the bounding boxes and colors them
     // Optimized O(logn) complexity
-    var hit_bvh: f32 = intersectBVH(ray).z;
+    var hit_bvh: f32 = intersectBVH(ray, 0.0, config.max_ray_distance).z;
     if (hit_bvh > 0.0) {
         // BVH node hit, color the pixel red
         let color_index = i32(hit_bvh) % 4; // Adjust 4 based on the number of layers
@@ -210,26 +480,60 @@ fn debug_bvh_bounding_color(ray: Ray) {
 }
 
 fn background_color(ray: Ray) -> vec3<f32> {
-    let null_sphere = Sphere(vec4<f32>(vec3<f32>(0.0, 0.0, 0.0), 1.0), vec4<f32>(0.0, 0.0, 0.0, 0.0), vec4<f32>(0.0, 0.0, 0.0, 0.0));
+    let null_sphere = Sphere(vec4<f32>(vec3<f32>(0.0, 0.0, 0.0), 1.0), vec4<f32>(0.0, 0.0, 0.0, 0.0), vec4<f32>(0.0, 0.0, 0.0, 0.0), vec4<f32>(-1.0, 0.0, 0.0, 0.0));
     let uv = sphereUVMapping(-1.0*ray.direction, null_sphere); // *-1 fixes upside down environment
     
-    if (background.material_ids.x != -1.0) && (background.material_ids.y != -1.0) {
+    let mode = u32(background.material_ids.z);
+    if (mode == BACKGROUND_TEXTURE && background.material_ids.x != -1.0 && background.material_ids.y != -1.0) {
         return textureSampleLevel(background_texture, texture_sampler, uv, 0.0).xyz * background.intensity.x * materials[i32(background.material_ids.x)].albedo.xyz;
-    //} else if (background.material_ids.x != -1.0) {
-    //    return background.intensity.x * materials[i32(background.material_ids.x)].albedo.xyz;
-    //} else if (background.material_ids.y != -1.0) {
-    //    return textureSampleLevel(background_texture, texture_sampler, uv, 0.0).xyz * background.intensity.x;
+    } else if (mode == BACKGROUND_COLOR) {
+        return background.intensity.yzw * background.intensity.x;
+    } else if (mode == BACKGROUND_GRADIENT) {
+        return gradient_color(ray) * background.intensity.x;
     } else {
-        return sky_color(ray);
+        return sky_color(ray) * background.intensity.x;
     }
 }
 
-fn calc_ray(screen_pos: vec2<u32>, screen_size: vec2<u32>) -> Ray {
+// Index of a sample of the current pixel in the sequence of all frames, counts up with every sample
+fn sample_index(color_sample: i32) -> u32 {
+    return u32(camera.frame[0]) * u32(max(config.samples, 1)) + u32(color_sample);
+}
+
+// Position of a camera ray inside its pixel in 0..1, following config.sampling_pattern.
+// The low-discrepancy sequences are the same for every pixel, so they are shifted by a random
+// offset per pixel (Cranley-Patterson rotation) to avoid structured aliasing across the image.
+fn pixel_jitter(screen_pos: vec2<u32>, screen_size: vec2<u32>, index: u32) -> vec2<f32> {
+    // Turned off while the camera moves, the rays go through the pixel centers
+    if config.jitter_enabled == 0 {
+        return vec2<f32>(0.5);
+    }
+    let pixel = screen_pos.y * screen_size.x + screen_pos.x;
+    let pixel_shift = vec2<f32>(f32(jenkinsHash(pixel) >> 8u), f32(jenkinsHash(pixel ^ 0x9e3779b9u) >> 8u)) / 16777216.0;
+    switch config.sampling_pattern {
+        case SAMPLING_HALTON: {
+            return fract(sample_offsets[index % SAMPLE_OFFSET_COUNT].xy + pixel_shift);
+        }
+        case SAMPLING_SOBOL: {
+            return fract(sample_offsets[index % SAMPLE_OFFSET_COUNT].zw + pixel_shift);
+        }
+        case SAMPLING_BLUE_NOISE: {
+            let noise = textureLoad(blue_noise, vec2<i32>(screen_pos % textureDimensions(blue_noise)), 0).xy;
+            // The R2 sequence moves the whole tile per sample, so every frame stays blue noise
+            return fract(noise + f32(index) * vec2<f32>(0.7548777, 0.5698403));
+        }
+        default: {
+            return vec2<f32>(rngNextFloat(), rngNextFloat());
+        }
+    }
+}
+
+fn calc_ray(screen_pos: vec2<u32>, screen_size: vec2<u32>, index: u32) -> Ray {
 
     //----------Camera----------------
     // Replace these with your camera properties
     let vfov: f32 = camera.frame[1]; // Vertical field of view in degrees
-    let aspect_ratio: f32 = f32(screen_size.x) / f32(screen_size.y);
+    let aspect_ratio: f32 = camera.projection[0]; // Aspect ratio of the window, kept up to date on resize
     let look_from: vec3<f32> = camera.view_pos.xyz; // Camera position
 
     // Redefine Lookat from cameralet 
@@ -244,8 +548,9 @@ fn calc_ray(screen_pos: vec2<u32>, screen_size: vec2<u32>) -> Ray {
     let viewport_height: f32 = 2.0 * h * focus_dist;
     let viewport_width: f32 = aspect_ratio * viewport_height;
 
-    let u: f32 = (f32(screen_pos.x) + -0.5+rngNextFloat()) / f32(screen_size.x);   // + Random offset
-    let v: f32 = (f32(screen_pos.y) + -0.5+rngNextFloat()) / f32(screen_size.y);
+    let jitter = pixel_jitter(screen_pos, screen_size, index);
+    let u: f32 = (f32(screen_pos.x) + -0.5+jitter.x) / f32(screen_size.x);   // + Sub-pixel offset
+    let v: f32 = (f32(screen_pos.y) + -0.5+jitter.y) / f32(screen_size.y);
 
     let w: vec3<f32> = normalize(look_from - look_at);
     let u_axis: vec3<f32> = normalize(cross(vec3<f32>(0.0, 1.0, 0.0), w));
@@ -270,9 +575,12 @@ fn calc_ray(screen_pos: vec2<u32>, screen_size: vec2<u32>) -> Ray {
     return Ray(ray_origin, ray_direction);
 }
 
-fn intersectBVH(ray: Ray, ) -> vec3<f32> {
+// Closest triangle or sphere hit with t_min < t < t_max, returns the primitive index in its buffer (-1 if none),
+// t (t_max if none), the number of visited nodes and the kind of the primitive
+fn intersectBVH(ray: Ray, t_min: f32, t_max: f32) -> vec4<f32> {
     var hit_bvh: i32 = -1;  //has any hit happened?
-    var t: f32 = config.max_ray_distance;     //at what t did it happen?
+    var hit_kind: f32 = BVH_PRIM_TRIANGLE;
+    var t: f32 = t_max;     //at what t did it happen?
     var hit_count: f32 = 0.0; //how many hits happened? (Only for debug shader)
 
     // Traverse the BVH
@@ -288,15 +596,23 @@ fn intersectBVH(ray: Ray, ) -> vec3<f32> {
             // If the ray intersects the BVH node's bounding box
             if (node.extra1.x > -1.0) {
                 // If it's a leaf node
-                // Check if the triangel is hit
+                // Check if the triangle or sphere is hit
                 for (var i = 0; i < i32(node.extra1.x); i = i + 1) {
-                    let primID = i32(bvh_prim_indices[i32(node.extra2.x)+i]);
-                    var hit: f32 = hit_tri(ray, triangles[primID]);
+                    let prim = bvh_prim_indices[i32(node.extra2.x)+i];
+                    let primID = i32(prim.y);
+                    var hit: f32;
+                    if (prim.x == BVH_PRIM_SPHERE) {
+                        hit = hit_sphere(ray, spheres[primID]);
+                    } else {
+                        hit = hit_tri(ray, triangles[primID]);
+                    }
 
-                    if (hit > 0.0) {
-                        if (hit < t+0.001){
+                    if (hit > t_min) {
+                        // Cut out parts of a surface are skipped, the ray continues to the surfaces behind them
+                        if (hit < t+0.001 && !alpha_cutout(ray, hit, prim.x, primID)){
                             t = hit;
                             hit_bvh = primID;
+                            hit_kind = prim.x;
                         }
                     }
                 }
@@ -332,7 +648,7 @@ fn intersectBVH(ray: Ray, ) -> vec3<f32> {
                 hit_count += 1.0;
             }
     }
-    let out = vec3<f32>(f32(hit_bvh), t, hit_count);
+    let out = vec4<f32>(f32(hit_bvh), t, hit_count, hit_kind);
     return out;
 }
 
@@ -394,12 +710,22 @@ fn hit_sphere(ray: Ray, sphere: Sphere) -> f32 {
     }
 }
 
+// Blends from the horizon to the top or bottom color by the height of the ray direction, same as SkyGradient::color
+fn gradient_color(ray: Ray) -> vec3<f32> {
+    let y = normalize(ray.direction).y;
+    if (y >= 0.0) {
+        return mix(background.gradient_horizon.xyz, background.gradient_top.xyz, min(y, 1.0));
+    }
+    return mix(background.gradient_horizon.xyz, background.gradient_bottom.xyz, min(-y, 1.0));
+}
+
 fn sky_color(ray: Ray) -> vec3<f32> {
     let unit_direction: vec3<f32> = normalize(ray.direction);
     let t: f32 = 0.5 * (unit_direction.y + 1.0);
     return mix(vec3<f32>(1.0, 1.0, 1.0), vec3<f32>(0.5, 0.7, 1.0), t);
 }
 
+// Traces a path from the camera ray. The ReferenceTracer in raytracer/src/reference.rs follows it on the CPU, keep them in sync
 fn color(primary_ray: Ray) -> vec4<f32> {
     let MAX_COLOR: f32 = 1.0;
 
@@ -411,76 +737,101 @@ fn color(primary_ray: Ray) -> vec4<f32> {
     var stacknr: i32 = 0;
 
     var weight = vec3<f32>(1.0,1.0,1.0);
+    // Light from the emissive triangles sampled at each bounce (next-event estimation)
+    var direct_light = vec3<f32>(0.0, 0.0, 0.0);
+    // Part of the emission that was already sampled directly at the previous bounce
+    var light_sampled: f32 = 0.0;
+    // Pdf of the last scattered direction if it came from the diffuse part, used to weight hits of emissive triangles
+    var bsdf_pdf: f32 = 0.0;
+    // Absorption coefficient of the dielectric the ray travels through, zero outside of objects
+    var medium_absorption = vec3<f32>(0.0, 0.0, 0.0);
 
     while (depth <= config.max_bounces) {
-        var t = config.max_ray_distance;
+        // Camera rays only hit surfaces between the near and far plane of the projection,
+        // the ray direction isn't normalized, so the distances are converted to ray parameters
+        var t_min: f32 = 0.0;
+        var t_max: f32 = config.max_ray_distance;
+        if (depth == 0) {
+            let range = camera_ray_range(ray);
+            t_min = range.x;
+            t_max = range.y;
+        }
+        var t = t_max;
         var closest_sphere: Sphere;
         var closest_tris: Triangle;
         var is_sphere: bool = false;
 
-        // Check if a Sphere is hit
-        for (var i = 0; i < i32(arrayLength(&spheres)); i = i + 1) {
-            // if no sphere is denfined, a "empty" sphere wth radius is 
-            //   added so that the buffer exists and the shader can be compiled
-            if spheres[i].radius.x == 0.0 { 
-                continue;
-            }
-            // Check if a Sphere is hit
-            var hit: f32 = hit_sphere(ray, spheres[i]);
-            if (hit > 0.0 && hit < t) {
-                t = hit;
-                closest_sphere = spheres[i];
-                is_sphere = true;
-            }
-        }
-
-        // Check if a BVH node is hit
-        var hit_bvh: vec3<f32> = intersectBVH(ray);
-        if (hit_bvh.x > -1.0 && hit_bvh.y < t) {
-            // Set 'bvh_hit' to the index of the hit BVH node
+        // Find the closest triangle or sphere, the BVH holds both
+        var hit_bvh: vec4<f32> = intersectBVH(ray, t_min, t_max);
+        if (hit_bvh.x > -1.0) {
             t = hit_bvh.y;
-            closest_tris = triangles[i32(hit_bvh.x)];
-            is_sphere = false;
+            is_sphere = hit_bvh.w == BVH_PRIM_SPHERE;
+            if (is_sphere) {
+                closest_sphere = spheres[i32(hit_bvh.x)];
+            } else {
+                closest_tris = triangles[i32(hit_bvh.x)];
+            }
         }
         
         // Return background color if no object is hit
-        if (t == config.max_ray_distance) {
+        if (t == t_max) {
             if (depth == 0){
+                first_hit = vec4<f32>(0.0, 0.0, 0.0, config.max_ray_distance);
+                if (config.transparent_background == 1) {
+                    return vec4<f32>(0.0);
+                }
                 return vec4<f32>(background_color(ray), 1.0);
             } else {
                 pixel_color = mix(pixel_color, background_color(ray), weight); //like this or with weight.x better?
-                return vec4<f32>(pixel_color, 1.0);
+                return vec4<f32>(pixel_color + direct_light, 1.0);
             }
         }
 
-        // Check for focus distance if focus viewer is enabled
-        if (config.focus_viewer_visible == 1) {
-            if (depth == 0) {
-                if (t > 1.0 - 0.005 && t < 1.0 + 0.005) {
-                    return vec4<f32>(1.0, 0.0, 0.0, 1.0);
-                }
+        let hit_point: vec3<f32> = ray.origin + ray.direction * t;
+
+        // Beer-Lambert absorption along the distance travelled inside the medium,
+        // the scattering below decides if the next segment is inside of it again
+        if (any(medium_absorption > vec3<f32>(0.0, 0.0, 0.0))) {
+            let transmittance = exp(-medium_absorption * t * length(ray.direction));
+            pixel_color *= transmittance;
+            weight *= transmittance;
+            medium_absorption = vec3<f32>(0.0, 0.0, 0.0);
+        }
+
+        // Check for focus distance if focus viewer is enabled, the tint is applied in main()
+        if (config.focus_viewer_visible == 1 && depth == 0) {
+            let view_depth = dot(hit_point - camera.view_pos.xyz, camera_forward());
+            if (abs(view_depth - config.focus_distance) < FOCUS_BAND_WIDTH * config.focus_distance) {
+                focus_plane_hits += 1.0;
             }
         }
-        
-        let hit_point: vec3<f32> = ray.origin + ray.direction * t;
+
         var normal: vec3<f32>;
         var material: Material;
         // Texture ids
         var texture_id_diffuse: i32;
         var texture_id_roughness: i32;
         var texture_id_normal: i32;
+        var texture_id_emission: i32;
     
         var uv: vec2<f32>;
+        var tangent: vec4<f32>;
+        // Set if the bounce samples the specular lobe of the metallic-roughness model
+        var specular = false;
+        // Set if the specular lobe is the one of the clearcoat
+        var clearcoat = false;
         if (is_sphere){
             normal = normalize(hit_point - closest_sphere.center.xyz);
             material = materials[i32(closest_sphere.material_texture_ids[0])];
             uv = sphereUVMapping(hit_point, closest_sphere);
+            tangent = sphere_uv_tangent(normal, closest_sphere);
             // Texture ids
             texture_id_diffuse = i32(closest_sphere.material_texture_ids[1]);
             texture_id_roughness = i32(closest_sphere.material_texture_ids[2]);
             texture_id_normal = i32(closest_sphere.material_texture_ids[3]);
+            texture_id_emission = i32(closest_sphere.emission_texture_id[0]);
         } else {
-            normal = normalize(closest_tris.normals.xyz);
+            normal = interpolate_normal(closest_tris, hit_point);
             material = materials[i32(closest_tris.material_texture_ids[0])];
 
             //new uv coords
@@ -488,50 +839,223 @@ fn color(primary_ray: Ray) -> vec4<f32> {
             let tex2 = closest_tris.tex_coords1.zw;
             let tex3 = closest_tris.tex_coords2.xy;
             uv = tex_coord(closest_tris.vertex1.xyz, closest_tris.vertex2.xyz, closest_tris.vertex3.xyz, tex1, tex2, tex3, hit_point);
+            tangent = closest_tris.tangent;
             // Texture ids
             texture_id_diffuse = i32(closest_tris.material_texture_ids[1]);
             texture_id_roughness = i32(closest_tris.material_texture_ids[2]);
             texture_id_normal = i32(closest_tris.material_texture_ids[3]);
+            texture_id_emission = i32(closest_tris.emission_texture_id[0]);
+        }
+
+        // Perturb the normal with the normal map
+        if (texture_id_normal > -1) {
+            normal = apply_normal_map(normal, tangent, get_texture_color(texture_id_normal, uv, material.wrap_mode));
+        }
+        // Thin surfaces are shaded from both sides. Dielectrics keep the outward normal, it tells if the ray enters or leaves
+        if (material.double_sided == 1u && material.ior == 0.0 && dot(normal, ray.direction) > 0.0) {
+            normal = -normal;
+        }
+        if (depth == 0) {
+            first_hit = vec4<f32>(normal, t);
+        }
+
+        // The emission map replaces the albedo as color of the emitted light
+        let emission_map = texture_id_emission > -1 && material.emission > 0.0;
+        var emission_color = material.albedo.xyz;
+        if (emission_map) {
+            emission_color = get_texture_color(texture_id_emission, uv, material.wrap_mode);
         }
 
         // Update color
-        if texture_id_diffuse > -1 {
-            pixel_color *= get_texture_color(texture_id_diffuse, uv);
-            weight *= get_texture_color(texture_id_roughness, uv); // Update weight based on material attenuation
+        if (texture_id_diffuse > -1 && !emission_map) {
+            pixel_color *= get_texture_color(texture_id_diffuse, uv, material.wrap_mode);
+            weight *= get_texture_color(texture_id_roughness, uv, material.wrap_mode); // Update weight based on material attenuation
         } else if (material.emission > 0.0) {
             // Handle emissive material directly
             if (depth == 0) {
-                pixel_color = material.albedo.xyz * material.emission;
-            } else{
-                pixel_color += material.albedo.xyz * material.emission * weight;
+                pixel_color = emission_color * material.emission;
+            } else if (is_sphere || emission_map) {
+                // Not sampled directly as lights, so the full emission is added
+                pixel_color += emission_color * material.emission * weight;
+            } else if (config.mis_enabled == 1) {
+                // The diffuse part could have sampled this triangle directly as well, weight it by the power heuristic
+                let direction_length = length(ray.direction);
+                let light_pdf = emissive_triangle_pdf(closest_tris, ray.direction / direction_length, t * direction_length);
+                let mis_weight = (1.0 - light_sampled) + light_sampled * power_heuristic(bsdf_pdf, light_pdf);
+                pixel_color += material.albedo.xyz * material.emission * weight * mis_weight;
+            } else {
+                // Emissive triangles were already sampled directly, only add the part that wasn't
+                pixel_color += material.albedo.xyz * material.emission * weight * (1.0 - light_sampled);
+            }
+            return vec4<f32>(pixel_color + direct_light, 1.0); // Terminate the loop when an emissive object is hit
+        } else if (material.ior == 0.0) {
+            // Metallic-roughness: metals reflect with the Fresnel of their albedo, dielectrics reflect an
+            // untinted part with the Fresnel of F0 = 0.04 and scatter the rest with their albedo
+            let cos_theta = abs(dot(normal, normalize(ray.direction)));
+            // The clearcoat is a dielectric layer on top that reflects untinted, the rest of the light reaches the base.
+            // Without a coat no random number is drawn, so the other lobes see the same sequence as before
+            if (material.clearcoat > 0.0 && rngNextFloat() < material.clearcoat * fresnel_schlick(vec3<f32>(DIELECTRIC_F0), cos_theta).x) {
+                specular = true;
+                clearcoat = true;
+            } else if (rngNextFloat() < material.metallic) {
+                pixel_color *= fresnel_schlick(material.albedo.xyz, cos_theta);
+                specular = true;
+            } else if (rngNextFloat() < fresnel_schlick(vec3<f32>(DIELECTRIC_F0), cos_theta).x) {
+                specular = true;
+            } else {
+                pixel_color *= material.albedo.xyz;
             }
-            return vec4<f32>(pixel_color, 1.0); // Terminate the loop when an emissive object is hit
+            weight *= material.attenuation.xyz; // Update weight based on material attenuation
         } else {
+            // The attenuation of dielectrics is their absorption, applied on the way through them
             pixel_color *= material.albedo.xyz;
-            weight *= material.attenuation.xyz; // Update weight based on material attenuation
         }
 
-        // Calculate new ray
+        // Sample a random emissive triangle. The scatter model blends between a mirror (roughness 0) and
+        // a diffuse reflection (roughness 1), so only the diffuse part is lit directly.
+        // The blend uses the roughness as is, only the width of the reflection lobes is remapped with roughness_alpha.
+        var light_sample = vec3<f32>(0.0, 0.0, 0.0);
+        light_sampled = 0.0;
+        let facing_normal = select(normal, -normal, dot(normal, ray.direction) > 0.0);
+        if (material.ior == 0.0 && material.roughness > 0.0 && !specular) {
+            light_sample = sample_emissive_triangle(hit_point, facing_normal) * material.roughness;
+            light_sampled = material.roughness;
+        }
+
+        // Calculate new ray, ggx_pdf is set if the direction was sampled from the GGX lobe
+        var ggx_pdf = 0.0;
         if (texture_id_roughness > -1 && texture_id_normal > -1){
-            ray = Ray(hit_point + normal*0.001, reflect(ray.direction,  get_texture_color(texture_id_normal, uv) + rngNextVec3InUnitSphere() * get_texture_color(texture_id_roughness, uv)));            
+            ray = Ray(hit_point + normal * config.ray_bias, reflect(ray.direction, normal + rngNextVec3InUnitSphere() * roughness_map_alpha(get_texture_color(texture_id_roughness, uv, material.wrap_mode))));
         } else if (texture_id_roughness > -1) {
-            ray = Ray(hit_point + normal*0.001, reflect(ray.direction, normal + rngNextVec3InUnitSphere() * material.roughness * get_texture_color(texture_id_roughness, uv))); //normal*0.01 is a offset to fix z-fighting
+            ray = Ray(hit_point + normal * config.ray_bias, reflect(ray.direction, normal + rngNextVec3InUnitSphere() * roughness_map_alpha(material.roughness * get_texture_color(texture_id_roughness, uv, material.wrap_mode))));
         } else if (texture_id_normal > -1) {
-            ray = Ray(hit_point + normal*0.001, reflect(ray.direction, normal * get_texture_color(texture_id_normal, uv)+ rngNextVec3InUnitSphere() * material.roughness)); //normal*0.01 is a offset to fix z-fighting
+            ray = Ray(hit_point + normal * config.ray_bias, reflect(ray.direction, normal + rngNextVec3InUnitSphere() * roughness_alpha(material.roughness)));
         } else if (material.ior > 0.0) {
             ray = dielectric_scatter(ray, hit_point, normal, material);
+            // Refracted into the object or reflected inside of it, the normal points outwards
+            medium_absorption = select(vec3<f32>(0.0, 0.0, 0.0), material.attenuation.xyz, dot(ray.direction, normal) < 0.0);
+        } else if (specular) {
+            // GGX importance sampling of the microfacet normal, the Fresnel term was applied above
+            let view = -normalize(ray.direction);
+            let alpha = max(roughness_alpha(select(material.roughness, material.clearcoat_roughness, clearcoat)), MIN_GGX_ALPHA);
+            let half_vector = sample_ggx(facing_normal, alpha);
+            let direction = reflect(-view, half_vector);
+            let n_dot_l = dot(facing_normal, direction);
+            if (n_dot_l <= 0.0) {
+                // Reflected below the surface, the path is absorbed
+                return vec4<f32>(direct_light, 1.0);
+            }
+            let n_dot_v = max(dot(facing_normal, view), 0.0001);
+            let n_dot_h = max(dot(facing_normal, half_vector), 0.0001);
+            let v_dot_h = max(dot(view, half_vector), 0.0001);
+            pixel_color *= ggx_sample_weight(n_dot_v, n_dot_l, n_dot_h, v_dot_h, alpha);
+            ray = Ray(hit_point + facing_normal * config.ray_bias, direction);
+            ggx_pdf = ggx_reflection_pdf(n_dot_h, v_dot_h, alpha);
+        } else if (rngNextFloat() < material.roughness) {
+            // The diffuse part, importance sampled like the lambertian lobe the light samples assume
+            ray = Ray(hit_point + facing_normal * config.ray_bias, sample_cosine_hemisphere(facing_normal));
         } else {
-            ray = Ray(hit_point + normal*0.001, reflect(ray.direction, normal + rngNextVec3InUnitSphere() * material.roughness)); //normal*0.01 is a offset to fix z-fighting
+            // Glossy reflection, blurred by the lobe width of the roughness
+            ray = Ray(hit_point + normal * config.ray_bias, reflect(ray.direction, normal + rngNextVec3InUnitSphere() * roughness_alpha(material.roughness)));
         }
 
-        weight *= material.attenuation.x; // Update weight based on material attenuation
+        // The diffuse part is treated as a cosine weighted lambertian lobe, like in sample_emissive_triangle
+        if (ggx_pdf > 0.0) {
+            bsdf_pdf = ggx_pdf;
+        } else {
+            bsdf_pdf = max(dot(facing_normal, normalize(ray.direction)), 0.0) / pi;
+        }
+
+        if (material.ior == 0.0) {
+            weight *= material.attenuation.x; // Update weight based on material attenuation
+        }
+        // Weighted like an emissive hit of the next bounce
+        direct_light += light_sample * weight;
         depth += 1;
     }
-    return vec4<f32>(pixel_color, 1.0);
+    return vec4<f32>(pixel_color + direct_light, 1.0);
 }
 
-fn tex_coord(tris1_pos: vec3<f32>, tris2_pos: vec3<f32>, tris3_pos: vec3<f32>, tex1: vec2<f32>, tex2: vec2<f32>, tex3: vec2<f32>, hit_point: vec3<f32>) -> vec2<f32> {
-    // Barycentric coordinates calculation
+// Next-event estimation: returns the light arriving at hit_point from a random point on a random emissive triangle.
+// The sample is divided by its pdf (1 / (count * area), converted to solid angle), the lambertian 1/pi is included.
+fn sample_emissive_triangle(hit_point: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    let count = arrayLength(&emissive_triangles);
+    if (emissive_triangles[0] == NO_EMISSIVE_TRIANGLES) {
+        return vec3<f32>(0.0, 0.0, 0.0);
+    }
+    let triangle_index = emissive_triangles[min(u32(rngNextFloat() * f32(count)), count - 1u)];
+    let light = triangles[triangle_index];
+
+    // Uniform point on the triangle, samples outside of it are mirrored back in
+    var u = rngNextFloat();
+    var v = rngNextFloat();
+    if (u + v > 1.0) {
+        u = 1.0 - u;
+        v = 1.0 - v;
+    }
+    let edge1 = light.vertex2.xyz - light.vertex1.xyz;
+    let edge2 = light.vertex3.xyz - light.vertex1.xyz;
+    let light_point = light.vertex1.xyz + edge1 * u + edge2 * v;
+    let light_cross = cross(edge1, edge2);
+    let area = 0.5 * length(light_cross);
+
+    let to_light = light_point - hit_point;
+    let distance_squared = dot(to_light, to_light);
+    let distance = sqrt(distance_squared);
+    let direction = to_light / distance;
+
+    // Lights emit on both sides
+    let cos_surface = dot(normal, direction);
+    let cos_light = abs(dot(light_cross / (2.0 * area), direction));
+    if (cos_surface <= 0.0 || cos_light <= 0.0) {
+        return vec3<f32>(0.0, 0.0, 0.0);
+    }
+
+    if (is_occluded(Ray(hit_point + normal * config.ray_bias, direction), distance * 0.999, i32(triangle_index))) {
+        return vec3<f32>(0.0, 0.0, 0.0);
+    }
+
+    // With MIS the sample is weighted against hitting the light with a diffuse bounce
+    var mis_weight = 1.0;
+    if (config.mis_enabled == 1) {
+        let light_pdf = distance_squared / (cos_light * area * f32(count));
+        mis_weight = power_heuristic(light_pdf, cos_surface / pi);
+    }
+
+    let material = materials[i32(light.material_texture_ids[0])];
+    return material.albedo.xyz * material.emission * cos_surface * cos_light * area * f32(count) * mis_weight / (distance_squared * pi);
+}
+
+// Solid angle pdf of sample_emissive_triangle choosing the given direction to a point on the light at distance
+fn emissive_triangle_pdf(light: Triangle, direction: vec3<f32>, distance: f32) -> f32 {
+    let light_cross = cross(light.vertex2.xyz - light.vertex1.xyz, light.vertex3.xyz - light.vertex1.xyz);
+    let area = 0.5 * length(light_cross);
+    let cos_light = abs(dot(light_cross / (2.0 * area), direction));
+    if (area <= 0.0 || cos_light <= 0.0) {
+        return 0.0;
+    }
+    return distance * distance / (cos_light * area * f32(arrayLength(&emissive_triangles)));
+}
+
+// Power heuristic (beta = 2) weight of a sample with pdf, when other_pdf could have produced it as well
+fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+    let pdf_squared = pdf * pdf;
+    let sum = pdf_squared + other_pdf * other_pdf;
+    if (sum <= 0.0) {
+        return 0.0;
+    }
+    return pdf_squared / sum;
+}
+
+// Shadow ray test, true if anything except the light triangle is hit closer than max_distance
+fn is_occluded(ray: Ray, max_distance: f32, light_index: i32) -> bool {
+    let hit_bvh: vec4<f32> = intersectBVH(ray, 0.0, config.max_ray_distance);
+    let is_light = hit_bvh.w == BVH_PRIM_TRIANGLE && i32(hit_bvh.x) == light_index;
+    return hit_bvh.x > -1.0 && !is_light && hit_bvh.y < max_distance;
+}
+
+// Barycentric coordinates of a point on the triangle, the weights of the first, second and third vertex
+fn barycentric(tris1_pos: vec3<f32>, tris2_pos: vec3<f32>, tris3_pos: vec3<f32>, hit_point: vec3<f32>) -> vec3<f32> {
     let v0 = tris2_pos - tris1_pos;
     let v1 = tris3_pos - tris1_pos;
     let v2 = hit_point - tris1_pos;
@@ -546,9 +1070,28 @@ fn tex_coord(tris1_pos: vec3<f32>, tris2_pos: vec3<f32>, tris3_pos: vec3<f32>, t
     let u = (dot11 * dot02 - dot01 * dot12) * invDenom;
     let v = (dot00 * dot12 - dot01 * dot02) * invDenom;
 
-    let texcoord = tex1 * (1.0 - u - v) +
-                   tex2 * u +
-                   tex3 * v;
+    return vec3<f32>(1.0 - u - v, u, v);
+}
+
+// Smooth shading normal, the vertex normals interpolated at the hit point.
+// Falls back to the face normal if the vertex normals cancel out.
+fn interpolate_normal(tris: Triangle, hit_point: vec3<f32>) -> vec3<f32> {
+    let weights = barycentric(tris.vertex1.xyz, tris.vertex2.xyz, tris.vertex3.xyz, hit_point);
+    let normal = tris.vertex_normal1.xyz * weights.x +
+                 tris.vertex_normal2.xyz * weights.y +
+                 tris.vertex_normal3.xyz * weights.z;
+    if (dot(normal, normal) < 1e-12) {
+        return normalize(tris.normals.xyz);
+    }
+    return normalize(normal);
+}
+
+fn tex_coord(tris1_pos: vec3<f32>, tris2_pos: vec3<f32>, tris3_pos: vec3<f32>, tex1: vec2<f32>, tex2: vec2<f32>, tex3: vec2<f32>, hit_point: vec3<f32>) -> vec2<f32> {
+    let weights = barycentric(tris1_pos, tris2_pos, tris3_pos, hit_point);
+
+    let texcoord = tex1 * weights.x +
+                   tex2 * weights.y +
+                   tex3 * weights.z;
 
     // Perform texture sampling using texcoord
     // Example: let color = textureSample(texture, texcoord);
@@ -556,6 +1099,12 @@ fn tex_coord(tris1_pos: vec3<f32>, tris2_pos: vec3<f32>, tris3_pos: vec3<f32>, t
     return vec2<f32>(texcoord);
 }
 
+// Moves the origin of a secondary ray by config.ray_bias to the side of the surface the ray leaves to,
+// so it doesn't hit the surface it starts on again
+fn offset_ray_origin(hit_point: vec3<f32>, normal: vec3<f32>, direction: vec3<f32>) -> vec3<f32> {
+    return hit_point + normal * select(-config.ray_bias, config.ray_bias, dot(direction, normal) >= 0.0);
+}
+
 // Dielectric material function
 fn dielectric_scatter(ray: Ray, hit_point: vec3<f32>, normal: vec3<f32>, material: Material) -> Ray {
     var etai_over_etat: f32;
@@ -576,14 +1125,74 @@ fn dielectric_scatter(ray: Ray, hit_point: vec3<f32>, normal: vec3<f32>, materia
     if ((rngNextFloat() - 0.5) < reflect_prob || cannot_refract) {
         // Reflect
         let reflected_direction: vec3<f32> = reflect(unit_direction, normal);
-        return Ray(hit_point, reflected_direction);   //normal*0.01 is a offset to fix z-fighting
+        return Ray(offset_ray_origin(hit_point, normal, reflected_direction), reflected_direction);
     } else {
         // Refract
         let refracted_direction: vec3<f32> = refract(unit_direction, normal, etai_over_etat);
-        return Ray(hit_point, refracted_direction);   //normal*0.01 is a offset to fix z-fighting
+        return Ray(offset_ray_origin(hit_point, normal, refracted_direction), refracted_direction);
     }
 }
 
+// Width of the reflection lobes (the GGX alpha) for a material roughness. The roughness is perceptual like in glTF
+// and squared, unless config.roughness_linear says it is alpha already. Same as roughness_to_alpha on the CPU side
+fn roughness_alpha(roughness: f32) -> f32 {
+    return select(roughness * roughness, roughness, config.roughness_linear == 1);
+}
+
+// roughness_alpha of every channel of a roughness map
+fn roughness_map_alpha(roughness: vec3<f32>) -> vec3<f32> {
+    return select(roughness * roughness, roughness, config.roughness_linear == 1);
+}
+
+// Samples a microfacet normal of the GGX (Trowbridge-Reitz) distribution with the given alpha (see roughness_alpha),
+// proportional to D(h) * dot(n, h)
+fn sample_ggx(normal: vec3<f32>, alpha: f32) -> vec3<f32> {
+    let u1 = rngNextFloat();
+    let u2 = rngNextFloat();
+    let cos_theta = sqrt((1.0 - u1) / (1.0 + (alpha * alpha - 1.0) * u1));
+    let sin_theta = sqrt(max(1.0 - cos_theta * cos_theta, 0.0));
+    let phi = 2.0 * pi * u2;
+    let tangent = sphere_tangent(normal).xyz;
+    let bitangent = cross(normal, tangent);
+    return normalize(tangent * sin_theta * cos(phi) + bitangent * sin_theta * sin(phi) + normal * cos_theta);
+}
+
+// Cosine weighted direction on the hemisphere around the normal, the pdf is dot(normal, direction) / pi.
+// A uniform point on the unit disk is projected up onto the hemisphere. Same as sample_cosine_hemisphere in scene/src/sampling.rs
+fn sample_cosine_hemisphere(normal: vec3<f32>) -> vec3<f32> {
+    let u1 = rngNextFloat();
+    let u2 = rngNextFloat();
+    let radius = sqrt(u1);
+    let phi = 2.0 * pi * u2;
+    let tangent = sphere_tangent(normal).xyz;
+    let bitangent = cross(normal, tangent);
+    let z = sqrt(max(1.0 - u1, 0.0));
+    return normalize(tangent * radius * cos(phi) + bitangent * radius * sin(phi) + normal * z);
+}
+
+// GGX normal distribution D(h)
+fn ggx_distribution(n_dot_h: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    let denominator = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (pi * denominator * denominator);
+}
+
+// Pdf of a direction reflected about a normal from sample_ggx, D(h) * dot(n, h) / (4 * dot(v, h))
+fn ggx_reflection_pdf(n_dot_h: f32, v_dot_h: f32, alpha: f32) -> f32 {
+    return ggx_distribution(n_dot_h, alpha) * n_dot_h / (4.0 * v_dot_h);
+}
+
+// Smith masking term of GGX for one direction
+fn smith_g1_ggx(n_dot_x: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    return 2.0 * n_dot_x / (n_dot_x + sqrt(a2 + (1.0 - a2) * n_dot_x * n_dot_x));
+}
+
+// brdf * cos / pdf of a direction from sample_ggx without the Fresnel term, G(v, l) * dot(v, h) / (dot(n, h) * dot(n, v))
+fn ggx_sample_weight(n_dot_v: f32, n_dot_l: f32, n_dot_h: f32, v_dot_h: f32, alpha: f32) -> f32 {
+    return smith_g1_ggx(n_dot_v, alpha) * smith_g1_ggx(n_dot_l, alpha) * v_dot_h / (n_dot_h * n_dot_v);
+}
+
 fn reflect(v: vec3<f32>, n: vec3<f32>) -> vec3<f32> {
     return v - 2.0 * dot(v, n) * n;
 }
@@ -595,6 +1204,11 @@ fn refract(direction: vec3<f32>, normal: vec3<f32>, etai_over_etat: f32) -> vec3
     return r_out_parallel + r_out_perp;
 }
 
+// Fresnel-Schlick with the reflectance at normal incidence, the albedo for metals and DIELECTRIC_F0 otherwise
+fn fresnel_schlick(f0: vec3<f32>, cos_theta: f32) -> vec3<f32> {
+    return f0 + (vec3<f32>(1.0) - f0) * pow(1.0 - clamp(cos_theta, 0.0, 1.0), 5.0);
+}
+
 fn schlick(cosine: f32, ref_idx: f32) -> f32 {
     var r0: f32 = (1.0 - ref_idx) / (1.0 + ref_idx);
     r0 = r0 * r0;
@@ -609,17 +1223,117 @@ fn length_squared(v: vec3<f32>) -> f32 {
 
 
 // Textures
-fn get_texture_color(texture_id: i32, uv: vec2<f32>) -> vec3<f32> {
-    return textureSampleLevel(textures, texture_sampler, uv, texture_id, 0.0).xyz;
+fn get_texture_color(texture_id: i32, uv: vec2<f32>, wrap_mode: u32) -> vec3<f32> {
+    return get_texture_sample(texture_id, uv, wrap_mode).xyz;
+}
+
+// Color and alpha of a texture, see get_texture_color
+fn get_texture_sample(texture_id: i32, uv: vec2<f32>, wrap_mode: u32) -> vec4<f32> {
+    let region = texture_regions[texture_id];
+    let layer = i32(region.layer.x);
+    if (region.layer.y > 0.5) {
+        // The samplers would wrap across the whole atlas page, so the uv is wrapped before mapping it into the region
+        let atlas_uv = region.uv_rect.xy + wrap_uv(uv, wrap_mode) * region.uv_rect.zw;
+        return textureSampleLevel(textures, texture_sampler_clamp, atlas_uv, layer, 0.0);
+    }
+    if (wrap_mode == 1u) {
+        return textureSampleLevel(textures, texture_sampler_clamp, uv, layer, 0.0);
+    } else if (wrap_mode == 2u) {
+        return textureSampleLevel(textures, texture_sampler_mirror, uv, layer, 0.0);
+    }
+    return textureSampleLevel(textures, texture_sampler, uv, layer, 0.0);
+}
+
+// True if the ray passes through the triangle or sphere hit at t, because its diffuse texture is transparent there.
+// Masked surfaces are cut out below the alpha cutoff, blended surfaces let the ray pass with a probability of 1 - alpha.
+fn alpha_cutout(ray: Ray, t: f32, kind: f32, prim_id: i32) -> bool {
+    var material_texture_ids: vec4<f32>;
+    if (kind == BVH_PRIM_SPHERE) {
+        material_texture_ids = spheres[prim_id].material_texture_ids;
+    } else {
+        material_texture_ids = triangles[prim_id].material_texture_ids;
+    }
+    let material = materials[i32(material_texture_ids[0])];
+    let texture_id_diffuse = i32(material_texture_ids[1]);
+    if (material.alpha_mode == ALPHA_MODE_OPAQUE || texture_id_diffuse < 0) {
+        return false;
+    }
+
+    let hit_point = ray.origin + ray.direction * t;
+    var uv: vec2<f32>;
+    if (kind == BVH_PRIM_SPHERE) {
+        uv = sphereUVMapping(hit_point, spheres[prim_id]);
+    } else {
+        let tris = triangles[prim_id];
+        uv = tex_coord(tris.vertex1.xyz, tris.vertex2.xyz, tris.vertex3.xyz, tris.tex_coords1.xy, tris.tex_coords1.zw, tris.tex_coords2.xy, hit_point);
+    }
+    let alpha = get_texture_sample(texture_id_diffuse, uv, material.wrap_mode).w;
+    if (material.alpha_mode == ALPHA_MODE_BLEND) {
+        return rngNextFloat() >= alpha;
+    }
+    return alpha < material.alpha_cutoff;
+}
+
+// Same as the address modes of the samplers: 0 repeat, 1 clamp, 2 mirror
+fn wrap_uv(uv: vec2<f32>, wrap_mode: u32) -> vec2<f32> {
+    if (wrap_mode == 1u) {
+        return clamp(uv, vec2<f32>(0.0), vec2<f32>(1.0));
+    } else if (wrap_mode == 2u) {
+        return vec2<f32>(1.0) - abs(fract(uv * 0.5) * 2.0 - vec2<f32>(1.0));
+    }
+    return fract(uv);
+}
+
+// Tangent space normal mapping, the normal map stores the normal in [0, 1] per channel
+fn apply_normal_map(normal: vec3<f32>, tangent: vec4<f32>, normal_map_color: vec3<f32>) -> vec3<f32> {
+    let tangent_normal = normal_map_color * 2.0 - 1.0;
+    // Re-orthogonalize, the tangent is only stored per triangle
+    let t = normalize(tangent.xyz - normal * dot(normal, tangent.xyz));
+    let b = cross(normal, t) * tangent.w;
+    return normalize(t * tangent_normal.x + b * tangent_normal.y + normal * tangent_normal.z);
+}
+
+// Analytic tangent frame of the sphere uv mapping, u grows around the y axis and v towards +y.
+// The tangent is the derivative of the hit point by u (d/dphi of the position) and the bitangent
+// cross(normal, tangent) * w the one by v, like the per-triangle tangents. sphere_uv_tangent turns it with the pole axis
+fn sphere_tangent(normal: vec3<f32>) -> vec4<f32> {
+    let t = vec3<f32>(-normal.z, 0.0, normal.x);
+    if (length(t) < 0.0001) {
+        // At the poles the direction of u is undefined
+        return vec4<f32>(1.0, 0.0, 0.0, -1.0);
+    }
+    return vec4<f32>(normalize(t), -1.0);
+}
+
+// Rotation from the uv frame of the sphere, which has the pole on the y axis, to world space.
+// The pole axis is stored in radius.yzw, zero keeps +y. Same as Sphere::uv_frame in scene/src/structs.rs
+fn sphere_uv_frame(sphere: Sphere) -> mat3x3<f32> {
+    let pole = sphere.radius.yzw;
+    if (dot(pole, pole) == 0.0) {
+        return mat3x3<f32>(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0), vec3<f32>(0.0, 0.0, 1.0));
+    }
+    let axis = normalize(pole);
+    let reference = select(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 0.0, 1.0), abs(axis.z) < 0.999);
+    let x_axis = normalize(cross(axis, reference));
+    return mat3x3<f32>(x_axis, axis, cross(x_axis, axis));
+}
+
+// Tangent frame of sphereUVMapping, same as Sphere::tangent in scene/src/structs.rs
+fn sphere_uv_tangent(normal: vec3<f32>, sphere: Sphere) -> vec4<f32> {
+    let frame = sphere_uv_frame(sphere);
+    let tangent = sphere_tangent(transpose(frame) * normal);
+    return vec4<f32>(frame * tangent.xyz, tangent.w);
 }
 
+// Equirectangular mapping, u goes around the pole axis (y by default) starting at +x and v from the bottom to the top.
+// The texture is turned around the pole by emission_texture_id.y turns. Same as Sphere::uv in scene/src/structs.rs
 fn sphereUVMapping(hit_point: vec3<f32>, sphere: Sphere) -> vec2<f32> {
-    let p: vec3<f32> = normalize(hit_point - sphere.center.xyz);
+    let p: vec3<f32> = transpose(sphere_uv_frame(sphere)) * normalize(hit_point - sphere.center.xyz);
     let phi: f32 = atan2(p.z, p.x);
-    let theta: f32 = acos(p.y);
+    let theta: f32 = acos(clamp(p.y, -1.0, 1.0));
     
-    // Normalize phi and theta to the [0, 1] range
-    let u: f32 = phi / (2.0 * pi);
+    // Normalize phi and theta to the [0, 1] range, fract keeps u in range behind the seam for clamped textures
+    let u: f32 = fract(phi / (2.0 * pi) - sphere.emission_texture_id.y);
     let v: f32 = (pi - theta) / pi;
     
     return vec2<f32>(u, v);
@@ -1,6 +1,82 @@
 @group(0) @binding(0) var screen_sampler : sampler;
 @group(0) @binding(1) var color_buffer : texture_2d<f32>;
 
+struct Shaderconfig  {
+    max_bounces: i32,
+    samples: i32,
+    max_ray_distance: f32,
+    ray_bias: f32,      // Offset of secondary ray origins along the normal
+    
+    focus_distance: f32,
+    aperture: f32,
+    lens_radius: f32,
+
+    debug_random_color_visible: i32,
+    focus_viewer_visible: i32,
+    debug_bvh_bounding_visible: i32,
+    debug_bvh_bounding_color_visible: i32,
+    debug_view: u32,
+
+    //adaptive sampling
+    adaptive_sampling_enabled: i32,
+    adaptive_variance_threshold: f32,
+
+    //light sampling
+    mis_enabled: i32,
+    firefly_clamp: f32,
+
+    //materials
+    roughness_linear: i32,
+
+    //anti-aliasing
+    sampling_pattern: u32,
+    jitter_mode: u32,
+    jitter_enabled: i32,
+
+    //denoising
+    first_pass: i32,
+    second_pass: i32,
+    denoise_history_length: i32,   //Only used when the denoising buffers are created
+
+    //temporal basic                            //Not used in this shader |
+    temporal_basic_low_threshold: f32,          //                        v
+    temporal_basic_high_threshold: f32,
+    temporal_basic_low_blend_factor: f32,
+    temporal_basic_high_blend_factor: f32,
+
+    //temporal adaptive
+    temporal_adaptive_motion_threshold: f32,
+    temporal_adaptive_direction_threshold: f32,
+    temporal_adaptive_low_threshold: f32,
+    temporal_adaptive_high_threshold: f32,
+    temporal_adaptive_low_blend_factor: f32,
+    temporal_adaptive_high_blend_factor: f32,
+
+    //spatial basic
+    spatial_kernel_size: i32,
+    //spatial bilateral
+    spatial_bilat_space_sigma: f32,
+    spatial_bilat_color_sigma: f32,
+    spatial_bilat_radius: i32,
+    //spatial non local means
+    spatial_den_cormpare_radius: i32,
+    spatial_den_patch_radius: i32,              //                        ^
+    spatial_den_significant_weight: f32,        //Not used in this shader |
+    //spatial a-trous wavelet
+    atrous_iterations: i32,
+    atrous_color_phi: f32,
+    atrous_normal_phi: f32,
+    atrous_depth_phi: f32,
+
+    //display
+    exposure: f32,
+    supersample: u32,
+
+    // Background is transparent (alpha 0) instead of showing the background color
+    transparent_background: i32,
+}
+@group(1) @binding(0) var<uniform> config: Shaderconfig;
+
 struct VertexOutput {
     @builtin(position) Position : vec4<f32>,
     @location(0) TexCoord : vec2<f32>,
@@ -33,7 +109,26 @@ fn vs_main(@builtin(vertex_index) VertexIndex : u32) -> VertexOutput {
     return output;
 }
 
+// Average of the factor x factor texels of the color buffer that belong to the window pixel at `position`
+fn downsample(position: vec2<f32>, factor: u32) -> vec4<f32> {
+    let first_texel = vec2<u32>(floor(position)) * factor;
+    let last_texel = vec2<u32>(textureDimensions(color_buffer)) - 1u;
+    var sum = vec4<f32>(0.0);
+    for (var y = 0u; y < factor; y += 1u) {
+        for (var x = 0u; x < factor; x += 1u) {
+            sum += textureLoad(color_buffer, min(first_texel + vec2<u32>(x, y), last_texel), 0);
+        }
+    }
+    return sum / f32(factor * factor);
+}
+
 @fragment
-fn fs_main(@location(0) TexCoord: vec2<f32>) -> @location(0) vec4<f32> {
-    return textureSample(color_buffer, screen_sampler, TexCoord);
+fn fs_main(@builtin(position) position: vec4<f32>, @location(0) TexCoord: vec2<f32>) -> @location(0) vec4<f32> {
+    var color = textureSample(color_buffer, screen_sampler, TexCoord);
+    // The color buffer is larger than the window while supersampling
+    if config.supersample > 1u {
+        color = downsample(position.xy, config.supersample);
+    }
+    // Exposure in stops, applied after the accumulation so changing it doesn't restart it
+    return vec4<f32>(color.rgb * exp2(config.exposure), color.a);
 }
\ No newline at end of file
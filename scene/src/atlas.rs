@@ -0,0 +1,198 @@
+/// Largest atlas page, textures bigger than this are scaled down to fit.
+pub const ATLAS_MAX_PAGE_SIZE: u32 = 4096;
+/// Empty texels kept around every texture in the atlas, so linear filtering doesn't pick up its neighbours.
+pub const ATLAS_PADDING: u32 = 1;
+
+/// Position of a texture in the atlas, in texels of its page.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AtlasRect {
+    pub page: u32,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Result of packing textures into atlas pages of `page_size` x `page_size` texels.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AtlasLayout {
+    pub page_size: u32,
+    pub page_count: u32,
+    /// One rect per texture, in the order the sizes were passed.
+    pub rects: Vec<AtlasRect>,
+}
+
+/// Where the shader finds a texture, indexed by the texture id of materials, spheres and triangles.
+///
+/// With the array layer path every texture fills its own layer and the hardware samplers handle the wrap mode.
+/// In an atlas the shader wraps the uv itself and maps it into `uv_rect` of the page.
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, Debug, PartialEq)]
+pub struct TextureRegion {
+    /// Offset (xy) and size (zw) of the texture on its page in uv coordinates.
+    pub uv_rect: [f32; 4],
+    /// Page or array layer, 1.0 if the texture is packed into an atlas, padding.
+    pub layer: [f32; 4],
+}
+
+impl TextureRegion {
+    /// A texture filling the whole array layer `layer`.
+    pub fn layer(layer: u32) -> Self {
+        Self {
+            uv_rect: [0.0, 0.0, 1.0, 1.0],
+            layer: [layer as f32, 0.0, 0.0, 0.0],
+        }
+    }
+
+    /// A texture packed into an atlas page. The rect is shrunk by half a texel on every side,
+    /// so filtering at the border only reads texels of the texture itself.
+    pub fn atlas(rect: &AtlasRect, page_size: u32) -> Self {
+        let page_size = page_size as f32;
+        Self {
+            uv_rect: [
+                (rect.x as f32 + 0.5) / page_size,
+                (rect.y as f32 + 0.5) / page_size,
+                (rect.width as f32 - 1.0).max(0.0) / page_size,
+                (rect.height as f32 - 1.0).max(0.0) / page_size,
+            ],
+            layer: [rect.page as f32, 1.0, 0.0, 0.0],
+        }
+    }
+}
+
+/// Packs textures of the given sizes into as few square pages as possible.
+///
+/// The page size starts at the smallest power of two fitting the largest texture and is doubled up to
+/// `max_page_size` while the textures need more than one page, so small scenes don't allocate huge pages.
+/// Returns an error if a texture (with `padding`) is larger than `max_page_size`.
+pub fn pack_atlas(sizes: &[(u32, u32)], max_page_size: u32, padding: u32) -> Result<AtlasLayout, String> {
+    let largest = sizes.iter()
+        .map(|&(width, height)| width.max(height) + 2 * padding)
+        .max()
+        .unwrap_or(1);
+    if largest > max_page_size {
+        return Err(format!("Texture of {} texels (with padding) doesn't fit into an atlas page of {} texels", largest, max_page_size));
+    }
+
+    let mut page_size = largest.next_power_of_two().min(max_page_size);
+    loop {
+        let (page_count, rects) = pack_shelves(sizes, page_size, padding);
+        if page_count <= 1 || page_size >= max_page_size {
+            return Ok(AtlasLayout { page_size, page_count: page_count.max(1), rects });
+        }
+        page_size = (page_size * 2).min(max_page_size);
+    }
+}
+
+/// Shelf packing: the textures are sorted by height and placed left to right in rows,
+/// a new row starts when the current one is full and a new page when the rows reach the bottom.
+fn pack_shelves(sizes: &[(u32, u32)], page_size: u32, padding: u32) -> (u32, Vec<AtlasRect>) {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&index| std::cmp::Reverse((sizes[index].1, sizes[index].0)));
+
+    let mut rects = vec![AtlasRect { page: 0, x: 0, y: 0, width: 0, height: 0 }; sizes.len()];
+    let (mut page, mut x, mut y, mut shelf_height) = (0, 0, 0, 0);
+    for index in order {
+        let (width, height) = sizes[index];
+        let (padded_width, padded_height) = (width + 2 * padding, height + 2 * padding);
+        if x + padded_width > page_size {
+            // Next row
+            x = 0;
+            y += shelf_height;
+            shelf_height = 0;
+        }
+        if y + padded_height > page_size {
+            // Next page
+            page += 1;
+            x = 0;
+            y = 0;
+            shelf_height = 0;
+        }
+        rects[index] = AtlasRect { page, x: x + padding, y: y + padding, width, height };
+        x += padded_width;
+        shelf_height = shelf_height.max(padded_height);
+    }
+    let page_count = if sizes.is_empty() { 0 } else { page + 1 };
+    (page_count, rects)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overlaps(a: &AtlasRect, b: &AtlasRect) -> bool {
+        a.page == b.page
+            && a.x < b.x + b.width && b.x < a.x + a.width
+            && a.y < b.y + b.height && b.y < a.y + a.height
+    }
+
+    #[test]
+    fn test_pack_atlas_single_page() {
+        let sizes = [(256, 256), (128, 64), (512, 512), (64, 128)];
+        let layout = pack_atlas(&sizes, ATLAS_MAX_PAGE_SIZE, ATLAS_PADDING).unwrap();
+        assert_eq!(layout.page_count, 1);
+        // Smallest power of two that holds everything
+        assert_eq!(layout.page_size, 1024);
+
+        for (rect, &(width, height)) in layout.rects.iter().zip(sizes.iter()) {
+            assert_eq!((rect.width, rect.height), (width, height));
+            assert!(rect.x >= ATLAS_PADDING && rect.y >= ATLAS_PADDING);
+            assert!(rect.x + rect.width + ATLAS_PADDING <= layout.page_size);
+            assert!(rect.y + rect.height + ATLAS_PADDING <= layout.page_size);
+        }
+        for (i, a) in layout.rects.iter().enumerate() {
+            for b in &layout.rects[i + 1..] {
+                assert!(!overlaps(a, b), "{:?} overlaps {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_pack_atlas_multiple_pages() {
+        // Four full pages of 1024 textures, only one fits per page
+        let sizes = [(1024, 1024); 4];
+        let layout = pack_atlas(&sizes, 2048, 1).unwrap();
+        assert_eq!(layout.page_size, 2048);
+        assert_eq!(layout.page_count, 4);
+        let mut pages: Vec<u32> = layout.rects.iter().map(|rect| rect.page).collect();
+        pages.sort();
+        assert_eq!(pages, vec![0, 1, 2, 3]);
+
+        // Without padding four of them fill a page
+        let layout = pack_atlas(&sizes, 2048, 0).unwrap();
+        assert_eq!(layout.page_count, 1);
+    }
+
+    #[test]
+    fn test_pack_atlas_saves_space() {
+        // 40 small textures would need 40 layers of 1024x1024
+        let sizes = [(256, 256); 40];
+        let layout = pack_atlas(&sizes, ATLAS_MAX_PAGE_SIZE, ATLAS_PADDING).unwrap();
+        assert_eq!(layout.page_size, 2048);
+        assert_eq!(layout.page_count, 1);
+        assert!(layout.page_count * layout.page_size * layout.page_size < 40 * 1024 * 1024);
+    }
+
+    #[test]
+    fn test_pack_atlas_edge_cases() {
+        let layout = pack_atlas(&[], ATLAS_MAX_PAGE_SIZE, ATLAS_PADDING).unwrap();
+        assert_eq!(layout.page_count, 1);
+        assert!(layout.rects.is_empty());
+
+        assert!(pack_atlas(&[(4096, 16)], ATLAS_MAX_PAGE_SIZE, ATLAS_PADDING).is_err());
+        assert!(pack_atlas(&[(4096, 16)], ATLAS_MAX_PAGE_SIZE, 0).is_ok());
+    }
+
+    #[test]
+    fn test_texture_region() {
+        assert_eq!(TextureRegion::layer(3), TextureRegion { uv_rect: [0.0, 0.0, 1.0, 1.0], layer: [3.0, 0.0, 0.0, 0.0] });
+
+        let rect = AtlasRect { page: 1, x: 10, y: 20, width: 11, height: 5 };
+        let region = TextureRegion::atlas(&rect, 100);
+        assert_eq!(region.layer, [1.0, 1.0, 0.0, 0.0]);
+        assert!((region.uv_rect[0] - 0.105).abs() < 1e-6);
+        assert!((region.uv_rect[1] - 0.205).abs() < 1e-6);
+        assert!((region.uv_rect[2] - 0.1).abs() < 1e-6);
+        assert!((region.uv_rect[3] - 0.04).abs() < 1e-6);
+    }
+}
@@ -1,10 +1,11 @@
 use cgmath::*;
-use winit::keyboard::{Key, NamedKey};
+use winit::keyboard::Key;
 use std::time::Duration;
 use winit::dpi::PhysicalPosition;
 use winit::event::*;
 
 use crate::ShaderConfig;
+use crate::key_bindings::{KeyAction, KeyBindings};
 /// Represents a camera in 3D space.
 ///
 /// The camera has a position and a rotation. The position is a point in 3D space, and the rotation is a quaternion that represents the orientation of the camera.
@@ -28,8 +29,72 @@ impl Camera {
         }
     }
 
+    /// Creates a camera at `eye` that faces `target`, `up` is the direction that points up on the screen.
+    ///
+    /// If `up` is parallel to the view direction, e.g. when looking straight down, another up vector is chosen.
+    pub fn look_at(eye: Point3<f32>, target: Point3<f32>, up: Vector3<f32>) -> Self {
+        let forward = (target - eye).normalize();
+        let mut right = forward.cross(up);
+        if right.magnitude2() < 1e-8 {
+            // Any vector that isn't parallel to the view direction works
+            let fallback = if forward.x.abs() < 0.9 { Vector3::unit_x() } else { Vector3::unit_z() };
+            right = forward.cross(fallback.cross(forward));
+        }
+        let right = right.normalize();
+        let up = right.cross(forward);
+        // The camera looks along its -z axis
+        let rotation = Quaternion::from(Matrix3::from_cols(right, up, -forward)).normalize();
+        Self {
+            position: eye,
+            rotation,
+        }
+    }
+
+    /// Direction of the ray through a point on the screen, like the primary rays of the raytracing shader.
+    ///
+    /// `screen_x` and `screen_y` are in 0..1, starting at the top left corner of the screen.
+    /// The returned direction is normalized.
+    pub fn ray_direction(&self, projection: &Projection, screen_x: f32, screen_y: f32) -> Vector3<f32> {
+        // Same basis as the shader, which keeps the world y axis up
+        let forward = self.rotation.rotate_vector(-Vector3::unit_z()).normalize();
+        let right = Vector3::unit_y().cross(-forward).normalize();
+        let down = -(-forward).cross(right).normalize();
+
+        let viewport_height = 2.0 * (projection.fovy.0 / 2.0).tan();
+        let viewport_width = projection.aspect * viewport_height;
+        (forward + right * (screen_x - 0.5) * viewport_width + down * (screen_y - 0.5) * viewport_height).normalize()
+    }
+
     pub fn calc_matrix(&self) -> Matrix4<f32> {
-        Matrix4::look_at_rh(self.position, self.position + self.rotation.rotate_vector(Vector3::unit_z()), Vector3::unit_y())
+        // The up vector follows the rotation so that a rolled camera stays rolled
+        Matrix4::look_at_rh(self.position, self.position + self.rotation.rotate_vector(Vector3::unit_z()), self.rotation.rotate_vector(Vector3::unit_y()))
+    }
+}
+
+/// Smoothly moves a camera to a target position, e.g. to fly to a point the user double clicked.
+///
+/// The rotation of the camera isn't changed.
+#[derive(Debug, Clone, Copy)]
+pub struct CameraAnimation {
+    start: Point3<f32>,
+    target: Point3<f32>,
+    duration: f32,
+    elapsed: f32,
+}
+
+impl CameraAnimation {
+    pub fn new(start: Point3<f32>, target: Point3<f32>, duration: Duration) -> Self {
+        Self { start, target, duration: duration.as_secs_f32(), elapsed: 0.0 }
+    }
+
+    /// Moves the camera along the animation and returns true once the target is reached.
+    pub fn update_camera(&mut self, camera: &mut Camera, dt: Duration) -> bool {
+        self.elapsed += dt.as_secs_f32();
+        let progress = if self.duration > 0.0 { (self.elapsed / self.duration).min(1.0) } else { 1.0 };
+        // Ease in and out
+        let eased = progress * progress * (3.0 - 2.0 * progress);
+        camera.position = self.start + (self.target - self.start) * eased;
+        progress >= 1.0
     }
 }
 
@@ -60,12 +125,50 @@ impl Projection {
     pub fn calc_matrix(&self) -> Matrix4<f32> {
         perspective(self.fovy, self.aspect, self.znear, self.zfar)
     }
+
+    /// Width divided by height of the window, the horizontal fov follows from it and the vertical fov.
+    pub fn aspect(&self) -> f32 {
+        self.aspect
+    }
+
+    /// Distances of the near and far clipping planes, camera rays only hit surfaces between them.
+    pub fn near_far(&self) -> (f32, f32) {
+        (self.znear, self.zfar)
+    }
+}
+
+/// What the mouse wheel does, set with `[camera] scroll` in the config.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ScrollMode {
+    /// Moves the camera forward and backward along the view direction.
+    #[default]
+    Dolly,
+    /// Zooms by narrowing and widening the vertical field of view.
+    Fov,
+}
+
+impl ScrollMode {
+    /// Parses the scroll mode names used in the config: "dolly" and "fov".
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "dolly" => Some(ScrollMode::Dolly),
+            "fov" => Some(ScrollMode::Fov),
+            _ => None,
+        }
+    }
 }
 
+/// Distance the camera moves per scroll unit (half a line of the mouse wheel), in seconds of moving at full speed.
+const SCROLL_DOLLY_TIME: f32 = 0.25;
+/// Limits of the vertical fov in degrees when zooming with the mouse wheel.
+const SCROLL_FOV_RANGE: std::ops::RangeInclusive<f32> = 1.0..=120.0;
+
 /// Controls the movement and rotation of a camera.
 ///
-/// The controller keeps track of the amount of movement in each direction (left, right, forward, backward, up, down), the amount of rotation (horizontal and vertical), and the amount of scrolling.
+/// The controller keeps track of the amount of movement in each direction (left, right, forward, backward, up, down), the amount of rotation (horizontal, vertical and roll), and the amount of scrolling.
 /// It also has a speed and a sensitivity, which control how fast the camera moves and how sensitive it is to rotation.
+/// Rolling around the forward axis (Q/E) is locked by default to keep the horizon stable.
+/// Scrolling dollies the camera by default, see `ScrollMode`.
 #[derive(Debug)]
 pub struct CameraController {
     amount_left: f32,
@@ -76,9 +179,14 @@ pub struct CameraController {
     amount_down: f32,
     rotate_horizontal: f32,
     rotate_vertical: f32,
+    amount_roll_left: f32,
+    amount_roll_right: f32,
     scroll: f32,
     speed: f32,
     sensitivity: f32,
+    lock_roll: bool,
+    scroll_mode: ScrollMode,
+    key_bindings: KeyBindings,
 }
 
 impl CameraController {
@@ -92,60 +200,75 @@ impl CameraController {
             amount_down: 0.0,
             rotate_horizontal: 0.0,
             rotate_vertical: 0.0,
+            amount_roll_left: 0.0,
+            amount_roll_right: 0.0,
             scroll: 0.0,
             speed,
             sensitivity,
+            lock_roll: true,
+            scroll_mode: ScrollMode::default(),
+            key_bindings: KeyBindings::default(),
         }
     }
 
+    /// Keys used by `process_keyboard`, the application also looks up its other keys here.
+    pub fn key_bindings(&self) -> &KeyBindings {
+        &self.key_bindings
+    }
+
+    pub fn set_key_bindings(&mut self, key_bindings: KeyBindings) {
+        self.key_bindings = key_bindings;
+    }
+
+    /// Enables or disables the Q/E roll control. If locked, the horizon stays level.
+    pub fn set_lock_roll(&mut self, lock_roll: bool) {
+        self.lock_roll = lock_roll;
+    }
+
+    pub fn set_scroll_mode(&mut self, scroll_mode: ScrollMode) {
+        self.scroll_mode = scroll_mode;
+    }
+
     pub fn process_keyboard(&mut self, key: &Key, state: &ElementState, shader_config: &mut ShaderConfig) -> bool {
         let amount = if state == &ElementState::Pressed {
             1.0
         } else {
             0.0
         };
-        match key {            
-            Key::Character(c) if c.to_lowercase() == "w" => {
+        match self.key_bindings.action(key) {
+            Some(KeyAction::Forward) => {
                 self.amount_forward = amount;
                 true
             }
-            Key::Character(c) if c.to_lowercase() == "s" => {
+            Some(KeyAction::Backward) => {
                 self.amount_backward = amount;
                 true
             }
-            Key::Character(c) if c.to_lowercase() == "a" => {
+            Some(KeyAction::Left) => {
                 self.amount_left = amount;
                 true
             }
-            Key::Character(c) if c.to_lowercase() == "d" => {
+            Some(KeyAction::Right) => {
                 self.amount_right = amount;
                 true
             }
-            Key::Named(NamedKey::ArrowUp) => {
-                self.amount_forward = amount;
-                true
-            }
-            Key::Named(NamedKey::ArrowDown) => {
-                self.amount_backward = amount;
-                true
-            }
-            Key::Named(NamedKey::ArrowLeft) => {
-                self.amount_left = amount;
+            Some(KeyAction::RollLeft) => {
+                self.amount_roll_left = amount;
                 true
             }
-            Key::Named(NamedKey::ArrowRight) => {
-                self.amount_right = amount;
+            Some(KeyAction::RollRight) => {
+                self.amount_roll_right = amount;
                 true
             }
-            Key::Named(NamedKey::Space) => {
+            Some(KeyAction::Up) => {
                 self.amount_up = amount;
                 true
             }
-            Key::Named(NamedKey::Shift) => {
+            Some(KeyAction::Down) => {
                 self.amount_down = amount;
                 true
             }
-            Key::Character(c) if c.to_lowercase() == "x" => {
+            Some(KeyAction::SafeMode) => {
                 println!("Set Shader Config to high performance, low quality safe mode");
                 shader_config.ray_max_bounces = 1;
                 shader_config.ray_samples_per_pixel = 1;
@@ -160,15 +283,19 @@ impl CameraController {
         self.rotate_vertical = mouse_dy as f32;
     }
 
+    /// Adds the scroll delta, the sum of all events since the last frame is applied by `update_camera`.
     pub fn process_scroll(&mut self, delta: &MouseScrollDelta) {
-        self.scroll = match delta {
-            // I'm assuming a line is about 100 pixels
+        self.scroll += match delta {
             MouseScrollDelta::LineDelta(_, scroll) => -scroll * 0.5,
-            MouseScrollDelta::PixelDelta(PhysicalPosition { y: scroll, .. }) => -*scroll as f32,
+            // I'm assuming a line is about 100 pixels
+            MouseScrollDelta::PixelDelta(PhysicalPosition { y: scroll, .. }) => -*scroll as f32 * 0.005,
         };
     }
 
-    pub fn update_camera(&mut self, camera: &mut Camera, dt: Duration) {
+    /// Moves and turns the camera by the pressed keys and the mouse movement since the last frame.
+    ///
+    /// The scrolling is applied to the camera position or the fov of the projection, depending on the `ScrollMode`.
+    pub fn update_camera(&mut self, camera: &mut Camera, projection: &mut Projection, dt: Duration) {
         let dt = dt.as_secs_f32();
 
         // Move forward/backward and left/right
@@ -185,6 +312,9 @@ impl CameraController {
         // let camera_pitch = Euler::from(camera.rotation).x;
         let pitch_quaternion = Quaternion::from_axis_angle(Vector3::unit_x(), Rad(-self.rotate_vertical) * self.sensitivity * dt);
         let yaw_quaternion = Quaternion::from_axis_angle(Vector3::unit_y(), Rad(self.rotate_horizontal) * self.sensitivity * dt);
+        // Roll around the forward axis of the camera (local z)
+        let roll_amount = if self.lock_roll { 0.0 } else { self.amount_roll_right - self.amount_roll_left };
+        let roll_quaternion = Quaternion::from_axis_angle(Vector3::unit_z(), Rad(roll_amount) * self.sensitivity * dt);
 
         // Combine pitch and yaw rotations using quaternion multiplication
         // Limit pitch rotation
@@ -193,7 +323,7 @@ impl CameraController {
         // } else if camera_pitch < Rad(-PI * 0.5) && self.rotate_vertical < 0.0 {
         //     camera.rotation = yaw_quaternion * camera.rotation;
         // } else {
-        camera.rotation = yaw_quaternion * camera.rotation * pitch_quaternion;
+        camera.rotation = yaw_quaternion * camera.rotation * pitch_quaternion * roll_quaternion;
         // }
         // println!("Camera x = {:?}", Euler::from(camera.rotation));
 
@@ -201,7 +331,17 @@ impl CameraController {
         self.rotate_horizontal = 0.0;
         self.rotate_vertical = 0.0;
 
-        // Update the scroll value if you want to use it for zooming
+        // Scrolling up (negative scroll) moves forward or zooms in
+        match self.scroll_mode {
+            ScrollMode::Dolly => {
+                let forward = camera.rotation.rotate_vector(Vector3::new(0.0, 0.0, -1.0)).normalize();
+                camera.position += forward * -self.scroll * self.speed * SCROLL_DOLLY_TIME;
+            }
+            ScrollMode::Fov => {
+                let fovy = Deg::from(projection.fovy).0 * (self.scroll * 0.5).exp2();
+                projection.fovy = Deg(fovy.clamp(*SCROLL_FOV_RANGE.start(), *SCROLL_FOV_RANGE.end())).into();
+            }
+        }
         self.scroll = 0.0;
     }
 }
@@ -210,6 +350,25 @@ impl CameraController {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_camera_look_at() {
+        let camera = Camera::look_at(Point3::new(0.0, 0.0, 5.0), Point3::new(0.0, 0.0, 0.0), Vector3::unit_y());
+        let forward = camera.rotation.rotate_vector(-Vector3::unit_z());
+        assert!((forward - -Vector3::unit_z()).magnitude() < 1e-5, "{:?}", forward);
+        assert_eq!(camera.position, Point3::new(0.0, 0.0, 5.0));
+
+        let camera = Camera::look_at(Point3::new(1.0, 2.0, 3.0), Point3::new(4.0, 2.0, 3.0), Vector3::unit_y());
+        let forward = camera.rotation.rotate_vector(-Vector3::unit_z());
+        let up = camera.rotation.rotate_vector(Vector3::unit_y());
+        assert!((forward - Vector3::unit_x()).magnitude() < 1e-5, "{:?}", forward);
+        assert!((up - Vector3::unit_y()).magnitude() < 1e-5, "{:?}", up);
+
+        // Looking straight down still gives a valid rotation
+        let camera = Camera::look_at(Point3::new(0.0, 5.0, 0.0), Point3::new(0.0, 0.0, 0.0), Vector3::unit_y());
+        let forward = camera.rotation.rotate_vector(-Vector3::unit_z());
+        assert!((forward - -Vector3::unit_y()).magnitude() < 1e-5, "{:?}", forward);
+    }
+
     #[test]
     fn test_camera_new() {
         let camera = Camera::new(Point3::new(0.0, 0.0, 0.0), Rad(0.0), Rad(0.0));
@@ -232,4 +391,134 @@ mod tests {
         projection.resize(1600, 900);
         assert_eq!(projection.aspect, 1600.0 / 900.0);
     }
-}
\ No newline at end of file
+
+    fn projection() -> Projection {
+        Projection::new(800, 600, Deg(45.0), 0.1, 100.0)
+    }
+
+    fn roll_with_key(controller: &mut CameraController, camera: &mut Camera, key: &str) {
+        let mut shader_config = ShaderConfig::default();
+        let key = Key::Character(key.into());
+        assert!(controller.process_keyboard(&key, &ElementState::Pressed, &mut shader_config));
+        controller.update_camera(camera, &mut projection(), Duration::from_millis(500));
+        controller.process_keyboard(&key, &ElementState::Released, &mut shader_config);
+    }
+
+    #[test]
+    fn test_camera_roll_q_then_e() {
+        let mut camera = Camera::new(Point3::new(0.0, 0.0, 0.0), Rad(0.0), Rad(0.0));
+        let mut controller = CameraController::new(4.0, 1.6);
+        controller.set_lock_roll(false);
+
+        roll_with_key(&mut controller, &mut camera, "q");
+        let rolled_up = camera.rotation.rotate_vector(Vector3::unit_y());
+        assert!(rolled_up.x.abs() > 0.1);
+
+        roll_with_key(&mut controller, &mut camera, "e");
+        let identity = Quaternion::new(1.0, 0.0, 0.0, 0.0);
+        assert!((camera.rotation.dot(identity).abs() - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_camera_remapped_forward() {
+        let mut camera = Camera::new(Point3::new(0.0, 0.0, 0.0), Rad(0.0), Rad(0.0));
+        let mut controller = CameraController::new(4.0, 1.6);
+        let mut bindings = KeyBindings::default();
+        bindings.set(KeyAction::Forward, &["i"]).unwrap();
+        controller.set_key_bindings(bindings);
+        let mut shader_config = ShaderConfig::default();
+
+        assert!(!controller.process_keyboard(&Key::Character("w".into()), &ElementState::Pressed, &mut shader_config));
+        controller.update_camera(&mut camera, &mut projection(), Duration::from_millis(500));
+        assert_eq!(camera.position, Point3::new(0.0, 0.0, 0.0));
+
+        assert!(controller.process_keyboard(&Key::Character("i".into()), &ElementState::Pressed, &mut shader_config));
+        controller.update_camera(&mut camera, &mut projection(), Duration::from_millis(500));
+        // The camera looks along -z
+        assert!(camera.position.z < -0.5, "{:?}", camera.position);
+    }
+
+    #[test]
+    fn test_camera_roll_locked() {
+        let mut camera = Camera::new(Point3::new(0.0, 0.0, 0.0), Rad(0.0), Rad(0.0));
+        let mut controller = CameraController::new(4.0, 1.6);
+
+        roll_with_key(&mut controller, &mut camera, "q");
+        assert_eq!(camera.rotation, Quaternion::new(1.0, 0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_camera_scroll_dolly() {
+        let mut camera = Camera::new(Point3::new(0.0, 0.0, 0.0), Rad(0.0), Rad(0.0));
+        let mut projection = projection();
+        let mut controller = CameraController::new(4.0, 1.6);
+
+        // Scrolling up moves along the view direction (-z), the fov stays
+        controller.process_scroll(&MouseScrollDelta::LineDelta(0.0, 1.0));
+        controller.process_scroll(&MouseScrollDelta::LineDelta(0.0, 1.0));
+        controller.update_camera(&mut camera, &mut projection, Duration::from_millis(16));
+        assert!((camera.position.z + 2.0 * 0.5 * 4.0 * SCROLL_DOLLY_TIME).abs() < 1e-5, "{:?}", camera.position);
+        assert_eq!(projection.fovy, Deg(45.0).into());
+
+        // The scroll is applied once
+        controller.update_camera(&mut camera, &mut projection, Duration::from_millis(16));
+        assert!((camera.position.z + 2.0 * 0.5 * 4.0 * SCROLL_DOLLY_TIME).abs() < 1e-5, "{:?}", camera.position);
+        controller.process_scroll(&MouseScrollDelta::PixelDelta(PhysicalPosition::new(0.0, -200.0)));
+        controller.update_camera(&mut camera, &mut projection, Duration::from_millis(16));
+        assert!(camera.position.z.abs() < 1e-5, "{:?}", camera.position);
+    }
+
+    #[test]
+    fn test_camera_scroll_fov() {
+        let mut camera = Camera::new(Point3::new(0.0, 0.0, 0.0), Rad(0.0), Rad(0.0));
+        let mut projection = projection();
+        let mut controller = CameraController::new(4.0, 1.6);
+        controller.set_scroll_mode(ScrollMode::Fov);
+
+        // Four lines of the mouse wheel halve the fov
+        controller.process_scroll(&MouseScrollDelta::LineDelta(0.0, 4.0));
+        controller.update_camera(&mut camera, &mut projection, Duration::from_millis(16));
+        assert!((Deg::from(projection.fovy).0 - 22.5).abs() < 1e-3, "{:?}", projection.fovy);
+        assert_eq!(camera.position, Point3::new(0.0, 0.0, 0.0));
+
+        // Zooming out stops at the widest fov
+        controller.process_scroll(&MouseScrollDelta::LineDelta(0.0, -100.0));
+        controller.update_camera(&mut camera, &mut projection, Duration::from_millis(16));
+        assert!((Deg::from(projection.fovy).0 - SCROLL_FOV_RANGE.end()).abs() < 1e-3, "{:?}", projection.fovy);
+
+        // The fov slot of the camera uniform follows the projection
+        let mut previous = crate::CameraUniform::new();
+        previous.update_view_proj(&camera, &self::projection());
+        let mut uniform = previous;
+        uniform.update_view_proj(&camera, &projection);
+        assert!(uniform.view_changed(&previous));
+    }
+
+    #[test]
+    fn test_camera_ray_direction() {
+        let camera = Camera::new(Point3::new(0.0, 0.0, 0.0), Rad(0.0), Rad(0.0));
+        let projection = Projection::new(800, 400, Deg(90.0), 0.1, 100.0);
+
+        let center = camera.ray_direction(&projection, 0.5, 0.5);
+        assert!((center - Vector3::new(0.0, 0.0, -1.0)).magnitude() < 1e-5);
+        // With a 90 degree fov the top edge is 45 degrees up
+        let top = camera.ray_direction(&projection, 0.5, 0.0);
+        assert!((top - Vector3::new(0.0, 1.0, -1.0).normalize()).magnitude() < 1e-5);
+        // The screen is twice as wide as high
+        let right = camera.ray_direction(&projection, 1.0, 0.5);
+        assert!((right - Vector3::new(2.0, 0.0, -1.0).normalize()).magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn test_camera_animation() {
+        let mut camera = Camera::new(Point3::new(0.0, 0.0, 0.0), Rad(0.0), Rad(0.0));
+        let rotation = camera.rotation;
+        let mut animation = CameraAnimation::new(camera.position, Point3::new(0.0, 0.0, -4.0), Duration::from_millis(300));
+
+        assert!(!animation.update_camera(&mut camera, Duration::from_millis(150)));
+        assert!((camera.position.z + 2.0).abs() < 1e-5);
+        assert!(animation.update_camera(&mut camera, Duration::from_millis(200)));
+        assert_eq!(camera.position, Point3::new(0.0, 0.0, -4.0));
+        assert_eq!(camera.rotation, rotation);
+    }
+}
@@ -0,0 +1,210 @@
+use cgmath::{Deg, Point3, Quaternion, Rotation3};
+
+use crate::camera::Camera;
+
+/// A single keyframe of a camera path.
+///
+/// The rotation is given in degrees like the camera rotation in the config file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraKeyframe {
+    pub time: f32,
+    pub position: [f32; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl CameraKeyframe {
+    pub fn new(time: f32, position: [f32; 3], yaw: f32, pitch: f32) -> Self {
+        Self { time, position, yaw, pitch }
+    }
+
+    /// Converts the keyframe into a camera with the same position and rotation.
+    pub fn to_camera(&self) -> Camera {
+        Camera {
+            position: Point3::from(self.position),
+            rotation: Quaternion::from_angle_y(Deg(self.yaw)) * Quaternion::from_angle_x(Deg(self.pitch)),
+        }
+    }
+}
+
+/// The interpolation used between the keyframes of a camera path.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum PathInterpolation {
+    Linear,
+    #[default]
+    CatmullRom,
+}
+
+/// An animated camera path made of keyframes, used for turntable and flythrough videos.
+///
+/// The keyframes are sorted by time. Sampling before the first or after the last keyframe
+/// returns the first or last keyframe, unless the path is looping.
+#[derive(Debug, Clone, Default)]
+pub struct CameraPath {
+    keyframes: Vec<CameraKeyframe>,
+    pub looping: bool,
+    pub interpolation: PathInterpolation,
+}
+
+impl CameraPath {
+    pub fn new(mut keyframes: Vec<CameraKeyframe>, looping: bool, interpolation: PathInterpolation) -> Self {
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Self { keyframes, looping, interpolation }
+    }
+
+    pub fn keyframes(&self) -> &[CameraKeyframe] {
+        &self.keyframes
+    }
+
+    /// Time between the first and the last keyframe in seconds.
+    pub fn duration(&self) -> f32 {
+        match (self.keyframes.first(), self.keyframes.last()) {
+            (Some(first), Some(last)) => last.time - first.time,
+            _ => 0.0,
+        }
+    }
+
+    /// Returns the interpolated keyframe at time `t` (in seconds), or `None` if the path has no keyframes.
+    pub fn sample(&self, t: f32) -> Option<CameraKeyframe> {
+        let first = self.keyframes.first()?;
+        let last = self.keyframes.last()?;
+        let duration = self.duration();
+
+        let t = if self.looping && duration > 0.0 {
+            first.time + (t - first.time).rem_euclid(duration)
+        } else {
+            t
+        };
+
+        if t <= first.time {
+            return Some(CameraKeyframe { time: t, ..*first });
+        }
+        if t >= last.time {
+            return Some(CameraKeyframe { time: t, ..*last });
+        }
+
+        // Index of the keyframe starting the segment containing t
+        let i = self.keyframes.iter().rposition(|k| k.time <= t).unwrap_or(0);
+        let k1 = &self.keyframes[i];
+        let k2 = &self.keyframes[i + 1];
+        let segment = k2.time - k1.time;
+        let u = if segment > 0.0 { (t - k1.time) / segment } else { 0.0 };
+
+        let keyframe = match self.interpolation {
+            PathInterpolation::Linear => CameraKeyframe {
+                time: t,
+                position: [lerp(k1.position[0], k2.position[0], u), lerp(k1.position[1], k2.position[1], u), lerp(k1.position[2], k2.position[2], u)],
+                yaw: lerp(k1.yaw, k2.yaw, u),
+                pitch: lerp(k1.pitch, k2.pitch, u),
+            },
+            PathInterpolation::CatmullRom => {
+                // Neighbours are clamped at the ends of the path
+                let k0 = &self.keyframes[i.saturating_sub(1)];
+                let k3 = &self.keyframes[(i + 2).min(self.keyframes.len() - 1)];
+                CameraKeyframe {
+                    time: t,
+                    position: [
+                        catmull_rom(k0.position[0], k1.position[0], k2.position[0], k3.position[0], u),
+                        catmull_rom(k0.position[1], k1.position[1], k2.position[1], k3.position[1], u),
+                        catmull_rom(k0.position[2], k1.position[2], k2.position[2], k3.position[2], u),
+                    ],
+                    yaw: catmull_rom(k0.yaw, k1.yaw, k2.yaw, k3.yaw, u),
+                    pitch: catmull_rom(k0.pitch, k1.pitch, k2.pitch, k3.pitch, u),
+                }
+            }
+        };
+        Some(keyframe)
+    }
+
+    /// Returns the camera at time `t` (in seconds), or `None` if the path has no keyframes.
+    pub fn camera_at(&self, t: f32) -> Option<Camera> {
+        self.sample(t).map(|keyframe| keyframe.to_camera())
+    }
+}
+
+fn lerp(a: f32, b: f32, u: f32) -> f32 {
+    a + (b - a) * u
+}
+
+/// Uniform Catmull-Rom spline between `p1` and `p2`, passing through both control points.
+fn catmull_rom(p0: f32, p1: f32, p2: f32, p3: f32, u: f32) -> f32 {
+    let u2 = u * u;
+    let u3 = u2 * u;
+    0.5 * ((2.0 * p1)
+        + (-p0 + p2) * u
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * u2
+        + (-p0 + 3.0 * p1 - 3.0 * p2 + p3) * u3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_path(interpolation: PathInterpolation, looping: bool) -> CameraPath {
+        CameraPath::new(vec![
+            CameraKeyframe::new(2.0, [2.0, 0.0, 0.0], 180.0, 0.0),
+            CameraKeyframe::new(0.0, [0.0, 0.0, 0.0], 0.0, 0.0),
+            CameraKeyframe::new(1.0, [1.0, 1.0, 0.0], 90.0, 10.0),
+        ], looping, interpolation)
+    }
+
+    #[test]
+    fn test_camera_path_sorted() {
+        let path = test_path(PathInterpolation::Linear, false);
+        let times: Vec<f32> = path.keyframes().iter().map(|k| k.time).collect();
+        assert_eq!(times, vec![0.0, 1.0, 2.0]);
+        assert_eq!(path.duration(), 2.0);
+    }
+
+    #[test]
+    fn test_camera_path_empty() {
+        let path = CameraPath::new(vec![], false, PathInterpolation::Linear);
+        assert!(path.sample(0.0).is_none());
+        assert!(path.camera_at(0.0).is_none());
+    }
+
+    #[test]
+    fn test_camera_path_linear() {
+        let path = test_path(PathInterpolation::Linear, false);
+        let keyframe = path.sample(0.5).unwrap();
+        assert_eq!(keyframe.position, [0.5, 0.5, 0.0]);
+        assert_eq!(keyframe.yaw, 45.0);
+        assert_eq!(keyframe.pitch, 5.0);
+    }
+
+    #[test]
+    fn test_camera_path_catmull_rom_hits_keyframes() {
+        let path = test_path(PathInterpolation::CatmullRom, false);
+        for keyframe in path.keyframes().to_vec() {
+            let sampled = path.sample(keyframe.time).unwrap();
+            assert_eq!(sampled.position, keyframe.position);
+            assert_eq!(sampled.yaw, keyframe.yaw);
+        }
+        // Smooth curve overshoots the straight line between the keyframes
+        let sampled = path.sample(1.5).unwrap();
+        assert!(sampled.position[1] > 0.5);
+    }
+
+    #[test]
+    fn test_camera_path_clamped() {
+        let path = test_path(PathInterpolation::CatmullRom, false);
+        assert_eq!(path.sample(-1.0).unwrap().position, [0.0, 0.0, 0.0]);
+        assert_eq!(path.sample(5.0).unwrap().position, [2.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_camera_path_looping() {
+        let path = test_path(PathInterpolation::Linear, true);
+        assert_eq!(path.sample(2.5).unwrap().position, path.sample(0.5).unwrap().position);
+        assert_eq!(path.sample(-1.5).unwrap().position, path.sample(0.5).unwrap().position);
+    }
+
+    #[test]
+    fn test_camera_path_camera_at() {
+        let path = test_path(PathInterpolation::Linear, false);
+        let camera = path.camera_at(1.0).unwrap();
+        let expected = Camera::new(Point3::new(1.0, 1.0, 0.0), Deg(90.0), Deg(10.0));
+        assert_eq!(camera.position, expected.position);
+        assert_eq!(camera.rotation, expected.rotation);
+    }
+}
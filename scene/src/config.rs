@@ -1,22 +1,140 @@
 use std::fs;
+use std::path::{Path, PathBuf};
 use serde::Deserialize;
 use toml;
 
-use crate::structs::{Material, Sphere};
-use crate::structs::Background;
+use crate::camera::ScrollMode;
+use crate::camera_path::{CameraKeyframe, CameraPath, PathInterpolation};
+use crate::structs::{AlphaMode, Material, ShaderConfig, Sphere, Triangle, WrapMode};
+use crate::structs::{Background, BackgroundMode, SkyGradient};
+use crate::texture::{ColorSpace, TextureFilter};
+use crate::key_bindings::{KeyAction, KeyBindings, NAMED_KEYS};
 
-#[derive(Debug, Deserialize)]
+/// Errors that can occur while loading the config file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    /// The file couldn't be read or parsed, or a required field is missing or has the wrong type.
+    Invalid(String),
+    /// A sphere has a radius that is zero, negative or not a number.
+    InvalidSphereRadius { sphere: usize, radius: f32 },
+    /// A material or texture id is smaller than -1 (-1 means unused).
+    InvalidId { field: String, index: usize, id: i64 },
+    /// The config was written for a version of the raytracer that is too new to load it.
+    UnsupportedVersion { version: i64, supported: u32 },
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Invalid(message) => write!(f, "{}", message),
+            ConfigError::InvalidSphereRadius { sphere, radius } => 
+                write!(f, "Sphere {} has an invalid radius of {}, the radius has to be greater than 0", sphere, radius),
+            ConfigError::InvalidId { field, index, id } => 
+                write!(f, "Invalid {} {} at entry {}, ids have to be >= -1 (-1 = unused)", field, id, index),
+            ConfigError::UnsupportedVersion { version, supported } =>
+                write!(f, "Config version {} is not supported, this raytracer reads config versions up to {}", version, supported),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<String> for ConfigError {
+    fn from(message: String) -> Self {
+        ConfigError::Invalid(message)
+    }
+}
+
+impl From<&str> for ConfigError {
+    fn from(message: &str) -> Self {
+        ConfigError::Invalid(message.to_string())
+    }
+}
+
+/// Version of the config format written by this raytracer, set with the top-level `version` field.
+///
+/// Files without a version are version 0 and are migrated when they are loaded. Files that are one version newer
+/// are loaded with a warning, since newer versions usually only add sections. Anything newer is rejected.
+pub const CONFIG_VERSION: u32 = 2;
+
+// Defaults for the optional config values, also used by the SceneBuilder
+pub(crate) const DEFAULT_CAMERA_NEAR_FAR: [f32; 2] = [0.1, 100.0];
+pub(crate) const DEFAULT_CAMERA_FOV: f32 = 45.0;
+pub(crate) const DEFAULT_CLEAR_COLOR: [f32; 4] = [0.1, 0.2, 0.3, 1.0];
+pub(crate) const DEFAULT_BVH_LEAF_SIZE: usize = 1;
+
+/// Algorithm that builds the BVH of the scene, see `[bvh] builder`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BvhBuilder {
+    /// Binned surface area heuristic ("sah"), the builder used before it was configurable.
+    #[default]
+    Sah,
+    /// Locally-ordered clustering ("locb").
+    Locb,
+}
+
+impl BvhBuilder {
+    /// Parses the name used in the config file.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "sah" => Some(BvhBuilder::Sah),
+            "locb" => Some(BvhBuilder::Locb),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct Textureset {
     pub diffuse_path: Option<String>,
     pub normal_path: Option<String>,
     pub roughness_path: Option<String>,
+    /// Color space of the diffuse texture, sRGB unless set with `diffuse_color_space = "linear"`.
+    #[serde(default)]
+    pub diffuse_color_space: ColorSpace,
+    /// Filter used when the textures of this set are scaled, nearest unless set with e.g. `filter = "lanczos"`.
+    #[serde(default)]
+    pub filter: TextureFilter,
+}
+
+impl Textureset {
+    /// The texture paths in the order they are added to the texture array (diffuse, roughness, normal) with their color space.
+    ///
+    /// The order matches the texture ids of spheres and triangles, see `texture_ids`.
+    /// Normal and roughness maps hold data instead of colors, so they are always linear.
+    pub fn slots(&self) -> [(Option<&str>, ColorSpace); 3] {
+        [
+            (self.diffuse_path.as_deref(), self.diffuse_color_space),
+            (self.roughness_path.as_deref(), ColorSpace::Linear),
+            (self.normal_path.as_deref(), ColorSpace::Linear),
+        ]
+    }
+
+    /// The ids of the diffuse, roughness and normal texture if the first texture of this set has the id `first_id`, -1 for a missing texture.
+    ///
+    /// Missing textures take no id, so the ids of the following textures move up.
+    pub fn texture_ids(&self, first_id: i32) -> [i32; 3] {
+        let mut next_id = first_id;
+        self.slots().map(|(path, _)| match path {
+            Some(_) => {
+                next_id += 1;
+                next_id - 1
+            }
+            None => -1,
+        })
+    }
 }
 
-#[derive(Debug, Default, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct ModelPaths {
     pub gltf_path: Option<String>,
     pub obj_path: Option<String>,
     pub obj_material_id: Option<i32>,
+    /// Polygons of this SVG file are extruded into triangles, see `load_svg`.
+    pub svg_path: Option<String>,
+    pub svg_z: f32,
+    pub svg_depth: f32,
+    pub svg_material_id: Option<i32>,
 }
 
 impl ModelPaths {
@@ -25,16 +143,27 @@ impl ModelPaths {
             gltf_path,
             obj_path,
             obj_material_id,
+            ..Default::default()
         }
     }
 }
 
-#[derive(Debug, Default, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct Config {
+    /// Config format version, always `CONFIG_VERSION` after loading since older files are migrated.
+    pub version: u32,
     pub camera_position: [f32; 3],
     pub camera_rotation: [f32; 2],
+    /// Point the camera looks at, used instead of `camera_rotation` if set, see `[camera] target`.
+    pub camera_target: Option<[f32; 3]>,
     pub camera_near_far: [f32; 2],
     pub camera_fov: f32,
+    pub camera_lock_roll: bool,
+    /// What the mouse wheel does, see `[camera] scroll`.
+    #[serde(skip)]
+    pub camera_scroll_mode: ScrollMode,
+    #[serde(skip)]
+    pub camera_path: Option<CameraPath>,
 
     pub materials: Option<Vec<Material>>,
     pub textures: Option<Vec<Textureset>>,
@@ -44,37 +173,165 @@ pub struct Config {
     pub spheres: Option<Vec<Sphere>>,
     #[serde(rename = "3d_model_paths")]
     pub model_paths: ModelPaths,
+    /// Triangles added in code (see `SceneBuilder`), loaded after the 3D models.
+    #[serde(skip)]
+    pub meshes: Option<Vec<Triangle>>,
+
+    pub shader_config: ShaderConfig,
+
+    pub render_clear_color: [f32; 4],
+    /// Gives every texture its own 1024x1024 array layer instead of packing the textures into atlas pages,
+    /// see `[render] texture_array_layers`.
+    pub render_texture_array_layers: bool,
+    /// Width and height of the tiles the raytracing pass is split into, 0 traces the whole screen in one dispatch,
+    /// see `[render] tile_size`.
+    pub render_tile_size: u32,
+    /// Tiles traced per frame, 0 traces all tiles of the screen in every frame, see `[render] tiles_per_frame`.
+    pub render_tiles_per_frame: u32,
+
+    /// Index or part of the name of the gpu adapter to use, see `[gpu] adapter`.
+    pub gpu_adapter: Option<String>,
+    /// Either "high_performance" or "low_power", see `[gpu] power_preference`.
+    pub gpu_power_preference: Option<String>,
+    /// Uses the software fallback adapter, e.g. for CI machines without a GPU.
+    pub gpu_force_fallback_adapter: bool,
+
+    /// Maximum number of primitives in a BVH leaf, see `[bvh] leaf_size`.
+    pub bvh_leaf_size: usize,
+    /// Algorithm that builds the BVH, see `[bvh] builder`.
+    #[serde(skip)]
+    pub bvh_builder: BvhBuilder,
+    /// Saves the BVH next to the model and loads it instead of building it while the geometry is unchanged, see `[bvh] cache`.
+    pub bvh_cache: bool,
+
+    /// Keyboard controls, the defaults with the keys of the `[keys]` section replaced.
+    #[serde(skip)]
+    pub key_bindings: KeyBindings,
+}
+
+impl Default for Config {
+    /// An empty scene with the camera at the origin looking down the negative z axis, see `default_scene`
+    /// for a scene that shows something.
+    fn default() -> Self {
+        Self {
+            version: CONFIG_VERSION,
+            camera_position: [0.0, 0.0, 0.0],
+            camera_rotation: [0.0, 0.0],
+            camera_target: None,
+            camera_near_far: DEFAULT_CAMERA_NEAR_FAR,
+            camera_fov: DEFAULT_CAMERA_FOV,
+            camera_lock_roll: true,
+            camera_scroll_mode: ScrollMode::default(),
+            camera_path: None,
+
+            materials: None,
+            textures: None,
+            background: None,
+            background_path: None,
+
+            spheres: None,
+            model_paths: ModelPaths::default(),
+            meshes: None,
+
+            shader_config: ShaderConfig::default(),
+
+            render_clear_color: DEFAULT_CLEAR_COLOR,
+            render_texture_array_layers: false,
+            render_tile_size: 0,
+            render_tiles_per_frame: 0,
+
+            gpu_adapter: None,
+            gpu_power_preference: None,
+            gpu_force_fallback_adapter: false,
+
+            bvh_leaf_size: DEFAULT_BVH_LEAF_SIZE,
+            bvh_builder: BvhBuilder::default(),
+            bvh_cache: false,
+
+            key_bindings: KeyBindings::default(),
+        }
+    }
 }
 
 impl Config {
-    pub fn new(config_path: &str) -> Result<Self, String> {
+    /// The scene used if no config file is given, a lit sphere on a ground plane, see `presets::default_scene`.
+    pub fn default_scene() -> Self {
+        crate::presets::default_scene().build().expect("The default scene is valid")
+    }
+
+    /// Loads the config file, files in its `include` list are looked up relative to its directory.
+    pub fn new(config_path: &str) -> Result<Self, ConfigError> {
         let toml_str = fs::read_to_string(config_path)
             .map_err(|e| format!("Could not find/read config file: {}", e))?;
-        Self::from_str(&toml_str)
+        let base_path = Path::new(config_path).parent().unwrap_or(Path::new(""));
+        let mut included = vec![fs::canonicalize(config_path).unwrap_or_else(|_| PathBuf::from(config_path))];
+        Self::from_str_with_includes(&toml_str, base_path, &mut included)
     }
 
-    pub fn from_str(toml_str: &str) -> Result<Self, String> {
-        let toml: toml::Value = toml::from_str(toml_str)
+    /// Parses a config, files in its `include` list are looked up relative to the working directory.
+    pub fn from_str(toml_str: &str) -> Result<Self, ConfigError> {
+        Self::from_str_in(toml_str, Path::new(""))
+    }
+
+    /// Parses a config, files in its `include` list are looked up relative to `base_path`.
+    pub fn from_str_in(toml_str: &str, base_path: &Path) -> Result<Self, ConfigError> {
+        Self::from_str_with_includes(toml_str, base_path, &mut Vec::new())
+    }
+
+    fn from_str_with_includes(toml_str: &str, base_path: &Path, included: &mut Vec<PathBuf>) -> Result<Self, ConfigError> {
+        let mut toml: toml::Value = toml::from_str(toml_str)
             .map_err(|e| format!("Could not parse TOML: {}", e))?;
+        // Every file is migrated from its own version before the included files are merged into it
+        migrate_config(&mut toml)?;
+        resolve_includes(&mut toml, base_path, included)?;
 
         // Extract required fields for Config struct
         let toml_camera = toml.get("camera").ok_or("Missing camera section")?;
         let camera_position_vec = parse_array(toml_camera.get("position").ok_or("Missing camera position")?)?;
         let camera_position = [camera_position_vec[0], camera_position_vec[1], camera_position_vec[2]];
-        let camera_rotation_vec = parse_array(toml_camera.get("rotation").ok_or("Missing camera rotation")?)?;
-        let camera_rotation = [camera_rotation_vec[0], camera_rotation_vec[1]];
+        // The camera either faces a target point or is rotated by yaw and pitch
+        let camera_target = match toml_camera.get("target") {
+            Some(value) => {
+                let target = parse_array(value)?;
+                if target.len() != 3 {
+                    return Err("Expected 3 values for camera target".into());
+                }
+                Some([target[0], target[1], target[2]])
+            }
+            None => None,
+        };
+        let camera_rotation = match (toml_camera.get("rotation"), camera_target) {
+            (Some(value), _) => {
+                let camera_rotation_vec = parse_array(value)?;
+                [camera_rotation_vec[0], camera_rotation_vec[1]]
+            }
+            (None, Some(_)) => [0.0, 0.0],
+            (None, None) => return Err("Missing camera rotation or target".into()),
+        };
         // Near and far aren't critical and only really needed in edge cases, so we can use defaults if they're missing making the values optional
         let toml_camera_near_far_vec = toml_camera.get("near_far");
         let camera_near_far_vec = match toml_camera_near_far_vec {
             Some(value) => parse_array(value)?,
             None => {
                 println!("No near_far defined in config, using default values");
-                vec![0.1, 100.0]
+                DEFAULT_CAMERA_NEAR_FAR.to_vec()
             },
         };
             
         let camera_near_far = [camera_near_far_vec[0], camera_near_far_vec[1]];
         let camera_fov = toml_camera.get("fov").ok_or("Missing camera fov")?.as_float().ok_or("Expected float for camera fov")? as f32;
+        // Roll is locked unless explicitly enabled to keep the horizon stable
+        let camera_lock_roll = match toml_camera.get("lock_roll") {
+            Some(value) => value.as_bool().ok_or("Expected bool for camera lock_roll")?,
+            None => true,
+        };
+        // The mouse wheel dollies the camera unless zooming the fov is chosen
+        let camera_scroll_mode = match toml_camera.get("scroll") {
+            Some(value) => value.as_str().and_then(ScrollMode::from_name).ok_or("Expected \"dolly\" or \"fov\" for camera scroll")?,
+            None => ScrollMode::default(),
+        };
+        // Optional animated camera path
+        let camera_path = load_camera_path_config(toml_camera.get("path"))?;
 
         // Materials
         let materials = load_materials_config(toml.get("materials"))?;
@@ -95,11 +352,32 @@ impl Config {
         // 3D Models
         let model_paths = load_3d_models_config(toml.get("3d_model_paths"))?;
 
+        // Shader settings
+        let mut shader_config = load_shader_config(toml.get("shader"))?;
+
+        // Render settings
+        let (render_clear_color, render_texture_array_layers, render_tile_size, render_tiles_per_frame) = load_render_config(toml.get("render"))?;
+        shader_config.transparent_background = if render_clear_color[3] == 0.0 { 1 } else { 0 };
+
+        // GPU settings
+        let (gpu_adapter, gpu_power_preference, gpu_force_fallback_adapter) = load_gpu_config(toml.get("gpu"))?;
+
+        // BVH settings
+        let (bvh_leaf_size, bvh_builder, bvh_cache) = load_bvh_config(toml.get("bvh"))?;
+
+        // Keyboard controls
+        let key_bindings = load_keys_config(toml.get("keys"))?;
+
         Ok(Self {
+            version: CONFIG_VERSION,
             camera_position,
             camera_rotation,
+            camera_target,
             camera_near_far,
             camera_fov,
+            camera_lock_roll,
+            camera_scroll_mode,
+            camera_path,
 
             materials,
             textures,
@@ -108,8 +386,31 @@ impl Config {
 
             spheres,
             model_paths,
+            meshes: None,
+
+            shader_config,
+
+            render_clear_color,
+            render_texture_array_layers,
+            render_tile_size,
+            render_tiles_per_frame,
+
+            gpu_adapter,
+            gpu_power_preference,
+            gpu_force_fallback_adapter,
+
+            bvh_leaf_size,
+            bvh_builder,
+            bvh_cache,
+
+            key_bindings,
         })
     }
+
+    /// Returns `true` if the clear color has an alpha of 0, which renders the background transparent.
+    pub fn transparent_background(&self) -> bool {
+        self.render_clear_color[3] == 0.0
+    }
 }
 
 fn parse_array(value: &toml::Value) -> Result<Vec<f32>, String> {
@@ -127,6 +428,19 @@ fn load_materials_config(value: Option<&toml::Value>) -> Result<Option<Vec<Mater
             let array = value.as_array().ok_or("Expected array for materials")?;
             let materials = array.iter().map(|v| {
                 let mut v = v.clone();
+                // A preset fills in the values that aren't set
+                if let Some(preset) = v.as_table_mut().and_then(|table| table.remove("preset")) {
+                    let name = preset.as_str().ok_or("Expected string for preset")?;
+                    let preset = Material::preset(name).ok_or("Expected \"glass\", \"gold\", \"mirror\" or \"plastic\" for preset")?;
+                    let floats = |values: &[f32]| toml::Value::Array(values.iter().map(|&value| toml::Value::Float(value as f64)).collect());
+                    let table = v.as_table_mut().unwrap();
+                    table.entry("color").or_insert_with(|| floats(&preset.albedo[..3]));
+                    table.entry("attenuation").or_insert_with(|| floats(&preset.attenuation[..3]));
+                    table.entry("roughness").or_insert(toml::Value::Float(preset.roughness as f64));
+                    table.entry("emission").or_insert(toml::Value::Float(preset.emission as f64));
+                    table.entry("ior").or_insert(toml::Value::Float(preset.ior as f64));
+                    table.entry("metallic").or_insert(toml::Value::Float(preset.metallic as f64));
+                }
                 // Make color and attenuation 4 elements instead of 3
                 let mut color = v.get("color").ok_or("Missing color")?.as_array().ok_or("Expected array for color")?.clone();
                 let mut attenuation = v.get("attenuation").ok_or("Missing attenuation")?.as_array().ok_or("Expected array for attenuation")?.clone();
@@ -138,7 +452,58 @@ fn load_materials_config(value: Option<&toml::Value>) -> Result<Option<Vec<Mater
                 // Update the color and attenuation in v
                 v.as_table_mut().unwrap().insert("color".to_string(), toml::Value::Array(color));
                 v.as_table_mut().unwrap().insert("attenuation".to_string(), toml::Value::Array(attenuation));
-                v.as_table_mut().unwrap().insert("__padding".to_string(), toml::Value::Float(0.0));
+                // The wrap mode is stored as number for the shader
+                let wrap_mode = match v.get("wrap_mode") {
+                    Some(wrap_mode) => {
+                        let name = wrap_mode.as_str().ok_or("Expected string for wrap_mode")?;
+                        WrapMode::from_name(name).ok_or("Expected \"repeat\", \"clamp\" or \"mirror\" for wrap_mode")?
+                    }
+                    None => WrapMode::Repeat,
+                };
+                v.as_table_mut().unwrap().insert("wrap_mode".to_string(), toml::Value::Integer(wrap_mode as i64));
+                // The alpha mode is stored as number for the shader, the cutoff is only used by "mask"
+                let alpha_cutoff = match v.get("alpha_cutoff") {
+                    Some(alpha_cutoff) => alpha_cutoff.as_float().ok_or("Expected float for alpha_cutoff")? as f32,
+                    None => AlphaMode::DEFAULT_CUTOFF,
+                };
+                if !(0.0..=1.0).contains(&alpha_cutoff) {
+                    return Err("Expected alpha_cutoff between 0.0 and 1.0");
+                }
+                let alpha_mode = match v.get("alpha_mode") {
+                    Some(alpha_mode) => {
+                        let name = alpha_mode.as_str().ok_or("Expected string for alpha_mode")?;
+                        AlphaMode::from_name(name, alpha_cutoff).ok_or("Expected \"opaque\", \"mask\" or \"blend\" for alpha_mode")?
+                    }
+                    None => AlphaMode::Opaque,
+                };
+                v.as_table_mut().unwrap().insert("alpha_mode".to_string(), toml::Value::Integer(alpha_mode.as_u32() as i64));
+                v.as_table_mut().unwrap().insert("alpha_cutoff".to_string(), toml::Value::Float(alpha_cutoff as f64));
+                // Stored as number for the shader, materials are single-sided by default
+                let double_sided = match v.get("double_sided") {
+                    Some(double_sided) => double_sided.as_bool().ok_or("Expected bool for double_sided")?,
+                    None => false,
+                };
+                v.as_table_mut().unwrap().insert("double_sided".to_string(), toml::Value::Integer(double_sided as i64));
+                // Metallic is optional, materials without it are dielectrics
+                if let Some(metallic) = v.get("metallic") {
+                    let metallic = metallic.as_float().ok_or("Expected float for metallic")?;
+                    if !(0.0..=1.0).contains(&metallic) {
+                        return Err("Expected metallic between 0.0 and 1.0");
+                    }
+                }
+                // The clearcoat is optional as well, materials without it have no coat
+                if let Some(clearcoat) = v.get("clearcoat") {
+                    let clearcoat = clearcoat.as_float().ok_or("Expected float for clearcoat")?;
+                    if !(0.0..=1.0).contains(&clearcoat) {
+                        return Err("Expected clearcoat between 0.0 and 1.0");
+                    }
+                }
+                if let Some(clearcoat_roughness) = v.get("clearcoat_roughness") {
+                    let clearcoat_roughness = clearcoat_roughness.as_float().ok_or("Expected float for clearcoat_roughness")?;
+                    if !(0.0..=1.0).contains(&clearcoat_roughness) {
+                        return Err("Expected clearcoat_roughness between 0.0 and 1.0");
+                    }
+                }
 
                 // Convert v to Material
                 v.try_into().map_err(|_| "Could not convert to Material")
@@ -160,11 +525,23 @@ fn load_textures_config(value: Option<&toml::Value>) -> Result<Option<Vec<Textur
                 let diffuse = v.get("diffuse").and_then(|v| v.as_str()).map(|v| v.to_string());
                 let normal = v.get("normal").and_then(|v| v.as_str()).map(|v| v.to_string());
                 let roughness = v.get("roughness").and_then(|v| v.as_str()).map(|v| v.to_string());
+                let diffuse_color_space = match v.get("diffuse_color_space") {
+                    Some(name) => name.as_str().and_then(ColorSpace::from_name)
+                        .ok_or("Expected \"srgb\" or \"linear\" for texture diffuse_color_space")?,
+                    None => ColorSpace::Srgb,
+                };
+                let filter = match v.get("filter") {
+                    Some(name) => name.clone().try_into::<TextureFilter>()
+                        .map_err(|_| "Expected \"nearest\", \"linear\", \"cubic\", \"gaussian\" or \"lanczos\" for texture filter")?,
+                    None => TextureFilter::Nearest,
+                };
                 if diffuse.is_some() || normal.is_some() || roughness.is_some() {
                     Ok(Textureset {
                         diffuse_path: diffuse,
                         normal_path: normal,
                         roughness_path: roughness,
+                        diffuse_color_space,
+                        filter,
                     })
                 } else {
                     Err("Missing texture paths".to_string())
@@ -180,7 +557,7 @@ fn load_textures_config(value: Option<&toml::Value>) -> Result<Option<Vec<Textur
 }
 
 // makes background optional in config
-fn load_background_config(value: Option<&toml::Value>) -> Result<(Option<Background>, Option<String>), String> {
+fn load_background_config(value: Option<&toml::Value>) -> Result<(Option<Background>, Option<String>), ConfigError> {
     match value {
         Some(value) => {
             // if v is empty, meaning no sphere is defined, return none
@@ -191,6 +568,33 @@ fn load_background_config(value: Option<&toml::Value>) -> Result<(Option<Backgro
             let background_path = value.get("background_path").and_then(|v| v.as_str()).map(|v| v.to_string());
             let intensity = value.get("intensity").and_then(|v| v.as_float()).map(|v| v as f32);
 
+            if let Some(material_id) = material_id {
+                validate_id("background material_id", 0, material_id as i64)?;
+            }
+
+            // The kind can be left out for a color or a gradient, the HDRI kind needs the material like before there were kinds
+            let kind = match value.get("kind") {
+                Some(kind) => Some(kind.as_str().and_then(BackgroundMode::from_name)
+                    .ok_or("Expected \"sky\", \"hdri\", \"color\" or \"gradient\" for background kind")?),
+                None => None,
+            };
+            let color = value.get("color").map(|color| parse_color3(color, "background color")).transpose()?;
+            let gradient = value.get("gradient").map(load_sky_gradient_config).transpose()?;
+            let kind = kind
+                .or(gradient.map(|_| BackgroundMode::Gradient))
+                .or(color.map(|_| BackgroundMode::Color));
+            if let Some(mode) = kind.filter(|&mode| mode != BackgroundMode::Texture) {
+                if mode == BackgroundMode::Gradient && gradient.is_none() {
+                    return Err("Missing gradient for background kind \"gradient\"".into());
+                }
+                // With a material the HDRI can still be selected in the GUI
+                let mut background = Background::new(material_id.unwrap_or(-1), if material_id.is_some() { 0 } else { -1 }, intensity.unwrap_or(1.0));
+                background.color = color.unwrap_or(background.color);
+                background.set_gradient(gradient.unwrap_or_default());
+                background.set_mode(mode);
+                return Ok((Some(background), background_path));
+            }
+
             if let (Some(material_id), Some(background_path), Some(intensity)) = (material_id, background_path.clone(), intensity) {
                 println!("Background defined in config");
                 Ok((
@@ -213,7 +617,7 @@ fn load_background_config(value: Option<&toml::Value>) -> Result<(Option<Backgro
                 ))
             } else {
                 print!("material_id: {:?}, background_path: {:?}, intensity: {:?}", material_id, background_path, intensity);
-                Err("Missing or invalid fields in background config".to_string())
+                Err("Missing or invalid fields in background config".into())
             }
         },
         None => {
@@ -226,14 +630,181 @@ fn load_background_config(value: Option<&toml::Value>) -> Result<(Option<Backgro
 
 
 
+// Three floats of a linear rgb color
+fn parse_color3(value: &toml::Value, name: &str) -> Result<[f32; 3], String> {
+    match parse_array(value)?.as_slice() {
+        &[r, g, b] => Ok([r, g, b]),
+        _ => Err(format!("Expected 3 floats for {}", name)),
+    }
+}
+
+// The gradient table of the background, e.g. `gradient = { top = [0.5, 0.7, 1.0], horizon = [1.0, 1.0, 1.0], bottom = [0.4, 0.4, 0.4] }`
+fn load_sky_gradient_config(value: &toml::Value) -> Result<SkyGradient, String> {
+    let color = |name: &str| value.get(name)
+        .ok_or(format!("Missing {} color in background gradient", name))
+        .and_then(|color| parse_color3(color, &format!("background gradient {}", name)));
+    Ok(SkyGradient {
+        top: color("top")?,
+        horizon: color("horizon")?,
+        bottom: color("bottom")?,
+    })
+}
+
+// Arrays that are concatenated when a config includes another one, all other values of the including file replace the included ones
+const INCLUDE_CONCATENATED_KEYS: [&str; 2] = ["materials", "textures"];
+
+// merges the files of the top-level `include` list into the parsed TOML, e.g. `include = ["materials.toml"]`
+// `included` holds the files that are currently being loaded, a file that includes one of them would never finish
+fn resolve_includes(toml: &mut toml::Value, base_path: &Path, included: &mut Vec<PathBuf>) -> Result<(), ConfigError> {
+    let table = match toml.as_table_mut() {
+        Some(table) => table,
+        None => return Ok(()),
+    };
+    let include = match table.remove("include") {
+        Some(include) => include,
+        None => return Ok(()),
+    };
+    let include_paths = include.as_array().ok_or("Expected array of file paths for include")?
+        .iter()
+        .map(|path| path.as_str().ok_or("Expected string for include path"))
+        .collect::<Result<Vec<&str>, _>>()?;
+
+    // Included files are merged in order, so later files override earlier ones and the including file overrides all of them
+    let mut merged = toml::Table::new();
+    for include_path in include_paths {
+        let path = base_path.join(include_path);
+        let canonical_path = fs::canonicalize(&path)
+            .map_err(|e| format!("Could not find/read included config file {}: {}", path.display(), e))?;
+        if included.contains(&canonical_path) {
+            return Err(format!("Config include cycle: {} includes itself", path.display()).into());
+        }
+        let include_str = fs::read_to_string(&canonical_path)
+            .map_err(|e| format!("Could not find/read included config file {}: {}", path.display(), e))?;
+        let mut include_toml: toml::Value = toml::from_str(&include_str)
+            .map_err(|e| format!("Could not parse TOML of included config file {}: {}", path.display(), e))?;
+        migrate_config(&mut include_toml)?;
+
+        included.push(canonical_path);
+        let include_base_path = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        resolve_includes(&mut include_toml, &include_base_path, included)?;
+        included.pop();
+
+        if let toml::Value::Table(include_table) = include_toml {
+            merge_config_tables(&mut merged, include_table);
+        }
+    }
+    merge_config_tables(&mut merged, std::mem::take(table));
+    *table = merged;
+    Ok(())
+}
+
+// merges `overrides` into `base`: tables are merged key by key, the concatenated arrays are appended, other values are replaced
+fn merge_config_tables(base: &mut toml::Table, overrides: toml::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base_table)), toml::Value::Table(table)) => merge_config_tables(base_table, table),
+            (Some(toml::Value::Array(base_array)), toml::Value::Array(array)) if INCLUDE_CONCATENATED_KEYS.contains(&key.as_str()) => {
+                base_array.extend(array);
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+// upgrades the parsed TOML of an older config version to the current layout, step by step
+fn migrate_config(toml: &mut toml::Value) -> Result<(), ConfigError> {
+    let version = match toml.get("version") {
+        Some(value) => value.as_integer().ok_or("Expected integer for config version")?,
+        None => 0,
+    };
+    if version < 0 {
+        return Err(format!("Invalid config version {}", version).into());
+    }
+    if version > CONFIG_VERSION as i64 + 1 {
+        return Err(ConfigError::UnsupportedVersion { version, supported: CONFIG_VERSION });
+    }
+    if version > CONFIG_VERSION as i64 {
+        println!("Config version {} is newer than the supported version {}, unknown settings may be ignored", version, CONFIG_VERSION);
+        return Ok(());
+    }
+
+    if version < 1 {
+        // Version 1 is the first versioned format, its layout is the same as the unversioned one
+        println!("Config has no version, reading it as version 1");
+    }
+    if version < 2 {
+        // The attenuation of dielectrics became the absorption inside of them (Beer-Lambert) instead of a weight per bounce
+        migrate_dielectric_attenuation(toml);
+    }
+    // Later versions add their migration here, e.g. `if version < 3 { ... }` filling in new required sections
+
+    if let Some(table) = toml.as_table_mut() {
+        table.insert("version".to_string(), toml::Value::Integer(CONFIG_VERSION as i64));
+    }
+    Ok(())
+}
+
+// converts the attenuation weight of dielectric materials (ior > 0, also from the glass preset) to an absorption coefficient
+// with the same transmittance over a distance of 1: a weight of 1 becomes 0 (clear) and 0.5 becomes ln(2)
+fn migrate_dielectric_attenuation(toml: &mut toml::Value) {
+    // A weight of 0 absorbs everything, it becomes the absorption of 1 / MIN_WEIGHT over a distance of 1
+    const MIN_WEIGHT: f64 = 1e-3;
+    let number = |value: &toml::Value| value.as_float().or_else(|| value.as_integer().map(|i| i as f64));
+    let Some(materials) = toml.get_mut("materials").and_then(|materials| materials.as_array_mut()) else {
+        return;
+    };
+    for material in materials.iter_mut().filter_map(|material| material.as_table_mut()) {
+        let preset_ior = material.get("preset").and_then(|preset| preset.as_str()).and_then(Material::preset).map(|preset| preset.ior as f64);
+        if material.get("ior").and_then(number).or(preset_ior).unwrap_or(0.0) <= 0.0 {
+            continue;
+        }
+        for value in material.get_mut("attenuation").and_then(|attenuation| attenuation.as_array_mut()).into_iter().flatten() {
+            if let Some(weight) = number(value) {
+                *value = toml::Value::Float((1.0 / weight.clamp(MIN_WEIGHT, 1.0)).ln());
+            }
+        }
+    }
+}
+
 // makes 3D models optional in config
-fn load_3d_models_config(value: Option<&toml::Value>) -> Result<ModelPaths, String> {
+fn load_3d_models_config(value: Option<&toml::Value>) -> Result<ModelPaths, ConfigError> {
     match value {
         Some(value) => {
             let gltf_path = value.get("gltf_path").and_then(|v| v.as_str()).map(|v| v.to_string());
             let obj_path = value.get("obj_path").and_then(|v| v.as_str()).map(|v| v.to_string());
-            let obj_material_id = value.get("obj_material_id").and_then(|v| v.as_integer()).map(|v| v as i32);
-            Ok(ModelPaths::new(gltf_path, obj_path, obj_material_id))
+            let obj_material_id = value.get("obj_material_id").and_then(|v| v.as_integer());
+            if let Some(obj_material_id) = obj_material_id {
+                validate_id("obj_material_id", 0, obj_material_id)?;
+            }
+
+            let svg_path = value.get("svg_path").and_then(|v| v.as_str()).map(|v| v.to_string());
+            // Integers are accepted as well, e.g. `svg_depth = 1`
+            let svg_float = |key: &str| -> Result<f32, ConfigError> {
+                match value.get(key) {
+                    Some(v) => v.as_float().or_else(|| v.as_integer().map(|i| i as f64))
+                        .map(|f| f as f32)
+                        .ok_or_else(|| ConfigError::from(format!("Expected number for {}", key))),
+                    None => Ok(0.0),
+                }
+            };
+            let svg_z = svg_float("svg_z")?;
+            let svg_depth = svg_float("svg_depth")?;
+            if svg_depth.is_nan() || svg_depth < 0.0 {
+                return Err("svg_depth must be 0 or positive".into());
+            }
+            let svg_material_id = value.get("svg_material_id").and_then(|v| v.as_integer());
+            if let Some(svg_material_id) = svg_material_id {
+                validate_id("svg_material_id", 0, svg_material_id)?;
+            }
+            Ok(ModelPaths {
+                svg_path,
+                svg_z,
+                svg_depth,
+                svg_material_id: svg_material_id.map(|v| v as i32),
+                ..ModelPaths::new(gltf_path, obj_path, obj_material_id.map(|v| v as i32))
+            })
         },
         None => {
             println!("No 3D model paths defined in config");
@@ -242,13 +813,22 @@ fn load_3d_models_config(value: Option<&toml::Value>) -> Result<ModelPaths, Stri
     }
 }
 
+// checks that an id from the config is either -1 (unused) or a valid index
+pub(crate) fn validate_id(field: &str, index: usize, id: i64) -> Result<(), ConfigError> {
+    if id < -1 {
+        return Err(ConfigError::InvalidId { field: field.to_string(), index, id });
+    }
+    Ok(())
+}
+
 // makes spheres optional in config
-fn load_spheres_config(value: Option<&toml::Value>) -> Result<Option<Vec<Sphere>>, String> {
+fn load_spheres_config(value: Option<&toml::Value>) -> Result<Option<Vec<Sphere>>, ConfigError> {
     match value {
         Some(value) => {
             let value = value.as_array().ok_or("Expected array")?
                 .iter()
-                .map(|v| {
+                .enumerate()
+                .map(|(index, v)| {
                     // if v is empty, meaning no sphere is defined, return none
                     if v.as_table().is_none() || (v.as_table().is_some() && v.as_table().unwrap().is_empty()) {
                         return Ok(None);
@@ -257,17 +837,48 @@ fn load_spheres_config(value: Option<&toml::Value>) -> Result<Option<Vec<Sphere>
                     let mut v = v.clone();
                     let mut position = v.get("position").ok_or("Missing position")?.as_array().ok_or("Expected array")?.clone();
 
-                    let texture_id: Vec<f32> = v.get("texture_id").ok_or("Missing texture_id")?.as_array().ok_or("Expected array")?
+                    let texture_id: Vec<i64> = v.get("texture_id").ok_or("Missing texture_id")?.as_array().ok_or("Expected array")?
                         .iter()
                         .map(|value: &toml::Value| value.as_integer().ok_or("Expected int"))
-                        .map(|value: Result<i64, &str>| value.map(|value| value as f32))
-                        .collect::<Result<Vec<f32>, _>>()?;
+                        .collect::<Result<Vec<i64>, _>>()?;
+                    // The emission texture is optional
+                    if texture_id.len() != 3 && texture_id.len() != 4 {
+                        return Err(ConfigError::Invalid(format!("Expected 3 or 4 texture ids for sphere {}, got {}", index, texture_id.len())));
+                    }
+                    for id in &texture_id {
+                        validate_id("sphere texture_id", index, *id)?;
+                    }
+                    let mut texture_id: Vec<f32> = texture_id.iter().map(|&id| id as f32).collect();
+                    texture_id.resize(4, -1.0);
 
                     let radius = v.get("radius").ok_or("Missing radius")?.as_float().ok_or("Expected float")? as f32;
-                    let material_id = v.get("material_id").ok_or("Missing material_id")?.as_integer().ok_or("Expected int")? as f32;
+                    // Also rejects NaN
+                    if !(radius > 0.0) {
+                        return Err(ConfigError::InvalidSphereRadius { sphere: index, radius });
+                    }
+                    let material_id = v.get("material_id").ok_or("Missing material_id")?.as_integer().ok_or("Expected int")?;
+                    validate_id("sphere material_id", index, material_id)?;
+                    let material_id = material_id as f32;
+
+                    // The texture orientation is optional, a pole axis of 0 keeps +y as the top of the texture
+                    let pole_axis = match v.get("pole_axis") {
+                        Some(value) => {
+                            let axis = parse_array(value)?;
+                            let length = axis.iter().map(|value| value * value).sum::<f32>().sqrt();
+                            if axis.len() != 3 || length == 0.0 || length.is_nan() {
+                                return Err(ConfigError::Invalid(format!("Expected a pole_axis of 3 values that aren't all 0 for sphere {}", index)));
+                            }
+                            [axis[0] / length, axis[1] / length, axis[2] / length]
+                        }
+                        None => [0.0; 3],
+                    };
+                    let uv_rotation = match v.get("uv_rotation") {
+                        Some(value) => value.as_float().ok_or("Expected float for uv_rotation")? as f32,
+                        None => 0.0,
+                    };
 
                     // Fix length of arrays
-                    let radius_array = vec![radius, 0.0, 0.0, 0.0].iter().map(|&value| toml::Value::Float(value as f64)).collect::<Vec<toml::Value>>();
+                    let radius_array = vec![radius, pole_axis[0], pole_axis[1], pole_axis[2]].iter().map(|&value| toml::Value::Float(value as f64)).collect::<Vec<toml::Value>>();
 
                     position.push(toml::Value::Float(0.0));
                     let material_texture_id = [
@@ -276,15 +887,17 @@ fn load_spheres_config(value: Option<&toml::Value>) -> Result<Option<Vec<Sphere>
                         texture_id[1],
                         texture_id[2],
                     ].iter().map(|&value| toml::Value::Float(value as f64)).collect::<Vec<toml::Value>>();
+                    let emission_texture_id = [texture_id[3], uv_rotation / 360.0, 0.0, 0.0].iter().map(|&value| toml::Value::Float(value as f64)).collect::<Vec<toml::Value>>();
 
                     // Update the color and attenuation in v
                     v.as_table_mut().unwrap().insert("center".to_string(), toml::Value::Array(position));
                     v.as_table_mut().unwrap().insert("radius".to_string(), toml::Value::Array(radius_array));
                     v.as_table_mut().unwrap().insert("material_texture_id".to_string(), toml::Value::Array(material_texture_id));
+                    v.as_table_mut().unwrap().insert("emission_texture_id".to_string(), toml::Value::Array(emission_texture_id));
 
                     // Convert v to Material
-                    v.try_into().map_err(|_| "Could not convert to Material".to_string())
-                }).collect::<Result<Option<Vec<Sphere>>, String>>()?;
+                    v.try_into().map_err(|_| ConfigError::from("Could not convert to Sphere"))
+                }).collect::<Result<Option<Vec<Sphere>>, ConfigError>>()?;
             Ok(value)
         },
         None => {
@@ -294,9 +907,154 @@ fn load_spheres_config(value: Option<&toml::Value>) -> Result<Option<Vec<Sphere>
     }
 }
 
+// makes the camera path optional in config
+fn load_camera_path_config(value: Option<&toml::Value>) -> Result<Option<CameraPath>, String> {
+    match value {
+        Some(value) => {
+            let looping = match value.get("loop") {
+                Some(looping) => looping.as_bool().ok_or("Expected bool for camera path loop")?,
+                None => false,
+            };
+            let interpolation = match value.get("interpolation").map(|v| v.as_str()) {
+                Some(Some("linear")) => PathInterpolation::Linear,
+                Some(Some("catmull_rom")) | None => PathInterpolation::CatmullRom,
+                _ => return Err("Expected \"linear\" or \"catmull_rom\" for camera path interpolation".to_string()),
+            };
+            let keyframes = value.get("keyframes").ok_or("Missing camera path keyframes")?
+                .as_array().ok_or("Expected array for camera path keyframes")?
+                .iter()
+                .map(|v| {
+                    let time = v.get("time").ok_or("Missing keyframe time")?.as_float().ok_or("Expected float for keyframe time")? as f32;
+                    let position = parse_array(v.get("position").ok_or("Missing keyframe position")?)?;
+                    let rotation = parse_array(v.get("rotation").ok_or("Missing keyframe rotation")?)?;
+                    if position.len() != 3 || rotation.len() != 2 {
+                        return Err("Expected 3 values for keyframe position and 2 for keyframe rotation".to_string());
+                    }
+                    Ok(CameraKeyframe::new(time, [position[0], position[1], position[2]], rotation[0], rotation[1]))
+                }).collect::<Result<Vec<CameraKeyframe>, String>>()?;
+            if keyframes.is_empty() {
+                return Err("Camera path needs at least one keyframe".to_string());
+            }
+            println!("Camera path with {} keyframes defined in config", keyframes.len());
+            Ok(Some(CameraPath::new(keyframes, looping, interpolation)))
+        },
+        None => Ok(None),
+    }
+}
+
+// makes shader settings optional in config, missing values are taken from the defaults
+fn load_shader_config(value: Option<&toml::Value>) -> Result<ShaderConfig, String> {
+    match value {
+        Some(value) => {
+            let shader_config: ShaderConfig = value.clone().try_into()
+                .map_err(|e| format!("Could not convert to ShaderConfig: {}", e))?;
+            println!("Shader settings defined in config");
+            Ok(shader_config)
+        },
+        None => {
+            println!("No shader settings defined in config, using default values");
+            Ok(ShaderConfig::default())
+        }
+    }
+}
+
+// makes gpu settings optional in config, returns the adapter selector (index or name)
+fn load_gpu_config(value: Option<&toml::Value>) -> Result<(Option<String>, Option<String>, bool), String> {
+    let adapter = match value.and_then(|gpu| gpu.get("adapter")) {
+        Some(toml::Value::String(name)) => Some(name.clone()),
+        Some(toml::Value::Integer(index)) if *index >= 0 => Some(index.to_string()),
+        Some(_) => return Err("Expected adapter name or index >= 0 for gpu adapter".to_string()),
+        None => None,
+    };
+    let power_preference = match value.and_then(|gpu| gpu.get("power_preference")) {
+        Some(toml::Value::String(name)) if name == "high_performance" || name == "low_power" => Some(name.clone()),
+        Some(_) => return Err("Expected \"high_performance\" or \"low_power\" for gpu power_preference".to_string()),
+        None => None,
+    };
+    let force_fallback_adapter = match value.and_then(|gpu| gpu.get("force_fallback_adapter")) {
+        Some(value) => value.as_bool().ok_or("Expected bool for gpu force_fallback_adapter")?,
+        None => false,
+    };
+    Ok((adapter, power_preference, force_fallback_adapter))
+}
+
+// makes bvh settings optional in config, returns the leaf size, the builder and if the BVH is cached
+fn load_bvh_config(value: Option<&toml::Value>) -> Result<(usize, BvhBuilder, bool), String> {
+    let leaf_size = match value.and_then(|bvh| bvh.get("leaf_size")) {
+        Some(toml::Value::Integer(leaf_size)) if *leaf_size >= 1 => *leaf_size as usize,
+        Some(_) => return Err("Expected integer >= 1 for bvh leaf_size".to_string()),
+        None => DEFAULT_BVH_LEAF_SIZE,
+    };
+    let builder = match value.and_then(|bvh| bvh.get("builder")) {
+        Some(toml::Value::String(name)) => BvhBuilder::from_name(name)
+            .ok_or("Expected \"sah\" or \"locb\" for bvh builder")?,
+        Some(_) => return Err("Expected \"sah\" or \"locb\" for bvh builder".to_string()),
+        None => BvhBuilder::default(),
+    };
+    let cache = match value.and_then(|bvh| bvh.get("cache")) {
+        Some(value) => value.as_bool().ok_or("Expected bool for bvh cache")?,
+        None => false,
+    };
+    Ok((leaf_size, builder, cache))
+}
+
+// makes the key bindings optional in config, every action is either a key or an array of keys, e.g. `forward = ["w", "ArrowUp"]`
+fn load_keys_config(value: Option<&toml::Value>) -> Result<KeyBindings, String> {
+    let mut key_bindings = KeyBindings::default();
+    let Some(value) = value else {
+        return Ok(key_bindings);
+    };
+    for (name, keys) in value.as_table().ok_or("Expected table for keys")? {
+        let action = KeyAction::from_config_name(name).ok_or(format!("Unknown action \"{}\" in keys", name))?;
+        let keys: Vec<&str> = match keys {
+            toml::Value::String(key) => vec![key.as_str()],
+            toml::Value::Array(keys) => keys.iter().map(|key| key.as_str().ok_or(format!("Expected strings for keys {}", name))).collect::<Result<_, _>>()?,
+            _ => return Err(format!("Expected string or array of strings for keys {}", name)),
+        };
+        key_bindings.set(action, &keys)
+            .map_err(|key| format!("Unknown key \"{}\" for keys {}, expected a single character or one of {}", key, name, NAMED_KEYS.join(", ")))?;
+    }
+    Ok(key_bindings)
+}
+
+// makes render settings optional in config, returns the clear color, if the textures use array layers instead of an atlas,
+// the tile size and the tiles per frame
+fn load_render_config(value: Option<&toml::Value>) -> Result<([f32; 4], bool, u32, u32), String> {
+    let texture_array_layers = match value.and_then(|render| render.get("texture_array_layers")) {
+        Some(value) => value.as_bool().ok_or("Expected bool for render texture_array_layers")?,
+        None => false,
+    };
+    let tile_size = load_render_count(value, "tile_size")?;
+    let tiles_per_frame = load_render_count(value, "tiles_per_frame")?;
+    let clear_color = match value.and_then(|render| render.get("clear_color")) {
+        Some(clear_color) => parse_array(clear_color)?,
+        None => return Ok((DEFAULT_CLEAR_COLOR, texture_array_layers, tile_size, tiles_per_frame)),
+    };
+
+    // Alpha is optional, a clear color without alpha is opaque
+    let clear_color = match clear_color.as_slice() {
+        [r, g, b] => [*r, *g, *b, 1.0],
+        [r, g, b, a] if (0.0..=1.0).contains(a) => [*r, *g, *b, *a],
+        [_, _, _, _] => return Err("Expected alpha between 0.0 and 1.0 for render clear_color".to_string()),
+        _ => return Err("Expected 3 or 4 values for render clear_color".to_string()),
+    };
+    Ok((clear_color, texture_array_layers, tile_size, tiles_per_frame))
+}
+
+// Non-negative integer of the render section, 0 if it isn't set
+fn load_render_count(value: Option<&toml::Value>, key: &str) -> Result<u32, String> {
+    match value.and_then(|render| render.get(key)) {
+        Some(count) => count.as_integer()
+            .and_then(|count| u32::try_from(count).ok())
+            .ok_or(format!("Expected non-negative integer for render {}", key)),
+        None => Ok(0),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::SamplingPattern;
 
     #[test]
     fn test_camera_missing() {
@@ -319,6 +1077,18 @@ mod tests {
         assert!(config.is_err());
     }
 
+    #[test]
+    fn test_camera_target() {
+        let config = Config::from_str("[camera]\nposition = [0.0, 0.0, 5.0]\ntarget = [0.0, 0.0, 0.0]\nfov = 45.0").unwrap();
+        assert_eq!(config.camera_target, Some([0.0, 0.0, 0.0]));
+        assert_eq!(config.camera_rotation, [0.0, 0.0]);
+
+        let config = Config::from_str("[camera]\nposition = [0.0, 0.0, 5.0]\nrotation = [10.0, 0.0]\nfov = 45.0").unwrap();
+        assert_eq!(config.camera_target, None);
+
+        assert!(Config::from_str("[camera]\nposition = [0.0, 0.0, 5.0]\ntarget = [0.0, 0.0]\nfov = 45.0").is_err());
+    }
+
     #[test]
     fn test_camera_missing_near_far() {
         let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0");
@@ -333,6 +1103,30 @@ mod tests {
         assert!(config.is_err());
     }
 
+    #[test]
+    fn test_camera_lock_roll() {
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0");
+        assert!(config.expect("Could not unwrap config").camera_lock_roll);
+
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0\nlock_roll = false");
+        assert!(!config.expect("Could not unwrap config").camera_lock_roll);
+
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0\nlock_roll = 1");
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn test_camera_scroll_mode() {
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0");
+        assert_eq!(config.expect("Could not unwrap config").camera_scroll_mode, ScrollMode::Dolly);
+
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0\nscroll = \"fov\"");
+        assert_eq!(config.expect("Could not unwrap config").camera_scroll_mode, ScrollMode::Fov);
+
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0\nscroll = \"zoom\"");
+        assert!(config.is_err());
+    }
+
     // Materials tests
     #[test]
     fn test_materials_missing() {
@@ -378,6 +1172,118 @@ mod tests {
         assert_eq!(materials[1].attenuation, [0.2, 0.2, 0.2, 0.0]);
     }
 
+    #[test]
+    fn test_materials_wrap_mode() {
+        let base = "[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nnear_far = [0.1, 100.0]\nfov = 45.0\n[[materials]]\ncolor = [1.0, 0.0, 0.0]\nattenuation = [0.1, 0.1, 0.1]\nroughness = 0.2\nemission = 0.0\nior = 0.0";
+
+        let config = Config::from_str(base).expect("Could not unwrap config");
+        assert_eq!(config.materials.unwrap()[0].wrap_mode, WrapMode::Repeat as u32);
+
+        let config = Config::from_str(&format!("{}\nwrap_mode = \"clamp\"", base)).expect("Could not unwrap config");
+        assert_eq!(config.materials.unwrap()[0].wrap_mode, WrapMode::Clamp as u32);
+
+        let config = Config::from_str(&format!("{}\nwrap_mode = \"mirror\"", base)).expect("Could not unwrap config");
+        assert_eq!(config.materials.unwrap()[0].wrap_mode, WrapMode::Mirror as u32);
+
+        assert!(Config::from_str(&format!("{}\nwrap_mode = \"border\"", base)).is_err());
+        assert!(Config::from_str(&format!("{}\nwrap_mode = 1", base)).is_err());
+    }
+
+    #[test]
+    fn test_materials_metallic() {
+        let base = "[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nnear_far = [0.1, 100.0]\nfov = 45.0\n[[materials]]\ncolor = [1.0, 0.0, 0.0]\nattenuation = [0.1, 0.1, 0.1]\nroughness = 0.2\nemission = 0.0\nior = 0.0";
+
+        // Older configs without metallic are dielectrics
+        let config = Config::from_str(base).expect("Could not unwrap config");
+        assert_eq!(config.materials.unwrap()[0].metallic, 0.0);
+
+        let config = Config::from_str(&format!("{}\nmetallic = 0.75", base)).expect("Could not unwrap config");
+        assert_eq!(config.materials.unwrap()[0].metallic, 0.75);
+
+        assert!(Config::from_str(&format!("{}\nmetallic = 1.5", base)).is_err());
+        assert!(Config::from_str(&format!("{}\nmetallic = \"gold\"", base)).is_err());
+    }
+
+    #[test]
+    fn test_materials_clearcoat() {
+        let base = "[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nnear_far = [0.1, 100.0]\nfov = 45.0\n[[materials]]\ncolor = [1.0, 0.0, 0.0]\nattenuation = [0.1, 0.1, 0.1]\nroughness = 0.6\nemission = 0.0\nior = 0.0";
+
+        // Materials without the clearcoat values have no coat
+        let material = Config::from_str(base).expect("Could not unwrap config").materials.unwrap()[0];
+        assert_eq!((material.clearcoat, material.clearcoat_roughness), (0.0, 0.0));
+
+        let material = Config::from_str(&format!("{}\nclearcoat = 1.0\nclearcoat_roughness = 0.05", base)).expect("Could not unwrap config").materials.unwrap()[0];
+        assert_eq!((material.clearcoat, material.clearcoat_roughness), (1.0, 0.05));
+        // The coat doesn't change the base
+        assert_eq!(material.roughness, 0.6);
+
+        // A preset keeps the clearcoat set next to it
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0\n[[materials]]\npreset = \"plastic\"\nclearcoat = 0.5").expect("Could not unwrap config");
+        assert_eq!(config.materials.unwrap()[0].clearcoat, 0.5);
+
+        assert!(Config::from_str(&format!("{}\nclearcoat = 1.5", base)).is_err());
+        assert!(Config::from_str(&format!("{}\nclearcoat = true", base)).is_err());
+        assert!(Config::from_str(&format!("{}\nclearcoat_roughness = -0.1", base)).is_err());
+        assert!(Config::from_str(&format!("{}\nclearcoat_roughness = \"smooth\"", base)).is_err());
+    }
+
+    #[test]
+    fn test_materials_alpha_mode() {
+        let base = "[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nnear_far = [0.1, 100.0]\nfov = 45.0\n[[materials]]\ncolor = [1.0, 0.0, 0.0]\nattenuation = [0.1, 0.1, 0.1]\nroughness = 0.2\nemission = 0.0\nior = 0.0";
+
+        let material = Config::from_str(base).expect("Could not unwrap config").materials.unwrap()[0];
+        assert_eq!(material.alpha_mode, AlphaMode::Opaque.as_u32());
+
+        // The cutoff defaults to the one of glTF
+        let material = Config::from_str(&format!("{}\nalpha_mode = \"mask\"", base)).expect("Could not unwrap config").materials.unwrap()[0];
+        assert_eq!(material.alpha_mode, AlphaMode::Mask { cutoff: 0.5 }.as_u32());
+        assert_eq!(material.alpha_cutoff, AlphaMode::DEFAULT_CUTOFF);
+
+        let material = Config::from_str(&format!("{}\nalpha_mode = \"mask\"\nalpha_cutoff = 0.25", base)).expect("Could not unwrap config").materials.unwrap()[0];
+        assert_eq!(material.alpha_cutoff, 0.25);
+
+        let material = Config::from_str(&format!("{}\nalpha_mode = \"blend\"", base)).expect("Could not unwrap config").materials.unwrap()[0];
+        assert_eq!(material.alpha_mode, AlphaMode::Blend.as_u32());
+
+        assert!(Config::from_str(&format!("{}\nalpha_mode = \"cutout\"", base)).is_err());
+        assert!(Config::from_str(&format!("{}\nalpha_mode = \"mask\"\nalpha_cutoff = 1.5", base)).is_err());
+    }
+
+    #[test]
+    fn test_materials_double_sided() {
+        let base = "[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nnear_far = [0.1, 100.0]\nfov = 45.0\n[[materials]]\ncolor = [1.0, 0.0, 0.0]\nattenuation = [0.1, 0.1, 0.1]\nroughness = 0.2\nemission = 0.0\nior = 0.0";
+
+        let material = Config::from_str(base).expect("Could not unwrap config").materials.unwrap()[0];
+        assert!(!material.is_double_sided());
+        let material = Config::from_str(&format!("{}\ndouble_sided = true", base)).expect("Could not unwrap config").materials.unwrap()[0];
+        assert!(material.is_double_sided());
+        let material = Config::from_str(&format!("{}\ndouble_sided = false", base)).expect("Could not unwrap config").materials.unwrap()[0];
+        assert!(!material.is_double_sided());
+
+        assert!(Config::from_str(&format!("{}\ndouble_sided = 1", base)).is_err());
+    }
+
+    #[test]
+    fn test_materials_preset() {
+        let base = "[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nnear_far = [0.1, 100.0]\nfov = 45.0\n[[materials]]";
+
+        let config = Config::from_str(&format!("{}\npreset = \"gold\"", base)).expect("Could not unwrap config");
+        let gold = config.materials.unwrap()[0];
+        assert_eq!(gold.albedo, [1.0, 0.766, 0.336, 0.0]);
+        assert_eq!(gold.roughness, Material::gold().roughness);
+        assert_eq!(gold.metallic, 1.0);
+
+        // Values that are set override the preset
+        let config = Config::from_str(&format!("{}\npreset = \"plastic\"\ncolor = [1.0, 0.0, 0.0]\nroughness = 0.9", base)).expect("Could not unwrap config");
+        let plastic = config.materials.unwrap()[0];
+        assert_eq!(plastic.albedo, [1.0, 0.0, 0.0, 0.0]);
+        assert_eq!(plastic.roughness, 0.9);
+        assert_eq!(plastic.ior, 0.0);
+
+        assert!(Config::from_str(&format!("{}\npreset = \"wood\"", base)).is_err());
+        assert!(Config::from_str(&format!("{}\npreset = 1", base)).is_err());
+    }
+
     #[test]
     fn test_materials_missing_fields() {
         let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nnear_far = [0.1, 100.0]\nfov = 45.0\n[[materials]]\nattenuation = [0.1, 0.1, 0.1]");
@@ -416,6 +1322,41 @@ mod tests {
         assert_eq!(textures[0].roughness_path.as_deref(), Some("path/to/roughness.png"));
     }
 
+    #[test]
+    fn test_textures_color_space() {
+        let base = "[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0\n[[textures]]\ndiffuse = \"diffuse.png\"\nnormal = \"normal.png\"\nroughness = \"roughness.png\"";
+        let config = Config::from_str(base).unwrap();
+        let textures = config.textures.unwrap();
+        assert_eq!(textures[0].slots(), [
+            (Some("diffuse.png"), ColorSpace::Srgb),
+            (Some("roughness.png"), ColorSpace::Linear),
+            (Some("normal.png"), ColorSpace::Linear),
+        ]);
+        assert_eq!(textures[0].texture_ids(3), [3, 4, 5]);
+
+        let config = Config::from_str(&format!("{}\ndiffuse_color_space = \"linear\"", base)).unwrap();
+        assert_eq!(config.textures.unwrap()[0].slots()[0].1, ColorSpace::Linear);
+        // Like other texture errors, the textures are skipped
+        assert!(Config::from_str(&format!("{}\ndiffuse_color_space = \"rec709\"", base)).unwrap().textures.is_none());
+    }
+
+    #[test]
+    fn test_textures_texture_ids_skip_missing() {
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0\n[[textures]]\ndiffuse = \"diffuse.png\"\nnormal = \"normal.png\"").unwrap();
+        assert_eq!(config.textures.unwrap()[0].texture_ids(0), [0, -1, 1]);
+    }
+
+    #[test]
+    fn test_textures_filter() {
+        let base = "[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0\n[[textures]]\ndiffuse = \"diffuse.png\"";
+        let config = Config::from_str(base).unwrap();
+        assert_eq!(config.textures.unwrap()[0].filter, TextureFilter::Nearest);
+
+        let config = Config::from_str(&format!("{}\nfilter = \"lanczos\"", base)).unwrap();
+        assert_eq!(config.textures.unwrap()[0].filter, TextureFilter::Lanczos);
+        assert!(Config::from_str(&format!("{}\nfilter = \"bilinear\"", base)).unwrap().textures.is_none());
+    }
+
     #[test]
     fn test_textures_missing_fields() {
         let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nnear_far = [0.1, 100.0]\nfov = 45.0\n[[textures]]\ndiffuse = \"path/to/diffuse.png\"");
@@ -438,6 +1379,31 @@ mod tests {
         assert_eq!(spheres[0].center, [0.0, 0.0, 0.0, 0.0]);
         assert_eq!(spheres[0].radius, [1.0, 0.0, 0.0, 0.0]);
         assert_eq!(spheres[0].material_texture_id, [0.0, 0.0, 1.0, 2.0]);
+        assert_eq!(spheres[0].emission_texture_id, [-1.0, 0.0, 0.0, 0.0]);
+
+        // The fourth texture id is the emission texture
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nnear_far = [0.1, 100.0]\nfov = 45.0\n[[spheres]]\nposition = [0.0, 0.0, 0.0]\nradius = 1.0\ntexture_id = [0, 1, 2, 3]\nmaterial_id = 0").unwrap();
+        assert_eq!(config.spheres.unwrap()[0].emission_texture_id, [3.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_spheres_uv_orientation() {
+        let base = "[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0\n[[spheres]]\nposition = [0.0, 0.0, 0.0]\nradius = 2.0\ntexture_id = [0, -1, -1]\nmaterial_id = 0";
+        // Without the optional fields the sphere uses the default mapping
+        let sphere = Config::from_str(base).unwrap().spheres.unwrap()[0];
+        assert_eq!(sphere.uv_frame(), glam::Mat3::IDENTITY);
+        assert_eq!(sphere.emission_texture_id[1], 0.0);
+
+        // The pole axis is normalized and the rotation is stored in turns
+        let sphere = Config::from_str(&format!("{}\npole_axis = [0.0, 0.0, 2.0]\nuv_rotation = 90.0", base)).unwrap().spheres.unwrap()[0];
+        assert_eq!(sphere.radius, [2.0, 0.0, 0.0, 1.0]);
+        assert_eq!(sphere.emission_texture_id[1], 0.25);
+        let expected = Sphere::new(cgmath::Point3::new(0.0, 0.0, 0.0), 2.0, 0, [0, -1, -1, -1]).with_uv_orientation([0.0, 0.0, 1.0], 90.0);
+        assert_eq!(sphere.uv(glam::Vec3::X), expected.uv(glam::Vec3::X));
+
+        assert!(Config::from_str(&format!("{}\npole_axis = [0.0, 0.0, 0.0]", base)).is_err());
+        assert!(Config::from_str(&format!("{}\npole_axis = [0.0, 1.0]", base)).is_err());
+        assert!(Config::from_str(&format!("{}\nuv_rotation = \"east\"", base)).is_err());
     }
 
     #[test]
@@ -498,4 +1464,412 @@ mod tests {
         let config = config.expect("Could not unwrap config");
         assert!(config.background.is_none());
     }
+
+    #[test]
+    fn test_background_gradient() {
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nnear_far = [0.1, 100.0]\nfov = 45.0\n[background]
+            \ngradient = { top = [0.2, 0.4, 0.8], horizon = [0.9, 0.9, 1.0], bottom = [0.3, 0.25, 0.2] }\nintensity = 2.0");
+        let config = config.expect("Could not unwrap config");
+        let background = config.background.expect("Missing background");
+        assert_eq!(background.mode(), BackgroundMode::Gradient);
+        assert_eq!(background.gradient(), SkyGradient { top: [0.2, 0.4, 0.8], horizon: [0.9, 0.9, 1.0], bottom: [0.3, 0.25, 0.2] });
+        assert_eq!(background.intensity, 2.0);
+        assert!(!background.has_texture());
+        assert!(config.background_path.is_none());
+    }
+
+    #[test]
+    fn test_background_kind() {
+        // The gradient is kept for the GUI while the HDRI is shown
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nnear_far = [0.1, 100.0]\nfov = 45.0\n[background]\nkind = \"hdri\"
+            \nmaterial_id = 1\nbackground_path = \"path/to/background.exr\"\nintensity = 0.5\ngradient = { top = [0.0, 0.0, 1.0], horizon = [1.0, 1.0, 1.0], bottom = [0.0, 0.0, 0.0] }");
+        let background = config.expect("Could not unwrap config").background.expect("Missing background");
+        assert_eq!(background.mode(), BackgroundMode::Texture);
+
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nnear_far = [0.1, 100.0]\nfov = 45.0\n[background]\nkind = \"color\"\ncolor = [0.1, 0.2, 0.3]\nmaterial_id = 0");
+        let background = config.expect("Could not unwrap config").background.expect("Missing background");
+        assert_eq!(background.mode(), BackgroundMode::Color);
+        assert_eq!(background.color, [0.1, 0.2, 0.3]);
+        assert_eq!(background.intensity, 1.0);
+        assert!(background.has_texture());
+
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nnear_far = [0.1, 100.0]\nfov = 45.0\n[background]\nkind = \"sky\"");
+        assert_eq!(config.expect("Could not unwrap config").background.expect("Missing background").mode(), BackgroundMode::Sky);
+    }
+
+    #[test]
+    fn test_background_invalid_gradient() {
+        let header = "[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nnear_far = [0.1, 100.0]\nfov = 45.0\n[background]\n";
+        assert!(Config::from_str(&format!("{}kind = \"gradient\"", header)).is_err());
+        assert!(Config::from_str(&format!("{}kind = \"clouds\"", header)).is_err());
+        assert!(Config::from_str(&format!("{}gradient = {{ top = [0.0, 0.0, 1.0], horizon = [1.0, 1.0, 1.0] }}", header)).is_err());
+        assert!(Config::from_str(&format!("{}gradient = {{ top = [0.0, 0.0], horizon = [1.0, 1.0, 1.0], bottom = [0.0, 0.0, 0.0] }}", header)).is_err());
+    }
+
+    #[test]
+    fn test_keys_config() {
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nnear_far = [0.1, 100.0]\nfov = 45.0\n[keys]\nforward = \"i\"\ndown = [\"c\", \"Control\"]");
+        let key_bindings = config.expect("Could not unwrap config").key_bindings;
+        assert_eq!(key_bindings.keys(KeyAction::Forward), ["i"]);
+        assert_eq!(key_bindings.keys(KeyAction::Down), ["c", "Control"]);
+        // Actions that aren't in the config keep their default keys
+        assert_eq!(key_bindings.keys(KeyAction::Backward), KeyBindings::default().keys(KeyAction::Backward));
+
+        let header = "[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nnear_far = [0.1, 100.0]\nfov = 45.0\n[keys]\n";
+        assert!(Config::from_str(&format!("{}jump = \"j\"", header)).is_err());
+        assert!(Config::from_str(&format!("{}forward = \"Hyper\"", header)).is_err());
+        assert!(Config::from_str(&format!("{}forward = 1", header)).is_err());
+    }
+
+    #[test]
+    fn test_shader_config_correct() {
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nnear_far = [0.1, 100.0]\nfov = 45.0\n[shader]\nray_max_bounces = 20\nray_aperture = 0.1");
+        assert!(config.is_ok());
+        let config = config.expect("Could not unwrap config");
+        assert_eq!(config.shader_config.ray_max_bounces, 20);
+        assert_eq!(config.shader_config.ray_aperture, 0.1);
+        // Values not in the config keep their defaults
+        assert_eq!(config.shader_config.ray_samples_per_pixel, ShaderConfig::default().ray_samples_per_pixel);
+    }
+
+    #[test]
+    fn test_shader_config_sampling_pattern() {
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nnear_far = [0.1, 100.0]\nfov = 45.0\n[shader]\nsampling_pattern = 2")
+            .expect("Could not unwrap config");
+        assert_eq!(SamplingPattern::from_u32(config.shader_config.sampling_pattern), SamplingPattern::Sobol);
+        assert_eq!(SamplingPattern::from_u32(ShaderConfig::default().sampling_pattern), SamplingPattern::Random);
+        // Unknown values use random offsets like the shader
+        assert_eq!(SamplingPattern::from_u32(7), SamplingPattern::Random);
+    }
+
+    #[test]
+    fn test_shader_config_missing() {
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nnear_far = [0.1, 100.0]\nfov = 45.0");
+        assert!(config.is_ok());
+        let config = config.expect("Could not unwrap config");
+        assert_eq!(config.shader_config.ray_max_bounces, ShaderConfig::default().ray_max_bounces);
+    }
+
+    #[test]
+    fn test_shader_config_unknown_field() {
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nnear_far = [0.1, 100.0]\nfov = 45.0\n[shader]\nray_max_bounce = 20");
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn test_shader_config_wrong_type() {
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nnear_far = [0.1, 100.0]\nfov = 45.0\n[shader]\nray_max_bounces = \"20\"");
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn test_gpu_adapter() {
+        let base = "[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0";
+        assert_eq!(Config::from_str(base).unwrap().gpu_adapter, None);
+        assert_eq!(Config::from_str(&format!("{}\n[gpu]\nadapter = \"nvidia\"", base)).unwrap().gpu_adapter.as_deref(), Some("nvidia"));
+        assert_eq!(Config::from_str(&format!("{}\n[gpu]\nadapter = 1", base)).unwrap().gpu_adapter.as_deref(), Some("1"));
+        assert!(Config::from_str(&format!("{}\n[gpu]\nadapter = -1", base)).is_err());
+    }
+
+    #[test]
+    fn test_gpu_power_preference_and_fallback() {
+        let base = "[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0";
+        let config = Config::from_str(base).unwrap();
+        assert_eq!(config.gpu_power_preference, None);
+        assert!(!config.gpu_force_fallback_adapter);
+
+        let config = Config::from_str(&format!("{}\n[gpu]\npower_preference = \"low_power\"\nforce_fallback_adapter = true", base)).unwrap();
+        assert_eq!(config.gpu_power_preference.as_deref(), Some("low_power"));
+        assert!(config.gpu_force_fallback_adapter);
+
+        assert!(Config::from_str(&format!("{}\n[gpu]\npower_preference = \"fast\"", base)).is_err());
+        assert!(Config::from_str(&format!("{}\n[gpu]\nforce_fallback_adapter = 1", base)).is_err());
+    }
+
+    #[test]
+    fn test_bvh_config() {
+        let base = "[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0";
+        let config = Config::from_str(base).unwrap();
+        assert_eq!(config.bvh_leaf_size, 1);
+        assert_eq!(config.bvh_builder, BvhBuilder::Sah);
+        assert!(!config.bvh_cache);
+
+        let config = Config::from_str(&format!("{}\n[bvh]\nleaf_size = 4\nbuilder = \"locb\"\ncache = true", base)).unwrap();
+        assert_eq!(config.bvh_leaf_size, 4);
+        assert_eq!(config.bvh_builder, BvhBuilder::Locb);
+        assert!(config.bvh_cache);
+
+        assert!(Config::from_str(&format!("{}\n[bvh]\nleaf_size = 0", base)).is_err());
+        assert!(Config::from_str(&format!("{}\n[bvh]\nbuilder = \"spatial\"", base)).is_err());
+        assert!(Config::from_str(&format!("{}\n[bvh]\ncache = \"yes\"", base)).is_err());
+    }
+
+    #[test]
+    fn test_render_texture_array_layers() {
+        let base = "[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0";
+        // The atlas is used by default
+        assert!(!Config::from_str(base).unwrap().render_texture_array_layers);
+        let config = Config::from_str(&format!("{}\n[render]\ntexture_array_layers = true", base)).unwrap();
+        assert!(config.render_texture_array_layers);
+        assert_eq!(config.render_clear_color, DEFAULT_CLEAR_COLOR);
+        assert!(Config::from_str(&format!("{}\n[render]\ntexture_array_layers = \"yes\"", base)).is_err());
+    }
+
+    #[test]
+    fn test_render_tiles() {
+        let base = "[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0";
+        // The whole screen is traced in one dispatch by default
+        let config = Config::from_str(base).unwrap();
+        assert_eq!((config.render_tile_size, config.render_tiles_per_frame), (0, 0));
+        let config = Config::from_str(&format!("{}\n[render]\ntile_size = 256\ntiles_per_frame = 4", base)).unwrap();
+        assert_eq!((config.render_tile_size, config.render_tiles_per_frame), (256, 4));
+        assert!(Config::from_str(&format!("{}\n[render]\ntile_size = -1", base)).is_err());
+        assert!(Config::from_str(&format!("{}\n[render]\ntiles_per_frame = 1.5", base)).is_err());
+    }
+
+    #[test]
+    fn test_config_default() {
+        // The defaults match a config file that only sets the required camera values
+        let config = Config::default();
+        let loaded = Config::from_str("[camera]\nposition = [0.0, 0.0, 0.0]\nrotation = [0.0, 0.0]\nfov = 45.0").unwrap();
+        assert_eq!(config.camera_fov, loaded.camera_fov);
+        assert_eq!(config.camera_near_far, loaded.camera_near_far);
+        assert_eq!(config.render_clear_color, loaded.render_clear_color);
+        assert_eq!(config.bvh_leaf_size, loaded.bvh_leaf_size);
+        assert_eq!(config.version, CONFIG_VERSION);
+
+        let scene = Config::default_scene();
+        assert!(scene.camera_fov > 0.0);
+        assert!(scene.spheres.is_some_and(|spheres| !spheres.is_empty()));
+        assert!(scene.materials.is_some_and(|materials| materials.iter().any(|material| material.emission > 0.0)));
+    }
+
+    #[test]
+    fn test_render_clear_color_missing() {
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0").expect("Could not unwrap config");
+        assert_eq!(config.render_clear_color, [0.1, 0.2, 0.3, 1.0]);
+        assert!(!config.transparent_background());
+        assert_eq!(config.shader_config.transparent_background, 0);
+    }
+
+    #[test]
+    fn test_render_clear_color_transparent() {
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0\n[render]\nclear_color = [0.0, 0.0, 0.0, 0.0]").expect("Could not unwrap config");
+        assert_eq!(config.render_clear_color, [0.0, 0.0, 0.0, 0.0]);
+        assert!(config.transparent_background());
+        assert_eq!(config.shader_config.transparent_background, 1);
+    }
+
+    #[test]
+    fn test_render_clear_color_without_alpha() {
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0\n[render]\nclear_color = [1.0, 1.0, 1.0]").expect("Could not unwrap config");
+        assert_eq!(config.render_clear_color, [1.0, 1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn test_render_clear_color_invalid() {
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0\n[render]\nclear_color = [1.0, 1.0]");
+        assert!(config.is_err());
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0\n[render]\nclear_color = [1.0, 1.0, 1.0, 2.0]");
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn test_camera_path_correct() {
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0\n[camera.path]\nloop = true\ninterpolation = \"linear\"
+            \nkeyframes = [{ time = 0.0, position = [0.0, 1.0, 2.0], rotation = [0.0, 0.0] }, { time = 4.0, position = [2.0, 1.0, 0.0], rotation = [90.0, -10.0] }]");
+        assert!(config.is_ok());
+        let config = config.expect("Could not unwrap config");
+        let path = config.camera_path.expect("Missing camera path");
+        assert_eq!(path.keyframes().len(), 2);
+        assert!(path.looping);
+        assert_eq!(path.interpolation, PathInterpolation::Linear);
+        assert_eq!(path.keyframes()[1], CameraKeyframe::new(4.0, [2.0, 1.0, 0.0], 90.0, -10.0));
+    }
+
+    #[test]
+    fn test_camera_path_missing() {
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0");
+        assert!(config.expect("Could not unwrap config").camera_path.is_none());
+    }
+
+    #[test]
+    fn test_camera_path_missing_fields() {
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0\n[camera.path]\nkeyframes = [{ time = 0.0, position = [0.0, 1.0, 2.0] }]");
+        assert!(config.is_err());
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0\n[camera.path]\nkeyframes = []");
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn test_spheres_zero_radius() {
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nnear_far = [0.1, 100.0]\nfov = 45.0\n[[spheres]]\nposition = [0.0, 0.0, 0.0]\nradius = 0.0\ntexture_id = [0, 1, 2]\nmaterial_id = 0");
+        assert_eq!(config.unwrap_err(), ConfigError::InvalidSphereRadius { sphere: 0, radius: 0.0 });
+    }
+
+    #[test]
+    fn test_spheres_negative_radius() {
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nnear_far = [0.1, 100.0]\nfov = 45.0\n[[spheres]]\nposition = [0.0, 0.0, 0.0]\nradius = 1.0\ntexture_id = [-1, -1, -1]\nmaterial_id = 0
+            \n[[spheres]]\nposition = [0.0, 0.0, 0.0]\nradius = -2.0\ntexture_id = [-1, -1, -1]\nmaterial_id = 0");
+        let error = config.unwrap_err();
+        assert_eq!(error, ConfigError::InvalidSphereRadius { sphere: 1, radius: -2.0 });
+        assert_eq!(error.to_string(), "Sphere 1 has an invalid radius of -2, the radius has to be greater than 0");
+    }
+
+    #[test]
+    fn test_spheres_invalid_ids() {
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nnear_far = [0.1, 100.0]\nfov = 45.0\n[[spheres]]\nposition = [0.0, 0.0, 0.0]\nradius = 1.0\ntexture_id = [-1, -1, -1]\nmaterial_id = -2");
+        assert!(matches!(config.unwrap_err(), ConfigError::InvalidId { id: -2, .. }));
+
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nnear_far = [0.1, 100.0]\nfov = 45.0\n[[spheres]]\nposition = [0.0, 0.0, 0.0]\nradius = 1.0\ntexture_id = [-1, -5, -1]\nmaterial_id = 0");
+        assert!(matches!(config.unwrap_err(), ConfigError::InvalidId { id: -5, .. }));
+
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nnear_far = [0.1, 100.0]\nfov = 45.0\n[[spheres]]\nposition = [0.0, 0.0, 0.0]\nradius = 1.0\ntexture_id = [-1, -1]\nmaterial_id = 0");
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn test_background_invalid_material_id() {
+        let config = Config::from_str("[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nnear_far = [0.1, 100.0]\nfov = 45.0\n[background]\nmaterial_id = -3\nintensity = 0.5");
+        assert!(matches!(config.unwrap_err(), ConfigError::InvalidId { id: -3, .. }));
+    }
+
+    #[test]
+    fn test_svg_model_paths() {
+        let camera = "[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nnear_far = [0.1, 100.0]\nfov = 45.0\n";
+        let config = Config::from_str(&format!("{}[3d_model_paths]\nsvg_path = \"logo.svg\"\nsvg_z = -2.5\nsvg_depth = 1\nsvg_material_id = 3", camera)).unwrap();
+        assert_eq!(config.model_paths.svg_path.as_deref(), Some("logo.svg"));
+        assert_eq!(config.model_paths.svg_z, -2.5);
+        assert_eq!(config.model_paths.svg_depth, 1.0);
+        assert_eq!(config.model_paths.svg_material_id, Some(3));
+
+        assert!(Config::from_str(&format!("{}[3d_model_paths]\nsvg_path = \"logo.svg\"\nsvg_depth = -1.0", camera)).is_err());
+        assert!(Config::from_str(&format!("{}[3d_model_paths]\nsvg_path = \"logo.svg\"\nsvg_z = \"front\"", camera)).is_err());
+    }
+
+    #[test]
+    fn test_config_version() {
+        let camera = "[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nnear_far = [0.1, 100.0]\nfov = 45.0\n";
+        // Without a version the config is read as version 0 and migrated
+        let config = Config::from_str(camera).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        let config = Config::from_str(&format!("version = {}\n{}", CONFIG_VERSION, camera)).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        // One version newer only warns
+        assert!(Config::from_str(&format!("version = {}\n{}", CONFIG_VERSION + 1, camera)).is_ok());
+
+        let config = Config::from_str(&format!("version = 1000\n{}", camera));
+        assert_eq!(config.unwrap_err(), ConfigError::UnsupportedVersion { version: 1000, supported: CONFIG_VERSION });
+        assert!(matches!(Config::from_str(&format!("version = -1\n{}", camera)), Err(ConfigError::Invalid(_))));
+        assert!(matches!(Config::from_str(&format!("version = \"1\"\n{}", camera)), Err(ConfigError::Invalid(_))));
+    }
+
+    #[test]
+    fn test_config_migrate_dielectric_attenuation() {
+        let scene = "[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0
+            \n[[materials]]\ncolor = [1.0, 1.0, 1.0]\nattenuation = [1, 0.5, 0.0]\nroughness = 0.0\nemission = 0.0\nior = 1.5
+            \n[[materials]]\ncolor = [1.0, 1.0, 1.0]\nattenuation = [0.5, 0.5, 0.5]\nroughness = 0.5\nemission = 0.0\nior = 0.0";
+        // Version 1 weights the bounces of dielectrics with the attenuation, opaque materials keep it
+        let config = Config::from_str(&format!("version = 1\n{}", scene)).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        let materials = config.materials.expect("Missing materials");
+        assert_eq!(materials[0].attenuation[0], 0.0);
+        assert!((materials[0].attenuation[1] - 2f32.ln()).abs() < 1e-6);
+        assert!((materials[0].attenuation[2] - 1000f32.ln()).abs() < 1e-4);
+        assert_eq!(materials[1].attenuation[..3], [0.5, 0.5, 0.5]);
+
+        // The current version is already the absorption
+        let config = Config::from_str(&format!("version = {}\n{}", CONFIG_VERSION, scene)).unwrap();
+        assert_eq!(config.materials.expect("Missing materials")[0].attenuation[..3], [1.0, 0.5, 0.0]);
+    }
+
+    // Writes the files into a new directory in the temp dir and returns its path
+    fn write_config_files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("raytracer_test_{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        for (file, content) in files {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_include_materials() {
+        let dir = write_config_files("include_materials", &[
+            ("library/materials.toml", "[[materials]]\npreset = \"gold\"\n[[materials]]\npreset = \"mirror\"\n[shader]\nray_max_bounces = 3\nray_aperture = 0.2"),
+            ("scene.toml", "include = [\"library/materials.toml\"]\n[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0\n[[materials]]
+                \ncolor = [1.0, 0.0, 0.0]\nattenuation = [0.1, 0.1, 0.1]\nroughness = 0.2\nemission = 0.0\nior = 0.0\n[shader]\nray_max_bounces = 20"),
+        ]);
+        let config = Config::new(dir.join("scene.toml").to_str().unwrap()).expect("Could not unwrap config");
+        let materials = config.materials.expect("Missing materials");
+        // The included materials come first, so their ids stay the same in every scene
+        assert_eq!(materials.len(), 3);
+        assert_eq!(materials[0].albedo, Material::gold().albedo);
+        assert_eq!(materials[1].roughness, Material::mirror().roughness);
+        assert_eq!(materials[2].albedo, [1.0, 0.0, 0.0, 0.0]);
+        // Values of the including file override the included ones, the others are kept
+        assert_eq!(config.shader_config.ray_max_bounces, 20);
+        assert_eq!(config.shader_config.ray_aperture, 0.2);
+
+        // Without a base path the include is relative to the working directory
+        let scene = fs::read_to_string(dir.join("scene.toml")).unwrap();
+        assert!(Config::from_str(&scene).is_err());
+        assert_eq!(Config::from_str_in(&scene, &dir).unwrap().materials.unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_include_nested_relative_to_included_file() {
+        let dir = write_config_files("include_nested", &[
+            ("library/base.toml", "[[materials]]\npreset = \"glass\""),
+            ("library/materials.toml", "include = [\"base.toml\"]\n[[materials]]\npreset = \"gold\""),
+            ("scene.toml", "include = [\"library/materials.toml\"]\n[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0"),
+        ]);
+        let config = Config::new(dir.join("scene.toml").to_str().unwrap()).expect("Could not unwrap config");
+        let materials = config.materials.expect("Missing materials");
+        assert_eq!(materials.len(), 2);
+        assert_eq!(materials[0].ior, Material::glass().ior);
+        assert_eq!(materials[1].albedo, Material::gold().albedo);
+    }
+
+    #[test]
+    fn test_include_migrated_per_file() {
+        let dir = write_config_files("include_versions", &[
+            ("materials.toml", &format!("version = {}\n[[materials]]\npreset = \"gold\"\n[[materials]]\npreset = \"glass\"\nattenuation = [0.5, 0.5, 0.5]", CONFIG_VERSION)),
+            ("too_new.toml", &format!("version = {}\n[[materials]]\npreset = \"gold\"", CONFIG_VERSION + 2)),
+            ("scene.toml", "include = [\"materials.toml\"]\n[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0\n[[materials]]\npreset = \"mirror\"\n[[materials]]\npreset = \"glass\"\nattenuation = [0.5, 0.5, 0.5]"),
+            ("scene_too_new.toml", "include = [\"too_new.toml\"]\n[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0"),
+        ]);
+        // The unversioned scene is migrated on its own, the version of the included file doesn't apply to it
+        let config = Config::new(dir.join("scene.toml").to_str().unwrap()).expect("Could not unwrap config");
+        assert_eq!(config.version, CONFIG_VERSION);
+        let materials = config.materials.expect("Missing materials");
+        assert_eq!(materials.len(), 4);
+        assert_eq!(materials[0].albedo, Material::gold().albedo);
+        // The current included file keeps its absorption, the glass of the unversioned scene is migrated
+        assert_eq!(materials[1].attenuation[..3], [0.5, 0.5, 0.5]);
+        assert_eq!(materials[2].roughness, Material::mirror().roughness);
+        assert!(materials[3].attenuation[..3].iter().all(|value| (value - 2f32.ln()).abs() < 1e-6));
+
+        let error = Config::new(dir.join("scene_too_new.toml").to_str().unwrap()).unwrap_err();
+        assert!(matches!(error, ConfigError::UnsupportedVersion { .. }), "{}", error);
+    }
+
+    #[test]
+    fn test_include_errors() {
+        let dir = write_config_files("include_errors", &[
+            ("a.toml", "include = [\"b.toml\"]"),
+            ("b.toml", "include = [\"a.toml\"]"),
+            ("self.toml", "include = [\"self.toml\"]\n[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0"),
+            ("cycle.toml", "include = [\"a.toml\"]\n[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0"),
+            ("missing.toml", "include = [\"does_not_exist.toml\"]\n[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0"),
+            ("wrong_type.toml", "include = \"a.toml\"\n[camera]\nposition = [0.0, 1.0, 2.0]\nrotation = [0.0, 0.0]\nfov = 45.0"),
+        ]);
+        for file in ["self.toml", "cycle.toml", "missing.toml", "wrong_type.toml"] {
+            assert!(Config::new(dir.join(file).to_str().unwrap()).is_err(), "{} should not load", file);
+        }
+        let error = Config::new(dir.join("cycle.toml").to_str().unwrap()).unwrap_err();
+        assert!(error.to_string().contains("cycle"), "{}", error);
+    }
 }
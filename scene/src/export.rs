@@ -0,0 +1,117 @@
+//! Writes the scene geometry to files for other tools.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::structs::{Material, Triangle};
+
+/// Writes the triangles to a Wavefront OBJ file and their materials to a `.mtl` file next to it.
+///
+/// The triangles are written in world space like they are rendered, with their vertex normals and texture coordinates.
+/// Every triangle gets its own three vertices, they aren't merged with the ones of the neighbouring triangles.
+/// The materials are named `material_<id>` after their index in `materials` and are converted to the closest
+/// MTL values, see `write_mtl`. Textures aren't exported.
+///
+/// # Errors
+///
+/// Returns an error if one of the files can't be written.
+pub fn export_obj(path: impl AsRef<Path>, triangles: &[Triangle], materials: &[Material]) -> io::Result<()> {
+    let path = path.as_ref();
+    let mtl_path = path.with_extension("mtl");
+
+    let mut obj = BufWriter::new(File::create(path)?);
+    writeln!(obj, "# {} triangles", triangles.len())?;
+    if let Some(mtl_name) = mtl_path.file_name() {
+        writeln!(obj, "mtllib {}", mtl_name.to_string_lossy())?;
+    }
+    for triangle in triangles {
+        for [x, y, z] in triangle.points {
+            writeln!(obj, "v {} {} {}", x, y, z)?;
+        }
+        for [u, v] in triangle.tex_coords {
+            writeln!(obj, "vt {} {}", u, v)?;
+        }
+        for [x, y, z] in triangle.vertex_normals {
+            writeln!(obj, "vn {} {} {}", x, y, z)?;
+        }
+    }
+    // The vertices, texture coordinates and normals of triangle i are the elements 3i+1 to 3i+3
+    let mut material_id = None;
+    for (index, triangle) in triangles.iter().enumerate() {
+        if material_id != Some(triangle.material_id) {
+            material_id = Some(triangle.material_id);
+            writeln!(obj, "usemtl material_{}", triangle.material_id)?;
+        }
+        let first = index * 3 + 1;
+        writeln!(obj, "f {0}/{0}/{0} {1}/{1}/{1} {2}/{2}/{2}", first, first + 1, first + 2)?;
+    }
+    obj.flush()?;
+
+    let mut mtl = BufWriter::new(File::create(&mtl_path)?);
+    write_mtl(&mut mtl, materials)?;
+    mtl.flush()
+}
+
+/// Writes the materials in the MTL format.
+///
+/// The albedo becomes the diffuse color `Kd`, the roughness the specular exponent `Ns` of a Blinn-Phong lobe
+/// of about the same width and the emission the emissive color `Ke`. Dielectrics also get their index of refraction `Ni`.
+fn write_mtl(writer: &mut impl Write, materials: &[Material]) -> io::Result<()> {
+    for (id, material) in materials.iter().enumerate() {
+        let [r, g, b, _] = material.albedo;
+        writeln!(writer, "newmtl material_{}", id)?;
+        writeln!(writer, "Kd {} {} {}", r, g, b)?;
+        writeln!(writer, "Ns {}", shininess(material.roughness))?;
+        writeln!(writer, "Ke {} {} {}", r * material.emission, g * material.emission, b * material.emission)?;
+        if material.ior > 0.0 {
+            writeln!(writer, "Ni {}", material.ior)?;
+        }
+        writeln!(writer, "illum 2")?;
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+// Blinn-Phong exponent with about the width of a GGX lobe of the roughness, limited to the range of MTL viewers
+fn shininess(roughness: f32) -> f32 {
+    let alpha = (roughness * roughness).max(0.001);
+    (2.0 / (alpha * alpha) - 2.0).clamp(0.0, 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::load_obj;
+
+    #[test]
+    fn test_export_obj_round_trip() {
+        let (mut triangles, _) = load_obj("../scene/src/test_files/cube_triangulated.obj".to_string(), 0).unwrap();
+        triangles[0].material_id = 1;
+        let materials = [Material::new([0.8, 0.2, 0.1], [0.5; 3], 1.0, 0.0, 0.0), Material::new([1.0; 3], [0.0; 3], 0.0, 2.0, 1.5)];
+        let path = std::env::temp_dir().join("raytracer_test_export_obj.obj");
+        export_obj(&path, &triangles, &materials).unwrap();
+
+        let (reloaded, _) = load_obj(path.to_string_lossy().to_string(), 0).unwrap();
+        assert_eq!(reloaded.len(), triangles.len());
+        for (reloaded, triangle) in reloaded.iter().zip(&triangles) {
+            assert_eq!(reloaded.points, triangle.points);
+            assert_eq!(reloaded.vertex_normals, triangle.vertex_normals);
+            assert_eq!(reloaded.tex_coords, triangle.tex_coords);
+        }
+
+        let obj = std::fs::read_to_string(&path).unwrap();
+        assert!(obj.contains("mtllib raytracer_test_export_obj.mtl"));
+        assert_eq!(obj.matches("usemtl").count(), 2);
+        let mtl = std::fs::read_to_string(path.with_extension("mtl")).unwrap();
+        assert!(mtl.contains("newmtl material_0\nKd 0.8 0.2 0.1\nNs 0\nKe 0 0 0\nillum 2"));
+        assert!(mtl.contains("newmtl material_1\nKd 1 1 1\nNs 1000\nKe 2 2 2\nNi 1.5"));
+    }
+
+    #[test]
+    fn test_shininess() {
+        assert_eq!(shininess(1.0), 0.0);
+        assert_eq!(shininess(0.0), 1000.0);
+        assert!(shininess(0.3) > shininess(0.6));
+    }
+}
@@ -0,0 +1,203 @@
+//! Keyboard controls of the application, remappable in the `[keys]` section of the config.
+
+use std::collections::HashMap;
+
+use winit::keyboard::Key;
+
+/// Something the user can do with a key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum KeyAction {
+    Forward,
+    Backward,
+    Left,
+    Right,
+    Up,
+    Down,
+    RollLeft,
+    RollRight,
+    /// Reduces the bounces and samples to the minimum, e.g. if the GPU can't keep up.
+    SafeMode,
+    /// Starts and stops the camera path playback.
+    CameraPath,
+    ExposureUp,
+    ExposureDown,
+    /// Cycles through the debug views.
+    DebugView,
+    /// Releases the captured cursor, or closes the window if the cursor is free.
+    Exit,
+}
+
+impl KeyAction {
+    /// All actions in the order they are listed in the GUI.
+    pub const ALL: [KeyAction; 14] = [
+        KeyAction::Forward, KeyAction::Backward, KeyAction::Left, KeyAction::Right, KeyAction::Up, KeyAction::Down,
+        KeyAction::RollLeft, KeyAction::RollRight, KeyAction::SafeMode, KeyAction::CameraPath,
+        KeyAction::ExposureUp, KeyAction::ExposureDown, KeyAction::DebugView, KeyAction::Exit,
+    ];
+
+    /// Name of the action in the `[keys]` section of the config.
+    pub fn config_name(self) -> &'static str {
+        match self {
+            KeyAction::Forward => "forward",
+            KeyAction::Backward => "backward",
+            KeyAction::Left => "left",
+            KeyAction::Right => "right",
+            KeyAction::Up => "up",
+            KeyAction::Down => "down",
+            KeyAction::RollLeft => "roll_left",
+            KeyAction::RollRight => "roll_right",
+            KeyAction::SafeMode => "safe_mode",
+            KeyAction::CameraPath => "camera_path",
+            KeyAction::ExposureUp => "exposure_up",
+            KeyAction::ExposureDown => "exposure_down",
+            KeyAction::DebugView => "debug_view",
+            KeyAction::Exit => "exit",
+        }
+    }
+
+    pub fn from_config_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.config_name() == name)
+    }
+
+    /// Description shown in the info window.
+    pub fn description(self) -> &'static str {
+        match self {
+            KeyAction::Forward => "Move forward",
+            KeyAction::Backward => "Move backward",
+            KeyAction::Left => "Move left",
+            KeyAction::Right => "Move right",
+            KeyAction::Up => "Move up",
+            KeyAction::Down => "Move down",
+            KeyAction::RollLeft => "Roll left (if lock_roll = false)",
+            KeyAction::RollRight => "Roll right (if lock_roll = false)",
+            KeyAction::SafeMode => "Reduce shader settings to min",
+            KeyAction::CameraPath => "Camera path playback",
+            KeyAction::ExposureUp => "Exposure up",
+            KeyAction::ExposureDown => "Exposure down",
+            KeyAction::DebugView => "Next debug view",
+            KeyAction::Exit => "Release mouse / close program",
+        }
+    }
+}
+
+/// Names of the non-character keys that can be bound, as in `winit::keyboard::NamedKey`.
+///
+/// `Shift`, `Control` and `Alt` are the keys on both sides of the keyboard.
+pub const NAMED_KEYS: [&str; 28] = [
+    "Space", "Shift", "Control", "Alt", "Tab", "Escape", "Enter", "Backspace", "Insert", "Delete", "Home", "End",
+    "PageUp", "PageDown", "ArrowUp", "ArrowDown", "ArrowLeft", "ArrowRight",
+    "F1", "F2", "F3", "F4", "F5", "F6", "F7", "F8", "F9", "F10",
+];
+
+/// Maps every `KeyAction` to the keys that trigger it.
+///
+/// Keys are single characters, matched case-insensitively, or one of `NAMED_KEYS`.
+/// The defaults are WASD and the arrow keys for moving, Space and Shift for up and down, Q/E for roll,
+/// X for the safe mode, P for the camera path, +/- for the exposure, Tab for the debug views and Escape to exit.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyBindings {
+    keys: HashMap<KeyAction, Vec<String>>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let keys = KeyAction::ALL.into_iter().map(|action| {
+            let keys: &[&str] = match action {
+                KeyAction::Forward => &["w", "ArrowUp"],
+                KeyAction::Backward => &["s", "ArrowDown"],
+                KeyAction::Left => &["a", "ArrowLeft"],
+                KeyAction::Right => &["d", "ArrowRight"],
+                KeyAction::Up => &["Space"],
+                KeyAction::Down => &["Shift"],
+                KeyAction::RollLeft => &["q"],
+                KeyAction::RollRight => &["e"],
+                KeyAction::SafeMode => &["x"],
+                KeyAction::CameraPath => &["p"],
+                // "=" is "+" without shift on most layouts
+                KeyAction::ExposureUp => &["+", "="],
+                KeyAction::ExposureDown => &["-"],
+                KeyAction::DebugView => &["Tab"],
+                KeyAction::Exit => &["Escape"],
+            };
+            (action, keys.iter().map(|key| key.to_string()).collect())
+        });
+        Self { keys: keys.collect() }
+    }
+}
+
+impl KeyBindings {
+    /// Replaces the keys of an action.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first key name that is neither a single character nor one of `NAMED_KEYS`.
+    pub fn set(&mut self, action: KeyAction, keys: &[&str]) -> Result<(), String> {
+        let keys = keys.iter().map(|key| normalize_key_name(key).ok_or_else(|| key.to_string())).collect::<Result<Vec<String>, String>>()?;
+        self.keys.insert(action, keys);
+        Ok(())
+    }
+
+    /// Names of the keys bound to the action.
+    pub fn keys(&self, action: KeyAction) -> &[String] {
+        self.keys.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// The action of a pressed key, the first in the order of `KeyAction::ALL` if the key is bound more than once.
+    pub fn action(&self, key: &Key) -> Option<KeyAction> {
+        let name = key_name(key)?;
+        KeyAction::ALL.into_iter().find(|&action| self.keys(action).contains(&name))
+    }
+
+    /// Returns true if the key is bound to the action.
+    pub fn matches(&self, action: KeyAction, key: &Key) -> bool {
+        key_name(key).is_some_and(|name| self.keys(action).contains(&name))
+    }
+}
+
+// Name of a key like in the bindings, characters are lowercase so Shift doesn't change them
+fn key_name(key: &Key) -> Option<String> {
+    match key {
+        Key::Character(c) => Some(c.to_lowercase()),
+        Key::Named(named) => Some(format!("{:?}", named)),
+        _ => None,
+    }
+}
+
+fn normalize_key_name(name: &str) -> Option<String> {
+    if name.chars().count() == 1 {
+        Some(name.to_lowercase())
+    } else {
+        NAMED_KEYS.iter().find(|named| named.eq_ignore_ascii_case(name)).map(|named| named.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::keyboard::NamedKey;
+
+    #[test]
+    fn test_key_bindings_default() {
+        let bindings = KeyBindings::default();
+        assert_eq!(bindings.action(&Key::Character("W".into())), Some(KeyAction::Forward));
+        assert_eq!(bindings.action(&Key::Named(NamedKey::ArrowUp)), Some(KeyAction::Forward));
+        assert_eq!(bindings.action(&Key::Named(NamedKey::Shift)), Some(KeyAction::Down));
+        assert_eq!(bindings.action(&Key::Character("=".into())), Some(KeyAction::ExposureUp));
+        assert_eq!(bindings.action(&Key::Character("k".into())), None);
+        assert!(bindings.matches(KeyAction::Exit, &Key::Named(NamedKey::Escape)));
+        assert!(KeyAction::ALL.iter().all(|&action| !bindings.keys(action).is_empty()));
+        assert!(KeyAction::ALL.iter().all(|&action| KeyAction::from_config_name(action.config_name()) == Some(action)));
+    }
+
+    #[test]
+    fn test_key_bindings_set() {
+        let mut bindings = KeyBindings::default();
+        bindings.set(KeyAction::Up, &["E", "pageup"]).unwrap();
+        assert_eq!(bindings.keys(KeyAction::Up), ["e", "PageUp"]);
+        assert!(bindings.matches(KeyAction::Up, &Key::Named(NamedKey::PageUp)));
+        // Roll right keeps its key, but comes after up
+        assert_eq!(bindings.action(&Key::Character("e".into())), Some(KeyAction::Up));
+        assert_eq!(bindings.set(KeyAction::Down, &["LeftShift"]), Err("LeftShift".to_string()));
+        assert_eq!(bindings.keys(KeyAction::Down), ["Shift"]);
+    }
+}
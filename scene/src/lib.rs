@@ -4,9 +4,17 @@
 //!
 //! ## Modules
 //!
+//! - `atlas`: Packs the textures into atlas pages and describes where the shader finds each texture.
 //! - `camera`: Contains the `Camera` struct and related functions for controlling the camera.
+//! - `camera_path`: Contains the `CameraPath` struct for animated camera flights.
 //! - `config`: Loads the configuration file and creates the scene outline.
+//! - `export`: Writes the scene geometry and materials to an OBJ and MTL file.
+//! - `session`: Contains the `SessionState` that restores the camera and the settings of the last run.
+//! - `scene_builder`: Contains the `SceneBuilder` for building a scene in code instead of a config file.
 //! - `models`: Contains the loading functions for different model types and the HDRI images.
+//! - `presets`: Contains scenes built in code, like the Cornell box.
+//! - `raycast`: Contains the CPU ray intersection used to pick points in the scene.
+//! - `sampling`: Contains the sub-pixel sampling patterns of the camera rays (Halton, Sobol and blue noise) and the cosine weighted hemisphere sampling of the shader.
 //! - `structs`: Contains the structs for the scene objects like `Material`, `Sphere`, `Triangle`, etc.
 //! - `texture`: Contains related functions for loading and managing textures on the gpu.
 //!
@@ -27,10 +35,28 @@ mod structs;
 mod models;
 mod texture;
 mod camera;
+mod camera_path;
+mod scene_builder;
+mod raycast;
+mod atlas;
+mod sampling;
+mod export;
+mod key_bindings;
+mod session;
+pub mod presets;
 
-pub use config::{Config, Textureset};
-pub use structs::{ShaderConfig, CameraUniform, Background, Material, Sphere, Triangle,
-            BvhUniform, TriangleUniform};
-pub use camera::{Camera, CameraController, Projection};
-pub use texture::{create_texture, load_textures_from_image, scale_texture};
-pub use models::{load_hdr, load_gltf, load_obj};
\ No newline at end of file
+pub use config::{BvhBuilder, Config, ConfigError, ModelPaths, Textureset, CONFIG_VERSION};
+pub use structs::{roughness_to_alpha, ShaderConfig, DebugView, CameraUniform, Background, BackgroundMode, SkyGradient, Material, Sphere, Triangle,
+            BvhUniform, BvhPrimitive, BVH_PRIM_SPHERE, BVH_PRIM_TRIANGLE, TriangleUniform, WrapMode, AlphaMode};
+pub use camera::{Camera, CameraAnimation, CameraController, Projection, ScrollMode};
+pub use camera_path::{CameraKeyframe, CameraPath, PathInterpolation};
+pub use scene_builder::{SceneBuilder, SceneSource};
+pub use texture::{to_linear, ColorSpace, TextureFilter, create_texture, load_textures_from_image, load_texture_into_atlas, missing_texture, scale_texture, TEXTURE_FORMAT,
+            checkerboard, uv_grid, solid, procedural_texture, PROCEDURAL_PREFIX};
+pub use models::{load_hdr, load_gltf, load_obj, load_svg, load_texture};
+pub use raycast::{closest_hit, raycast, HitPrimitive, RayHit};
+pub use export::export_obj;
+pub use key_bindings::{KeyAction, KeyBindings, NAMED_KEYS};
+pub use session::SessionState;
+pub use atlas::{pack_atlas, AtlasLayout, AtlasRect, TextureRegion, ATLAS_MAX_PAGE_SIZE, ATLAS_PADDING};
+pub use sampling::{blue_noise, blue_noise_image, precomputed_blue_noise, cosine_hemisphere_pdf, halton, sample_cosine_hemisphere, sample_offsets, sobol_2d, JitterMode, SamplingPattern, BLUE_NOISE_SIZE, SAMPLE_OFFSET_COUNT};
\ No newline at end of file
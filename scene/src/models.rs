@@ -1,7 +1,8 @@
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use image::{DynamicImage, ImageBuffer, Rgba};
-use crate::structs::{Triangle, Material};
+use crate::structs::{AlphaMode, Triangle, Material};
+use crate::texture::{to_linear, ColorSpace};
 use core::ops::Deref;
 use image::Pixel;
 use exr;
@@ -42,16 +43,6 @@ pub fn load_obj(file_path: String, obj_material_id: i32) -> Result<(Vec<Triangle
                     let tex_coord = [values[0], values[1]];
                     texture_coords.push(tex_coord);
                 }
-                // Parse texture coordinates
-                let values: Vec<f32> = line[3..]
-                    .split_whitespace()
-                    .map(|x| x.parse::<f32>())
-                    .collect::<Result<_, _>>()?;
-
-                if values.len() >= 2 {
-                    let tex_coord = [values[0], values[1]];
-                    texture_coords.push(tex_coord);
-                }
             }
             Some("vn") => {
                 // Parse normals
@@ -66,24 +57,32 @@ pub fn load_obj(file_path: String, obj_material_id: i32) -> Result<(Vec<Triangle
                 }
             }
             Some("f") => {
-                // Parse face indices
-                let indices: Vec<(usize, usize, usize)> = line[2..]
+                // Parse face indices, a face vertex is `v`, `v/vt`, `v//vn` or `v/vt/vn`
+                let indices: Vec<FaceVertex> = line[2..]
                     .split_whitespace()
-                    .map(|x| {
-                        let indices: Vec<usize> = x
-                            .split('/')
-                            .map(|y| y.parse::<usize>())
-                            .collect::<Result<_, _>>()
-                            .unwrap();
-                        (indices[0], indices[1], indices[2])
-                    })
-                    .collect();
-            
+                    .map(parse_face_vertex)
+                    .collect::<Result<_, _>>()?;
+
                 if indices.len() == 3 {
-                    let v1_index = indices[0].0 - 1;
-                    let v2_index = indices[1].0 - 1;
-                    let v3_index = indices[2].0 - 1;
-                    let normal_index = indices[0].2 - 1;
+                    let mut points = [[0.0; 3]; 3];
+                    for (point, index) in points.iter_mut().zip(indices.iter()) {
+                        *point = obj_element(&vertices, index.0, "vertex")?;
+                    }
+
+                    // Missing texture coordinates are set to 0, missing normals to the face normal
+                    let edge1 = glam::Vec3::from(points[1]) - glam::Vec3::from(points[0]);
+                    let edge2 = glam::Vec3::from(points[2]) - glam::Vec3::from(points[0]);
+                    let face_normal: [f32; 3] = edge1.cross(edge2).normalize_or_zero().into();
+                    let mut tex_coords = [[0.0; 2]; 3];
+                    let mut vertex_normals = [face_normal; 3];
+                    for (i, &(_, tex_coord_index, normal_index)) in indices.iter().enumerate() {
+                        if let Some(tex_coord_index) = tex_coord_index {
+                            tex_coords[i] = obj_element(&texture_coords, tex_coord_index, "texture coordinate")?;
+                        }
+                        if let Some(normal_index) = normal_index {
+                            vertex_normals[i] = obj_element(&normals, normal_index, "normal")?;
+                        }
+                    }
 
                     // let mut rng = rand::thread_rng();
                     // let r: f32 = rng.gen_range(0.0..1.0);
@@ -91,20 +90,12 @@ pub fn load_obj(file_path: String, obj_material_id: i32) -> Result<(Vec<Triangle
                     // let b: f32 = rng.gen_range(0.0..1.0);
             
                     let triangle = Triangle::new(
-                        [
-                            vertices[v1_index],
-                            vertices[v2_index],
-                            vertices[v3_index],
-                        ],
-                        normals[normal_index],
+                        points,
+                        vertex_normals[0],
                         obj_material_id,
-                        [-1.0, -1.0, -1.0],
-                        [
-                            texture_coords[indices[0].1 - 1],
-                            texture_coords[indices[1].1 - 1],
-                            texture_coords[indices[2].1 - 1],
-                        ],
-                    );
+                        [-1.0, -1.0, -1.0, -1.0],
+                        tex_coords,
+                    ).with_vertex_normals(vertex_normals);
                     faces.push(triangle);
                 } else {
                     return Err("Invalid face indices count (Tip: Try triangulating the mesh)".into());
@@ -118,8 +109,19 @@ pub fn load_obj(file_path: String, obj_material_id: i32) -> Result<(Vec<Triangle
     Ok((faces,Vec::new()))
 }
 
+/// Loads the triangles, materials and textures of a `.gltf` or `.glb` file.
+///
+/// # Errors
+///
+/// Returns an error if the file has another extension, or if it can't be read or parsed. The message includes the path.
 pub fn load_gltf(path: String, material_count: i32, texture_count: i32) -> Result<(Vec<Triangle>, Vec<Material>, Vec<DynamicImage>), Box<dyn std::error::Error>> {
-    let scenes = easy_gltf::load(path).expect("Failed to load glTF");
+    let extension = std::path::Path::new(&path).extension().and_then(|extension| extension.to_str()).map(str::to_lowercase);
+    match extension.as_deref() {
+        Some("gltf") | Some("glb") => {}
+        _ => return Err(format!("Unsupported file format for glTF model {}. Supported formats are: .gltf, .glb", path).into()),
+    }
+    let scenes = easy_gltf::load(&path).map_err(|e| format!("Failed to load glTF file {}: {}", path, e))?;
+    let material_properties = load_gltf_material_properties(&path)?;
     let mut converted_triangles = Vec::new();
     let mut converted_materials = Vec::new();
     let mut material_index = material_count;
@@ -137,6 +139,7 @@ pub fn load_gltf(path: String, material_count: i32, texture_count: i32) -> Resul
 
         for model in scene.models {
             let material = model.material();
+            let has_tangents = model.has_tangents();
 
             match &material.pbr.base_color_texture {
                 Some(texture) => {
@@ -148,8 +151,10 @@ pub fn load_gltf(path: String, material_count: i32, texture_count: i32) -> Resul
             }
 
             // Convert material to own format
+            let properties = material.index().and_then(|index| material_properties.get(index)).copied().unwrap_or_default();
             let base_color_factor = material.pbr.base_color_factor;
             let roughness_factor = material.pbr.roughness_factor;
+            let metallic_factor = material.pbr.metallic_factor;
 
             converted_materials.push(Material::new(
                 [base_color_factor[0], base_color_factor[1], base_color_factor[2]],
@@ -157,102 +162,30 @@ pub fn load_gltf(path: String, material_count: i32, texture_count: i32) -> Resul
                 roughness_factor,
                 material.emissive.factor[0],    // emissive_factor is returned as rgb but we only use the first value
                 0.0
-            ));
+            ).with_metallic(metallic_factor.clamp(0.0, 1.0))
+            .with_alpha_mode(properties.alpha_mode)
+            .with_double_sided(properties.double_sided)
+            .with_clearcoat(properties.clearcoat, properties.clearcoat_roughness));
 
 
             // Convert textures to own format
-            let mut has_base_color_texture = false;
-            let mut has_roughness_texture = false;
-            let mut has_normal_texture = false;
-            let mut has_emissive_texture = false;
-
-            if let Some(base_color_texture) = &material.pbr.base_color_texture {
-                let base_color_image = convert_to_dynamic_image(base_color_texture);
-                textures.push(base_color_image);
-                texture_index += 1;
-                has_base_color_texture = true;
-            }
-            if let Some(roughness_texture) = &material.pbr.roughness_texture {
-                let roughness_image = convert_to_dynamic_image(roughness_texture);
-                textures.push(roughness_image);
-                texture_index += 1;
-                has_roughness_texture = true;
-            }
-            if let Some(normal) = &material.normal {
-                let normal_image = convert_to_dynamic_image(&normal.texture);
-                textures.push(normal_image);
-                texture_index += 1;
-                has_normal_texture = true;
-            }
-            if let Some(emissive) = &material.emissive.texture {
-                let emissive_image = convert_to_dynamic_image(emissive);
-                textures.push(emissive_image);
-                texture_index += 1;
-                has_emissive_texture = true;
-            }
+            let texture_ids = load_gltf_textures(&material, &mut textures, &mut texture_index);
 
-            let mut texture_ids = [-1,-1,-1];
-
-            if has_base_color_texture && has_roughness_texture && has_normal_texture && has_emissive_texture {
-                texture_ids[0] = texture_index - 4;
-                texture_ids[1] = texture_index - 3;
-                texture_ids[2] = texture_index - 2;
-                // texture_ids[3] = texture_index - 1;
-            } else if has_base_color_texture && has_roughness_texture && has_normal_texture {
-                texture_ids[0] = texture_index - 3;
-                texture_ids[1] = texture_index - 2;
-                texture_ids[2] = texture_index - 1;
-            } else if has_base_color_texture && has_roughness_texture && has_emissive_texture {
-                texture_ids[0] = texture_index - 3;
-                texture_ids[1] = texture_index - 2;
-                // texture_ids[3] = texture_index - 1;
-            } else if has_base_color_texture && has_normal_texture && has_emissive_texture {
-                texture_ids[0] = texture_index - 3;
-                texture_ids[2] = texture_index - 2;
-                // texture_ids[3] = texture_index - 1;
-            } else if has_roughness_texture && has_normal_texture && has_emissive_texture {
-                texture_ids[1] = texture_index - 3;
-                texture_ids[2] = texture_index - 2;
-                // texture_ids[3] = texture_index - 1;
-            } else if has_base_color_texture && has_roughness_texture {
-                texture_ids[0] = texture_index - 2;
-                texture_ids[1] = texture_index - 1;
-            } else if has_base_color_texture && has_normal_texture {
-                texture_ids[0] = texture_index - 2;
-                texture_ids[2] = texture_index - 1;
-            } else if has_base_color_texture && has_emissive_texture {
-                texture_ids[0] = texture_index - 2;
-                // texture_ids[3] = texture_index - 1;
-            } else if has_roughness_texture && has_normal_texture {
-                texture_ids[1] = texture_index - 2;
-                texture_ids[2] = texture_index - 1;
-            } else if has_roughness_texture && has_emissive_texture {
-                texture_ids[1] = texture_index - 2;
-                // texture_ids[3] = texture_index - 1;
-            } else if has_normal_texture && has_emissive_texture {
-                texture_ids[2] = texture_index - 2;
-                // texture_ids[3] = texture_index - 1;
-            } else if has_base_color_texture {
-                texture_ids[0] = texture_index - 1;
-            } else if has_roughness_texture {
-                texture_ids[1] = texture_index - 1;
-            } else if has_normal_texture {
-                texture_ids[2] = texture_index - 1;
-            } else if has_emissive_texture {
-                // texture_ids[3] = texture_index - 1;
-            }
             // Convert the mesh to a triangle list
             match model.triangles() {
                 Ok(triangles) => {
                     for triangle in triangles {
                         // Process each triangle
+                        // The positions are already in world space (easy_gltf applies the node hierarchy transforms)
+                        let points = [
+                            [triangle[0].position.x, triangle[0].position.y, triangle[0].position.z],
+                            [triangle[1].position.x, triangle[1].position.y, triangle[1].position.z],
+                            [triangle[2].position.x, triangle[2].position.y, triangle[2].position.z],
+                        ];
+                        let face_normal = world_face_normal(points, [triangle[0].normal.x, triangle[0].normal.y, triangle[0].normal.z]);
                         let converted_triangle = Triangle::new(
-                            [
-                                [triangle[0].position.x, triangle[0].position.y, triangle[0].position.z],
-                                [triangle[1].position.x, triangle[1].position.y, triangle[1].position.z],
-                                [triangle[2].position.x, triangle[2].position.y, triangle[2].position.z],	
-                            ],
-                            [triangle[0].normal.x, triangle[0].normal.y, triangle[0].normal.z],
+                            points,
+                            face_normal,
                             material_index,
                             texture_ids.map(|x| x as f32),
                             [
@@ -260,7 +193,17 @@ pub fn load_gltf(path: String, material_count: i32, texture_count: i32) -> Resul
                                 [triangle[1].tex_coords.x, triangle[1].tex_coords.y],
                                 [triangle[2].tex_coords.x, triangle[2].tex_coords.y],
                             ],
-                        );
+                        ).with_vertex_normals([
+                            vertex_normal_or_face([triangle[0].normal.x, triangle[0].normal.y, triangle[0].normal.z], face_normal),
+                            vertex_normal_or_face([triangle[1].normal.x, triangle[1].normal.y, triangle[1].normal.z], face_normal),
+                            vertex_normal_or_face([triangle[2].normal.x, triangle[2].normal.y, triangle[2].normal.z], face_normal),
+                        ]);
+                        // Normal maps are baked against the tangents of the file, recomputing them can get mirrored uvs wrong
+                        let converted_triangle = if has_tangents {
+                            converted_triangle.with_vertex_tangents(triangle.map(|vertex| vertex.tangent.into()))
+                        } else {
+                            converted_triangle
+                        };
                         converted_triangles.push(converted_triangle);
                         // println!(" TEx_coords: {:?}", converted_triangle.tex_coords);
                     };
@@ -283,6 +226,299 @@ pub fn load_gltf(path: String, material_count: i32, texture_count: i32) -> Resul
     Ok((converted_triangles, converted_materials, textures))
 }
 
+/// Properties of a glTF material that easy_gltf doesn't load.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+struct GltfMaterialProperties {
+    alpha_mode: AlphaMode,
+    double_sided: bool,
+    /// `clearcoatFactor` of `KHR_materials_clearcoat`, 0.0 without the extension.
+    clearcoat: f32,
+    /// `clearcoatRoughnessFactor` of `KHR_materials_clearcoat`, perceptual roughness like the base.
+    clearcoat_roughness: f32,
+}
+
+/// Reads the alpha mode, whether the materials of a glTF file are double-sided and their clearcoat, by material index.
+///
+/// easy_gltf loads none of them, so they are read from the glTF document itself. The clearcoat textures aren't supported,
+/// only the factors are used. The default material of primitives without a material has no index and stays opaque,
+/// single-sided and without a coat.
+fn load_gltf_material_properties(path: &str) -> Result<Vec<GltfMaterialProperties>, Box<dyn std::error::Error>> {
+    let document = gltf::Gltf::open(path).map_err(|e| format!("Failed to load glTF file {}: {}", path, e))?;
+    Ok(document.materials()
+        .map(|material| {
+            let alpha_mode = match material.alpha_mode() {
+                gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+                gltf::material::AlphaMode::Mask => AlphaMode::Mask { cutoff: material.alpha_cutoff().unwrap_or(AlphaMode::DEFAULT_CUTOFF) },
+                gltf::material::AlphaMode::Blend => AlphaMode::Blend,
+            };
+            let clearcoat = material.extension_value("KHR_materials_clearcoat");
+            let factor = |name: &str| clearcoat.and_then(|clearcoat| clearcoat.get(name)).and_then(|value| value.as_f64()).unwrap_or(0.0).clamp(0.0, 1.0) as f32;
+            GltfMaterialProperties {
+                alpha_mode,
+                double_sided: material.double_sided(),
+                clearcoat: factor("clearcoatFactor"),
+                clearcoat_roughness: factor("clearcoatRoughnessFactor"),
+            }
+        })
+        .collect())
+}
+
+/// Converts the textures of a glTF material and appends them to `textures`.
+///
+/// `texture_index` is the id of the next texture and is advanced for every added texture.
+///
+/// # Returns
+///
+/// The ids of the diffuse, roughness, normal and emission texture, -1 if the material has no such texture.
+fn load_gltf_textures(material: &easy_gltf::Material, textures: &mut Vec<DynamicImage>, texture_index: &mut i32) -> [i32; 4] {
+    let mut texture_ids = [-1; 4];
+    let mut push = |slot: usize, image: DynamicImage| {
+        textures.push(image);
+        texture_ids[slot] = *texture_index;
+        *texture_index += 1;
+    };
+
+    if let Some(base_color_texture) = &material.pbr.base_color_texture {
+        // Color textures are sRGB in glTF, the other maps hold linear data
+        push(0, to_linear(convert_to_dynamic_image(base_color_texture), ColorSpace::Srgb));
+    }
+    if let Some(roughness_texture) = &material.pbr.roughness_texture {
+        push(1, convert_to_dynamic_image(roughness_texture));
+    }
+    if let Some(normal) = &material.normal {
+        push(2, convert_to_dynamic_image(&normal.texture));
+    }
+    if let Some(emissive) = &material.emissive.texture {
+        push(3, to_linear(convert_to_dynamic_image(emissive), ColorSpace::Srgb));
+    }
+    texture_ids
+}
+
+/// Computes the normal of a triangle whose vertices are already transformed into world space.
+///
+/// easy_gltf multiplies the vertex normals with the plain world matrix, which skews them as soon as a node
+/// is scaled non-uniformly. The face normal of the transformed vertices is what the inverse-transpose
+/// of the world matrix would produce, so it is used instead and flipped to the side of the supplied normal.
+/// Degenerate triangles keep the supplied normal.
+fn world_face_normal(points: [[f32; 3]; 3], vertex_normal: [f32; 3]) -> [f32; 3] {
+    let edge1 = glam::Vec3::from(points[1]) - glam::Vec3::from(points[0]);
+    let edge2 = glam::Vec3::from(points[2]) - glam::Vec3::from(points[0]);
+    let face_normal = edge1.cross(edge2);
+    if face_normal.length_squared() <= f32::EPSILON * f32::EPSILON {
+        return vertex_normal;
+    }
+
+    let face_normal = face_normal.normalize();
+    if face_normal.dot(glam::Vec3::from(vertex_normal)) < 0.0 {
+        (-face_normal).into()
+    } else {
+        face_normal.into()
+    }
+}
+
+/// Vertex, texture coordinate and normal index of an OBJ face vertex.
+type FaceVertex = (usize, Option<usize>, Option<usize>);
+
+/// Parses a vertex of an OBJ face into the vertex index and the optional texture coordinate and normal indices.
+///
+/// Supports the forms `v`, `v/vt`, `v//vn` and `v/vt/vn`. The indices are 1-based like in the file.
+fn parse_face_vertex(vertex: &str) -> Result<FaceVertex, Box<dyn std::error::Error>> {
+    let mut parts = vertex.split('/');
+    let mut next_index = || -> Result<Option<usize>, Box<dyn std::error::Error>> {
+        match parts.next() {
+            Some("") | None => Ok(None),
+            Some(index) => Ok(Some(index.parse::<usize>()
+                .map_err(|e| format!("Invalid face index {:?} in {:?}: {}", index, vertex, e))?)),
+        }
+    };
+    let vertex_index = next_index()?.ok_or_else(|| format!("Missing vertex index in face vertex {:?}", vertex))?;
+    let tex_coord_index = next_index()?;
+    let normal_index = next_index()?;
+    Ok((vertex_index, tex_coord_index, normal_index))
+}
+
+/// Looks up a 1-based OBJ index, returning an error instead of panicking if it is out of range.
+fn obj_element<T: Copy>(elements: &[T], index: usize, name: &str) -> Result<T, Box<dyn std::error::Error>> {
+    index.checked_sub(1)
+        .and_then(|index| elements.get(index))
+        .copied()
+        .ok_or_else(|| format!("Face references {} {} but the file has {}", name, index, elements.len()).into())
+}
+
+/// Normalizes a vertex normal for smooth shading.
+///
+/// Meshes without normals have zero vertex normals, those fall back to the face normal.
+fn vertex_normal_or_face(vertex_normal: [f32; 3], face_normal: [f32; 3]) -> [f32; 3] {
+    match glam::Vec3::from(vertex_normal).try_normalize() {
+        Some(normal) => normal.into(),
+        None => face_normal,
+    }
+}
+
+/// Loads the `<polygon>` elements of an SVG file as flat, extruded triangle meshes.
+///
+/// SVG coordinates are used as scene units, with the SVG y axis (pointing down) flipped to point up.
+/// The front faces lie at `z` and face +z, the back faces lie at `z - depth`. With a depth of 0 only the front faces are created.
+///
+/// # Errors
+///
+/// Returns an error if the file can't be read, isn't valid XML, or contains a polygon that has
+/// invalid coordinates, less than 3 points, or can't be triangulated (e.g. because it intersects itself).
+pub fn load_svg(file_path: &str, z: f32, depth: f32, material_id: i32) -> Result<Vec<Triangle>, Box<dyn std::error::Error>> {
+    if depth.is_nan() || depth < 0.0 {
+        return Err(format!("SVG depth must be 0 or positive, got {}", depth).into());
+    }
+    let svg = std::fs::read_to_string(file_path)?;
+    let mut triangles = Vec::new();
+    for (index, polygon) in parse_svg_polygons(&svg)?.iter().enumerate() {
+        let polygon_triangles = extrude_polygon(polygon, z, depth, material_id)
+            .map_err(|e| format!("SVG polygon {}: {}", index, e))?;
+        triangles.extend(polygon_triangles);
+    }
+    Ok(triangles)
+}
+
+/// Returns the points of all `<polygon>` elements, with the y axis already flipped to point up.
+fn parse_svg_polygons(svg: &str) -> Result<Vec<Vec<[f32; 2]>>, String> {
+    let mut reader = quick_xml::Reader::from_str(svg);
+    let mut polygons = Vec::new();
+    loop {
+        match reader.read_event() {
+            Ok(quick_xml::events::Event::Start(element)) | Ok(quick_xml::events::Event::Empty(element)) => {
+                if element.local_name().as_ref() != b"polygon" {
+                    continue;
+                }
+                let points = element.try_get_attribute("points")
+                    .map_err(|e| format!("Invalid polygon attributes: {}", e))?
+                    .ok_or("Polygon without points attribute")?;
+                let points = points.unescape_value()
+                    .map_err(|e| format!("Invalid polygon points: {}", e))?;
+                polygons.push(parse_svg_points(&points)?);
+            }
+            Ok(quick_xml::events::Event::Eof) => break,
+            Ok(_) => {}
+            Err(e) => return Err(format!("Invalid SVG at position {}: {}", reader.buffer_position(), e)),
+        }
+    }
+    Ok(polygons)
+}
+
+/// Parses the `points` attribute of a polygon, e.g. `"0,0 10,0 10,10"`.
+fn parse_svg_points(points: &str) -> Result<Vec<[f32; 2]>, String> {
+    let values = points
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|value| !value.is_empty())
+        .map(|value| value.parse::<f32>().map_err(|_| format!("Invalid polygon coordinate '{}'", value)))
+        .collect::<Result<Vec<f32>, String>>()?;
+    if values.len() % 2 != 0 {
+        return Err("Polygon has an odd number of coordinates".to_string());
+    }
+
+    let mut points: Vec<[f32; 2]> = values.chunks(2).map(|point| [point[0], -point[1]]).collect();
+    // SVG polygons are closed implicitly, a repeated first point is dropped
+    if points.len() > 1 && points.first() == points.last() {
+        points.pop();
+    }
+    if points.len() < 3 {
+        return Err("Polygon needs at least 3 points".to_string());
+    }
+    Ok(points)
+}
+
+/// Twice the signed area of a polygon, positive if the points are in counter-clockwise order.
+fn polygon_double_area(points: &[[f32; 2]]) -> f32 {
+    (0..points.len())
+        .map(|i| {
+            let (a, b) = (points[i], points[(i + 1) % points.len()]);
+            a[0] * b[1] - b[0] * a[1]
+        })
+        .sum()
+}
+
+/// Splits a simple polygon into triangles by ear clipping.
+///
+/// Returns the vertex indices of the triangles in counter-clockwise order,
+/// or `None` if the polygon has no area or intersects itself.
+fn triangulate_polygon(points: &[[f32; 2]]) -> Option<Vec<[usize; 3]>> {
+    let cross = |a: [f32; 2], b: [f32; 2], c: [f32; 2]| (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0]);
+
+    let double_area = polygon_double_area(points);
+    if double_area.abs() <= f32::EPSILON {
+        return None;
+    }
+
+    // Work on a counter-clockwise polygon, so convex corners have a positive cross product
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    if double_area < 0.0 {
+        remaining.reverse();
+    }
+
+    let mut triangles = Vec::with_capacity(points.len() - 2);
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let ear = (0..count).find(|&i| {
+            let (prev, current, next) = (remaining[(i + count - 1) % count], remaining[i], remaining[(i + 1) % count]);
+            let (a, b, c) = (points[prev], points[current], points[next]);
+            if cross(a, b, c) <= 0.0 {
+                return false;
+            }
+            // No other corner may lie inside the ear
+            remaining.iter()
+                .filter(|&&other| other != prev && other != current && other != next)
+                .all(|&other| {
+                    let p = points[other];
+                    cross(a, b, p) < 0.0 || cross(b, c, p) < 0.0 || cross(c, a, p) < 0.0
+                })
+        })?;
+        triangles.push([remaining[(ear + count - 1) % count], remaining[ear], remaining[(ear + 1) % count]]);
+        remaining.remove(ear);
+    }
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+    Some(triangles)
+}
+
+/// Triangulates a polygon at `z` and extrudes it by `depth` along -z.
+fn extrude_polygon(points: &[[f32; 2]], z: f32, depth: f32, material_id: i32) -> Result<Vec<Triangle>, String> {
+    let faces = triangulate_polygon(points).ok_or("Polygon has no area or intersects itself")?;
+    let front = |i: usize| [points[i][0], points[i][1], z];
+    let back = |i: usize| [points[i][0], points[i][1], z - depth];
+    let triangle = |corners: [[f32; 3]; 3], normal: [f32; 3]| Triangle::new(corners, normal, material_id, [-1.0; 4], [[0.0; 2]; 3]);
+
+    let mut triangles: Vec<Triangle> = faces.iter()
+        .map(|&[a, b, c]| triangle([front(a), front(b), front(c)], [0.0, 0.0, 1.0]))
+        .collect();
+    if depth == 0.0 {
+        return Ok(triangles);
+    }
+
+    triangles.extend(faces.iter().map(|&[a, b, c]| triangle([back(a), back(c), back(b)], [0.0, 0.0, -1.0])));
+
+    // Side walls, walking the outline counter-clockwise puts the outside on the right of each edge
+    let mut outline: Vec<usize> = (0..points.len()).collect();
+    if polygon_double_area(points) < 0.0 {
+        outline.reverse();
+    }
+    for i in 0..outline.len() {
+        let (a, b) = (outline[i], outline[(i + 1) % outline.len()]);
+        let edge = glam::Vec2::from(points[b]) - glam::Vec2::from(points[a]);
+        let Some(outward) = glam::Vec2::new(edge.y, -edge.x).try_normalize() else { continue };
+        let normal = [outward.x, outward.y, 0.0];
+        triangles.push(triangle([front(a), back(a), back(b)], normal));
+        triangles.push(triangle([front(a), back(b), front(b)], normal));
+    }
+    Ok(triangles)
+}
+
+/// Loads a material texture. `.hdr` and `.exr` files are loaded as float images via [`load_hdr`],
+/// so values above 1.0 (e.g. for emissive maps) are kept. All other formats are opened with `image`.
+pub fn load_texture(path: &str) -> Result<DynamicImage, Box<dyn std::error::Error>> {
+    let extension = path.rsplit('.').next().unwrap_or("").to_lowercase();
+    match extension.as_str() {
+        "hdr" | "exr" => load_hdr(path.to_string()),
+        _ => Ok(image::open(path)?),
+    }
+}
+
 pub fn load_hdr(path: String) -> Result<DynamicImage, Box<dyn std::error::Error>> {
     // check fiel extension if hdr or exr
     let binding = path.split('.').collect::<Vec<&str>>();
@@ -298,17 +534,14 @@ pub fn load_hdri(path: String) -> Result<DynamicImage, Box<dyn std::error::Error
     let contents = std::fs::read(path)?;
     let mut data = zune_hdr::HdrDecoder::new(contents);
     let pix: Vec<f32> = data.decode()?;
-    let dimensions = data.get_dimensions().unwrap();
-    println!("first pix:{:?}", (pix[0], pix[1], pix[2]));
+    let dimensions = data.get_dimensions().ok_or("Missing dimensions in HDR file")?;
 
-    let image = ImageBuffer::<Rgba<u8>, Vec<u8>>::from_fn(dimensions.0 as u32, dimensions.1 as u32, |x, y| {
+    // Keep the linear float values, the texture arrays are stored as float textures
+    let image = ImageBuffer::<Rgba<f32>, Vec<f32>>::from_fn(dimensions.0 as u32, dimensions.1 as u32, |x, y| {
         let index = (y * dimensions.0 as u32 + x) as usize * 3;
-        let r = (pix[index] * 255.0) as u8;
-        let g = (pix[index + 1] * 255.0) as u8;
-        let b = (pix[index + 2] * 255.0) as u8;
-        Rgba([r, g, b, 255])
+        Rgba([pix[index], pix[index + 1], pix[index + 2], 1.0])
     });
-    let texture: DynamicImage = DynamicImage::ImageRgba8(image);
+    let texture: DynamicImage = DynamicImage::ImageRgba32F(image);
 
     Ok(texture)
 }
@@ -317,46 +550,36 @@ pub fn load_exr(path: String) -> Result<DynamicImage, Box<dyn std::error::Error>
     use exr::prelude::*;
     use exr::prelude as exrs;
 
-    // read from the exr file directly into a new `png::RgbaImage` image without intermediate buffers
+    // read from the exr file directly into a new `image::Rgba32FImage` without intermediate buffers
     let reader = exrs::read()
         .no_deep_data()
         .largest_resolution_level()
         .rgba_channels(
-        |resolution, _channels: &RgbaChannels| -> image::RgbaImage {
+        |resolution, _channels: &RgbaChannels| -> image::Rgba32FImage {
                 image::ImageBuffer::new(
                     resolution.width() as u32,
                     resolution.height() as u32
                 )
             },
 
-            // set each pixel in the png buffer from the exr file
-            |png_pixels, position, (r,g,b,a): (f32,f32,f32,f32)| { // TODO implicit argument types!
-                png_pixels.put_pixel(
+            // set each pixel in the float buffer from the exr file, values above 1.0 are kept
+            |pixels, position, (r,g,b,a): (f32,f32,f32,f32)| { // TODO implicit argument types!
+                pixels.put_pixel(
                     position.x() as u32, position.y() as u32,
-                    image::Rgba([tone_map(r), tone_map(g), tone_map(b), (a * 255.0) as u8])
+                    image::Rgba([r, g, b, a])
                 );
             }
         )
         .first_valid_layer()
         .all_attributes();
 
-    // an image that contains a single layer containing an png rgba buffer
-    let image: Image<Layer<SpecificChannels<image::RgbaImage, RgbaChannels>>> = reader
-        .from_file(path)
-        .expect("failed to read exr file");
-
-
-    /// compress any possible f32 into the range of [0,1].
-    /// and then convert it to an unsigned byte.
-    fn tone_map(linear: f32) -> u8 {
-        // TODO does the `image` crate expect gamma corrected data?
-        let clamped = (linear - 0.5).tanh() * 0.5 + 0.5;
-        (clamped * 255.0) as u8
-    }
+    // an image that contains a single layer containing an rgba float buffer
+    let image: Image<Layer<SpecificChannels<image::Rgba32FImage, RgbaChannels>>> = reader
+        .from_file(path)?;
 
     let pixel_buffer = image.layer_data.channel_data.pixels;
     // convert the image to a dynamic image
-    let image = DynamicImage::ImageRgba8(pixel_buffer);
+    let image = DynamicImage::ImageRgba32F(pixel_buffer);
     Ok(image)
 }
 
@@ -391,6 +614,45 @@ mod tests {
         assert_eq!(materials.len(), 0);
     }
 
+    #[test]
+    fn test_load_obj_vertex_normals() {
+        let (triangles, _) = load_obj("../scene/src/test_files/smooth_triangle.obj".to_string(), 0).unwrap();
+        assert_eq!(triangles.len(), 1);
+        assert_eq!(triangles[0].normal, [0.0, 0.0, 1.0]);
+        assert_eq!(triangles[0].vertex_normals, [[0.0, 0.0, 1.0], [0.6, 0.0, 0.8], [0.0, 0.6, 0.8]]);
+    }
+
+    #[test]
+    fn test_load_obj_without_texture_coords() {
+        let (triangles, _) = load_obj("../scene/src/test_files/cube_no_texcoords.obj".to_string(), 0).unwrap();
+        assert_eq!(triangles.len(), 12);
+        for triangle in &triangles {
+            assert_eq!(triangle.tex_coords, [[0.0; 2]; 3]);
+        }
+        assert_eq!(triangles[0].normal, [0.0, 0.0, 1.0]);
+        assert_eq!(triangles[2].vertex_normals, [[0.0, 0.0, -1.0]; 3]);
+    }
+
+    #[test]
+    fn test_parse_face_vertex() {
+        assert_eq!(parse_face_vertex("3").unwrap(), (3, None, None));
+        assert_eq!(parse_face_vertex("3/2").unwrap(), (3, Some(2), None));
+        assert_eq!(parse_face_vertex("3//1").unwrap(), (3, None, Some(1)));
+        assert_eq!(parse_face_vertex("3/2/1").unwrap(), (3, Some(2), Some(1)));
+        assert!(parse_face_vertex("/2/1").is_err());
+        assert!(parse_face_vertex("a/2/1").is_err());
+
+        assert_eq!(obj_element(&[[1.0, 2.0]], 1, "texture coordinate").unwrap(), [1.0, 2.0]);
+        assert!(obj_element(&[[1.0, 2.0]], 2, "texture coordinate").is_err());
+        assert!(obj_element(&[[1.0, 2.0]], 0, "texture coordinate").is_err());
+    }
+
+    #[test]
+    fn test_vertex_normal_or_face() {
+        assert_eq!(vertex_normal_or_face([0.0, 2.0, 0.0], [0.0, 0.0, 1.0]), [0.0, 1.0, 0.0]);
+        assert_eq!(vertex_normal_or_face([0.0, 0.0, 0.0], [0.0, 0.0, 1.0]), [0.0, 0.0, 1.0]);
+    }
+
     #[test]
     fn test_load_obj_empty() {
         let obj_content = load_obj("../scene/src/test_files/empty_scene.obj".to_string(), 0);
@@ -439,6 +701,108 @@ mod tests {
         assert_eq!(textures.len(), 0);
     }
 
+    #[test]
+    fn test_load_gltf_missing_file() {
+        let gltf_content = load_gltf("../scene/src/test_files/does_not_exist.gltf".to_string(), 0, 0);
+        let error = gltf_content.unwrap_err().to_string();
+        assert!(error.starts_with("Failed to load glTF file ../scene/src/test_files/does_not_exist.gltf: "));
+    }
+
+    #[test]
+    fn test_load_gltf_wrong_extension() {
+        let gltf_content = load_gltf("../scene/src/test_files/cube.obj".to_string(), 0, 0);
+        assert_eq!(gltf_content.unwrap_err().to_string(),
+            "Unsupported file format for glTF model ../scene/src/test_files/cube.obj. Supported formats are: .gltf, .glb");
+    }
+
+    #[test]
+    fn test_load_gltf_translated_child_node() {
+        // Parent node is translated by (1, 0, 0), its child by (0, 2, 0)
+        let gltf_content = load_gltf("../scene/src/test_files/translated_child_node.gltf".to_string(), 0, 0);
+        assert!(gltf_content.is_ok());
+        let (triangles, _, _) = gltf_content.unwrap();
+        assert_eq!(triangles.len(), 1);
+        assert_eq!(triangles[0].points, [[1.0, 2.0, 0.0], [2.0, 2.0, 0.0], [1.0, 3.0, 0.0]]);
+        assert_eq!(triangles[0].normal, [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn test_load_gltf_tangents() {
+        // Triangle in the xy plane with mirrored uvs, the file stores the tangent (-1, 0, 0) with a bitangent sign of -1
+        let gltf_content = load_gltf("../scene/src/test_files/mirrored_uv_tangents.gltf".to_string(), 0, 0);
+        let (triangles, _, _) = gltf_content.expect("Failed to load gltf file");
+        assert_eq!(triangles.len(), 1);
+        assert_eq!(triangles[0].vertex_tangents, Some([[-1.0, 0.0, 0.0, -1.0]; 3]));
+
+        let [x, y, z, w] = triangles[0].tangent();
+        let tangent = glam::Vec3::new(x, y, z);
+        assert!(tangent.length() > 0.99);
+        assert!(tangent.dot(glam::Vec3::from(triangles[0].normal)).abs() < 1e-5);
+        assert_eq!(w, -1.0);
+    }
+
+    #[test]
+    fn test_load_gltf_material_properties() {
+        let properties = load_gltf_material_properties("../examples/8-alpha_cutout/res/cutout_quad.gltf").expect("Failed to load gltf file");
+        assert_eq!(properties.len(), 1);
+        assert_eq!((properties[0].alpha_mode, properties[0].double_sided), (AlphaMode::Mask { cutoff: 0.5 }, true));
+
+        // Materials without an alpha mode are opaque
+        let properties = load_gltf_material_properties("../scene/src/test_files/translated_child_node.gltf").expect("Failed to load gltf file");
+        assert_eq!((properties[0].alpha_mode, properties[0].double_sided), (AlphaMode::Opaque, true));
+        // and materials without KHR_materials_clearcoat have no coat
+        assert_eq!(properties[0].clearcoat, 0.0);
+    }
+
+    #[test]
+    fn test_load_gltf_clearcoat() {
+        let properties = load_gltf_material_properties("../scene/src/test_files/clearcoat_quad.gltf").expect("Failed to load gltf file");
+        let paint = properties.first().expect("Missing material");
+        assert_eq!((paint.clearcoat, paint.clearcoat_roughness), (1.0, 0.1));
+        assert_eq!(paint.alpha_mode, AlphaMode::Opaque);
+    }
+
+    #[test]
+    fn test_load_gltf_textures_emissive() {
+        let image = |color: u8| std::sync::Arc::new(image::RgbImage::from_pixel(2, 2, image::Rgb([color; 3])));
+        let material = easy_gltf::Material {
+            normal: Some(easy_gltf::NormalMap { texture: image(128), factor: 1.0 }),
+            emissive: easy_gltf::Emissive { texture: Some(image(255)), factor: cgmath::Vector3::new(1.0, 1.0, 1.0) },
+            ..Default::default()
+        };
+        let mut textures = vec![DynamicImage::new_rgba8(1, 1)];
+        let mut texture_index = 1;
+
+        let texture_ids = load_gltf_textures(&material, &mut textures, &mut texture_index);
+        assert_eq!(texture_ids, [-1, -1, 1, 2]);
+        assert_eq!(texture_index, 3);
+        assert_eq!(textures.len(), 3);
+
+        // Without an emission texture the slot stays empty
+        let texture_ids = load_gltf_textures(&easy_gltf::Material::default(), &mut textures, &mut texture_index);
+        assert_eq!(texture_ids, [-1; 4]);
+        assert_eq!(textures.len(), 3);
+    }
+
+    #[test]
+    fn test_world_face_normal_non_uniform_scale() {
+        // Triangle in the plane x + y = 1 after scaling x by 2: the plain matrix would give a skewed normal
+        let points = [[2.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 1.0, 1.0]];
+        let normal = world_face_normal(points, [2.0, 1.0, 0.0]);
+        let expected = glam::Vec3::new(1.0, 2.0, 0.0).normalize();
+        assert!((glam::Vec3::from(normal) - expected).length() < 1e-6);
+
+        // Flipped to the side of the supplied normal
+        let normal = world_face_normal(points, [-1.0, -1.0, 0.0]);
+        assert!((glam::Vec3::from(normal) + expected).length() < 1e-6);
+    }
+
+    #[test]
+    fn test_world_face_normal_degenerate() {
+        let points = [[0.0; 3], [1.0, 0.0, 0.0], [2.0, 0.0, 0.0]];
+        assert_eq!(world_face_normal(points, [0.0, 1.0, 0.0]), [0.0, 1.0, 0.0]);
+    }
+
     #[test]
     fn test_load_hdr_correct_hdr() {
         let hdr_content = load_hdr("../scene/src/test_files/image.hdr".to_string());
@@ -450,6 +814,22 @@ mod tests {
         assert_eq!(image::GenericImageView::dimensions(&texture), (1024, 512));
     }
 
+    #[test]
+    fn test_load_hdr_keeps_values_above_one() {
+        // A 2x1 Radiance file with flat RGBE pixels, the value of a channel is mantissa / 256 * 2^(exponent - 128)
+        let mut contents = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 2\n".to_vec();
+        contents.extend_from_slice(&[128, 128, 128, 131]); // 4.0, 4.0, 4.0
+        contents.extend_from_slice(&[128, 64, 32, 129]);   // 1.0, 0.5, 0.25
+        let path = std::env::temp_dir().join("raytracer_test_load_hdr_bright.hdr");
+        std::fs::write(&path, contents).unwrap();
+
+        let texture = load_hdr(path.to_str().unwrap().to_string()).expect("Failed to load hdr file");
+        let texture = texture.as_rgba32f().expect("HDR files are loaded as float images");
+        assert_eq!(texture.dimensions(), (2, 1));
+        assert_eq!(texture.get_pixel(0, 0).0, [4.0, 4.0, 4.0, 1.0]);
+        assert_eq!(texture.get_pixel(1, 0).0, [1.0, 0.5, 0.25, 1.0]);
+    }
+
     #[test]
     fn test_load_hdr_correct_exr() {
         let hdr_content = load_hdr("../scene/src/test_files/image.exr".to_string());
@@ -461,6 +841,19 @@ mod tests {
         assert_eq!(image::GenericImageView::dimensions(&texture), (1024, 512));
     }
 
+    #[test]
+    fn test_load_texture_exr_is_float() {
+        let texture = load_texture("../scene/src/test_files/image.exr").unwrap();
+        assert!(matches!(texture, DynamicImage::ImageRgba32F(_)));
+        assert_eq!(image::GenericImageView::dimensions(&texture), (1024, 512));
+    }
+
+    #[test]
+    fn test_load_texture_png() {
+        let texture = load_texture("../scene/src/test_files/image.png").unwrap();
+        assert!(matches!(texture, DynamicImage::ImageRgba8(_) | DynamicImage::ImageRgb8(_)));
+    }
+
     #[test]
     fn test_load_hdr_wrong_extension() {
         let hdr_content = load_hdr("../scene/src/test_files/image.png".to_string());
@@ -468,4 +861,48 @@ mod tests {
         let error = hdr_content.unwrap_err();
         assert_eq!(error.to_string(), "Unsupported file format for background image. Supported formats are: .hdr, .exr");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_load_svg_two_triangles() {
+        let triangles = load_svg("../scene/src/test_files/two_triangles.svg", 1.0, 0.0, 2).unwrap();
+        assert_eq!(triangles.len(), 2);
+        assert!(triangles.iter().all(|triangle| triangle.material_id == 2 && triangle.normal == [0.0, 0.0, 1.0]));
+        assert!(triangles.iter().all(|triangle| triangle.points.iter().all(|point| point[2] == 1.0)));
+        // The y axis of the SVG points down
+        assert!(triangles[0].points.contains(&[0.0, -1.0, 1.0]));
+
+        // Front, back and two triangles for each of the 3 side walls per triangle
+        let triangles = load_svg("../scene/src/test_files/two_triangles.svg", 1.0, 0.5, 2).unwrap();
+        assert_eq!(triangles.len(), 16);
+        assert!(triangles.iter().any(|triangle| triangle.points.iter().all(|point| point[2] == 0.5)));
+    }
+
+    #[test]
+    fn test_load_svg_malformed() {
+        assert!(parse_svg_polygons("<svg><polygon points=\"0,0 1,0 x,1\"/></svg>").is_err());
+        assert!(parse_svg_polygons("<svg><polygon points=\"0,0 1,0\"/></svg>").is_err());
+        assert!(parse_svg_polygons("<svg><polygon points=\"0,0 1,0 1\"/></svg>").is_err());
+        assert!(parse_svg_polygons("<svg><polygon/></svg>").is_err());
+        assert!(parse_svg_polygons("<svg><polygon points=\"0,0 1,0 1,1\"></svg>").is_err());
+        assert!(load_svg("../scene/src/test_files/two_triangles.svg", 0.0, -1.0, 0).is_err());
+        assert!(load_svg("../scene/src/test_files/does_not_exist.svg", 0.0, 0.0, 0).is_err());
+    }
+
+    #[test]
+    fn test_triangulate_concave_polygon() {
+        // L-shape, the corner at (1, 1) is concave
+        let points = [[0.0, 0.0], [2.0, 0.0], [2.0, 1.0], [1.0, 1.0], [1.0, 2.0], [0.0, 2.0]];
+        let faces = triangulate_polygon(&points).unwrap();
+        assert_eq!(faces.len(), 4);
+        let area: f32 = faces.iter().map(|&[a, b, c]| polygon_double_area(&[points[a], points[b], points[c]]) / 2.0).sum();
+        assert!((area - 3.0).abs() < 1e-5);
+
+        // Clockwise input gives counter-clockwise triangles as well
+        let mut clockwise = points;
+        clockwise.reverse();
+        let faces = triangulate_polygon(&clockwise).unwrap();
+        assert!(faces.iter().all(|&[a, b, c]| polygon_double_area(&[clockwise[a], clockwise[b], clockwise[c]]) > 0.0));
+
+        assert!(triangulate_polygon(&[[0.0, 0.0], [1.0, 0.0], [2.0, 0.0]]).is_none());
+    }
+}
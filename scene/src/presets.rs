@@ -0,0 +1,158 @@
+//! Scenes built in code, e.g. as ground truth for tests of the renderer.
+
+use cgmath::Point3;
+use glam::{Quat, Vec3};
+
+use crate::scene_builder::SceneBuilder;
+use crate::structs::{Background, Material, Sphere, Triangle};
+
+/// Material ids of the default scene, in the order they are added by `default_scene`.
+pub const DEFAULT_SCENE_GROUND: i32 = 0;
+pub const DEFAULT_SCENE_SPHERE: i32 = 1;
+pub const DEFAULT_SCENE_LIGHT: i32 = 2;
+
+/// Half of the width and depth of the ground plane of the default scene.
+const DEFAULT_SCENE_GROUND_HALF_SIZE: f32 = 10.0;
+
+/// The scene shown when no config file is given: a red sphere on a grey ground plane, lit by a small light above it
+/// and a neutral grey background, with the camera looking at the sphere.
+pub fn default_scene() -> SceneBuilder {
+    let size = DEFAULT_SCENE_GROUND_HALF_SIZE;
+    let ground = quad([[-size, 0.0, size], [size, 0.0, size], [size, 0.0, -size], [-size, 0.0, -size]], [0.0, 1.0, 0.0], DEFAULT_SCENE_GROUND);
+
+    SceneBuilder::new()
+        .add_material(Material::new([0.5, 0.5, 0.5], [0.5, 0.5, 0.5], 1.0, 0.0, 0.0))
+        .add_material(Material::plastic([0.8, 0.2, 0.1]))
+        .add_material(Material::new([1.0, 0.95, 0.9], [0.0, 0.0, 0.0], 1.0, 10.0, 0.0))
+        .add_sphere(Sphere::new(Point3::new(0.0, 1.0, 0.0), 1.0, DEFAULT_SCENE_SPHERE, [-1; 4]))
+        .add_sphere(Sphere::new(Point3::new(2.0, 4.0, 2.0), 0.5, DEFAULT_SCENE_LIGHT, [-1; 4]))
+        .add_triangle_mesh(ground.to_vec())
+        .set_background(Background::default().with_color([0.5, 0.5, 0.5]), None)
+        .set_camera_look_at([0.0, 2.0, 5.0], [0.0, 1.0, 0.0], 45.0)
+}
+
+/// Material ids of the Cornell box, in the order they are added by `cornell_box`.
+pub const CORNELL_WHITE: i32 = 0;
+pub const CORNELL_RED: i32 = 1;
+pub const CORNELL_GREEN: i32 = 2;
+pub const CORNELL_LIGHT: i32 = 3;
+
+/// Half of the width and depth of the ceiling light, the size of the original box scaled to the room.
+const CORNELL_LIGHT_HALF_SIZE: [f32; 2] = [0.235, 0.19];
+
+/// The Cornell box: a room with a white floor, ceiling and back wall, a red left and a green right wall,
+/// a rectangular light below the ceiling and a short and a tall white box. The front of the room is open.
+///
+/// The room spans -1..1 in x and z and 0..2 in y, the camera looks at it from the open side.
+/// The scene is only lit by the ceiling light, the background is black.
+pub fn cornell_box() -> SceneBuilder {
+    let white = CORNELL_WHITE;
+    let mut triangles = Vec::new();
+    // Floor, ceiling and walls facing into the room
+    triangles.extend(quad([[-1.0, 0.0, 1.0], [1.0, 0.0, 1.0], [1.0, 0.0, -1.0], [-1.0, 0.0, -1.0]], [0.0, 1.0, 0.0], white));
+    triangles.extend(quad([[-1.0, 2.0, -1.0], [1.0, 2.0, -1.0], [1.0, 2.0, 1.0], [-1.0, 2.0, 1.0]], [0.0, -1.0, 0.0], white));
+    triangles.extend(quad([[-1.0, 0.0, -1.0], [1.0, 0.0, -1.0], [1.0, 2.0, -1.0], [-1.0, 2.0, -1.0]], [0.0, 0.0, 1.0], white));
+    triangles.extend(quad([[-1.0, 0.0, 1.0], [-1.0, 0.0, -1.0], [-1.0, 2.0, -1.0], [-1.0, 2.0, 1.0]], [1.0, 0.0, 0.0], CORNELL_RED));
+    triangles.extend(quad([[1.0, 0.0, -1.0], [1.0, 0.0, 1.0], [1.0, 2.0, 1.0], [1.0, 2.0, -1.0]], [-1.0, 0.0, 0.0], CORNELL_GREEN));
+
+    // The light hangs slightly below the ceiling, so it isn't coplanar with it
+    let [x, z] = CORNELL_LIGHT_HALF_SIZE;
+    let y = 1.99;
+    triangles.extend(quad([[-x, y, -z], [x, y, -z], [x, y, z], [-x, y, z]], [0.0, -1.0, 0.0], CORNELL_LIGHT));
+
+    // Short box in the front right and tall box in the back left, turned towards each other
+    triangles.extend(block([0.33, 0.29], [0.6, 0.6, 0.6], -17.0, white));
+    triangles.extend(block([-0.33, -0.29], [0.6, 1.2, 0.6], 17.0, white));
+
+    SceneBuilder::new()
+        .add_material(Material::new([0.73, 0.73, 0.73], [0.0, 0.0, 0.0], 1.0, 0.0, 0.0))
+        .add_material(Material::new([0.65, 0.05, 0.05], [0.0, 0.0, 0.0], 1.0, 0.0, 0.0))
+        .add_material(Material::new([0.12, 0.45, 0.15], [0.0, 0.0, 0.0], 1.0, 0.0, 0.0))
+        // Warm light of the original scene, radiance (17, 12, 4)
+        .add_material(Material::new([1.0, 12.0 / 17.0, 4.0 / 17.0], [0.0, 0.0, 0.0], 1.0, 17.0, 0.0))
+        .add_triangle_mesh(triangles)
+        .set_background(Background::new(-1, -1, 0.0), None)
+        .set_camera_look_at([0.0, 1.0, 3.9], [0.0, 1.0, 0.0], 40.0)
+}
+
+// Two triangles of the quad with the corners in counter-clockwise order seen from the side `normal` points to
+fn quad(corners: [[f32; 3]; 4], normal: [f32; 3], material_id: i32) -> [Triangle; 2] {
+    let [a, b, c, d] = corners;
+    [
+        Triangle::new([a, b, c], normal, material_id, [-1.0; 4], [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0]]),
+        Triangle::new([a, c, d], normal, material_id, [-1.0; 4], [[0.0, 0.0], [1.0, 1.0], [0.0, 1.0]]),
+    ]
+}
+
+// Box standing on the floor at `center` (x, z), turned by `angle` degrees around the y axis.
+// The bottom is left out, it lies on the floor and can't be seen
+fn block(center: [f32; 2], size: [f32; 3], angle: f32, material_id: i32) -> Vec<Triangle> {
+    let rotation = Quat::from_rotation_y(angle.to_radians());
+    let half = Vec3::new(size[0], 0.0, size[2]) * 0.5;
+    let base = Vec3::new(center[0], 0.0, center[1]);
+    let corner = |x: f32, y: f32, z: f32| (base + rotation * Vec3::new(x * half.x, y * size[1], z * half.z)).to_array();
+    let normal = |normal: Vec3| (rotation * normal).to_array();
+
+    let mut triangles = Vec::new();
+    triangles.extend(quad([corner(-1.0, 1.0, 1.0), corner(1.0, 1.0, 1.0), corner(1.0, 1.0, -1.0), corner(-1.0, 1.0, -1.0)], normal(Vec3::Y), material_id));
+    triangles.extend(quad([corner(-1.0, 0.0, 1.0), corner(1.0, 0.0, 1.0), corner(1.0, 1.0, 1.0), corner(-1.0, 1.0, 1.0)], normal(Vec3::Z), material_id));
+    triangles.extend(quad([corner(1.0, 0.0, -1.0), corner(-1.0, 0.0, -1.0), corner(-1.0, 1.0, -1.0), corner(1.0, 1.0, -1.0)], normal(Vec3::NEG_Z), material_id));
+    triangles.extend(quad([corner(-1.0, 0.0, -1.0), corner(-1.0, 0.0, 1.0), corner(-1.0, 1.0, 1.0), corner(-1.0, 1.0, -1.0)], normal(Vec3::NEG_X), material_id));
+    triangles.extend(quad([corner(1.0, 0.0, 1.0), corner(1.0, 0.0, -1.0), corner(1.0, 1.0, -1.0), corner(1.0, 1.0, 1.0)], normal(Vec3::X), material_id));
+    triangles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cornell_box() {
+        let scene = cornell_box().build().expect("Invalid scene");
+        let materials = scene.materials.expect("Missing materials");
+        let triangles = scene.meshes.expect("Missing triangles");
+
+        // Five walls and the light with two triangles each, two boxes with five faces each
+        assert_eq!(triangles.len(), 5 * 2 + 2 + 2 * 5 * 2);
+        let emissive: Vec<usize> = (0..materials.len()).filter(|&id| materials[id].emission > 0.0).collect();
+        assert_eq!(emissive, vec![CORNELL_LIGHT as usize]);
+        assert!(triangles.iter().all(|triangle| (0..materials.len() as i32).contains(&triangle.material_id)));
+        assert!(triangles.iter().all(|triangle| !triangle.is_degenerate()));
+
+        // The light has the size of the original one and faces the floor
+        let light: Vec<&Triangle> = triangles.iter().filter(|triangle| triangle.material_id == CORNELL_LIGHT).collect();
+        let area: f32 = light.iter().map(|triangle| triangle.area()).sum();
+        assert!((area - 4.0 * CORNELL_LIGHT_HALF_SIZE[0] * CORNELL_LIGHT_HALF_SIZE[1]).abs() < 1e-6);
+        assert!(light.iter().all(|triangle| triangle.normal == [0.0, -1.0, 0.0]));
+
+        // The winding order matches the normals, so the tangents and the flat normals agree
+        for triangle in &triangles {
+            let [a, b, c] = triangle.points.map(Vec3::from);
+            let winding = (b - a).cross(c - a).normalize();
+            assert!(winding.dot(Vec3::from(triangle.normal)) > 0.999, "{:?}", triangle.points);
+        }
+        assert!(scene.spheres.is_none());
+        assert_eq!(scene.background.unwrap().intensity, 0.0);
+    }
+
+    #[test]
+    fn test_default_scene() {
+        let scene = default_scene().build().expect("Invalid scene");
+        assert!(scene.camera_fov > 0.0);
+        assert!(scene.camera_near_far[0] > 0.0 && scene.camera_near_far[0] < scene.camera_near_far[1]);
+
+        let materials = scene.materials.expect("Missing materials");
+        let spheres = scene.spheres.expect("Missing spheres");
+        assert!(spheres.iter().all(|sphere| (0..materials.len()).contains(&(sphere.material_texture_id[0] as usize))));
+        assert!(materials[DEFAULT_SCENE_LIGHT as usize].emission > 0.0);
+        assert_eq!(scene.meshes.expect("Missing ground").len(), 2);
+
+        // The sphere is in front of the camera and within the far plane
+        let target = Vec3::from(scene.camera_target.expect("Missing camera target"));
+        let camera = Vec3::from(scene.camera_position);
+        let sphere = Vec3::from_slice(&spheres[0].center[..3]);
+        assert_eq!(spheres[0].material_texture_id[0] as i32, DEFAULT_SCENE_SPHERE);
+        assert!((target - camera).normalize().dot((sphere - camera).normalize()) > 0.99);
+        assert!(camera.distance(sphere) < scene.camera_near_far[1]);
+    }
+}
@@ -0,0 +1,153 @@
+use glam::Vec3;
+
+use crate::structs::{Sphere, Triangle};
+
+/// Rays closer than this don't count as a hit, same as in the raytracing shader.
+const MIN_DISTANCE: f32 = 0.0001;
+
+/// Distance along the ray to the closest sphere or triangle, or `None` if nothing is hit before `max_distance`.
+///
+/// This is the CPU counterpart of the intersection tests in the raytracing shader, used for picking a point
+/// in the scene, e.g. the surface under the cursor. All primitives are tested, so it isn't meant to run every frame.
+/// The distance is measured in units of `direction`, so pass a normalized direction to get a distance in scene units.
+pub fn raycast(origin: Vec3, direction: Vec3, spheres: &[Sphere], triangles: &[Triangle], max_distance: f32) -> Option<f32> {
+    closest_hit(origin, direction, spheres, triangles, 0.0, max_distance).map(|hit| hit.distance)
+}
+
+/// Primitive hit by a ray, see `closest_hit`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HitPrimitive {
+    /// Index into the spheres.
+    Sphere(usize),
+    /// Index into the triangles.
+    Triangle(usize),
+}
+
+/// Closest hit of a ray found by `closest_hit`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayHit {
+    pub primitive: HitPrimitive,
+    /// Distance along the ray in units of the direction.
+    pub distance: f32,
+}
+
+/// Like `raycast`, but also returns which primitive was hit. Only hits between `min_distance` and `max_distance` count,
+/// e.g. the near and far plane of a camera ray.
+pub fn closest_hit(origin: Vec3, direction: Vec3, spheres: &[Sphere], triangles: &[Triangle], min_distance: f32, max_distance: f32) -> Option<RayHit> {
+    let min_distance = min_distance.max(MIN_DISTANCE);
+    let sphere_hits = spheres.iter().enumerate().filter_map(|(index, sphere)| {
+        intersect_sphere(origin, direction, sphere, min_distance).map(|distance| RayHit { primitive: HitPrimitive::Sphere(index), distance })
+    });
+    let triangle_hits = triangles.iter().enumerate().filter_map(|(index, triangle)| {
+        intersect_triangle(origin, direction, triangle)
+            .filter(|&distance| distance > min_distance)
+            .map(|distance| RayHit { primitive: HitPrimitive::Triangle(index), distance })
+    });
+    sphere_hits.chain(triangle_hits)
+        .filter(|hit| hit.distance < max_distance)
+        .min_by(|a, b| a.distance.total_cmp(&b.distance))
+}
+
+/// Closest intersection further than `min_distance`, if the origin is inside the sphere this is the exit point.
+fn intersect_sphere(origin: Vec3, direction: Vec3, sphere: &Sphere, min_distance: f32) -> Option<f32> {
+    let radius = sphere.radius[0];
+    if radius.is_nan() || radius <= 0.0 {
+        return None;
+    }
+    let oc = origin - Vec3::new(sphere.center[0], sphere.center[1], sphere.center[2]);
+    let a = direction.dot(direction);
+    let b = 2.0 * oc.dot(direction);
+    let c = oc.dot(oc) - radius * radius;
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 || a == 0.0 {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    [(-b - root) / (2.0 * a), (-b + root) / (2.0 * a)].into_iter().find(|&t| t > min_distance)
+}
+
+/// Möller-Trumbore ray triangle intersection.
+fn intersect_triangle(origin: Vec3, direction: Vec3, triangle: &Triangle) -> Option<f32> {
+    let v0 = Vec3::from(triangle.points[0]);
+    let edge1 = Vec3::from(triangle.points[1]) - v0;
+    let edge2 = Vec3::from(triangle.points[2]) - v0;
+
+    let ray_cross_e2 = direction.cross(edge2);
+    let det = edge1.dot(ray_cross_e2);
+    if det.abs() < f32::EPSILON {
+        return None; // Ray is parallel to the triangle
+    }
+
+    let inv_det = 1.0 / det;
+    let centered = origin - v0;
+    let u = inv_det * centered.dot(ray_cross_e2);
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let centered_cross_e1 = centered.cross(edge1);
+    let v = inv_det * direction.dot(centered_cross_e1);
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let t = inv_det * edge2.dot(centered_cross_e1);
+    (t > MIN_DISTANCE).then_some(t)
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Point3;
+
+    use super::*;
+
+    fn triangle_at_z(z: f32) -> Triangle {
+        Triangle::new([[-1.0, -1.0, z], [1.0, -1.0, z], [0.0, 1.0, z]], [0.0, 0.0, 1.0], 0, [-1.0; 4], [[0.0; 2]; 3])
+    }
+
+    #[test]
+    fn test_raycast_closest_hit() {
+        let spheres = [Sphere::new(Point3::new(0.0, 0.0, -10.0), 1.0, 0, [-1, -1, -1, -1])];
+        let triangles = [triangle_at_z(-5.0), triangle_at_z(-3.0)];
+        let forward = Vec3::new(0.0, 0.0, -1.0);
+
+        let t = raycast(Vec3::ZERO, forward, &spheres, &triangles, 100.0).unwrap();
+        assert!((t - 3.0).abs() < 1e-5);
+        let t = raycast(Vec3::ZERO, forward, &spheres, &[], 100.0).unwrap();
+        assert!((t - 9.0).abs() < 1e-5);
+        // Too far away
+        assert!(raycast(Vec3::ZERO, forward, &spheres, &[], 5.0).is_none());
+    }
+
+    #[test]
+    fn test_raycast_miss() {
+        let spheres = [Sphere::new(Point3::new(0.0, 0.0, -10.0), 1.0, 0, [-1, -1, -1, -1]), Sphere::empty()];
+        let triangles = [triangle_at_z(-3.0), Triangle::empty()];
+        // Pointing away from everything
+        assert!(raycast(Vec3::ZERO, Vec3::new(0.0, 0.0, 1.0), &spheres, &triangles, 100.0).is_none());
+        // Passing next to the triangle and the sphere
+        assert!(raycast(Vec3::new(5.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0), &spheres, &triangles, 100.0).is_none());
+    }
+
+    #[test]
+    fn test_closest_hit() {
+        let spheres = [Sphere::new(Point3::new(0.0, 0.0, -10.0), 1.0, 0, [-1, -1, -1, -1])];
+        let triangles = [triangle_at_z(-5.0), triangle_at_z(-3.0)];
+        let forward = Vec3::new(0.0, 0.0, -1.0);
+
+        let hit = closest_hit(Vec3::ZERO, forward, &spheres, &triangles, 0.0, 100.0).unwrap();
+        assert_eq!(hit.primitive, HitPrimitive::Triangle(1));
+        // Hits before the minimum distance are skipped, like at the near plane
+        let hit = closest_hit(Vec3::ZERO, forward, &spheres, &triangles, 6.0, 100.0).unwrap();
+        assert_eq!(hit.primitive, HitPrimitive::Sphere(0));
+        assert!((hit.distance - 9.0).abs() < 1e-5);
+        let hit = closest_hit(Vec3::ZERO, forward, &spheres, &triangles, 9.5, 100.0).unwrap();
+        assert!((hit.distance - 11.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_raycast_inside_sphere() {
+        let spheres = [Sphere::new(Point3::new(0.0, 0.0, 0.0), 2.0, 0, [-1, -1, -1, -1])];
+        let t = raycast(Vec3::ZERO, Vec3::new(1.0, 0.0, 0.0), &spheres, &[], 100.0).unwrap();
+        assert!((t - 2.0).abs() < 1e-5);
+    }
+}
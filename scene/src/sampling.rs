@@ -0,0 +1,379 @@
+use glam::Vec3;
+use image::{DynamicImage, Rgba, Rgba32FImage};
+use rand::{Rng, SeedableRng};
+
+/// Number of precomputed sub-pixel offsets of the low-discrepancy patterns, the shader wraps around after them.
+pub const SAMPLE_OFFSET_COUNT: usize = 64;
+/// Width and height of the blue noise tile, repeated over the screen.
+pub const BLUE_NOISE_SIZE: u32 = 64;
+/// Ranks of `blue_noise(BLUE_NOISE_SIZE, 0)` as a 16 bit grayscale PNG, generating the tile takes too long for every start.
+const BLUE_NOISE_PNG: &[u8] = include_bytes!("../../res/blue_noise.png");
+
+/// How the camera rays are jittered inside their pixel, stored as `ShaderConfig::sampling_pattern`.
+///
+/// The low-discrepancy patterns cover the pixel more evenly than random offsets,
+/// so the accumulated image converges faster.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum SamplingPattern {
+    /// Offsets from the random number generator of the shader.
+    #[default]
+    Random = 0,
+    /// Halton sequence with the bases 2 and 3, shifted per pixel.
+    Halton = 1,
+    /// The first two dimensions of the Sobol sequence, shifted per pixel.
+    Sobol = 2,
+    /// A blue noise tile, shifted by the golden ratio per sample.
+    BlueNoise = 3,
+}
+
+impl SamplingPattern {
+    pub const ALL: [SamplingPattern; 4] = [SamplingPattern::Random, SamplingPattern::Halton, SamplingPattern::Sobol, SamplingPattern::BlueNoise];
+
+    /// Converts the value of `ShaderConfig::sampling_pattern`, unknown values use random offsets like the shader.
+    pub fn from_u32(value: u32) -> Self {
+        Self::ALL.get(value as usize).copied().unwrap_or_default()
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SamplingPattern::Random => "Random",
+            SamplingPattern::Halton => "Halton",
+            SamplingPattern::Sobol => "Sobol",
+            SamplingPattern::BlueNoise => "Blue Noise",
+        }
+    }
+}
+
+/// When the camera rays are jittered inside their pixel, stored as `ShaderConfig::jitter_mode`.
+///
+/// Jittered rays anti-alias the accumulated image, but while the camera moves the temporal denoiser
+/// blends the jittered frames into a blurry image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum JitterMode {
+    /// Jitter only while the camera stands still, moving images stay sharp.
+    #[default]
+    Auto = 0,
+    /// Always jitter the rays.
+    Always = 1,
+    /// Always trace through the pixel centers.
+    Never = 2,
+}
+
+impl JitterMode {
+    pub const ALL: [JitterMode; 3] = [JitterMode::Auto, JitterMode::Always, JitterMode::Never];
+
+    /// Converts the value of `ShaderConfig::jitter_mode`, unknown values use `Auto`.
+    pub fn from_u32(value: u32) -> Self {
+        Self::ALL.get(value as usize).copied().unwrap_or_default()
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            JitterMode::Auto => "Auto",
+            JitterMode::Always => "Always",
+            JitterMode::Never => "Never",
+        }
+    }
+
+    /// Returns `true` if the rays of a frame are jittered, `camera_moving` is set if the camera moved since the last frame.
+    pub fn enabled(self, camera_moving: bool) -> bool {
+        match self {
+            JitterMode::Auto => !camera_moving,
+            JitterMode::Always => true,
+            JitterMode::Never => false,
+        }
+    }
+}
+
+/// Element `index` of the Halton sequence with the given base (the radical inverse of `index`), in 0..1.
+pub fn halton(mut index: u32, base: u32) -> f32 {
+    let mut result = 0.0;
+    let mut fraction = 1.0 / base as f32;
+    while index > 0 {
+        result += (index % base) as f32 * fraction;
+        index /= base;
+        fraction /= base as f32;
+    }
+    result
+}
+
+/// Point `index` of the two dimensional Sobol sequence, in 0..1.
+pub fn sobol_2d(mut index: u32) -> [f32; 2] {
+    let (mut x, mut y) = (0u32, 0u32);
+    // Direction numbers of the second dimension, the first one is the van der Corput sequence
+    let mut direction = 1u32 << 31;
+    let mut bit = 0;
+    while index > 0 {
+        if index & 1 == 1 {
+            x ^= 1 << (31 - bit);
+            y ^= direction;
+        }
+        index >>= 1;
+        direction ^= direction >> 1;
+        bit += 1;
+    }
+    // Only 24 bits fit into the mantissa, so the values stay below 1.0
+    [(x >> 8) as f32 / (1 << 24) as f32, (y >> 8) as f32 / (1 << 24) as f32]
+}
+
+/// Sub-pixel offsets for the shader, `[halton base 2, halton base 3, sobol x, sobol y]` per sample.
+///
+/// Both sequences start at index 1, their first point would be the pixel corner.
+pub fn sample_offsets(count: usize) -> Vec<[f32; 4]> {
+    (1..=count as u32).map(|index| {
+        let [sobol_x, sobol_y] = sobol_2d(index);
+        [halton(index, 2), halton(index, 3), sobol_x, sobol_y]
+    }).collect()
+}
+
+/// Cosine weighted direction on the hemisphere around `normal`, from the two uniform random numbers `u1` and `u2` in 0..1.
+///
+/// A point is picked uniformly on the unit disk and projected up onto the hemisphere (Malley's method), so directions
+/// near the normal are more likely, proportional to the cosine to the normal like the light reflected by a lambertian surface.
+/// Same as `sample_cosine_hemisphere` in `raygen.wgsl`, the pdf of the direction is `cosine_hemisphere_pdf`.
+pub fn sample_cosine_hemisphere(normal: Vec3, u1: f32, u2: f32) -> Vec3 {
+    let radius = u1.sqrt();
+    let phi = 2.0 * std::f32::consts::PI * u2;
+    let (x, y) = (radius * phi.cos(), radius * phi.sin());
+    let z = (1.0 - u1).max(0.0).sqrt();
+    let (tangent, bitangent) = tangent_frame(normal);
+    (tangent * x + bitangent * y + normal * z).normalize()
+}
+
+/// Pdf of a direction from `sample_cosine_hemisphere` per solid angle, `cos(theta) / pi` and 0 below the surface.
+pub fn cosine_hemisphere_pdf(normal: Vec3, direction: Vec3) -> f32 {
+    normal.dot(direction.normalize()).max(0.0) / std::f32::consts::PI
+}
+
+// Tangent and bitangent around the normal, the tangent is the one of `sphere_tangent` in the shader
+fn tangent_frame(normal: Vec3) -> (Vec3, Vec3) {
+    let tangent = Vec3::new(-normal.z, 0.0, normal.x);
+    // At the poles the tangent is undefined
+    let tangent = if tangent.length() < 0.0001 { Vec3::X } else { tangent.normalize() };
+    (tangent, normal.cross(tangent))
+}
+
+/// Generates a tileable `size` x `size` blue noise texture with the void and cluster method.
+///
+/// Every texel has a different rank, the values are the ranks divided by the number of texels, so they are evenly spread over 0..1
+/// and texels with similar values are far apart. The result is the same for the same `seed`.
+pub fn blue_noise(size: u32, seed: u64) -> Vec<f32> {
+    let size = size as usize;
+    let count = size * size;
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+
+    // Gaussian energy of a set texel at every toroidal offset
+    let sigma = 1.5f32;
+    let kernel: Vec<f32> = (0..count).map(|index| {
+        let wrap = |value: usize| value.min(size - value) as f32;
+        let (dx, dy) = (wrap(index % size), wrap(index / size));
+        (-(dx * dx + dy * dy) / (2.0 * sigma * sigma)).exp()
+    }).collect();
+    let mut energy = vec![0.0f32; count];
+    let mut set = vec![false; count];
+    let update = |energy: &mut Vec<f32>, texel: usize, sign: f32| {
+        let (x, y) = (texel % size, texel / size);
+        for (index, value) in energy.iter_mut().enumerate() {
+            let dx = (index % size + size - x) % size;
+            let dy = (index / size + size - y) % size;
+            *value += sign * kernel[dy * size + dx];
+        }
+    };
+    let tightest_cluster = |energy: &[f32], set: &[bool]| (0..count).filter(|&index| set[index])
+        .max_by(|&a, &b| energy[a].total_cmp(&energy[b])).unwrap();
+    let largest_void = |energy: &[f32], set: &[bool]| (0..count).filter(|&index| !set[index])
+        .min_by(|&a, &b| energy[a].total_cmp(&energy[b])).unwrap();
+
+    // Random initial pattern of about a tenth of the texels
+    let initial_count = (count / 10).max(1);
+    while set.iter().filter(|&&value| value).count() < initial_count {
+        let index = rng.gen_range(0..count);
+        if !set[index] {
+            set[index] = true;
+            update(&mut energy, index, 1.0);
+        }
+    }
+    // Spread it out by moving the texel of the tightest cluster into the largest void until it stays
+    for _ in 0..count {
+        let cluster = tightest_cluster(&energy, &set);
+        set[cluster] = false;
+        update(&mut energy, cluster, -1.0);
+        let void = largest_void(&energy, &set);
+        set[void] = true;
+        update(&mut energy, void, 1.0);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut ranks = vec![0usize; count];
+    // The texels of the initial pattern are ranked by removing the tightest clusters first
+    let mut removed = set.clone();
+    let mut removed_energy = energy.clone();
+    for rank in (0..initial_count).rev() {
+        let cluster = tightest_cluster(&removed_energy, &removed);
+        removed[cluster] = false;
+        update(&mut removed_energy, cluster, -1.0);
+        ranks[cluster] = rank;
+    }
+    // The remaining texels fill the largest voids
+    for rank in initial_count..count {
+        let void = largest_void(&energy, &set);
+        set[void] = true;
+        update(&mut energy, void, 1.0);
+        ranks[void] = rank;
+    }
+
+    ranks.iter().map(|&rank| rank as f32 / count as f32).collect()
+}
+
+/// The `BLUE_NOISE_SIZE` x `BLUE_NOISE_SIZE` blue noise tile shipped in `res/blue_noise.png`, same as `blue_noise(BLUE_NOISE_SIZE, 0)`.
+pub fn precomputed_blue_noise() -> Vec<f32> {
+    let ranks = image::load_from_memory(BLUE_NOISE_PNG).expect("The blue noise tile is a valid PNG").into_luma16();
+    let count = ranks.len();
+    ranks.pixels().map(|rank| rank[0] as f32 / count as f32).collect()
+}
+
+/// Blue noise tile for the shader with two independent values per texel, from a `size` x `size` tile of `blue_noise`.
+///
+/// The red channel holds `noise`, the green channel the same tile shifted by half its size,
+/// which keeps both channels blue noise but uncorrelated for the x and y offset of a ray.
+pub fn blue_noise_image(noise: &[f32], size: u32) -> DynamicImage {
+    let half = size / 2;
+    let image = Rgba32FImage::from_fn(size, size, |x, y| {
+        let shifted = ((y + half) % size) * size + (x + half) % size;
+        Rgba([noise[(y * size + x) as usize], noise[shifted as usize], 0.0, 1.0])
+    });
+    DynamicImage::ImageRgba32F(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jitter_mode() {
+        assert!(JitterMode::Auto.enabled(false));
+        assert!(!JitterMode::Auto.enabled(true));
+        assert!(JitterMode::Always.enabled(true));
+        assert!(!JitterMode::Never.enabled(false));
+        assert_eq!(JitterMode::from_u32(2), JitterMode::Never);
+        assert_eq!(JitterMode::from_u32(9), JitterMode::Auto);
+    }
+
+    #[test]
+    fn test_halton() {
+        assert_eq!(halton(0, 2), 0.0);
+        assert_eq!([halton(1, 2), halton(2, 2), halton(3, 2), halton(4, 2)], [0.5, 0.25, 0.75, 0.125]);
+        let expected = [1.0 / 3.0, 2.0 / 3.0, 1.0 / 9.0, 4.0 / 9.0];
+        for (index, expected) in (1..=4).zip(expected) {
+            assert!((halton(index, 3) - expected).abs() < 1e-6, "halton({}, 3)", index);
+        }
+
+        // Every interval of 1/8 holds one of the first 8 points
+        let mut buckets = [0; 8];
+        for index in 0..8 {
+            buckets[(halton(index, 2) * 8.0) as usize] += 1;
+        }
+        assert_eq!(buckets, [1; 8]);
+    }
+
+    #[test]
+    fn test_sobol_2d() {
+        assert_eq!(sobol_2d(0), [0.0, 0.0]);
+        assert_eq!(sobol_2d(1), [0.5, 0.5]);
+        assert_eq!(sobol_2d(2), [0.25, 0.75]);
+        assert_eq!(sobol_2d(3), [0.75, 0.25]);
+
+        // The first 16 points are a (0, 4, 2)-net: every 4x4 cell holds exactly one point
+        let mut cells = [0; 16];
+        for index in 0..16 {
+            let [x, y] = sobol_2d(index);
+            cells[(y * 4.0) as usize * 4 + (x * 4.0) as usize] += 1;
+        }
+        assert_eq!(cells, [1; 16]);
+        assert!(sobol_2d(u32::MAX).iter().all(|&value| value < 1.0));
+    }
+
+    #[test]
+    fn test_sample_offsets() {
+        let offsets = sample_offsets(SAMPLE_OFFSET_COUNT);
+        assert_eq!(offsets.len(), SAMPLE_OFFSET_COUNT);
+        assert_eq!(offsets[0], [0.5, 1.0 / 3.0, 0.5, 0.5]);
+        assert!(offsets.iter().flatten().all(|value| (0.0..1.0).contains(value)));
+    }
+
+    #[test]
+    fn test_sample_cosine_hemisphere() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+        let count = 200_000;
+        for normal in [Vec3::Y, Vec3::NEG_Y, Vec3::new(1.0, 2.0, -3.0).normalize()] {
+            let directions: Vec<Vec3> = (0..count).map(|_| sample_cosine_hemisphere(normal, rng.gen(), rng.gen())).collect();
+            assert!(directions.iter().all(|direction| (direction.length() - 1.0).abs() < 1e-4 && direction.dot(normal) >= -1e-6));
+
+            // The mean direction is the normal scaled by E[cos(theta)] = 2/3
+            let mean = directions.iter().sum::<Vec3>() / count as f32;
+            assert!((mean - normal * (2.0 / 3.0)).length() < 0.01, "{:?} for {:?}", mean, normal);
+
+            // Half of the samples are within 45 degrees of the normal, P(cos^2(theta) > 1/2) = 1/2
+            let within = directions.iter().filter(|direction| direction.dot(normal).powi(2) > 0.5).count();
+            assert!((within as f32 / count as f32 - 0.5).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn test_cosine_hemisphere_pdf() {
+        let normal = Vec3::new(0.3, -0.8, 0.2).normalize();
+        assert!((cosine_hemisphere_pdf(normal, normal * 2.0) - 1.0 / std::f32::consts::PI).abs() < 1e-6);
+        assert_eq!(cosine_hemisphere_pdf(normal, -normal), 0.0);
+
+        // Integrated over the sphere with a midpoint rule in theta and phi around the z axis
+        let (steps_theta, steps_phi) = (400, 800);
+        let (d_theta, d_phi) = (std::f32::consts::PI / steps_theta as f32, 2.0 * std::f32::consts::PI / steps_phi as f32);
+        let mut integral = 0.0;
+        for i in 0..steps_theta {
+            let theta = (i as f32 + 0.5) * d_theta;
+            for j in 0..steps_phi {
+                let phi = (j as f32 + 0.5) * d_phi;
+                let direction = Vec3::new(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos());
+                integral += cosine_hemisphere_pdf(normal, direction) * theta.sin() * d_theta * d_phi;
+            }
+        }
+        assert!((integral - 1.0).abs() < 1e-3, "{}", integral);
+    }
+
+    #[test]
+    fn test_blue_noise() {
+        let size = 16;
+        let noise = blue_noise(size, 7);
+        assert_eq!(noise, blue_noise(size, 7));
+
+        // Every rank is used once
+        let mut ranks: Vec<usize> = noise.iter().map(|value| (value * noise.len() as f32).round() as usize).collect();
+        ranks.sort();
+        assert_eq!(ranks, (0..noise.len()).collect::<Vec<_>>());
+
+        let image = blue_noise_image(&noise, size).to_rgba32f();
+        assert_eq!(image.dimensions(), (size, size));
+        assert_eq!(image.get_pixel(0, 0)[0], noise[0]);
+        assert_eq!(image.get_pixel(0, 0)[1], noise[(size / 2 * size + size / 2) as usize]);
+
+        // The darkest tenth of the texels are spread out, none of them are direct neighbours
+        let size = size as usize;
+        let dark: Vec<usize> = (0..noise.len()).filter(|&index| noise[index] < 0.1).collect();
+        for &a in &dark {
+            for &b in &dark {
+                let dx = (a % size).abs_diff(b % size);
+                let dy = (a / size).abs_diff(b / size);
+                let (dx, dy) = (dx.min(size - dx), dy.min(size - dy));
+                assert!(a == b || dx + dy > 1, "texels {} and {} are neighbours", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_precomputed_blue_noise() {
+        // Regenerate res/blue_noise.png if `blue_noise` changes
+        assert_eq!(precomputed_blue_noise(), blue_noise(BLUE_NOISE_SIZE, 0));
+    }
+}
@@ -0,0 +1,243 @@
+use std::path::Path;
+
+use crate::camera_path::CameraPath;
+use crate::config::{validate_id, Config, ConfigError};
+use crate::structs::{Background, Material, ShaderConfig, Sphere, Triangle};
+
+/// Where the scene rendered by the raytracer comes from.
+///
+/// Converts from a config path (`&str`, `String` or `Option<&str>`) or from a `Config`,
+/// e.g. one built with the `SceneBuilder`.
+#[derive(Debug)]
+pub enum SceneSource {
+    /// Loads the scene from a TOML config file.
+    File(String),
+    /// Uses a scene that is already loaded or was built in code.
+    Scene(Box<Config>),
+}
+
+impl SceneSource {
+    /// Config file used if no path is given.
+    pub const DEFAULT_PATH: &'static str = "res/Config.toml";
+
+    /// Returns the config of the scene, reading the config file if necessary.
+    ///
+    /// If there is no config file at `DEFAULT_PATH`, the built-in `Config::default_scene` is used instead,
+    /// so the first start always shows something.
+    pub fn load(self) -> Result<Config, ConfigError> {
+        match self {
+            SceneSource::File(path) if path == Self::DEFAULT_PATH && !Path::new(&path).exists() => Ok(Config::default_scene()),
+            SceneSource::File(path) => Config::new(&path),
+            SceneSource::Scene(config) => Ok(*config),
+        }
+    }
+}
+
+impl From<&str> for SceneSource {
+    fn from(path: &str) -> Self {
+        SceneSource::File(path.to_string())
+    }
+}
+
+impl From<String> for SceneSource {
+    fn from(path: String) -> Self {
+        SceneSource::File(path)
+    }
+}
+
+impl From<Option<&str>> for SceneSource {
+    /// Without a path the config file at `DEFAULT_PATH` is used.
+    fn from(path: Option<&str>) -> Self {
+        SceneSource::File(path.unwrap_or(Self::DEFAULT_PATH).to_string())
+    }
+}
+
+impl From<Config> for SceneSource {
+    fn from(config: Config) -> Self {
+        SceneSource::Scene(Box::new(config))
+    }
+}
+
+/// Builds a scene in code instead of loading it from a config file.
+///
+/// The result is the same `Config` that is loaded from a TOML file, so it can be passed to the renderer directly.
+/// Materials are numbered in the order they are added, starting at 0, and are referenced by these ids
+/// from spheres and triangles. Values that aren't set use the same defaults as a config file.
+///
+/// # Example
+///
+/// ```
+/// use cgmath::Point3;
+/// use scene::{Material, SceneBuilder, Sphere};
+///
+/// let scene = SceneBuilder::new()
+///     .add_material(Material::new([0.8, 0.3, 0.2], [0.5, 0.5, 0.5], 0.5, 0.0, 0.0))  // material 0
+///     .add_material(Material::new([0.8, 0.8, 0.8], [0.5, 0.5, 0.5], 1.0, 0.0, 0.0))  // material 1
+///     .add_sphere(Sphere::new(Point3::new(0.0, 0.0, -2.0), 0.5, 0, [-1, -1, -1, -1]))
+///     .add_sphere(Sphere::new(Point3::new(0.0, -100.5, -2.0), 100.0, 1, [-1, -1, -1, -1]))
+///     .set_camera([0.0, 0.5, 1.0], [0.0, -10.0], 45.0)
+///     .build()
+///     .expect("Invalid scene");
+///
+/// assert_eq!(scene.spheres.as_ref().map(|spheres| spheres.len()), Some(2));
+/// assert_eq!(scene.materials.as_ref().map(|materials| materials.len()), Some(2));
+/// assert_eq!(scene.camera_fov, 45.0);
+/// ```
+#[derive(Debug)]
+pub struct SceneBuilder {
+    config: Config,
+}
+
+impl Default for SceneBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SceneBuilder {
+    /// Creates an empty scene with the camera at the origin looking down the negative z axis.
+    pub fn new() -> Self {
+        Self { config: Config::default() }
+    }
+
+    /// Adds a material, its id is the number of materials added before.
+    pub fn add_material(mut self, material: Material) -> Self {
+        self.config.materials.get_or_insert_with(Vec::new).push(material);
+        self
+    }
+
+    pub fn add_sphere(mut self, sphere: Sphere) -> Self {
+        self.config.spheres.get_or_insert_with(Vec::new).push(sphere);
+        self
+    }
+
+    /// Adds the triangles of a mesh. They are rendered through the BVH like the triangles of loaded models.
+    pub fn add_triangle_mesh(mut self, triangles: Vec<Triangle>) -> Self {
+        self.config.meshes.get_or_insert_with(Vec::new).extend(triangles);
+        self
+    }
+
+    /// Sets the camera position, rotation (yaw and pitch in degrees) and vertical field of view in degrees.
+    pub fn set_camera(mut self, position: [f32; 3], rotation: [f32; 2], fov: f32) -> Self {
+        self.config.camera_position = position;
+        self.config.camera_rotation = rotation;
+        self.config.camera_target = None;
+        self.config.camera_fov = fov;
+        self
+    }
+
+    /// Sets the camera position, the point it looks at and the vertical field of view in degrees.
+    pub fn set_camera_look_at(mut self, position: [f32; 3], target: [f32; 3], fov: f32) -> Self {
+        self.config.camera_position = position;
+        self.config.camera_target = Some(target);
+        self.config.camera_fov = fov;
+        self
+    }
+
+    pub fn set_camera_path(mut self, camera_path: CameraPath) -> Self {
+        self.config.camera_path = Some(camera_path);
+        self
+    }
+
+    /// Sets the background and optionally the path of the HDRI shown as background.
+    pub fn set_background(mut self, background: Background, hdri_path: Option<&str>) -> Self {
+        self.config.background = Some(background);
+        self.config.background_path = hdri_path.map(str::to_string);
+        self
+    }
+
+    pub fn set_shader_config(mut self, shader_config: ShaderConfig) -> Self {
+        self.config.shader_config = ShaderConfig { transparent_background: self.config.shader_config.transparent_background, ..shader_config };
+        self
+    }
+
+    /// Sets the clear color, an alpha of 0 renders the background transparent.
+    pub fn set_clear_color(mut self, clear_color: [f32; 4]) -> Self {
+        self.config.render_clear_color = clear_color;
+        self.config.shader_config.transparent_background = if clear_color[3] == 0.0 { 1 } else { 0 };
+        self
+    }
+
+    /// Returns the scene, after checking the spheres like the config loader does.
+    pub fn build(self) -> Result<Config, ConfigError> {
+        for (index, sphere) in self.config.spheres.iter().flatten().enumerate() {
+            if sphere.radius[0].is_nan() || sphere.radius[0] <= 0.0 {
+                return Err(ConfigError::InvalidSphereRadius { sphere: index, radius: sphere.radius[0] });
+            }
+            validate_id("sphere material_id", index, sphere.material_texture_id[0] as i64)?;
+            for id in sphere.material_texture_id[1..].iter().chain(&sphere.emission_texture_id[..1]) {
+                validate_id("sphere texture_id", index, *id as i64)?;
+            }
+        }
+        Ok(self.config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Point3;
+
+    use super::*;
+    use crate::config::{CONFIG_VERSION, DEFAULT_CAMERA_NEAR_FAR, DEFAULT_BVH_LEAF_SIZE, DEFAULT_CLEAR_COLOR};
+
+    #[test]
+    fn test_scene_builder_defaults() {
+        let scene = SceneBuilder::new().build().unwrap();
+        assert!(scene.materials.is_none());
+        assert!(scene.spheres.is_none());
+        assert!(scene.meshes.is_none());
+        assert_eq!(scene.camera_near_far, DEFAULT_CAMERA_NEAR_FAR);
+        assert_eq!(scene.render_clear_color, DEFAULT_CLEAR_COLOR);
+        assert_eq!(scene.bvh_leaf_size, DEFAULT_BVH_LEAF_SIZE);
+        assert_eq!(scene.version, CONFIG_VERSION);
+        assert!(!scene.transparent_background());
+    }
+
+    #[test]
+    fn test_scene_builder_mesh_and_background() {
+        let triangle = Triangle::new([[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]], [0.0, 0.0, 1.0], 0, [-1.0; 4], [[0.0; 2]; 3]);
+        let scene = SceneBuilder::new()
+            .add_triangle_mesh(vec![triangle, triangle])
+            .add_triangle_mesh(vec![triangle])
+            .set_background(Background::new(-1, -1, 2.0), Some("sky.hdr"))
+            .set_clear_color([0.0, 0.0, 0.0, 0.0])
+            .build()
+            .unwrap();
+        assert_eq!(scene.meshes.map(|meshes| meshes.len()), Some(3));
+        assert_eq!(scene.background.unwrap().intensity, 2.0);
+        assert_eq!(scene.background_path.as_deref(), Some("sky.hdr"));
+        assert_eq!(scene.shader_config.transparent_background, 1);
+    }
+
+    #[test]
+    fn test_scene_builder_invalid_sphere() {
+        let result = SceneBuilder::new()
+            .add_sphere(Sphere::new(Point3::new(0.0, 0.0, 0.0), 0.0, 0, [-1, -1, -1, -1]))
+            .build();
+        assert_eq!(result.unwrap_err(), ConfigError::InvalidSphereRadius { sphere: 0, radius: 0.0 });
+
+        let result = SceneBuilder::new()
+            .add_sphere(Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, 0, [-1, -2, -1, -1]))
+            .build();
+        assert!(matches!(result, Err(ConfigError::InvalidId { .. })));
+    }
+
+    #[test]
+    fn test_scene_source_from() {
+        assert!(matches!(SceneSource::from(None), SceneSource::File(path) if path == SceneSource::DEFAULT_PATH));
+        assert!(matches!(SceneSource::from(Some("scene.toml")), SceneSource::File(path) if path == "scene.toml"));
+
+        let scene = SceneBuilder::new().set_camera([1.0, 2.0, 3.0], [0.0, 0.0], 60.0).build().unwrap();
+        let loaded = SceneSource::from(scene).load().unwrap();
+        assert_eq!(loaded.camera_position, [1.0, 2.0, 3.0]);
+        assert_eq!(loaded.camera_fov, 60.0);
+    }
+
+    #[test]
+    fn test_scene_source_default_scene() {
+        // The tests run in the crate directory, which has no default config file
+        assert!(!Path::new(SceneSource::DEFAULT_PATH).exists());
+        let config = SceneSource::from(None).load().unwrap();
+        assert!(config.spheres.is_some() && config.camera_fov > 0.0);
+    }
+}
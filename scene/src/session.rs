@@ -0,0 +1,108 @@
+//! State of the application that is saved on exit and restored on the next start, kept apart from the scene config.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use cgmath::{Deg, InnerSpace, Point3, Quaternion};
+use serde::{Deserialize, Serialize};
+
+use crate::camera::{Camera, Projection};
+use crate::config::ConfigError;
+use crate::structs::ShaderConfig;
+
+/// Camera pose, shader and denoising settings and window size of the last run.
+///
+/// The session is written to its own TOML file next to the scene config (see `path_for`), so the authored
+/// scene isn't changed. Values in the session override the ones from the config when the scene is loaded again.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct SessionState {
+    pub camera_position: [f32; 3],
+    /// Rotation of the camera as a quaternion `[w, x, y, z]`.
+    pub camera_rotation: [f32; 4],
+    /// Vertical field of view in degrees.
+    pub camera_fov: f32,
+    /// Physical size of the window.
+    pub window_size: [u32; 2],
+    pub shader_config: ShaderConfig,
+}
+
+impl SessionState {
+    pub fn new(camera: &Camera, projection: &Projection, window_size: [u32; 2], shader_config: ShaderConfig) -> Self {
+        let rotation = camera.rotation;
+        Self {
+            camera_position: camera.position.into(),
+            camera_rotation: [rotation.s, rotation.v.x, rotation.v.y, rotation.v.z],
+            camera_fov: Deg::from(projection.fovy).0,
+            window_size,
+            shader_config,
+        }
+    }
+
+    /// Session file of the scene config at `config_path`, e.g. `res/config.session.toml` for `res/config.toml`.
+    pub fn path_for(config_path: impl AsRef<Path>) -> PathBuf {
+        config_path.as_ref().with_extension("session.toml")
+    }
+
+    /// Reads a session file written by `save`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read or isn't a valid session, e.g. one of an older version.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|e| format!("Could not read session {}: {}", path.display(), e))?;
+        toml::from_str(&text).map_err(|e| ConfigError::Invalid(format!("Invalid session {}: {}", path.display(), e)))
+    }
+
+    /// Writes the session to a TOML file, replacing an existing one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be written.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let text = toml::to_string(self).map_err(io::Error::other)?;
+        std::fs::write(path, text)
+    }
+
+    /// Moves the camera to the saved pose and sets the saved fov.
+    pub fn apply_camera(&self, camera: &mut Camera, projection: &mut Projection) {
+        let [w, x, y, z] = self.camera_rotation;
+        camera.position = Point3::from(self.camera_position);
+        camera.rotation = Quaternion::new(w, x, y, z).normalize();
+        projection.fovy = Deg(self.camera_fov).into();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Rad;
+
+    #[test]
+    fn test_session_state_round_trip() {
+        let camera = Camera::new(Point3::new(1.0, 2.0, -3.5), Rad(0.3), Rad(-0.1));
+        let projection = Projection::new(800, 600, Deg(35.0), 0.1, 100.0);
+        let shader_config = ShaderConfig { ray_max_bounces: 3, exposure: -1.5, atrous_iterations: 2, ..ShaderConfig::default() };
+        let session = SessionState::new(&camera, &projection, [1024, 768], shader_config);
+
+        let text = toml::to_string(&session).unwrap();
+        assert_eq!(toml::from_str::<SessionState>(&text).unwrap(), session);
+
+        // Unique per test run, so test runs at the same time don't share the file
+        let path = std::env::temp_dir().join(format!("raytracer_test_session_{}.toml", std::process::id()));
+        session.save(&path).unwrap();
+        let loaded = SessionState::load(&path).unwrap();
+        assert_eq!(loaded, session);
+
+        let mut restored = Camera::new(Point3::new(0.0, 0.0, 0.0), Rad(0.0), Rad(0.0));
+        let mut restored_projection = Projection::new(800, 600, Deg(90.0), 0.1, 100.0);
+        loaded.apply_camera(&mut restored, &mut restored_projection);
+        assert_eq!(restored.position, camera.position);
+        assert!((restored.rotation.dot(camera.rotation) - 1.0).abs() < 1e-5);
+        assert!((Deg::from(restored_projection.fovy).0 - 35.0).abs() < 1e-4);
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(SessionState::load(&path).is_err());
+        assert_eq!(SessionState::path_for("res/config.toml"), PathBuf::from("res/config.session.toml"));
+    }
+}
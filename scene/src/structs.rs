@@ -2,18 +2,20 @@
 use rand::Rng;
 use cgmath::{Matrix4, Point3, SquareMatrix};
 use rtbvh::{Aabb, Primitive, SpatialTriangle, BvhNode};
-use serde::Deserialize;
-use glam::Vec3;
+use serde::{Deserialize, Serialize};
+use glam::{Affine3A, Mat3, Vec3};
 
 use crate::camera::{Camera, Projection};
+use crate::sampling::{JitterMode, SamplingPattern};
 
 //-----------Camera-----------------
 #[repr(C)]
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct CameraUniform {
-    frame: [f32; 4],
+    frame: [f32; 4], //[frame counter, vertical fov in degrees, near plane, far plane]
     view_position: [f32; 4],
     view_proj: [[f32; 4]; 4],
+    projection: [f32; 4], //[aspect ratio (width / height), unused, unused, unused]
 }
 
 impl CameraUniform {
@@ -22,6 +24,7 @@ impl CameraUniform {
             frame: [0.0; 4],
             view_position: [0.0; 4],
             view_proj: Matrix4::identity().into(),
+            projection: [1.0, 0.0, 0.0, 0.0],
         }
     }
 
@@ -29,26 +32,117 @@ impl CameraUniform {
         self.view_position = camera.position.to_homogeneous().into();
         self.view_proj = Matrix4::from(camera.rotation).into();
         self.frame[1] = projection.fovy.0.to_degrees() as f32;
+        (self.frame[2], self.frame[3]) = projection.near_far();
+        // The shader widens the vertical fov by the aspect ratio, so the image isn't stretched on wide windows
+        self.projection[0] = projection.aspect();
     }
 
     pub fn update_frame(&mut self) {
         self.frame[0] += 1.0;
     }
+
+    /// Number of frames traced since the start, counted by `update_frame`.
+    pub fn frame_count(&self) -> u32 {
+        self.frame[0] as u32
+    }
+
+    /// Returns `true` if the camera moved, turned or zoomed since `previous`, the frame counter is ignored.
+    pub fn view_changed(&self, previous: &CameraUniform) -> bool {
+        self.view_position != previous.view_position
+            || self.view_proj != previous.view_proj
+            || self.frame[1..] != previous.frame[1..]
+            || self.projection != previous.projection
+    }
 }
 
 
 //-----------Material-----------------
+/// How texture coordinates outside of 0..1 are handled when sampling the textures of a material.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum WrapMode {
+    #[default]
+    Repeat = 0,
+    Clamp = 1,
+    Mirror = 2,
+}
+
+impl WrapMode {
+    /// Parses the wrap mode names used in the config: "repeat", "clamp" and "mirror".
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "repeat" => Some(WrapMode::Repeat),
+            "clamp" => Some(WrapMode::Clamp),
+            "mirror" => Some(WrapMode::Mirror),
+            _ => None,
+        }
+    }
+}
+
+/// How the alpha of the diffuse texture of a material is used, stored in `Material::alpha_mode` and `Material::alpha_cutoff`.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum AlphaMode {
+    /// The alpha is ignored, every hit is shaded.
+    #[default]
+    Opaque,
+    /// Rays pass through the surface where the alpha is below `cutoff`, e.g. for leaves and fences.
+    Mask { cutoff: f32 },
+    /// Rays pass through the surface with a probability of 1 - alpha, which averages to a semi-transparent surface.
+    Blend,
+}
+
+impl AlphaMode {
+    /// Cutoff of `Mask` if none is given, the same as in glTF.
+    pub const DEFAULT_CUTOFF: f32 = 0.5;
+
+    /// Parses the alpha mode names used in the config: "opaque", "mask" and "blend".
+    pub fn from_name(name: &str, cutoff: f32) -> Option<Self> {
+        match name {
+            "opaque" => Some(AlphaMode::Opaque),
+            "mask" => Some(AlphaMode::Mask { cutoff }),
+            "blend" => Some(AlphaMode::Blend),
+            _ => None,
+        }
+    }
+
+    /// Value of `Material::alpha_mode` in the shader.
+    pub fn as_u32(self) -> u32 {
+        match self {
+            AlphaMode::Opaque => 0,
+            AlphaMode::Mask { .. } => 1,
+            AlphaMode::Blend => 2,
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, Debug, Deserialize)]
 pub struct Material {
     #[serde(rename = "color")]
     pub albedo: [f32; 4],
+    /// Weight of the bounce for opaque materials. For dielectrics (`ior > 0.0`) it is the absorption
+    /// coefficient per unit of distance inside the material, [0, 0, 0] is clear glass.
     pub attenuation: [f32; 4],
-    pub roughness: f32,     //0.0 - 1.0 0.0 = mirror, 1.0 = diffuse
+    /// Perceptual roughness like in glTF, 0.0 = mirror, 1.0 = diffuse. The width of the reflection lobes is
+    /// `roughness_to_alpha`, i.e. roughness squared unless `ShaderConfig::roughness_linear` is set.
+    pub roughness: f32,
     pub emission: f32,      //0.0 - 1.0 0.0 = no emission, >0.0 = emission
-    ior: f32,           //index of refraction
-    __padding: f32,
-
+    pub ior: f32,           //index of refraction
+    #[serde(default)]
+    pub wrap_mode: u32,     //WrapMode as u32, selects the sampler in the shader
+    #[serde(default)]
+    pub metallic: f32,      //0.0 = dielectric, 1.0 = metal, the reflection of metals is tinted by the albedo
+    #[serde(default)]
+    pub alpha_mode: u32,    //AlphaMode as u32, rays pass through the surface where the diffuse texture is transparent
+    #[serde(default)]
+    pub alpha_cutoff: f32,  //alpha below which AlphaMode::Mask lets the rays pass
+    #[serde(default)]
+    pub double_sided: u32,  //1 = the back of the surface is shaded like the front, e.g. for thin planes and leaves
+    #[serde(default)]
+    pub clearcoat: f32,     //0.0 - 1.0 strength of an untinted glossy layer on top of opaque materials, e.g. car paint or varnish
+    #[serde(default)]
+    pub clearcoat_roughness: f32, //perceptual roughness of the clearcoat, remapped like roughness
+    #[serde(skip)]
+    _padding: [f32; 2],     //the shader reads the materials as 16 byte aligned structs
 }
 
 impl Material {
@@ -59,39 +153,257 @@ impl Material {
             roughness: roughness,
             emission: emission,
             ior: ior,
-            __padding: 0.0,
+            wrap_mode: WrapMode::Repeat as u32,
+            metallic: 0.0,
+            alpha_mode: AlphaMode::Opaque.as_u32(),
+            alpha_cutoff: AlphaMode::DEFAULT_CUTOFF,
+            double_sided: 0,
+            clearcoat: 0.0,
+            clearcoat_roughness: 0.0,
+            _padding: [0.0; 2],
+        }
+    }
+
+    pub fn with_wrap_mode(self, wrap_mode: WrapMode) -> Self {
+        Self { wrap_mode: wrap_mode as u32, ..self }
+    }
+
+    /// Sets the metalness between 0.0 (dielectric) and 1.0 (metal).
+    pub fn with_metallic(self, metallic: f32) -> Self {
+        Self { metallic, ..self }
+    }
+
+    /// Adds a clearcoat with the given strength and perceptual roughness, both between 0.0 and 1.0.
+    pub fn with_clearcoat(self, clearcoat: f32, clearcoat_roughness: f32) -> Self {
+        Self { clearcoat, clearcoat_roughness, ..self }
+    }
+
+    /// Sets how the alpha of the diffuse texture cuts holes into the surface.
+    pub fn with_alpha_mode(self, alpha_mode: AlphaMode) -> Self {
+        let alpha_cutoff = match alpha_mode {
+            AlphaMode::Mask { cutoff } => cutoff,
+            _ => AlphaMode::DEFAULT_CUTOFF,
+        };
+        Self { alpha_mode: alpha_mode.as_u32(), alpha_cutoff, ..self }
+    }
+
+    /// Shades the back of the surface like the front instead of with the normal facing away from the ray.
+    pub fn with_double_sided(self, double_sided: bool) -> Self {
+        Self { double_sided: double_sided as u32, ..self }
+    }
+
+    pub fn is_double_sided(&self) -> bool {
+        self.double_sided != 0
+    }
+
+    /// The alpha mode stored in `alpha_mode` and `alpha_cutoff`, unknown values are opaque.
+    pub fn alpha(&self) -> AlphaMode {
+        match self.alpha_mode {
+            1 => AlphaMode::Mask { cutoff: self.alpha_cutoff },
+            2 => AlphaMode::Blend,
+            _ => AlphaMode::Opaque,
         }
     }
 
     pub fn default() -> Self {
-        Self { albedo: [1.0, 1.0, 1.0, 1.0], attenuation: [1.0, 1.0, 1.0, 1.0], roughness: 0.5, emission: 0.0, ior: 0.0, __padding: 0.0 }
+        Self { albedo: [1.0, 1.0, 1.0, 1.0], attenuation: [1.0, 1.0, 1.0, 1.0], roughness: 0.5, emission: 0.0, ior: 0.0, wrap_mode: WrapMode::Repeat as u32, metallic: 0.0, alpha_mode: AlphaMode::Opaque.as_u32(), alpha_cutoff: AlphaMode::DEFAULT_CUTOFF, double_sided: 0, clearcoat: 0.0, clearcoat_roughness: 0.0, _padding: [0.0; 2] }
+    }
+
+    /// Names of the presets that can be used with `preset`.
+    pub const PRESETS: [&'static str; 4] = ["glass", "gold", "mirror", "plastic"];
+
+    /// Clear glass, refracts with the index of refraction of window glass and absorbs no light.
+    pub fn glass() -> Self {
+        Self::new([1.0, 1.0, 1.0], [0.0, 0.0, 0.0], 0.0, 0.0, 1.5)
+    }
+
+    /// Slightly rough gold, the albedo is the reflectance of gold at normal incidence.
+    pub fn gold() -> Self {
+        Self::new([1.0, 0.766, 0.336], [1.0, 1.0, 1.0], 0.2, 0.0, 0.0).with_metallic(1.0)
+    }
+
+    /// A perfect mirror with a slightly grey silver coating.
+    pub fn mirror() -> Self {
+        Self::new([0.95, 0.95, 0.95], [1.0, 1.0, 1.0], 0.0, 0.0, 0.0).with_metallic(1.0)
+    }
+
+    /// A glossy dielectric with a colored base and untinted highlights.
+    pub fn plastic(color: [f32; 3]) -> Self {
+        Self::new(color, [1.0, 1.0, 1.0], 0.6, 0.0, 0.0)
+    }
+
+    /// Looks up a preset by one of the `PRESETS` names, plastic is white.
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "glass" => Some(Self::glass()),
+            "gold" => Some(Self::gold()),
+            "mirror" => Some(Self::mirror()),
+            "plastic" => Some(Self::plastic([0.8, 0.8, 0.8])),
+            _ => None,
+        }
+    }
+}
+
+/// Converts a material roughness to the alpha of the GGX distribution, the width of the reflection lobes.
+///
+/// Roughness is perceptual by default, as in glTF and most DCC tools, and squared to get alpha, which makes
+/// the visible blur grow evenly with the roughness. With `linear` the roughness is used as alpha directly.
+/// Same as `roughness_alpha` in the raytracing shader, with `linear` set from `ShaderConfig::roughness_linear`.
+pub fn roughness_to_alpha(roughness: f32, linear: bool) -> f32 {
+    if linear {
+        roughness
+    } else {
+        roughness * roughness
+    }
+}
+
+/// What rays that leave the scene see, stored as float in `Background::material_texture_id[2]`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum BackgroundMode {
+    /// The built-in sky gradient.
+    #[default]
+    Sky = 0,
+    /// The background texture (HDRI) tinted with the color of the background material.
+    Texture = 1,
+    /// A single color, `Background::color`.
+    Color = 2,
+    /// Top, horizon and bottom colors blended by the height of the ray direction, see `SkyGradient`.
+    Gradient = 3,
+}
+
+impl BackgroundMode {
+    pub const ALL: [BackgroundMode; 4] = [BackgroundMode::Sky, BackgroundMode::Texture, BackgroundMode::Color, BackgroundMode::Gradient];
+
+    /// Parses the background kinds used in the config: "sky", "hdri", "color" and "gradient".
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "sky" => Some(BackgroundMode::Sky),
+            "hdri" => Some(BackgroundMode::Texture),
+            "color" => Some(BackgroundMode::Color),
+            "gradient" => Some(BackgroundMode::Gradient),
+            _ => None,
+        }
+    }
+
+    /// Converts the stored mode, unknown values show the sky like in the shader.
+    pub fn from_u32(value: u32) -> Self {
+        Self::ALL.get(value as usize).copied().unwrap_or_default()
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BackgroundMode::Sky => "Sky",
+            BackgroundMode::Texture => "HDRI",
+            BackgroundMode::Color => "Solid Color",
+            BackgroundMode::Gradient => "Gradient",
+        }
+    }
+}
+
+/// Linear rgb colors of `BackgroundMode::Gradient`.
+///
+/// Rays above the horizon blend from the horizon to the top color, rays below it to the bottom color,
+/// by the height of the normalized ray direction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SkyGradient {
+    pub top: [f32; 3],
+    pub horizon: [f32; 3],
+    pub bottom: [f32; 3],
+}
+
+impl Default for SkyGradient {
+    /// Blue sky over a grey ground, with a white horizon.
+    fn default() -> Self {
+        Self {
+            top: [0.5, 0.7, 1.0],
+            horizon: [1.0, 1.0, 1.0],
+            bottom: [0.4, 0.4, 0.4],
+        }
+    }
+}
+
+impl SkyGradient {
+    /// Color seen by a ray with the given height of its normalized direction, same as `gradient_color` in the raytracing shader.
+    pub fn color(&self, direction_y: f32) -> [f32; 3] {
+        let (end, t) = if direction_y >= 0.0 { (self.top, direction_y) } else { (self.bottom, -direction_y) };
+        let t = t.min(1.0);
+        [0, 1, 2].map(|i| self.horizon[i] + (end[i] - self.horizon[i]) * t)
     }
 }
 
 #[repr(C)]
-#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, Debug, Deserialize)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, Debug, Deserialize, PartialEq)]
 pub struct Background {
-    pub material_texture_id: [f32; 4], //[material_id, texture_id_diffuse, ,]
+    pub material_texture_id: [f32; 4], //[material_id, texture_id_diffuse, BackgroundMode, ]
+    /// Multiplier of the background color in every mode
     pub intensity: f32,
-    pub _padding: [f32; 3],
+    /// Linear rgb color of `BackgroundMode::Color`
+    pub color: [f32; 3],
+    /// Top, horizon and bottom color of `BackgroundMode::Gradient`, the fourth value is unused, see `gradient`
+    pub gradient: [[f32; 4]; 3],
 }
 
 impl Background {
+    /// Uses the background texture if both ids are set, otherwise the sky.
     pub fn new(material_id: i32, texture_id: i32, intensity: f32) -> Self {
+        let mode = if material_id >= 0 && texture_id >= 0 { BackgroundMode::Texture } else { BackgroundMode::Sky };
         Self {
-            material_texture_id: [material_id as f32, texture_id as f32, 0.0, 0.0],
+            material_texture_id: [material_id as f32, texture_id as f32, mode as u32 as f32, 0.0],
             intensity: intensity,
-            _padding: [0.0; 3],
+            color: [1.0; 3],
+            gradient: Self::gradient_colors(SkyGradient::default()),
         }
     }
     
     pub fn default() -> Self {
         Self {
-            material_texture_id: [-1.0, -1.0, 0.0, 0.0],
+            material_texture_id: [-1.0, -1.0, BackgroundMode::Sky as u32 as f32, 0.0],
             intensity: 1.0,
-            _padding: [0.0; 3],
+            color: [1.0; 3],
+            gradient: Self::gradient_colors(SkyGradient::default()),
         }
     }
+
+    /// Switches to a solid color background.
+    pub fn with_color(mut self, color: [f32; 3]) -> Self {
+        self.color = color;
+        self.set_mode(BackgroundMode::Color);
+        self
+    }
+
+    /// Switches to a gradient background.
+    pub fn with_gradient(mut self, gradient: SkyGradient) -> Self {
+        self.set_gradient(gradient);
+        self.set_mode(BackgroundMode::Gradient);
+        self
+    }
+
+    /// Colors of the gradient background, also kept while another mode is used.
+    pub fn gradient(&self) -> SkyGradient {
+        let [top, horizon, bottom] = self.gradient.map(|[r, g, b, _]| [r, g, b]);
+        SkyGradient { top, horizon, bottom }
+    }
+
+    pub fn set_gradient(&mut self, gradient: SkyGradient) {
+        self.gradient = Self::gradient_colors(gradient);
+    }
+
+    fn gradient_colors(gradient: SkyGradient) -> [[f32; 4]; 3] {
+        [gradient.top, gradient.horizon, gradient.bottom].map(|[r, g, b]| [r, g, b, 0.0])
+    }
+
+    pub fn mode(&self) -> BackgroundMode {
+        BackgroundMode::from_u32(self.material_texture_id[2] as u32)
+    }
+
+    pub fn set_mode(&mut self, mode: BackgroundMode) {
+        self.material_texture_id[2] = mode as u32 as f32;
+    }
+
+    /// Returns true if the texture mode can be used, which needs a background material and texture.
+    pub fn has_texture(&self) -> bool {
+        self.material_texture_id[0] >= 0.0 && self.material_texture_id[1] >= 0.0
+    }
 }
 
 //-----------Sphere-----------------
@@ -100,26 +412,80 @@ impl Background {
 #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, Deserialize, Debug)]
 pub struct Sphere {
     pub center: [f32; 4],
-    pub radius: [f32; 4],
+    pub radius: [f32; 4], //[radius, pole_axis], a pole axis of 0 is +y, see `with_uv_orientation`
     pub material_texture_id: [f32; 4], //[material_id, texture_id_diffuse, texture_id_roughness, texture_id_normal]
+    pub emission_texture_id: [f32; 4], //[texture_id_emission, uv_rotation in turns, 0.0, 0.0]
 }
 
 impl Sphere {
-    pub fn new(center: Point3<f32>, radius: f32, material_id: i32, texture_ids: [i32; 3]) -> Self {
+    /// `texture_ids` are the diffuse, roughness, normal and emission texture, -1 if unused.
+    pub fn new(center: Point3<f32>, radius: f32, material_id: i32, texture_ids: [i32; 4]) -> Self {
         let mut rng = rand::thread_rng();
         Self {
             center: [center[0], center[1], center[2], rng.gen_range(0.0..1.0)],//rand number in last slot
             radius: [radius, 0.0, 0.0, 0.0],
             material_texture_id: [material_id as f32, texture_ids[0] as f32, texture_ids[1] as f32, texture_ids[2] as f32], //material_id, texture_id_diffuse, texture_id_roughness, texture_id_normal
+            emission_texture_id: [texture_ids[3] as f32, 0.0, 0.0, 0.0],
         }
     }
 
+    /// Placeholder with a radius of 0 for scenes without spheres, it uses material 0 and no textures.
     pub fn empty() -> Self {
         Self {
             center: [0.0; 4],
             radius: [0.0; 4],
-            material_texture_id: [0.0; 4],
+            material_texture_id: [0.0, -1.0, -1.0, -1.0],
+            emission_texture_id: [-1.0, 0.0, 0.0, 0.0],
+        }
+    }
+
+    /// Orients the texture of the sphere, e.g. for planets with a tilted axis or labeled balls.
+    ///
+    /// `pole_axis` points to the top of the texture (v = 1) instead of +y, and the texture is turned
+    /// around it by `uv_rotation` degrees. A pole axis of zero keeps +y.
+    pub fn with_uv_orientation(mut self, pole_axis: [f32; 3], uv_rotation: f32) -> Self {
+        self.radius[1..].copy_from_slice(&Vec3::from(pole_axis).normalize_or_zero().to_array());
+        self.emission_texture_id[1] = uv_rotation / 360.0;
+        self
+    }
+
+    /// Rotation from the frame of the uv mapping, which has the pole on the y axis, to world space.
+    /// Same as `sphere_uv_frame` in the raytracing shader, the default pole gives the identity.
+    pub fn uv_frame(&self) -> Mat3 {
+        let pole = Vec3::new(self.radius[1], self.radius[2], self.radius[3]);
+        if pole == Vec3::ZERO {
+            return Mat3::IDENTITY;
+        }
+        let pole = pole.normalize();
+        let reference = if pole.z.abs() < 0.999 { Vec3::Z } else { Vec3::X };
+        let x_axis = pole.cross(reference).normalize();
+        Mat3::from_cols(x_axis, pole, x_axis.cross(pole))
+    }
+
+    /// Texture coordinates of a point on the sphere, same as `sphereUVMapping` in the raytracing shader.
+    ///
+    /// u goes once around the pole axis, from 0 at +x towards +z for the default +y pole, and v from 0 at the bottom to 1 at the top.
+    pub fn uv(&self, point: Vec3) -> [f32; 2] {
+        let p = self.uv_frame().transpose() * (point - self.center()).normalize();
+        let phi = p.z.atan2(p.x);
+        let theta = p.y.clamp(-1.0, 1.0).acos();
+        [(phi / (2.0 * std::f32::consts::PI) - self.emission_texture_id[1]).rem_euclid(1.0), (std::f32::consts::PI - theta) / std::f32::consts::PI]
+    }
+
+    /// Tangent frame of `uv` at a point with the given surface normal, same as `sphere_uv_tangent` in the raytracing shader.
+    ///
+    /// The tangent points towards growing u and the bitangent `cross(normal, tangent) * w` towards growing v.
+    /// At the poles, where u is undefined, the x axis of the uv frame is used.
+    pub fn tangent(&self, normal: Vec3) -> [f32; 4] {
+        let frame = self.uv_frame();
+        let local = frame.transpose() * normal;
+        let tangent = Vec3::new(-local.z, 0.0, local.x);
+        if tangent.length() < 0.0001 {
+            let [x, y, z] = frame.x_axis.to_array();
+            return [x, y, z, -1.0];
         }
+        let [x, y, z] = (frame * tangent.normalize()).to_array();
+        [x, y, z, -1.0]
     }
 }
 
@@ -141,17 +507,129 @@ impl Primitive for Sphere {
 pub struct Triangle{
     pub points: [[f32; 3]; 3],
     pub normal: [f32; 3],
+    /// Normals at the three vertices, interpolated across the triangle for smooth shading
+    pub vertex_normals: [[f32; 3]; 3],
+    /// Tangents at the three vertices as stored in the model file (x, y, z, bitangent sign), computed from the UVs if `None`
+    pub vertex_tangents: Option<[[f32; 4]; 3]>,
     pub material_id: i32,
-    pub texture_ids: [f32; 3],
+    /// Diffuse, roughness, normal and emission texture, -1 if unused
+    pub texture_ids: [f32; 4],
     pub tex_coords: [[f32; 2]; 3],
 }
 
 impl Triangle{
-    pub fn new(points: [[f32; 3]; 3], normal: [f32; 3], material_id: i32, texture_ids: [f32; 3], tex_coords: [[f32;2];3]) -> Triangle{
-        Self{points, normal, material_id, texture_ids, tex_coords}
+    /// Creates a flat shaded triangle, all vertex normals are set to `normal`.
+    pub fn new(points: [[f32; 3]; 3], normal: [f32; 3], material_id: i32, texture_ids: [f32; 4], tex_coords: [[f32;2];3]) -> Triangle{
+        Self{points, normal, vertex_normals: [normal; 3], vertex_tangents: None, material_id, texture_ids, tex_coords}
     }
     pub fn empty() -> Triangle{
-        Self{points: [[0.0; 3]; 3], normal: [0.0; 3], material_id: 0, texture_ids: [0.0; 3], tex_coords: [[0.0; 2]; 3]}
+        Self{points: [[0.0; 3]; 3], normal: [0.0; 3], vertex_normals: [[0.0; 3]; 3], vertex_tangents: None, material_id: 0, texture_ids: [0.0; 4], tex_coords: [[0.0; 2]; 3]}
+    }
+
+    /// Sets the per-vertex normals used for smooth shading.
+    pub fn with_vertex_normals(mut self, vertex_normals: [[f32; 3]; 3]) -> Triangle{
+        self.vertex_normals = vertex_normals;
+        self
+    }
+
+    /// Sets the per-vertex tangents loaded from a model file, they take precedence over the computed tangent.
+    pub fn with_vertex_tangents(mut self, vertex_tangents: [[f32; 4]; 3]) -> Triangle{
+        self.vertex_tangents = Some(vertex_tangents);
+        self
+    }
+
+    /// Surface area of the triangle, half the length of the cross product of two edges.
+    pub fn area(&self) -> f32 {
+        let edge1 = Vec3::from(self.points[1]) - Vec3::from(self.points[0]);
+        let edge2 = Vec3::from(self.points[2]) - Vec3::from(self.points[0]);
+        0.5 * edge1.cross(edge2).length()
+    }
+
+    /// Returns true if the triangle has (almost) no area, e.g. because two vertices are equal or all are on a line.
+    ///
+    /// Such triangles can't be hit by a ray and break the SAH binning of the BVH builder.
+    pub fn is_degenerate(&self) -> bool {
+        let edge1 = Vec3::from(self.points[1]) - Vec3::from(self.points[0]);
+        let edge2 = Vec3::from(self.points[2]) - Vec3::from(self.points[0]);
+        let double_area = edge1.cross(edge2).length();
+        !(double_area > f32::EPSILON * edge1.length().max(edge2.length()))
+    }
+
+    /// Returns the triangle with `transform` applied, e.g. to place a mesh in the scene.
+    ///
+    /// The points are transformed by the full matrix. Normals are transformed by the inverse-transpose of its
+    /// linear part and renormalized, so they stay perpendicular to the surface and keep facing outwards under
+    /// non-uniform scale. Rotations with a uniform scale take a fast path with the linear part itself.
+    /// Tangents follow the surface like the edges, their bitangent sign flips if the transform mirrors.
+    pub fn transformed(&self, transform: Affine3A) -> Triangle {
+        let linear = Mat3::from(transform.matrix3);
+        let normal_matrix = normal_matrix(linear);
+        let transform_normal = |normal: [f32; 3]| -> [f32; 3] { (normal_matrix * Vec3::from(normal)).normalize_or_zero().into() };
+        let mirrored = linear.determinant() < 0.0;
+        Triangle {
+            points: self.points.map(|point| transform.transform_point3(Vec3::from(point)).into()),
+            normal: transform_normal(self.normal),
+            vertex_normals: self.vertex_normals.map(transform_normal),
+            vertex_tangents: self.vertex_tangents.map(|tangents| tangents.map(|tangent| {
+                let direction = (linear * Vec3::new(tangent[0], tangent[1], tangent[2])).normalize_or_zero();
+                [direction.x, direction.y, direction.z, if mirrored { -tangent[3] } else { tangent[3] }]
+            })),
+            ..*self
+        }
+    }
+
+    /// Computes the tangent used for normal mapping from the positions and texture coordinates.
+    ///
+    /// The tangent points in the direction of increasing u and is made orthogonal to the normal.
+    /// The fourth component is the sign of the bitangent (`cross(normal, tangent) * w`), which flips for mirrored UVs.
+    /// If the triangle has vertex tangents from its model file, their average is used instead, since the normal maps
+    /// were baked with them. If the texture coordinates are degenerate, any tangent orthogonal to the normal is returned.
+    pub fn tangent(&self) -> [f32; 4] {
+        let normal = Vec3::from(self.normal).normalize_or_zero();
+        if let Some(vertex_tangents) = self.vertex_tangents {
+            let tangent: Vec3 = vertex_tangents.iter().map(|tangent| Vec3::new(tangent[0], tangent[1], tangent[2])).sum();
+            let tangent = (tangent - normal * normal.dot(tangent)).normalize_or_zero();
+            // Files without tangents leave them zero, those fall through to the computed tangent
+            if tangent != Vec3::ZERO {
+                let sign = if vertex_tangents[0][3] < 0.0 { -1.0 } else { 1.0 };
+                return [tangent.x, tangent.y, tangent.z, sign];
+            }
+        }
+
+        let edge1 = Vec3::from(self.points[1]) - Vec3::from(self.points[0]);
+        let edge2 = Vec3::from(self.points[2]) - Vec3::from(self.points[0]);
+        let delta_uv1 = [self.tex_coords[1][0] - self.tex_coords[0][0], self.tex_coords[1][1] - self.tex_coords[0][1]];
+        let delta_uv2 = [self.tex_coords[2][0] - self.tex_coords[0][0], self.tex_coords[2][1] - self.tex_coords[0][1]];
+
+        let det = delta_uv1[0] * delta_uv2[1] - delta_uv2[0] * delta_uv1[1];
+        if det.abs() > f32::EPSILON {
+            let tangent = (edge1 * delta_uv2[1] - edge2 * delta_uv1[1]) / det;
+            let bitangent = (edge2 * delta_uv1[0] - edge1 * delta_uv2[0]) / det;
+            // Gram-Schmidt orthogonalization against the normal
+            let tangent = (tangent - normal * normal.dot(tangent)).normalize_or_zero();
+            if tangent != Vec3::ZERO {
+                let sign = if normal.cross(tangent).dot(bitangent) < 0.0 { -1.0 } else { 1.0 };
+                return [tangent.x, tangent.y, tangent.z, sign];
+            }
+        }
+
+        let tangent = normal.any_orthonormal_vector();
+        [tangent.x, tangent.y, tangent.z, 1.0]
+    }
+}
+
+/// Matrix that transforms normals like `linear` transforms the surface, the inverse-transpose of `linear`.
+///
+/// For a rotation with a uniform scale the inverse-transpose only differs from `linear` by a scale factor,
+/// which the renormalization removes, so `linear` is returned without inverting it.
+fn normal_matrix(linear: Mat3) -> Mat3 {
+    // The columns of a uniformly scaled rotation are orthogonal and have the same length
+    let gram = linear.transpose() * linear;
+    let scale_squared = gram.x_axis.x;
+    if gram.abs_diff_eq(Mat3::from_diagonal(Vec3::splat(scale_squared)), 1e-5 * scale_squared) {
+        linear
+    } else {
+        linear.inverse().transpose()
     }
 }
 
@@ -165,6 +643,9 @@ pub struct TriangleUniform {
     texcords1: [f32; 4],
     texcords2: [f32; 4],    // tex3x, tex3y, 0.0, 0.0
     material_texture_id: [f32; 4], //[material_id, texture_id_diffuse, texture_id_roughness, texture_id_normal]
+    tangent: [f32; 4],  // tangent x, y, z, bitangent sign
+    vertex_normals: [[f32; 4]; 3],
+    emission_texture_id: [f32; 4], //[texture_id_emission, 0.0, 0.0, 0.0]
 }
 
 impl TriangleUniform {
@@ -177,6 +658,9 @@ impl TriangleUniform {
             material_texture_id: [triangle.material_id as f32, triangle.texture_ids[0] as f32, triangle.texture_ids[1] as f32, triangle.texture_ids[2] as f32],
             texcords1: [triangle.tex_coords[0][0], triangle.tex_coords[0][1], triangle.tex_coords[1][0], triangle.tex_coords[1][1]],
             texcords2: [triangle.tex_coords[2][0], triangle.tex_coords[2][1], 0.0, 0.0],
+            tangent: triangle.tangent(),
+            vertex_normals: triangle.vertex_normals.map(|normal| [normal[0], normal[1], normal[2], 0.0]),
+            emission_texture_id: [triangle.texture_ids[3], 0.0, 0.0, 0.0],
         }
     }
     pub fn empty() -> Self {
@@ -188,6 +672,9 @@ impl TriangleUniform {
             material_texture_id: [0.0; 4],
             texcords1: [0.0; 4],
             texcords2: [0.0; 4],
+            tangent: [0.0; 4],
+            vertex_normals: [[0.0; 4]; 3],
+            emission_texture_id: [-1.0, 0.0, 0.0, 0.0],
         }
     }
 }
@@ -222,9 +709,51 @@ impl SpatialTriangle for Triangle {
     }
 }
 
+//-----------BVH-----------------
+/// Kind of a primitive in the leaves of the BVH, stored next to its index so the shader picks the right intersection test.
+pub const BVH_PRIM_TRIANGLE: f32 = 0.0;
+pub const BVH_PRIM_SPHERE: f32 = 1.0;
+
+/// A triangle or a sphere of the scene, so both can be put into the same BVH.
+#[derive(Clone, Copy, Debug)]
+pub enum BvhPrimitive<'a> {
+    Triangle(&'a Triangle),
+    Sphere(&'a Sphere),
+}
+
+impl<'a> BvhPrimitive<'a> {
+    /// Collects the triangles followed by the spheres.
+    /// Spheres with a radius of 0 are skipped, they are the placeholder of a scene without spheres.
+    ///
+    /// Returns the primitives and, for each of them, its kind and index in the triangle or sphere buffer.
+    pub fn collect(triangles: &'a [Triangle], spheres: &'a [Sphere]) -> (Vec<BvhPrimitive<'a>>, Vec<[f32; 2]>) {
+        let triangles = triangles.iter().enumerate()
+            .map(|(index, triangle)| (BvhPrimitive::Triangle(triangle), [BVH_PRIM_TRIANGLE, index as f32]));
+        let spheres = spheres.iter().enumerate()
+            .filter(|(_, sphere)| sphere.radius[0] > 0.0)
+            .map(|(index, sphere)| (BvhPrimitive::Sphere(sphere), [BVH_PRIM_SPHERE, index as f32]));
+        triangles.chain(spheres).unzip()
+    }
+}
+
+impl Primitive for BvhPrimitive<'_> {
+    fn center(&self) -> glam::Vec3 {
+        match self {
+            BvhPrimitive::Triangle(triangle) => triangle.center(),
+            BvhPrimitive::Sphere(sphere) => sphere.center(),
+        }
+    }
+
+    fn aabb(&self) -> Aabb {
+        match self {
+            BvhPrimitive::Triangle(triangle) => triangle.aabb(),
+            BvhPrimitive::Sphere(sphere) => sphere.aabb(),
+        }
+    }
+}
 
 #[repr(C)]
-#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, Debug, PartialEq, Deserialize, Serialize)]
 pub struct BvhUniform {
     bounds_min: [f32; 4],
     bounds_max: [f32; 4],
@@ -241,16 +770,83 @@ impl BvhUniform {
             bounds_extra2: [bvh.bounds.extra2 as f32, 0.0, 0.0, 0.0],
         }
     }
+
+    /// Minimum and maximum corner of the bounding box.
+    pub fn bounds(&self) -> ([f32; 3], [f32; 3]) {
+        ([self.bounds_min[0], self.bounds_min[1], self.bounds_min[2]], [self.bounds_max[0], self.bounds_max[1], self.bounds_max[2]])
+    }
+
+    /// Range of the node in the primitive index list if it is a leaf, read the same way as in `intersectBVH` of the shader.
+    pub fn leaf_primitives(&self) -> Option<std::ops::Range<usize>> {
+        if self.bounds_extra1[0] > -1.0 {
+            let first = self.bounds_extra2[0] as usize;
+            Some(first..first + self.bounds_extra1[0] as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Index of the left child if the node is an inner node, the right child follows it.
+    pub fn left_child(&self) -> Option<usize> {
+        match self.leaf_primitives() {
+            Some(_) => None,
+            None => Some(self.bounds_extra2[0] as usize),
+        }
+    }
+}
+
+//-----------Debug View-----------------
+/// Output of the raytracing shader, selected with `ShaderConfig::debug_view`.
+///
+/// All views except `Beauty` show a value of the first hit of the camera ray instead of the traced color.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum DebugView {
+    #[default]
+    Beauty = 0,
+    Albedo = 1,
+    Normal = 2,
+    Depth = 3,
+    /// Number of visited BVH nodes, blue for few and red for many.
+    BvhHeatmap = 4,
+}
+
+impl DebugView {
+    pub const ALL: [DebugView; 5] = [DebugView::Beauty, DebugView::Albedo, DebugView::Normal, DebugView::Depth, DebugView::BvhHeatmap];
+
+    /// Converts the value of `ShaderConfig::debug_view`, unknown values show the beauty pass like in the shader.
+    pub fn from_u32(value: u32) -> Self {
+        Self::ALL.get(value as usize).copied().unwrap_or_default()
+    }
+
+    /// The next view, wraps around to `Beauty` after the last one.
+    pub fn next(self) -> Self {
+        Self::ALL[(self as usize + 1) % Self::ALL.len()]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DebugView::Beauty => "Beauty",
+            DebugView::Albedo => "Albedo",
+            DebugView::Normal => "Normal",
+            DebugView::Depth => "Depth",
+            DebugView::BvhHeatmap => "BVH Heatmap",
+        }
+    }
 }
 
 //-----------Shader Config-----------------
+// Fields missing in the [shader] section of the config keep their default values
 #[repr(C)]
-#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct ShaderConfig {
     //raytracing shader
     pub ray_max_bounces: i32,
     pub ray_samples_per_pixel: i32,
     pub ray_max_ray_distance: f32,
+    // Distance secondary rays start above the surface they leave, so they don't hit it again (shadow acne).
+    // Scale it with the scene, camera rays aren't offset, they start at the near plane instead
+    pub ray_bias: f32,
 
     //camera
     pub ray_focus_distance: f32,
@@ -261,12 +857,37 @@ pub struct ShaderConfig {
     pub ray_focus_viewer_visible: i32, //used as bool
     pub ray_debug_bvh_bounding_box: i32, //used as bool
     pub ray_debug_bvh_bounding_color: i32, //used as bool
-
-
+    pub debug_view: u32, //DebugView as u32
+
+    //adaptive sampling
+    pub adaptive_sampling_enabled: i32, //used as bool
+    pub adaptive_variance_threshold: f32,
+
+    //light sampling
+    // Combines light samples and hits of emissive triangles with multiple importance sampling
+    pub mis_enabled: i32, //used as bool
+    // Maximum luminance of a single sample, brighter samples are scaled down. Removes fireflies but darkens
+    // bright caustics and small lights, so the result no longer converges to the exact image
+    pub firefly_clamp: f32,
+
+    //materials
+    // The roughness of the materials is already the GGX alpha instead of perceptual roughness, see roughness_to_alpha
+    pub roughness_linear: i32, //used as bool
+
+    //anti-aliasing
+    // Sub-pixel offsets of the camera rays, SamplingPattern as u32
+    pub sampling_pattern: u32,
+    // When the camera rays are jittered inside their pixel, JitterMode as u32
+    pub jitter_mode: u32,
+    // Set every frame by the renderer from jitter_mode and the camera movement
+    #[serde(skip)]
+    pub jitter_enabled: i32, //used as bool
 
     //denoising shader
     pub first_pass: i32,
     pub second_pass: i32,
+    // Number of frames kept for temporal denoising, only read when the denoising buffers are created
+    pub denoise_history_length: i32,
 
     //temporal basic
     pub temporal_basic_low_threshold: f32,
@@ -292,6 +913,22 @@ pub struct ShaderConfig {
     pub spatial_den_cormpare_radius: i32,
     pub spatial_den_patch_radius: i32,
     pub spatial_den_significant_weight: f32,  
+    //spatial a-trous wavelet
+    pub atrous_iterations: i32,
+    pub atrous_color_phi: f32,
+    pub atrous_normal_phi: f32,
+    pub atrous_depth_phi: f32,
+
+    //display
+    // Exposure in stops, applied by the screen shader
+    pub exposure: f32,
+    // The render textures are this many times the window size in each direction, the screen shader averages
+    // the texels of every window pixel. Set by the renderer to the factor it actually uses, see MAX_SUPERSAMPLE
+    pub supersample: u32,
+
+    // Set from the [render] clear color, not part of the [shader] section
+    #[serde(skip)]
+    pub transparent_background: i32, //used as bool
 }
 
 impl Default for ShaderConfig {
@@ -300,6 +937,7 @@ impl Default for ShaderConfig {
             ray_max_bounces: 10,
             ray_samples_per_pixel: 1,
             ray_max_ray_distance: 10_000.0,
+            ray_bias: Self::DEFAULT_RAY_BIAS,
             ray_focus_distance: 2.5,
             ray_aperture: 0.005,
             ray_lens_radius: 0.0,
@@ -307,9 +945,19 @@ impl Default for ShaderConfig {
             ray_focus_viewer_visible: 0,
             ray_debug_bvh_bounding_box: 0,
             ray_debug_bvh_bounding_color: 0,
+            debug_view: DebugView::Beauty as u32,
+            adaptive_sampling_enabled: 0,
+            adaptive_variance_threshold: 0.0001,
+            mis_enabled: 1,
+            firefly_clamp: Self::FIREFLY_CLAMP_OFF,
+            roughness_linear: 0,
+            sampling_pattern: SamplingPattern::Random as u32,
+            jitter_mode: JitterMode::Auto as u32,
+            jitter_enabled: 1,
 
             first_pass: 4,
             second_pass: 2,
+            denoise_history_length: 1,
 
             temporal_basic_low_threshold: 0.05,
             temporal_basic_high_threshold: 0.2,
@@ -332,12 +980,68 @@ impl Default for ShaderConfig {
 
             spatial_den_cormpare_radius: 13,
             spatial_den_patch_radius: 5,
-            spatial_den_significant_weight: 0.001
+            spatial_den_significant_weight: 0.001,
+
+            atrous_iterations: 4,
+            atrous_color_phi: 0.3,
+            atrous_normal_phi: 64.0,
+            atrous_depth_phi: 0.05,
+
+            exposure: 0.0,
+            supersample: 1,
+
+            transparent_background: 0,
         }
     }
 }
 
 impl ShaderConfig {
+    /// Value of `first_pass`/`second_pass` that selects the À-Trous wavelet filter.
+    pub const ATROUS_PASS: i32 = 6;
+    /// Upper limit of `atrous_iterations`, the filter stride doubles every iteration.
+    pub const ATROUS_MAX_ITERATIONS: i32 = 8;
+    /// Upper limit of `denoise_history_length`.
+    pub const MAX_DENOISE_HISTORY: i32 = 8;
+    /// Default of `ray_bias`, for scenes of a few units up to a few hundred units.
+    pub const DEFAULT_RAY_BIAS: f32 = 0.001;
+    /// Range of `ray_bias` offered in the GUI.
+    pub const RAY_BIAS_RANGE: std::ops::RangeInclusive<f32> = 1.0e-6..=1.0;
+    /// Default of `firefly_clamp`, high enough that no sample is clamped.
+    pub const FIREFLY_CLAMP_OFF: f32 = 1.0e6;
+    /// Range of `exposure` in stops.
+    pub const EXPOSURE_RANGE: std::ops::RangeInclusive<f32> = -10.0..=10.0;
+    /// Change of `exposure` per press of the `+`/`-` keys.
+    pub const EXPOSURE_STEP: f32 = 0.5;
+    /// Upper limit of `supersample`, every step multiplies the traced rays by its square.
+    pub const MAX_SUPERSAMPLE: u32 = 4;
+
+    /// Changes the exposure by `steps` times `EXPOSURE_STEP`, clamped to `EXPOSURE_RANGE`.
+    pub fn step_exposure(&mut self, steps: f32) {
+        self.exposure = (self.exposure + steps * Self::EXPOSURE_STEP)
+            .clamp(*Self::EXPOSURE_RANGE.start(), *Self::EXPOSURE_RANGE.end());
+    }
+
+    /// Supersampling factor, `supersample` clamped to `1..=MAX_SUPERSAMPLE`.
+    pub fn supersample_factor(&self) -> u32 {
+        self.supersample.clamp(1, Self::MAX_SUPERSAMPLE)
+    }
+
+    /// Number of frames in the temporal denoising history, `denoise_history_length` clamped to a valid range.
+    pub fn denoise_history_layers(&self) -> u32 {
+        self.denoise_history_length.clamp(1, Self::MAX_DENOISE_HISTORY) as u32
+    }
+
+    /// Number of dispatches needed for a denoising pass with the given mode.
+    ///
+    /// The À-Trous filter runs `atrous_iterations` times (at least once), all other filters once.
+    pub fn denoising_iterations(&self, pass_mode: i32) -> u32 {
+        if pass_mode == Self::ATROUS_PASS {
+            self.atrous_iterations.clamp(1, Self::ATROUS_MAX_ITERATIONS) as u32
+        } else {
+            1
+        }
+    }
+
     pub fn default_denoise(shaderconfig: ShaderConfig) -> Self {
         Self {
             first_pass: 4,
@@ -364,6 +1068,11 @@ impl ShaderConfig {
             spatial_den_cormpare_radius: 13,
             spatial_den_patch_radius: 5,
             spatial_den_significant_weight: 0.001,
+
+            atrous_iterations: 4,
+            atrous_color_phi: 0.3,
+            atrous_normal_phi: 64.0,
+            atrous_depth_phi: 0.05,
             ..shaderconfig
         }
     }
@@ -373,6 +1082,7 @@ impl ShaderConfig {
             ray_max_bounces: 10,
             ray_samples_per_pixel: 1,
             ray_max_ray_distance: 10_000.0,
+            ray_bias: Self::DEFAULT_RAY_BIAS,
             ray_focus_distance: 2.5,
             ray_aperture: 0.005,
             ray_lens_radius: 0.0,
@@ -380,6 +1090,13 @@ impl ShaderConfig {
             ray_focus_viewer_visible: 0,
             ray_debug_bvh_bounding_box: 0,
             ray_debug_bvh_bounding_color: 0,
+            debug_view: DebugView::Beauty as u32,
+            adaptive_sampling_enabled: 0,
+            adaptive_variance_threshold: 0.0001,
+            mis_enabled: 1,
+            firefly_clamp: Self::FIREFLY_CLAMP_OFF,
+            sampling_pattern: SamplingPattern::Random as u32,
+            jitter_mode: JitterMode::Auto as u32,
             ..shaderconfig
         }
     }
@@ -389,6 +1106,161 @@ impl ShaderConfig {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_camera_uniform_frame_count() {
+        let camera = Camera::new(Point3::new(0.0, 1.0, 2.0), cgmath::Rad(0.0), cgmath::Rad(0.0));
+        let projection = Projection::new(800, 600, cgmath::Deg(45.0), 0.5, 250.0);
+        let mut camera_uniform = CameraUniform::new();
+        assert_eq!(camera_uniform.frame_count(), 0);
+        camera_uniform.update_frame();
+        camera_uniform.update_frame();
+        // Moving the camera doesn't reset the counter
+        camera_uniform.update_view_proj(&camera, &projection);
+        assert_eq!(camera_uniform.frame_count(), 2);
+    }
+
+    #[test]
+    fn test_camera_uniform_near_far() {
+        let camera = Camera::new(Point3::new(0.0, 1.0, 2.0), cgmath::Rad(0.0), cgmath::Rad(0.0));
+        let projection = Projection::new(800, 600, cgmath::Deg(45.0), 0.5, 250.0);
+        let mut camera_uniform = CameraUniform::new();
+        camera_uniform.update_view_proj(&camera, &projection);
+
+        assert_eq!(camera_uniform.frame[2], 0.5);
+        assert_eq!(camera_uniform.frame[3], 250.0);
+        assert!((camera_uniform.frame[1] - 45.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_camera_uniform_view_changed() {
+        let mut camera = Camera::new(Point3::new(0.0, 1.0, 2.0), cgmath::Rad(0.0), cgmath::Rad(0.0));
+        let mut projection = Projection::new(800, 600, cgmath::Deg(45.0), 0.1, 100.0);
+        let mut previous = CameraUniform::new();
+        previous.update_view_proj(&camera, &projection);
+
+        // A new frame of a still camera
+        let mut current = previous;
+        current.update_frame();
+        current.update_view_proj(&camera, &projection);
+        assert!(!current.view_changed(&previous));
+        assert!(JitterMode::Auto.enabled(current.view_changed(&previous)));
+
+        camera.position.x += 0.01;
+        current.update_view_proj(&camera, &projection);
+        assert!(current.view_changed(&previous));
+        assert!(!JitterMode::Auto.enabled(current.view_changed(&previous)));
+        assert!(JitterMode::Always.enabled(current.view_changed(&previous)));
+
+        camera.position.x -= 0.01;
+        let turned = Camera::new(camera.position, cgmath::Rad(0.1), cgmath::Rad(0.0));
+        current.update_view_proj(&turned, &projection);
+        assert!(current.view_changed(&previous));
+
+        current.update_view_proj(&camera, &projection);
+        assert!(!current.view_changed(&previous));
+        projection.fovy = cgmath::Deg(60.0).into();
+        current.update_view_proj(&camera, &projection);
+        assert!(current.view_changed(&previous));
+    }
+
+    #[test]
+    fn test_camera_uniform_aspect() {
+        let camera = Camera::new(Point3::new(0.0, 0.0, 0.0), cgmath::Rad(0.0), cgmath::Rad(0.0));
+        let mut projection = Projection::new(800, 600, cgmath::Deg(60.0), 0.1, 100.0);
+        projection.resize(1600, 800);
+        let mut camera_uniform = CameraUniform::new();
+        camera_uniform.update_view_proj(&camera, &projection);
+        assert_eq!(camera_uniform.projection[0], 2.0);
+
+        // Tangent of the half horizontal fov, from the ray through the right edge of the screen
+        let tan_half_vertical = (camera_uniform.frame[1].to_radians() / 2.0).tan();
+        let direction = camera.ray_direction(&projection, 1.0, 0.5);
+        let tan_half_horizontal = direction.x.abs() / direction.z.abs();
+        assert!((tan_half_horizontal - 2.0 * tan_half_vertical).abs() < 1e-5);
+        assert!((tan_half_horizontal - camera_uniform.projection[0] * tan_half_vertical).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_shader_config_denoising_iterations() {
+        let shader_config = ShaderConfig { atrous_iterations: 5, ..ShaderConfig::default() };
+        assert_eq!(shader_config.denoising_iterations(ShaderConfig::ATROUS_PASS), 5);
+        assert_eq!(shader_config.denoising_iterations(1), 1);
+
+        let shader_config = ShaderConfig { atrous_iterations: 0, ..ShaderConfig::default() };
+        assert_eq!(shader_config.denoising_iterations(ShaderConfig::ATROUS_PASS), 1);
+        let shader_config = ShaderConfig { atrous_iterations: 100, ..ShaderConfig::default() };
+        assert_eq!(shader_config.denoising_iterations(ShaderConfig::ATROUS_PASS), ShaderConfig::ATROUS_MAX_ITERATIONS as u32);
+    }
+
+    #[test]
+    fn test_shader_config_denoise_history_layers() {
+        assert_eq!(ShaderConfig::default().denoise_history_layers(), 1);
+        let shader_config = ShaderConfig { denoise_history_length: 3, ..ShaderConfig::default() };
+        assert_eq!(shader_config.denoise_history_layers(), 3);
+        let shader_config = ShaderConfig { denoise_history_length: 0, ..ShaderConfig::default() };
+        assert_eq!(shader_config.denoise_history_layers(), 1);
+        let shader_config = ShaderConfig { denoise_history_length: 100, ..ShaderConfig::default() };
+        assert_eq!(shader_config.denoise_history_layers(), ShaderConfig::MAX_DENOISE_HISTORY as u32);
+    }
+
+    #[test]
+    fn test_shader_config_adaptive_sampling_default() {
+        let shader_config = ShaderConfig::default();
+        assert_eq!(shader_config.adaptive_sampling_enabled, 0);
+        assert!(shader_config.adaptive_variance_threshold > 0.0);
+
+        // Resetting the raytracing settings also turns adaptive sampling off
+        let changed = ShaderConfig { adaptive_sampling_enabled: 1, adaptive_variance_threshold: 1.0, ..ShaderConfig::default() };
+        let reset = ShaderConfig::default_raytrace(changed);
+        assert_eq!(reset.adaptive_sampling_enabled, 0);
+        assert_eq!(reset.adaptive_variance_threshold, shader_config.adaptive_variance_threshold);
+    }
+
+    #[test]
+    fn test_shader_config_mis_default() {
+        assert_eq!(ShaderConfig::default().mis_enabled, 1);
+        let changed = ShaderConfig { mis_enabled: 0, ..ShaderConfig::default() };
+        assert_eq!(ShaderConfig::default_raytrace(changed).mis_enabled, 1);
+    }
+
+    #[test]
+    fn test_shader_config_firefly_clamp_off_by_default() {
+        assert_eq!(ShaderConfig::default().firefly_clamp, ShaderConfig::FIREFLY_CLAMP_OFF);
+        let changed = ShaderConfig { firefly_clamp: 5.0, ..ShaderConfig::default() };
+        assert_eq!(ShaderConfig::default_raytrace(changed).firefly_clamp, ShaderConfig::FIREFLY_CLAMP_OFF);
+    }
+
+    #[test]
+    fn test_shader_config_ray_bias() {
+        assert_eq!(ShaderConfig::default().ray_bias, ShaderConfig::DEFAULT_RAY_BIAS);
+        assert!(ShaderConfig::RAY_BIAS_RANGE.contains(&ShaderConfig::DEFAULT_RAY_BIAS));
+        let config: ShaderConfig = toml::from_str("ray_bias = 0.05").unwrap();
+        assert_eq!(config.ray_bias, 0.05);
+        assert_eq!(ShaderConfig::default_raytrace(config).ray_bias, ShaderConfig::DEFAULT_RAY_BIAS);
+    }
+
+    #[test]
+    fn test_shader_config_step_exposure() {
+        let mut shader_config = ShaderConfig::default();
+        assert_eq!(shader_config.exposure, 0.0);
+        shader_config.step_exposure(1.0);
+        assert_eq!(shader_config.exposure, ShaderConfig::EXPOSURE_STEP);
+        shader_config.step_exposure(-3.0);
+        assert_eq!(shader_config.exposure, -2.0 * ShaderConfig::EXPOSURE_STEP);
+        shader_config.step_exposure(100.0);
+        assert_eq!(shader_config.exposure, 10.0);
+        shader_config.step_exposure(-100.0);
+        assert_eq!(shader_config.exposure, -10.0);
+    }
+
+    #[test]
+    fn test_shader_config_supersample_factor() {
+        assert_eq!(ShaderConfig::default().supersample_factor(), 1);
+        assert_eq!(ShaderConfig { supersample: 0, ..ShaderConfig::default() }.supersample_factor(), 1);
+        assert_eq!(ShaderConfig { supersample: 3, ..ShaderConfig::default() }.supersample_factor(), 3);
+        assert_eq!(ShaderConfig { supersample: 100, ..ShaderConfig::default() }.supersample_factor(), ShaderConfig::MAX_SUPERSAMPLE);
+    }
+
     #[test]
     fn test_camera_uniform() {
         let camera = CameraUniform::new();
@@ -421,32 +1293,153 @@ mod tests {
         assert_eq!(material.roughness, 0.5);
         assert_eq!(material.emission, 0.0);
         assert_eq!(material.ior, 0.0);
+        assert_eq!(material.metallic, 0.0);
+        assert_eq!(material.with_metallic(1.0).metallic, 1.0);
+        assert_eq!(material.alpha(), AlphaMode::Opaque);
+        assert_eq!(material.with_alpha_mode(AlphaMode::Mask { cutoff: 0.25 }).alpha(), AlphaMode::Mask { cutoff: 0.25 });
+        assert_eq!(material.with_alpha_mode(AlphaMode::Blend).alpha(), AlphaMode::Blend);
+        assert!(!material.is_double_sided());
+        assert!(material.with_double_sided(true).is_double_sided());
+        assert_eq!((material.clearcoat, material.clearcoat_roughness), (0.0, 0.0));
+        let coated = material.with_clearcoat(1.0, 0.1);
+        assert_eq!((coated.clearcoat, coated.clearcoat_roughness), (1.0, 0.1));
+        // The shader reads the materials as an array of 16 byte aligned structs
+        assert_eq!(std::mem::size_of::<Material>() % 16, 0);
+    }
+
+    #[test]
+    fn test_roughness_to_alpha() {
+        assert_eq!(roughness_to_alpha(0.5, false), 0.25);
+        assert_eq!(roughness_to_alpha(0.5, true), 0.5);
+        // Mirrors and fully rough surfaces are the same in both conventions
+        for linear in [false, true] {
+            assert_eq!(roughness_to_alpha(0.0, linear), 0.0);
+            assert_eq!(roughness_to_alpha(1.0, linear), 1.0);
+        }
+    }
+
+    #[test]
+    fn test_material_presets() {
+        assert!((Material::glass().ior - 1.5).abs() < 1e-6);
+        assert_eq!(Material::glass().attenuation[..3], [0.0, 0.0, 0.0]);
+        assert_eq!(Material::gold().metallic, 1.0);
+        assert_eq!(Material::mirror().roughness, 0.0);
+        assert_eq!(Material::plastic([0.1, 0.2, 0.3]).albedo, [0.1, 0.2, 0.3, 0.0]);
+        assert_eq!(Material::plastic([0.1, 0.2, 0.3]).metallic, 0.0);
+        for name in Material::PRESETS {
+            assert!(Material::preset(name).is_some(), "missing preset {}", name);
+        }
+        assert!(Material::preset("wood").is_none());
     }
 
     #[test]
     fn test_background() {
         let background = Background::new(1, 1, 1.0);
-        assert_eq!(background.material_texture_id, [1.0, 1.0, 0.0, 0.0]);
+        assert_eq!(background.material_texture_id, [1.0, 1.0, 1.0, 0.0]);
         assert_eq!(background.intensity, 1.0);
+        assert_eq!(background.mode(), BackgroundMode::Texture);
+        assert!(background.has_texture());
+
+        let background = Background::new(-1, -1, 2.0);
+        assert_eq!(background.mode(), BackgroundMode::Sky);
+        assert!(!background.has_texture());
+
+        let background = Background::default().with_color([0.2, 0.4, 0.6]);
+        assert_eq!(background.mode(), BackgroundMode::Color);
+        assert_eq!(background.color, [0.2, 0.4, 0.6]);
+        assert_eq!(BackgroundMode::from_u32(7), BackgroundMode::Sky);
+    }
+
+    #[test]
+    fn test_background_gradient() {
+        let gradient = SkyGradient { top: [0.0, 0.0, 1.0], horizon: [1.0, 1.0, 1.0], bottom: [0.0, 0.0, 0.0] };
+        let background = Background::default().with_gradient(gradient);
+        assert_eq!(background.mode(), BackgroundMode::Gradient);
+        assert_eq!(background.gradient(), gradient);
+        assert_eq!(BackgroundMode::from_u32(3), BackgroundMode::Gradient);
+
+        assert_eq!(gradient.color(1.0), [0.0, 0.0, 1.0]);
+        assert_eq!(gradient.color(0.0), [1.0, 1.0, 1.0]);
+        assert_eq!(gradient.color(-0.5), [0.5, 0.5, 0.5]);
+        assert_eq!(gradient.color(-2.0), [0.0, 0.0, 0.0]);
     }
 
     #[test]
     fn test_sphere() {
-        let sphere = Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, 1, [1, 1, 1]);
+        let sphere = Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, 1, [1, 2, 3, 4]);
         assert_eq!(sphere.center[0..3], [0.0, 0.0, 0.0]);
         assert_eq!(sphere.radius, [1.0, 0.0, 0.0, 0.0]);
-        assert_eq!(sphere.material_texture_id, [1.0, 1.0, 1.0, 1.0]);
+        assert_eq!(sphere.material_texture_id, [1.0, 1.0, 2.0, 3.0]);
+        assert_eq!(sphere.emission_texture_id, [4.0, 0.0, 0.0, 0.0]);
+        assert_eq!(Sphere::empty().material_texture_id, [0.0, -1.0, -1.0, -1.0]);
+        assert_eq!(Sphere::empty().emission_texture_id[0], -1.0);
+    }
+
+    #[test]
+    fn test_sphere_uv_and_tangent() {
+        let sphere = Sphere::new(Point3::new(1.0, 2.0, 3.0), 2.0, 0, [-1; 4]);
+        let center = sphere.center();
+        let point = |phi: f32, theta: f32| center + 2.0 * Vec3::new(theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin());
+
+        assert_eq!(sphere.uv(center + Vec3::new(2.0, 0.0, 0.0)), [0.0, 0.5]);
+        assert!((sphere.uv(center + Vec3::new(0.0, 2.0, 0.0))[1] - 1.0).abs() < 1e-6);
+        // u stays in 0..1 behind the seam, so clamped and mirrored textures work as well
+        let [u, _] = sphere.uv(center + Vec3::new(0.0, 0.0, -2.0));
+        assert!((u - 0.75).abs() < 1e-6);
+
+        let epsilon = 1e-3;
+        for (phi, theta) in [(0.3_f32, 1.2_f32), (2.5, 0.4), (-1.0, 2.8), (-2.9, 1.6)] {
+            let hit = point(phi, theta);
+            let normal = (hit - center).normalize();
+            let [x, y, z, w] = sphere.tangent(normal);
+            let tangent = Vec3::new(x, y, z);
+            let bitangent = normal.cross(tangent) * w;
+
+            // The tangent follows the derivative of the position by u and the bitangent the one by v
+            let du = (point(phi + epsilon, theta) - hit).normalize();
+            let dv = (point(phi, theta - epsilon) - hit).normalize();
+            assert!(tangent.dot(du) > 0.999, "tangent {:?} for phi {} theta {}", tangent, phi, theta);
+            assert!(bitangent.dot(dv) > 0.999, "bitangent {:?} for phi {} theta {}", bitangent, phi, theta);
+            assert!(tangent.dot(normal).abs() < 1e-5);
+            assert!(sphere.uv(point(phi + epsilon, theta))[0] > sphere.uv(hit)[0]);
+            assert!(sphere.uv(point(phi, theta - epsilon))[1] > sphere.uv(hit)[1]);
+        }
+        assert_eq!(sphere.tangent(Vec3::Y), [1.0, 0.0, 0.0, -1.0]);
+    }
+
+    #[test]
+    fn test_sphere_uv_orientation() {
+        let sphere = Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, 0, [-1; 4]);
+        assert_eq!(sphere.uv_frame(), Mat3::IDENTITY);
+        assert_eq!(sphere.with_uv_orientation([0.0, 2.0, 0.0], 0.0).uv_frame(), Mat3::IDENTITY);
+
+        // The top of the texture is where the pole axis points, the texture turns around it
+        let tilted = sphere.with_uv_orientation([1.0, 0.0, 0.0], 0.0);
+        assert!((tilted.uv(Vec3::X)[1] - 1.0).abs() < 1e-6);
+        assert!(tilted.uv(Vec3::NEG_X)[1].abs() < 1e-6);
+        let turned = sphere.with_uv_orientation([0.0, 1.0, 0.0], 90.0);
+        let [u, v] = turned.uv(Vec3::Z);
+        assert!(u.abs() < 1e-6 && (v - 0.5).abs() < 1e-6, "{} {}", u, v);
+
+        // The tangent frame follows the orientation
+        let sphere = sphere.with_uv_orientation([0.3, -0.5, 0.8], 30.0);
+        let point = Vec3::new(0.6, 0.0, 0.8);
+        let [x, y, z, _] = sphere.tangent(point);
+        let epsilon = 1e-3;
+        let moved = (point + Vec3::new(x, y, z) * epsilon).normalize();
+        assert!(sphere.uv(moved)[0] > sphere.uv(point)[0]);
+        assert!((sphere.uv(moved)[1] - sphere.uv(point)[1]).abs() < 1e-5);
     }
 
     #[test]
     fn test_sphere_center() {
-        let sphere = Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, 1, [1, 1, 1]);
+        let sphere = Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, 1, [1, 1, 1, 1]);
         assert_eq!(sphere.center(), glam::Vec3::new(0.0, 0.0, 0.0));
     }
 
     #[test]
     fn test_sphere_aabb() {
-        let sphere = Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, 1, [1, 1, 1]);
+        let sphere = Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, 1, [1, 1, 1, 1]);
         let aabb = sphere.aabb();
         assert_eq!(aabb.min, Vec3::new(-1.0, -1.0, -1.0));
         assert_eq!(aabb.max, Vec3::new(1.0, 1.0, 1.0));
@@ -454,31 +1447,103 @@ mod tests {
 
     #[test]
     fn test_triangle() {
-        let triangle = Triangle::new([[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]], [0.0, 0.0, 1.0], 1, [1.0, 1.0, 1.0], [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]]);
+        let triangle = Triangle::new([[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]], [0.0, 0.0, 1.0], 1, [1.0, 1.0, 1.0, 1.0], [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]]);
         assert_eq!(triangle.points, [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]);
         assert_eq!(triangle.normal, [0.0, 0.0, 1.0]);
         assert_eq!(triangle.material_id, 1);
-        assert_eq!(triangle.texture_ids, [1.0, 1.0, 1.0]);
+        assert_eq!(triangle.texture_ids, [1.0, 1.0, 1.0, 1.0]);
         assert_eq!(triangle.tex_coords, [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]]);
     }
 
+    // Unit cube around the origin, two triangles per face with the outward face normal
+    fn cube() -> Vec<Triangle> {
+        let mut triangles = Vec::new();
+        for axis in 0..3 {
+            for sign in [-1.0, 1.0] {
+                let normal = Vec3::AXES[axis] * sign;
+                let (u, v) = (Vec3::AXES[(axis + 1) % 3] * 0.5, Vec3::AXES[(axis + 2) % 3] * 0.5);
+                let center = normal * 0.5;
+                let corners = [center - u - v, center + u - v, center + u + v, center - u + v].map(<[f32; 3]>::from);
+                for points in [[corners[0], corners[1], corners[2]], [corners[0], corners[2], corners[3]]] {
+                    triangles.push(Triangle::new(points, normal.into(), 0, [-1.0; 4], [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0]]));
+                }
+            }
+        }
+        triangles
+    }
+
+    #[test]
+    fn test_triangle_transformed_non_uniform_scale() {
+        // Turned by 45 degrees first, so the scale along x skews the side faces
+        let transform = Affine3A::from_scale(Vec3::new(2.0, 1.0, 1.0)) * Affine3A::from_rotation_z(std::f32::consts::FRAC_PI_4);
+        for triangle in cube().iter().map(|triangle| triangle.transformed(transform)) {
+            let normal = Vec3::from(triangle.normal);
+            assert!((normal.length() - 1.0).abs() < 1e-5, "normal {} isn't unit length", normal);
+            // Perpendicular to the transformed face and pointing away from the center of the cube at the origin
+            let points = triangle.points.map(Vec3::from);
+            assert!(normal.dot(points[1] - points[0]).abs() < 1e-5);
+            assert!(normal.dot(points[2] - points[0]).abs() < 1e-5);
+            assert!(normal.dot(triangle.center()) > 0.0, "normal {} points into the cube", normal);
+            assert!(triangle.vertex_normals.iter().all(|vertex_normal| Vec3::from(*vertex_normal).abs_diff_eq(normal, 1e-5)));
+        }
+
+        // The plain matrix would skew the normal of the face at +x towards the scaled axis
+        let face = cube()[2].transformed(transform);
+        let skewed = (transform.matrix3 * Vec3::X).normalize();
+        assert!(Vec3::from(face.normal).dot(skewed) < 0.99);
+    }
+
+    #[test]
+    fn test_triangle_transformed_uniform_scale() {
+        // The fast path without the inverse gives the same normals as the inverse-transpose
+        let linear = Mat3::from_rotation_y(0.7) * Mat3::from_diagonal(Vec3::splat(3.0));
+        assert!(normal_matrix(linear).abs_diff_eq(linear, 1e-6));
+        let transform = Affine3A::from_mat3_translation(linear, Vec3::new(1.0, 2.0, 3.0));
+        for (triangle, original) in cube().iter().map(|triangle| triangle.transformed(transform)).zip(cube()) {
+            let expected = (linear.inverse().transpose() * Vec3::from(original.normal)).normalize();
+            assert!(Vec3::from(triangle.normal).abs_diff_eq(expected, 1e-5));
+            assert!((triangle.area() - original.area() * 9.0).abs() < 1e-4);
+        }
+    }
+
     #[test]
     fn test_triangle_center() {
-        let triangle = Triangle::new([[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]], [0.0, 0.0, 1.0], 1, [1.0, 1.0, 1.0], [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]]);
+        let triangle = Triangle::new([[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]], [0.0, 0.0, 1.0], 1, [1.0, 1.0, 1.0, 1.0], [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]]);
         assert_eq!(triangle.center(), glam::Vec3::new(0.33333334, 0.33333334, 0.0));
     }
 
     #[test]
     fn test_triangle_aabb() {
-        let triangle = Triangle::new([[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]], [0.0, 0.0, 1.0], 1, [1.0, 1.0, 1.0], [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]]);
+        let triangle = Triangle::new([[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]], [0.0, 0.0, 1.0], 1, [1.0, 1.0, 1.0, 1.0], [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]]);
         let aabb = triangle.aabb();
         assert_eq!(aabb.min, Vec3::new(0.0, 0.0, 0.0));
         assert_eq!(aabb.max, Vec3::new(1.0, 1.0, 0.0));
     }
 
+    #[test]
+    fn test_triangle_degenerate() {
+        let triangle = Triangle::new([[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]], [0.0, 0.0, 1.0], 1, [1.0, 1.0, 1.0, 1.0], [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]]);
+        assert!(!triangle.is_degenerate());
+        let triangle = Triangle::new([[0.0, 0.0, 0.0], [1.0, 1.0, 1.0], [2.0, 2.0, 2.0]], [0.0, 0.0, 1.0], 1, [1.0, 1.0, 1.0, 1.0], [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]]);
+        assert!(triangle.is_degenerate());
+        assert!(Triangle::empty().is_degenerate());
+    }
+
+    #[test]
+    fn test_triangle_area() {
+        let triangle = Triangle::new([[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]], [0.0, 0.0, 1.0], 1, [1.0, 1.0, 1.0, 1.0], [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]]);
+        assert_eq!(triangle.area(), 0.5);
+        // The area doesn't depend on the orientation or the winding order
+        let triangle = Triangle::new([[0.0, 0.0, 0.0], [0.0, 3.0, 0.0], [0.0, 0.0, 2.0]], [1.0, 0.0, 0.0], 1, [1.0, 1.0, 1.0, 1.0], [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]]);
+        assert_eq!(triangle.area(), 3.0);
+        let triangle = Triangle::new([[0.0, 0.0, 0.0], [1.0, 1.0, 1.0], [2.0, 2.0, 2.0]], [0.0, 0.0, 1.0], 1, [1.0, 1.0, 1.0, 1.0], [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]]);
+        assert_eq!(triangle.area(), 0.0);
+        assert_eq!(Triangle::empty().area(), 0.0);
+    }
+
     #[test]
     fn test_triangle_uniform() {
-        let triangle = Triangle::new([[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]], [0.0, 0.0, 1.0], 1, [1.0, 1.0, 1.0], [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]]);
+        let triangle = Triangle::new([[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]], [0.0, 0.0, 1.0], 1, [1.0, 1.0, 1.0, 1.0], [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]]);
         let triangle_uniform = TriangleUniform::new(triangle);
         assert_eq!(triangle_uniform.vertex1, [0.0, 0.0, 0.0, 0.0]);
         assert_eq!(triangle_uniform.vertex2, [1.0, 0.0, 0.0, 0.0]);
@@ -487,6 +1552,90 @@ mod tests {
         assert_eq!(triangle_uniform.material_texture_id, [1.0, 1.0, 1.0, 1.0]);
         assert_eq!(triangle_uniform.texcords1, [0.0, 0.0, 1.0, 0.0]);
         assert_eq!(triangle_uniform.texcords2, [0.0, 1.0, 0.0, 0.0]);
+        assert_eq!(triangle_uniform.tangent, [1.0, 0.0, 0.0, 1.0]);
+        // Flat shaded triangles use the face normal at every vertex
+        assert_eq!(triangle_uniform.vertex_normals, [[0.0, 0.0, 1.0, 0.0]; 3]);
+        assert_eq!(triangle_uniform.emission_texture_id, [1.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_triangle_uniform_vertex_normals() {
+        let vertex_normals = [[0.0, 0.0, 1.0], [0.6, 0.0, 0.8], [0.0, 0.6, 0.8]];
+        let triangle = Triangle::new([[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]], [0.0, 0.0, 1.0], 1, [1.0, 1.0, 1.0, 1.0], [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]])
+            .with_vertex_normals(vertex_normals);
+        let triangle_uniform = TriangleUniform::new(triangle);
+        assert_eq!(triangle_uniform.normal, [0.0, 0.0, 1.0, 0.0]);
+        assert_eq!(triangle_uniform.vertex_normals, [[0.0, 0.0, 1.0, 0.0], [0.6, 0.0, 0.8, 0.0], [0.0, 0.6, 0.8, 0.0]]);
+    }
+
+    #[test]
+    fn test_triangle_tangent_orthogonal() {
+        // Tilted triangle with a slightly inaccurate normal and rotated, mirrored uvs
+        let triangle = Triangle::new([[0.0, 0.0, 0.0], [1.0, 0.0, 1.0], [0.0, 2.0, 0.5]], [-0.6, -0.2, 0.75], 1, [1.0, 1.0, 1.0, 1.0], [[0.2, 0.1], [0.4, 0.9], [1.0, 0.3]]);
+        let tangent = TriangleUniform::new(triangle).tangent;
+        let normal = Vec3::from(triangle.normal).normalize();
+        let tangent_vec = Vec3::new(tangent[0], tangent[1], tangent[2]);
+        assert!(normal.dot(tangent_vec).abs() < 1e-5);
+        assert!((tangent_vec.length() - 1.0).abs() < 1e-5);
+        assert!(tangent[3] == 1.0 || tangent[3] == -1.0);
+    }
+
+    #[test]
+    fn test_triangle_tangent_degenerate_uvs() {
+        let triangle = Triangle::new([[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]], [0.0, 0.0, 1.0], 1, [1.0, 1.0, 1.0, 1.0], [[0.0, 0.0]; 3]);
+        let tangent = triangle.tangent();
+        assert!(Vec3::new(tangent[0], tangent[1], tangent[2]).dot(Vec3::Z).abs() < 1e-5);
+        assert_eq!(tangent[3], 1.0);
+    }
+
+    #[test]
+    fn test_triangle_tangent_from_vertex_tangents() {
+        // The stored sign wins over the one computed from the uvs, the tangent is made orthogonal to the normal
+        let triangle = Triangle::new([[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]], [0.0, 0.0, 1.0], 1, [1.0, 1.0, 1.0, 1.0], [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]])
+            .with_vertex_tangents([[1.0, 0.0, 0.2, -1.0], [1.0, 0.0, 0.0, -1.0], [1.0, 0.0, -0.2, -1.0]]);
+        assert_eq!(triangle.tangent(), [1.0, 0.0, 0.0, -1.0]);
+
+        // Zero tangents of a file without tangents fall back to the computed tangent
+        let triangle = triangle.with_vertex_tangents([[0.0; 4]; 3]);
+        assert_eq!(triangle.tangent(), [1.0, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn test_bvh_primitive_collect() {
+        let triangles = vec![Triangle::empty(); 2];
+        let spheres = vec![
+            Sphere::new(Point3::new(1.0, 2.0, 3.0), 0.5, 0, [-1; 4]),
+            Sphere::empty(),
+            Sphere::new(Point3::new(-1.0, 0.0, 0.0), 2.0, 0, [-1; 4]),
+        ];
+        let (primitives, prim_ids) = BvhPrimitive::collect(&triangles, &spheres);
+
+        assert_eq!(primitives.len(), 4);
+        assert_eq!(prim_ids, vec![
+            [BVH_PRIM_TRIANGLE, 0.0],
+            [BVH_PRIM_TRIANGLE, 1.0],
+            [BVH_PRIM_SPHERE, 0.0],
+            [BVH_PRIM_SPHERE, 2.0],
+        ]);
+        let aabb = primitives[2].aabb();
+        assert_eq!(aabb.min, Vec3::new(0.5, 1.5, 2.5));
+        assert_eq!(aabb.max, Vec3::new(1.5, 2.5, 3.5));
+        assert_eq!(primitives[3].center(), Vec3::new(-1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_debug_view() {
+        assert_eq!(ShaderConfig::default().debug_view, DebugView::Beauty as u32);
+        assert_eq!(DebugView::from_u32(DebugView::Depth as u32), DebugView::Depth);
+        assert_eq!(DebugView::from_u32(100), DebugView::Beauty);
+
+        // Cycling visits every view once and returns to the beauty pass
+        let mut view = DebugView::Beauty;
+        for expected in DebugView::ALL.iter().skip(1) {
+            view = view.next();
+            assert_eq!(view, *expected);
+        }
+        assert_eq!(view.next(), DebugView::Beauty);
     }
 
     #[test]
@@ -1,10 +1,92 @@
 use image::{DynamicImage, GenericImageView};
 use wgpu::{Device, Queue, Texture, TextureDimension, TextureFormat, SurfaceConfiguration};
 
-pub fn create_texture(device: &Device, config: &SurfaceConfiguration, texture_width: u32, texture_height: u32, num_textures: u32) -> Texture {
+/// Format of the material texture array and the background texture.
+///
+/// Half floats keep HDR values above 1.0 (e.g. emissive maps loaded from `.hdr`/`.exr`) and are
+/// filterable without extra device features. Ldr images are converted on upload, which doubles
+/// the memory per texel compared to `Rgba8Unorm`.
+pub const TEXTURE_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+/// Color space of the values stored in a texture file.
+///
+/// The texture array is linear, so sRGB textures are decoded on the CPU before they are uploaded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorSpace {
+    /// Colors authored for display, e.g. diffuse and emissive maps.
+    #[default]
+    Srgb,
+    /// Data that is used as is, e.g. normal and roughness maps.
+    Linear,
+}
+
+impl ColorSpace {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "srgb" => Some(ColorSpace::Srgb),
+            "linear" => Some(ColorSpace::Linear),
+            _ => None,
+        }
+    }
+}
+
+/// Filter used when a texture is scaled to fit a texture array layer or an atlas page.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TextureFilter {
+    /// Keeps hard edges, e.g. for pixel art.
+    #[default]
+    Nearest,
+    Linear,
+    Cubic,
+    Gaussian,
+    /// Sharpest result for photos, but the slowest.
+    Lanczos,
+}
+
+impl TextureFilter {
+    pub fn filter_type(self) -> image::imageops::FilterType {
+        match self {
+            TextureFilter::Nearest => image::imageops::FilterType::Nearest,
+            TextureFilter::Linear => image::imageops::FilterType::Triangle,
+            TextureFilter::Cubic => image::imageops::FilterType::CatmullRom,
+            TextureFilter::Gaussian => image::imageops::FilterType::Gaussian,
+            TextureFilter::Lanczos => image::imageops::FilterType::Lanczos3,
+        }
+    }
+}
+
+/// Decodes an sRGB image to linear values, alpha stays unchanged.
+///
+/// Float images (`.hdr`, `.exr`) are always linear and are returned as they are, like all images in `ColorSpace::Linear`.
+pub fn to_linear(image: DynamicImage, color_space: ColorSpace) -> DynamicImage {
+    let is_float = matches!(image, DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_));
+    if color_space == ColorSpace::Linear || is_float {
+        return image;
+    }
+    let mut linear = image.to_rgba32f();
+    for pixel in linear.pixels_mut() {
+        for channel in pixel.0.iter_mut().take(3) {
+            *channel = srgb_to_linear(*channel);
+        }
+    }
+    DynamicImage::ImageRgba32F(linear)
+}
+
+/// sRGB transfer function (IEC 61966-2-1) from an encoded value in 0..1 to a linear value.
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn create_texture(device: &Device, _config: &SurfaceConfiguration, texture_width: u32, texture_height: u32, num_textures: u32) -> Texture {
     return device.create_texture(&wgpu::TextureDescriptor {
         label: Some("Texture Array"),
-        view_formats: &[config.format], // Use sRGB format for storage
+        view_formats: &[],
         size: wgpu::Extent3d {
             width: texture_width,
             height: texture_height,
@@ -13,7 +95,7 @@ pub fn create_texture(device: &Device, config: &SurfaceConfiguration, texture_wi
         mip_level_count: 1,
         sample_count: 1,
         dimension: TextureDimension::D2,
-        format: TextureFormat::Rgba8Unorm, // Adjust format as needed
+        format: TEXTURE_FORMAT,
         usage: wgpu::TextureUsages::COPY_DST | 
                wgpu::TextureUsages::TEXTURE_BINDING | 
                wgpu::TextureUsages::RENDER_ATTACHMENT,
@@ -22,9 +104,9 @@ pub fn create_texture(device: &Device, config: &SurfaceConfiguration, texture_wi
 
 fn write_texture(queue: &Queue, texture: &Texture, image: &DynamicImage, offset: wgpu::Origin3d) {
     let (width, height) = image.dimensions();
-    let bytes_per_pixel = 4; // Assuming RGBA8Unorm format
+    let bytes_per_pixel = 8; // RGBA16Float, see TEXTURE_FORMAT
     let bytes_per_row = width * bytes_per_pixel;
-    let data = image.to_rgba8().into_raw();
+    let data = image_to_rgba16f_bytes(image);
 
     queue.write_texture(
         wgpu::ImageCopyTexture {
@@ -47,48 +129,159 @@ fn write_texture(queue: &Queue, texture: &Texture, image: &DynamicImage, offset:
     );
 }
 
-pub fn load_textures_from_image(queue: &Queue, textureset: Texture, image: &DynamicImage, index: i32) -> Result<Texture, Box<dyn std::error::Error>> {
+/// Converts an image of any format to the raw bytes of an RGBA16Float texture.
+fn image_to_rgba16f_bytes(image: &DynamicImage) -> Vec<u8> {
+    image.to_rgba32f()
+        .into_raw()
+        .into_iter()
+        // Very bright HDR pixels (e.g. the sun) would become infinite in half precision and turn the lighting into NaNs
+        .map(|value| value.min(half::f16::MAX.to_f32()))
+        .flat_map(|value| half::f16::from_f32(value).to_le_bytes())
+        .collect()
+}
+
+/// Placeholder for textures that couldn't be loaded, a magenta and black checkerboard that is easy to spot in the render.
+pub fn missing_texture() -> DynamicImage {
+    let image = image::RgbaImage::from_fn(64, 64, |x, y| {
+        if (x / 8 + y / 8) % 2 == 0 {
+            image::Rgba([255, 0, 255, 255])
+        } else {
+            image::Rgba([0, 0, 0, 255])
+        }
+    });
+    DynamicImage::ImageRgba8(image)
+}
+
+/// Prefix of texture paths in the config that are generated instead of loaded, e.g. `"procedural:checker?squares=8"`.
+pub const PROCEDURAL_PREFIX: &str = "procedural:";
+
+/// Checkerboard of `squares` x `squares` fields alternating between `color_a` (top left) and `color_b`.
+pub fn checkerboard(size: u32, color_a: [u8; 4], color_b: [u8; 4], squares: u32) -> DynamicImage {
+    let squares = squares.clamp(1, size.max(1));
+    let image = image::RgbaImage::from_fn(size, size, |x, y| {
+        if (x * squares / size + y * squares / size) & 1 == 0 {
+            image::Rgba(color_a)
+        } else {
+            image::Rgba(color_b)
+        }
+    });
+    DynamicImage::ImageRgba8(image)
+}
+
+/// Texture for checking texture coordinates: u is shown in red, v in green, with white lines every 1/8.
+pub fn uv_grid(size: u32) -> DynamicImage {
+    let cell = (size / 8).max(1);
+    let image = image::RgbaImage::from_fn(size, size, |x, y| {
+        if x % cell == 0 || y % cell == 0 {
+            image::Rgba([255, 255, 255, 255])
+        } else {
+            let u = (x * 255 / size.max(1)) as u8;
+            let v = (y * 255 / size.max(1)) as u8;
+            image::Rgba([u, v, 0, 255])
+        }
+    });
+    DynamicImage::ImageRgba8(image)
+}
+
+/// Single pixel texture of one color.
+pub fn solid(color: [u8; 4]) -> DynamicImage {
+    DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(1, 1, image::Rgba(color)))
+}
+
+/// Generates the texture for a procedural path like `"procedural:checker?squares=8&size=256"`.
+///
+/// Supported generators and their optional parameters:
+/// * `checker` - `size` (256), `squares` (8), `color_a` (`1,1,1`) and `color_b` (`0,0,0`)
+/// * `uv_grid` - `size` (256)
+/// * `solid` - `color` (`1,1,1`)
+///
+/// Colors are given as 3 or 4 values between 0.0 and 1.0 separated by commas, like the colors in the config.
+pub fn procedural_texture(path: &str) -> Result<DynamicImage, String> {
+    let spec = path.strip_prefix(PROCEDURAL_PREFIX).ok_or(format!("Procedural texture paths have to start with \"{}\"", PROCEDURAL_PREFIX))?;
+    let (name, query) = spec.split_once('?').unwrap_or((spec, ""));
+
+    let mut size = 256;
+    let mut squares = 8;
+    let mut color_a = [255, 255, 255, 255];
+    let mut color_b = [0, 0, 0, 255];
+    let mut color = [255, 255, 255, 255];
+    for parameter in query.split('&').filter(|parameter| !parameter.is_empty()) {
+        let (key, value) = parameter.split_once('=').ok_or(format!("Expected key=value in procedural texture parameter \"{}\"", parameter))?;
+        match key {
+            "size" => size = value.parse().map_err(|_| format!("Expected integer for procedural texture size, got \"{}\"", value))?,
+            "squares" => squares = value.parse().map_err(|_| format!("Expected integer for procedural texture squares, got \"{}\"", value))?,
+            "color_a" => color_a = parse_color(value)?,
+            "color_b" => color_b = parse_color(value)?,
+            "color" => color = parse_color(value)?,
+            _ => return Err(format!("Unknown procedural texture parameter \"{}\"", key)),
+        }
+    }
+    if size == 0 {
+        return Err("Procedural texture size has to be greater than 0".to_string());
+    }
+
+    match name {
+        "checker" => Ok(checkerboard(size, color_a, color_b, squares)),
+        "uv_grid" => Ok(uv_grid(size)),
+        "solid" => Ok(solid(color)),
+        _ => Err(format!("Unknown procedural texture \"{}\", expected checker, uv_grid or solid", name)),
+    }
+}
+
+// parses "r,g,b" or "r,g,b,a" with values between 0.0 and 1.0
+fn parse_color(value: &str) -> Result<[u8; 4], String> {
+    let channels = value.split(',')
+        .map(|channel| channel.trim().parse::<f32>().map_err(|_| format!("Expected float color values, got \"{}\"", value)))
+        .collect::<Result<Vec<f32>, String>>()?;
+    let to_u8 = |channel: f32| (channel.clamp(0.0, 1.0) * 255.0).round() as u8;
+    match channels.as_slice() {
+        [r, g, b] => Ok([to_u8(*r), to_u8(*g), to_u8(*b), 255]),
+        [r, g, b, a] => Ok([to_u8(*r), to_u8(*g), to_u8(*b), to_u8(*a)]),
+        _ => Err(format!("Expected 3 or 4 color values, got \"{}\"", value)),
+    }
+}
+
+/// Writes the image into the layer `index` of the texture array.
+///
+/// Returns an error without writing anything if the layer doesn't exist or the image is larger than the texture.
+pub fn load_textures_from_image(queue: &Queue, textureset: &Texture, image: &DynamicImage, index: i32) -> Result<(), Box<dyn std::error::Error>> {
+    if index < 0 {
+        return Err(format!("Texture index {} is out of range (texture has {} layers)", index, textureset.depth_or_array_layers()).into());
+    }
+    load_texture_into_atlas(queue, textureset, image, 0, 0, index as u32)
+}
+
+/// Writes the image at `x`, `y` into the atlas page `page` of the texture array.
+///
+/// Returns an error without writing anything if the page doesn't exist or the image doesn't fit at this position.
+pub fn load_texture_into_atlas(queue: &Queue, textureset: &Texture, image: &DynamicImage, x: u32, y: u32, page: u32) -> Result<(), Box<dyn std::error::Error>> {
+    // Writing outside of the texture would be a validation error on the gpu
+    if page >= textureset.depth_or_array_layers() {
+        return Err(format!("Texture index {} is out of range (texture has {} layers)", page, textureset.depth_or_array_layers()).into());
+    }
+    if x + image.width() > textureset.width() || y + image.height() > textureset.height() {
+        return Err(format!("Image of size {:?} at {}, {} doesn't fit into the texture of size {}x{}", image.dimensions(), x, y, textureset.width(), textureset.height()).into());
+    }
+
     let offset = wgpu::Origin3d {
-        x: 0,
-        y: 0,
-        z: index as u32,
+        x,
+        y,
+        z: page,
     };
 
-    write_texture(queue, &textureset, image, offset);
-
-    Ok(textureset)
-}
-
-//index only added for easier debugging
-pub fn scale_texture(texture: &DynamicImage, width: u32, height: u32, _index: i32) -> DynamicImage {
-    // Inspect images: if uncommented
-    // Save the original texture
-    // let original_path = format!("textures_{}_original.png", index);
-    // let res = texture.save(original_path);
-    // match res {
-    //     Ok(_) => {
-    //         println!("IMG successfully saved")
-    //     }
-    //     Err(res) => {
-    //         println!("Failed to save IMG {:?}", res)
-    //     }
-    // }
-
-    // Resize the texture
-    let resized_texture = texture.resize(width, height, image::imageops::FilterType::Nearest);
-
-    // Save the resized texture
-    // let resized_path = format!("textures_{}_resized.png", index);
-    // let res = resized_texture.save(resized_path);
-    // match res {
-    //     Ok(_) => {
-    //         println!("IMG successfully saved (resized)")
-    //     }
-    //     Err(res) => {
-    //         println!("Failed to save IMG (resized) {:?}", res)
-    //     }
-    // }
-    return resized_texture;
+    write_texture(queue, textureset, image, offset);
+
+    Ok(())
+}
+
+/// Scales a texture to fit into `width` x `height`, keeping its aspect ratio.
+///
+/// A texture that already has the target size is returned unchanged, so it isn't filtered.
+pub fn scale_texture(texture: &DynamicImage, width: u32, height: u32, filter: TextureFilter) -> DynamicImage {
+    if texture.dimensions() == (width, height) {
+        return texture.clone();
+    }
+    texture.resize(width, height, filter.filter_type())
 }
 
 #[cfg(test)]
@@ -99,10 +292,118 @@ mod tests {
     #[test]
     fn test_scale_texture() {
         let texture = ImageReader::open("../scene/src/test_files/image.png").unwrap().decode().unwrap();
-        let scaled_texture = scale_texture(&texture, 100, 100, 0);
+        let scaled_texture = scale_texture(&texture, 100, 100, TextureFilter::default());
         assert_eq!(scaled_texture.dimensions(), (100, 42));
     }
 
+    #[test]
+    fn test_scale_texture_nearest_keeps_edges() {
+        let checker = DynamicImage::ImageRgba8(image::RgbaImage::from_fn(2, 2, |x, y| {
+            if (x + y) % 2 == 0 { image::Rgba([255, 255, 255, 255]) } else { image::Rgba([0, 0, 0, 255]) }
+        }));
+        let scaled = scale_texture(&checker, 4, 4, TextureFilter::Nearest);
+        assert_eq!(scaled.dimensions(), (4, 4));
+        for (x, y, pixel) in scaled.pixels() {
+            let expected = if (x / 2 + y / 2) % 2 == 0 { [255, 255, 255, 255] } else { [0, 0, 0, 255] };
+            assert_eq!(pixel.0, expected, "pixel {}, {}", x, y);
+        }
+
+        // Smooth filters blend the squares at the edges
+        let scaled = scale_texture(&checker, 4, 4, TextureFilter::Linear);
+        assert!(scaled.pixels().any(|(_, _, pixel)| pixel.0[0] != 0 && pixel.0[0] != 255));
+
+        // Textures that already have the target size are kept as they are
+        let scaled = scale_texture(&checker, 2, 2, TextureFilter::Lanczos);
+        assert_eq!(scaled.as_bytes(), checker.as_bytes());
+    }
+
+    #[test]
+    fn test_missing_texture() {
+        let texture = missing_texture();
+        assert_eq!(texture.dimensions(), (64, 64));
+        assert_eq!(texture.get_pixel(0, 0).0, [255, 0, 255, 255]);
+        assert_eq!(texture.get_pixel(8, 0).0, [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_image_to_rgba16f_bytes() {
+        let image = DynamicImage::ImageRgba32F(image::Rgba32FImage::from_pixel(2, 1, image::Rgba([2.5, 0.5, 0.0, 1.0])));
+        let bytes = image_to_rgba16f_bytes(&image);
+        assert_eq!(bytes.len(), 2 * 4 * 2);
+
+        // Values above 1.0 must not be clamped
+        let red = half::f16::from_le_bytes([bytes[0], bytes[1]]);
+        assert_eq!(red.to_f32(), 2.5);
+
+        // Values above the half precision range are clamped to its maximum instead of becoming infinite
+        let sun = DynamicImage::ImageRgba32F(image::Rgba32FImage::from_pixel(1, 1, image::Rgba([1.0e6, 0.5, 0.0, 1.0])));
+        let bytes = image_to_rgba16f_bytes(&sun);
+        assert_eq!(half::f16::from_le_bytes([bytes[0], bytes[1]]), half::f16::MAX);
+
+        // Ldr images are normalized to 0..1
+        let ldr = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(1, 1, image::Rgba([255, 0, 0, 255])));
+        let bytes = image_to_rgba16f_bytes(&ldr);
+        assert_eq!(half::f16::from_le_bytes([bytes[0], bytes[1]]).to_f32(), 1.0);
+    }
+
+    #[test]
+    fn test_checkerboard() {
+        let texture = checkerboard(64, [255, 0, 0, 255], [0, 0, 255, 255], 4);
+        assert_eq!(texture.dimensions(), (64, 64));
+        // 16 pixels per square
+        assert_eq!(texture.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(texture.get_pixel(15, 15).0, [255, 0, 0, 255]);
+        assert_eq!(texture.get_pixel(16, 0).0, [0, 0, 255, 255]);
+        assert_eq!(texture.get_pixel(0, 16).0, [0, 0, 255, 255]);
+        assert_eq!(texture.get_pixel(16, 16).0, [255, 0, 0, 255]);
+        assert_eq!(texture.get_pixel(63, 0).0, [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn test_uv_grid_and_solid() {
+        let texture = uv_grid(64);
+        assert_eq!(texture.get_pixel(0, 5).0, [255, 255, 255, 255]);
+        let pixel = texture.get_pixel(60, 4).0;
+        assert!(pixel[0] > 200 && pixel[1] < 20);
+
+        let texture = solid([10, 20, 30, 255]);
+        assert_eq!(texture.dimensions(), (1, 1));
+        assert_eq!(texture.get_pixel(0, 0).0, [10, 20, 30, 255]);
+    }
+
+    #[test]
+    fn test_procedural_texture() {
+        let texture = procedural_texture("procedural:checker?squares=2&size=32&color_a=1,0,0").unwrap();
+        assert_eq!(texture.dimensions(), (32, 32));
+        assert_eq!(texture.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(texture.get_pixel(16, 0).0, [0, 0, 0, 255]);
+
+        let texture = procedural_texture("procedural:solid?color=0,1,0,0.5").unwrap();
+        assert_eq!(texture.get_pixel(0, 0).0, [0, 255, 0, 128]);
+
+        assert_eq!(procedural_texture("procedural:uv_grid").unwrap().dimensions(), (256, 256));
+        assert!(procedural_texture("procedural:noise").is_err());
+        assert!(procedural_texture("procedural:checker?squares=many").is_err());
+        assert!(procedural_texture("procedural:checker?size=0").is_err());
+        assert!(procedural_texture("textures/checker.png").is_err());
+    }
+
     // No other tests realistic since they require a wgpu context
     // and a device to be created which is not possible in a normal test environment
-}
\ No newline at end of file
+
+    #[test]
+    fn test_to_linear() {
+        let image = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(1, 1, image::Rgba([255, 128, 0, 128])));
+        let linear = to_linear(image.clone(), ColorSpace::Srgb).to_rgba32f();
+        let pixel = linear.get_pixel(0, 0);
+        assert_eq!(pixel[0], 1.0);
+        assert!((pixel[1] - 0.2158605).abs() < 1e-4);
+        assert_eq!(pixel[2], 0.0);
+        // Alpha isn't color
+        assert!((pixel[3] - 128.0 / 255.0).abs() < 1e-6);
+
+        assert_eq!(to_linear(image.clone(), ColorSpace::Linear), image);
+        let float_image = DynamicImage::ImageRgba32F(image.to_rgba32f());
+        assert_eq!(to_linear(float_image.clone(), ColorSpace::Srgb), float_image);
+    }
+}
@@ -1,11 +1,80 @@
-
-use raytracing_lib::run;
+use raytracing_lib::{run, run_benchmark, validate_config, OutputFormat, RunConfig, DEFAULT_BENCHMARK_FRAMES};
 
 /// Entry point for the application.
 ///
+/// With `--bench [frames]` the raytracing pass is benchmarked instead and the result is printed as a single line.
+/// With `--validate <config>` the config and its assets are checked without rendering, the exit code is 1 if there are problems.
+/// With `--frames <count> [--output <path>] [--headless]` the application exits once that many frames are accumulated,
+/// after saving a screenshot to `path`, the exit code is 1 if it can't be saved. `--headless` hides the window while the scene is traced.
 /// It then calls the `run` function and blocks until it completes.
 fn main() {
     std::env::set_var("RUST_BACKTRACE", "1");
     std::env::set_var("CARGO_CACHE", "1");
-    pollster::block_on(run(None));
-}
\ No newline at end of file
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(index) = args.iter().position(|arg| arg == "--validate") {
+        let Some(path) = args.get(index + 1) else {
+            eprintln!("Missing config path for --validate");
+            std::process::exit(2);
+        };
+        match validate_config(path) {
+            Ok(warnings) if warnings.is_empty() => println!("{}: ok", path),
+            Ok(warnings) => {
+                for warning in &warnings {
+                    println!("{}: {}", path, warning);
+                }
+                println!("{}: {} problem(s)", path, warnings.len());
+                std::process::exit(1);
+            }
+            Err(error) => {
+                eprintln!("{}: {}", path, error);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Some(index) = args.iter().position(|arg| arg == "--bench") {
+        let frames = match args.get(index + 1) {
+            Some(frames) => frames.parse().unwrap_or_else(|_| {
+                eprintln!("Invalid frame count for --bench: {}", frames);
+                std::process::exit(2);
+            }),
+            None => DEFAULT_BENCHMARK_FRAMES,
+        };
+        pollster::block_on(run_benchmark(RunConfig::default(), frames));
+        return;
+    }
+
+    let frames = args.iter().position(|arg| arg == "--frames").map(|index| {
+        match args.get(index + 1).and_then(|frames| frames.parse::<u32>().ok()) {
+            Some(frames) if frames > 0 => frames,
+            _ => {
+                eprintln!("--frames needs a frame count greater than 0");
+                std::process::exit(2);
+            }
+        }
+    });
+    let output = args.iter().position(|arg| arg == "--output").map(|index| {
+        let Some(path) = args.get(index + 1) else {
+            eprintln!("Missing screenshot path for --output");
+            std::process::exit(2);
+        };
+        // Checked before rendering, so an unsupported extension doesn't throw away the accumulated frames
+        if let Err(error) = OutputFormat::from_path(path) {
+            eprintln!("Invalid --output: {}", error);
+            std::process::exit(2);
+        }
+        path.clone()
+    });
+    if output.is_some() && frames.is_none() {
+        eprintln!("--output needs --frames");
+        std::process::exit(2);
+    }
+
+    pollster::block_on(run(RunConfig {
+        frames,
+        output,
+        headless: args.iter().any(|arg| arg == "--headless"),
+        ..Default::default()
+    }));
+}
@@ -29,24 +29,27 @@ This allows it to generate high-quality images much faster than a CPU-only ray t
 
 ## Usage
 
-To use the ray tracer, you can create a `State` object and call its `run` method to start the ray tracing process.
-The `State` object will handle setting up the window, initializing the GPU, and rendering the scene.
+To use the ray tracer, call the `run` function with a `RunConfig` to start the ray tracing process.
+It sets up the window and a `State` object, which initializes the GPU and renders the scene.
 
 ```rust no_run
 // Import the `block_on` function from the `pollster` crate.
 // This is used to block the current thread until the `run` function completes.
 use pollster;
 
-// Import the `run` function from the `raytracing_lib` crate.
-use raytracing_lib::run;
+// Import the `run` function and its options from the `raytracing_lib` crate.
+use raytracing_lib::{run, RunConfig};
 
 // Entry point for the application.
 //
 // It then calls the `run` function and blocks until it completes.
 // Since we are not passing any configuration file and instead using the default settings,
-// we pass `None` as the argument to the `run` function.
+// only the window title is changed.
 fn main() {
-    pollster::block_on(run(None));
+    pollster::block_on(run(RunConfig {
+        title: "My raytracer".to_string(),
+        ..Default::default()
+    }));
 }
 
 ```
@@ -63,6 +66,9 @@ cargo run --example 2-obj_model
 cargo run --example 3-gltf_model
 cargo run --example 4-complex_material
 cargo run --example 5-cornell_box
+cargo run --example 7-glossy_metals
+cargo run --example 8-alpha_cutout
+cargo run --example 9-tinted_glass
 ```
 
 */
\ No newline at end of file
@@ -41,10 +41,14 @@ impl<'a> Default for BufferInitDescriptor<'a> {
 /// An enum representing the template for a binding resource.
 /// This shortens the amount of code needed to create a bind group layout and bind group.
 ///
-/// This enum can be one of three variants: `Buffer`, `TextureView`, or `Sampler`.
+/// The variants are read-only and read-write storage buffers, uniform buffers, storage textures, sampled textures and samplers.
 #[derive(Clone, Debug)]
 pub enum BindingResourceTemplate<'a> {
+    /// Storage buffer the shaders only read, e.g. the scene data of the raytracing pass.
     BufferStorage(wgpu::BindingResource<'a>),
+    /// Storage buffer the shaders write to (`var<storage, read_write>`), e.g. for atomic counters.
+    /// The passes of the raytracer currently only write to storage textures, so none of them needs it yet.
+    BufferStorageRW(wgpu::BindingResource<'a>),
     BufferUniform(wgpu::BindingResource<'a>),
     StorageTexture(wgpu::BindingResource<'a>),
     TextureView(wgpu::BindingResource<'a>),
@@ -57,6 +61,7 @@ pub enum BindingResourceTemplate<'a> {
 pub fn get_binding_resource<'a>(template: BindingResourceTemplate<'a>) -> wgpu::BindingResource<'a> {
     match template {
         BindingResourceTemplate::BufferStorage(binding_resource) => binding_resource,
+        BindingResourceTemplate::BufferStorageRW(binding_resource) => binding_resource,
         BindingResourceTemplate::BufferUniform(binding_resource) => binding_resource,
         BindingResourceTemplate::StorageTexture(binding_resource) => binding_resource,
         BindingResourceTemplate::TextureView(binding_resource) => binding_resource,
@@ -68,16 +73,21 @@ pub fn get_binding_resource<'a>(template: BindingResourceTemplate<'a>) -> wgpu::
 /// This enables the user to specify the type of buffer and the view dimension in a compact way.
 /// This struct can be piced appart to create a bind group layout and bind group.
 ///
-/// This struct contains a `BindingResourceTemplate` and an optional `TextureViewDimension`.
+/// This struct contains a `BindingResourceTemplate`, an optional `TextureViewDimension`, the format of a storage texture
+/// and for buffers whether they are bound with a dynamic offset and their minimum binding size.
 pub struct BufferType<'a> {
     ty: BindingResourceTemplate<'a>,
     view_dimension: Option<wgpu::TextureViewDimension>,
+    storage_format: Option<wgpu::TextureFormat>,
+    has_dynamic_offset: bool,
+    min_binding_size: Option<wgpu::BufferSize>,
 }
 
 impl PartialEq for BindingResourceTemplate<'_> {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (BindingResourceTemplate::BufferStorage(_), BindingResourceTemplate::BufferStorage(_)) => true,
+            (BindingResourceTemplate::BufferStorageRW(_), BindingResourceTemplate::BufferStorageRW(_)) => true,
             (BindingResourceTemplate::BufferUniform(_), BindingResourceTemplate::BufferUniform(_)) => true,
             (BindingResourceTemplate::StorageTexture(_), BindingResourceTemplate::StorageTexture(_)) => true,
             (BindingResourceTemplate::TextureView(_), BindingResourceTemplate::TextureView(_)) => true,
@@ -89,20 +99,88 @@ impl PartialEq for BindingResourceTemplate<'_> {
 
 impl<'a> BufferType<'a> {
     pub fn new(ty: BindingResourceTemplate<'a>) -> Self {
-        Self { ty, view_dimension: None }
+        Self { ty, view_dimension: None, storage_format: None, has_dynamic_offset: false, min_binding_size: None }
+    }
+
+    /// Creates a buffer binding with a dynamic offset and/or a minimum binding size.
+    ///
+    /// With `has_dynamic_offset` the offset is passed to `set_bind_group` for every draw or dispatch, so one buffer can hold
+    /// the data of several passes. The binding resource then has to be a `wgpu::BufferBinding` with the size of one entry,
+    /// see `dynamic_offset_stride` for the distance between the entries.
+    pub fn with_buffer_layout(ty: BindingResourceTemplate<'a>, has_dynamic_offset: bool, min_binding_size: Option<wgpu::BufferSize>) -> Self {
+        match ty {
+            BindingResourceTemplate::BufferStorage(_) | BindingResourceTemplate::BufferStorageRW(_) | BindingResourceTemplate::BufferUniform(_) => {
+                Self { ty, view_dimension: None, storage_format: None, has_dynamic_offset, min_binding_size }
+            }
+            _ => panic!("BufferType::with_buffer_layout can only be used with buffers"),
+        }
     }
 
     pub fn with_view_dimension(ty: BindingResourceTemplate<'a>, view_dimension: wgpu::TextureViewDimension) -> Self {
-        // Check if the binding type is a texture view or Storage Texture,
-        //Other types aren't alowed to have a view dimension
+        // Check if the binding type is a texture view,
+        // other types aren't alowed to have a view dimension and storage textures need a format as well
         if let BindingResourceTemplate::TextureView(_) = ty {
-            Self { ty, view_dimension: Some(view_dimension) }
-        } else if let BindingResourceTemplate::StorageTexture(_) = ty {
-            Self { ty, view_dimension: Some(view_dimension) }
-        } else{
-            panic!("BufferType::with_view_dimension can only be used with BindingResource::TextureView");
+            Self { ty, view_dimension: Some(view_dimension), storage_format: None, has_dynamic_offset: false, min_binding_size: None }
+        } else {
+            panic!("BufferType::with_view_dimension can only be used with BindingResource::TextureView, use BufferType::with_storage_format for storage textures");
         }
     }
+
+    /// Creates a storage texture binding, `format` has to be the format of the texture and of the declaration in the shader.
+    pub fn with_storage_format(ty: BindingResourceTemplate<'a>, view_dimension: wgpu::TextureViewDimension, format: wgpu::TextureFormat) -> Self {
+        if let BindingResourceTemplate::StorageTexture(_) = ty {
+            Self { ty, view_dimension: Some(view_dimension), storage_format: Some(format), has_dynamic_offset: false, min_binding_size: None }
+        } else {
+            panic!("BufferType::with_storage_format can only be used with BindingResource::StorageTexture");
+        }
+    }
+
+    /// Format of a storage texture binding, `None` for all other bindings.
+    pub fn storage_format(&self) -> Option<wgpu::TextureFormat> {
+        self.storage_format
+    }
+
+    /// Layout entry of this binding at index `binding`, visible to the shader stages `visibility`.
+    pub fn layout_entry(&self, binding: u32, visibility: wgpu::ShaderStages) -> wgpu::BindGroupLayoutEntry {
+        let ty = match &self.ty {
+            BindingResourceTemplate::BufferStorage(_) => wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: self.has_dynamic_offset,
+                min_binding_size: self.min_binding_size,
+            },
+            BindingResourceTemplate::BufferStorageRW(_) => wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: self.has_dynamic_offset,
+                min_binding_size: self.min_binding_size,
+            },
+            BindingResourceTemplate::BufferUniform(_) => wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: self.has_dynamic_offset,
+                min_binding_size: self.min_binding_size,
+            },
+            BindingResourceTemplate::StorageTexture(_) => wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::ReadWrite,
+                format: self.storage_format.expect("Storage textures are created with BufferType::with_storage_format"),
+                view_dimension: self.view_dimension.unwrap(),
+            },
+            BindingResourceTemplate::TextureView(_) => wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: self.view_dimension.unwrap(),
+                multisampled: false,
+            },
+            BindingResourceTemplate::Sampler(_) => wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+        };
+        wgpu::BindGroupLayoutEntry { binding, visibility, ty, count: None }
+    }
+}
+
+/// Distance in bytes between the entries of a buffer bound with a dynamic offset.
+///
+/// Dynamic offsets have to be multiples of `alignment`, which is `min_uniform_buffer_offset_alignment` or
+/// `min_storage_buffer_offset_alignment` of the device limits, so `entry_size` is rounded up to it.
+pub fn dynamic_offset_stride(entry_size: u64, alignment: u32) -> u32 {
+    let alignment = alignment.max(1) as u64;
+    (entry_size.max(1).div_ceil(alignment) * alignment) as u32
 }
 
 /// A struct representing a descriptor for a bind group.
@@ -125,6 +203,23 @@ impl<'a> BindGroupDescriptor<'a> {
     ///
     /// This method takes a reference to a `wgpu::Device` and returns a `wgpu::BindGroup`.
     pub fn generate_bind_group(&mut self, device: &wgpu::Device) -> wgpu::BindGroup {
+        //generate bind group layout
+        self.generate_bind_group_layout(device);
+
+        //ensure bind group layout is Some
+        let bg_layout;
+        match &self.layout {
+            Some(layout) => bg_layout = layout,
+            None => panic!("BindGroupLayout is None"),
+        };
+
+        self.generate_bind_group_with_layout(device, bg_layout)
+    }
+
+    /// A method to generate a bind group for an existing layout.
+    ///
+    /// Used to recreate a bind group with new resources, e.g. after a resize, that still matches the layout of the pipeline.
+    pub fn generate_bind_group_with_layout(&self, device: &wgpu::Device, layout: &wgpu::BindGroupLayout) -> wgpu::BindGroup {
         //count the number of bindings
         let mut binding_index = 0;
         
@@ -138,23 +233,12 @@ impl<'a> BindGroupDescriptor<'a> {
 
         //append _bind_group if lable is Some
         let mod_label = self.label.as_ref().map(|label| format!("{}_bind_group", label));
-        //generate bind group layout
-        self.generate_bind_group_layout(device);
 
-        //ensure bind group layout is Some
-        let bg_layout;
-        match &self.layout {
-            Some(layout) => bg_layout = layout,
-            None => panic!("BindGroupLayout is None"),
-        };
-
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: mod_label.as_deref(),
-            layout: bg_layout,
+            layout,
             entries: &entries,
-        });
-
-        return bind_group;
+        })
     }
 
     /// A method to generate a bind group layout.
@@ -165,72 +249,11 @@ impl<'a> BindGroupDescriptor<'a> {
         //append _bind_group if lable is Some
         let mod_label = self.label.as_ref().map(|label| format!("{}_bind_group_label", label));
 
-        //count the number of bindings
-        let mut binding_index = 0;
-
         self.layout = Some(device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: mod_label.as_deref(),
-            entries: &self.bindings.iter().map(|binding| {
-                binding_index += 1;
-                match &binding.ty {
-                    BindingResourceTemplate::BufferStorage(_) => {
-                        wgpu::BindGroupLayoutEntry {
-                            binding: binding_index - 1,
-                            visibility: self.vis,
-                            ty: wgpu::BindingType::Buffer {
-                                ty: wgpu::BufferBindingType::Storage { read_only: true },
-                                has_dynamic_offset: false,
-                                min_binding_size: None,
-                            },
-                            count: None,
-                        }
-                    }
-                    BindingResourceTemplate::BufferUniform(_) => {
-                        wgpu::BindGroupLayoutEntry {
-                            binding: binding_index - 1,
-                            visibility: self.vis,
-                            ty: wgpu::BindingType::Buffer {
-                                ty: wgpu::BufferBindingType::Uniform,
-                                has_dynamic_offset: false,
-                                min_binding_size: None,
-                            },
-                            count: None,
-                        }
-                    }
-                    BindingResourceTemplate::StorageTexture(_) => {
-                        wgpu::BindGroupLayoutEntry {
-                            binding: binding_index - 1,
-                            visibility: self.vis,
-                            ty: wgpu::BindingType::StorageTexture {
-                                access: wgpu::StorageTextureAccess::ReadWrite,
-                                format: wgpu::TextureFormat::Rgba8Unorm, //update to config.format
-                                view_dimension: binding.view_dimension.unwrap(),
-                            },
-                            count: None,
-                        }
-                    }
-                    BindingResourceTemplate::TextureView(_) => {
-                        wgpu::BindGroupLayoutEntry {
-                            binding: binding_index - 1,
-                            visibility: self.vis,
-                            ty: wgpu::BindingType::Texture {
-                                sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                                view_dimension: binding.view_dimension.unwrap(),
-                                multisampled: false,
-                            },
-                            count: None,
-                        }
-                    }
-                    BindingResourceTemplate::Sampler(_) => {
-                        wgpu::BindGroupLayoutEntry {
-                            binding: binding_index - 1,
-                            visibility: self.vis,
-                            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                            count: None,
-                        }
-                    } 
-                }
-            }).collect::<Vec<_>>(),
+            entries: &self.bindings.iter().enumerate()
+                .map(|(binding_index, binding)| binding.layout_entry(binding_index as u32, self.vis))
+                .collect::<Vec<_>>(),
         }));
     }
 }
@@ -277,4 +300,131 @@ mod tests {
         let binding_resource_template = BindingResourceTemplate::BufferStorage(binding_resource.clone());
         assert_eq!(binding_resource_template, BindingResourceTemplate::BufferStorage(binding_resource));
     }
+
+    #[test]
+    fn test_storage_texture_format() {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+        let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default())).unwrap();
+        let (device, _) = block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None)).unwrap();
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Test HDR Texture"),
+            size: wgpu::Extent3d { width: 4, height: 4, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba16Float,
+            usage: wgpu::TextureUsages::STORAGE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let binding = BufferType::with_storage_format(
+            BindingResourceTemplate::StorageTexture(wgpu::BindingResource::TextureView(&view)),
+            wgpu::TextureViewDimension::D2,
+            wgpu::TextureFormat::Rgba16Float,
+        );
+        assert_eq!(binding.storage_format(), Some(wgpu::TextureFormat::Rgba16Float));
+
+        let entry = binding.layout_entry(3, wgpu::ShaderStages::COMPUTE);
+        assert_eq!(entry.binding, 3);
+        assert_eq!(entry.ty, wgpu::BindingType::StorageTexture {
+            access: wgpu::StorageTextureAccess::ReadWrite,
+            format: wgpu::TextureFormat::Rgba16Float,
+            view_dimension: wgpu::TextureViewDimension::D2,
+        });
+
+        // Other bindings have no storage format
+        let sampled = BufferType::with_view_dimension(
+            BindingResourceTemplate::TextureView(wgpu::BindingResource::TextureView(&view)),
+            wgpu::TextureViewDimension::D2,
+        );
+        assert_eq!(sampled.storage_format(), None);
+    }
+
+    #[test]
+    fn test_storage_buffer_read_write() {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+        let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default())).unwrap();
+        let (device, _) = block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None)).unwrap();
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Test Buffer"),
+            size: 256,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let read_only = BufferType::new(BindingResourceTemplate::BufferStorage(buffer.as_entire_binding()));
+        let read_write = BufferType::new(BindingResourceTemplate::BufferStorageRW(buffer.as_entire_binding()));
+        let storage = |read_only| wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        };
+        assert_eq!(read_only.layout_entry(0, wgpu::ShaderStages::COMPUTE).ty, storage(true));
+        assert_eq!(read_write.layout_entry(0, wgpu::ShaderStages::COMPUTE).ty, storage(false));
+        assert_ne!(BindingResourceTemplate::BufferStorage(buffer.as_entire_binding()), BindingResourceTemplate::BufferStorageRW(buffer.as_entire_binding()));
+
+        // The layout and bind group can be created with a writable buffer
+        let mut descriptor = BindGroupDescriptor::new(Some("test"), wgpu::ShaderStages::COMPUTE, vec![read_write]);
+        descriptor.generate_bind_group(&device);
+        assert!(descriptor.layout.is_some());
+    }
+
+    #[test]
+    fn test_dynamic_offset_layout() {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+        let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default())).unwrap();
+        let (device, _) = block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None)).unwrap();
+
+        // Two u32 entries, each at an offset the device accepts
+        let alignment = device.limits().min_uniform_buffer_offset_alignment;
+        let stride = dynamic_offset_stride(4, alignment);
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Test Pass Buffer"),
+            size: 2 * stride as u64,
+            usage: wgpu::BufferUsages::UNIFORM,
+            mapped_at_creation: false,
+        });
+        let entry_size = wgpu::BufferSize::new(4);
+        let binding = BufferType::with_buffer_layout(
+            BindingResourceTemplate::BufferUniform(wgpu::BindingResource::Buffer(wgpu::BufferBinding { buffer: &buffer, offset: 0, size: entry_size })),
+            true,
+            entry_size,
+        );
+        assert_eq!(binding.layout_entry(0, wgpu::ShaderStages::COMPUTE).ty, wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: true,
+            min_binding_size: entry_size,
+        });
+        // Bindings created without it keep the defaults
+        let plain = BufferType::new(BindingResourceTemplate::BufferUniform(buffer.as_entire_binding()));
+        assert_eq!(plain.layout_entry(0, wgpu::ShaderStages::COMPUTE).ty, wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        });
+
+        let mut descriptor = BindGroupDescriptor::new(Some("test"), wgpu::ShaderStages::COMPUTE, vec![binding]);
+        descriptor.generate_bind_group(&device);
+        assert!(descriptor.layout.is_some());
+    }
+
+    #[test]
+    fn test_dynamic_offset_stride() {
+        assert_eq!(dynamic_offset_stride(4, 256), 256);
+        assert_eq!(dynamic_offset_stride(256, 256), 256);
+        assert_eq!(dynamic_offset_stride(257, 256), 512);
+        assert_eq!(dynamic_offset_stride(0, 256), 256);
+        assert_eq!(dynamic_offset_stride(12, 0), 12);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_buffer_layout_only_for_buffers() {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+        let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default())).unwrap();
+        let (device, _) = block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None)).unwrap();
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+        BufferType::with_buffer_layout(BindingResourceTemplate::Sampler(wgpu::BindingResource::Sampler(&sampler)), true, None);
+    }
 }
\ No newline at end of file
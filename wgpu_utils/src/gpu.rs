@@ -3,7 +3,15 @@ use wgpu::Features;
 use winit::window::Window;
 
 
-pub async fn setup_gpu<'a> (window: Window, config_path: &str) -> (Window, wgpu::Device, wgpu::Queue, wgpu::Surface<'a> , wgpu::SurfaceConfiguration, wgpu::TextureView, Config, winit::dpi::PhysicalSize<u32>) {
+/// Sets up the device, the surface of the window and the color texture.
+///
+/// The adapter is chosen with `AdapterSelector` if one is set, otherwise wgpu picks one using the `GpuOptions` of the config.
+///
+/// # Errors
+///
+/// Returns an error if no adapter matches the options, e.g. because there is no GPU and the fallback adapter isn't enabled.
+#[allow(clippy::type_complexity)]
+pub async fn setup_gpu<'a> (window: Window, userconfig: Config) -> Result<(Window, wgpu::Device, wgpu::Queue, wgpu::Surface<'a> , wgpu::SurfaceConfiguration, Vec<wgpu::PresentMode>, wgpu::Texture, Config, winit::dpi::PhysicalSize<u32>, DeviceInfo), String> {
     
     let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
         backends: wgpu::Backends::DX12,
@@ -27,21 +35,53 @@ pub async fn setup_gpu<'a> (window: Window, config_path: &str) -> (Window, wgpu:
         }
     };
 
-    let adapter = instance
-        .request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::HighPerformance,
-            compatible_surface: Some(&surface),
-            force_fallback_adapter: false,
-        })
-        .await
-        .unwrap();
+    // List all adapters that can render to the window, so the user can pick one by index or name
+    let mut adapters: Vec<wgpu::Adapter> = instance.enumerate_adapters(wgpu::Backends::DX12)
+        .into_iter()
+        .filter(|adapter| adapter.is_surface_supported(&surface))
+        .collect();
+    let adapter_names: Vec<String> = adapters.iter().map(|adapter| adapter.get_info().name).collect();
+    for (index, name) in adapter_names.iter().enumerate() {
+        println!("Adapter {}: {}", index, name);
+    }
+
+    // The environment variable takes precedence over the config
+    let selector = std::env::var(ADAPTER_ENV_VAR).ok()
+        .or_else(|| userconfig.gpu_adapter.clone())
+        .map(|selector| AdapterSelector::parse(&selector));
+    let selected_adapter = match &selector {
+        Some(selector) => match selector.select(&adapter_names) {
+            Some(index) => Some(adapters.swap_remove(index)),
+            None => {
+                println!("No adapter matches {:?}, using the high performance adapter", selector);
+                None
+            }
+        },
+        None => None,
+    };
+
+    let options = GpuOptions::from_config(&userconfig);
+    let adapter = match selected_adapter {
+        Some(adapter) => adapter,
+        None => instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: options.power_preference,
+                compatible_surface: Some(&surface),
+                force_fallback_adapter: options.force_fallback_adapter,
+            })
+            .await
+            .ok_or_else(|| format!("No gpu adapter found for {:?}. Available adapters: {:?}", options, adapter_names))?,
+    };
     
-    println!("{}", adapter.get_info().name);
+    println!("Using adapter: {}", adapter.get_info().name);
+
+    // Timestamp queries are only used for benchmarking, so they are optional
+    let optional_features = adapter.features() & Features::TIMESTAMP_QUERY;
 
     let (device, queue) = adapter
         .request_device(
             &wgpu::DeviceDescriptor {
-                required_features: Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
+                required_features: Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES | optional_features,
                 label: None,
                 required_limits: wgpu::Limits {
                     max_bind_groups: 6, // Not every old GPU supports more than 4 bind groups, 
@@ -63,25 +103,89 @@ pub async fn setup_gpu<'a> (window: Window, config_path: &str) -> (Window, wgpu:
         format: wgpu::TextureFormat::Rgba8Unorm,
         width: size.width,
         height: size.height,
-        present_mode: surface_caps.present_modes[0],
-        alpha_mode: surface_caps.alpha_modes[0],
+        present_mode: select_present_mode(&surface_caps.present_modes, wgpu::PresentMode::Fifo),
+        alpha_mode: select_alpha_mode(&surface_caps.alpha_modes, userconfig.transparent_background()),
         view_formats: vec![],
         desired_maximum_frame_latency: 10,
     };
     surface.configure(&device, &config);     
-    
-    let userconfig_result = Config::new(config_path);
-    let userconfig = match userconfig_result {
-        Ok(config) => config,
-        Err(e) => {
-            println!("Fatal: Error loading config: {}", e);
-            std::process::exit(1);
-        }
-    };
 
     //----------Color Buffer-------------
+    let color_texture = create_color_texture(&device, &config);
+
+    // What the device actually supports, for the info window and bug reports
+    let device_info = DeviceInfo::new(&adapter.get_info(), &device.limits(), device.features());
+
+    Ok((window, device, queue, surface, config, surface_caps.present_modes, color_texture, userconfig, size, device_info))
+}
+
+/// Adapter, limits and enabled features of the device created by `setup_gpu`.
+///
+/// `Display` writes one value per line, e.g. to paste it into a bug report.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DeviceInfo {
+    pub adapter_name: String,
+    pub backend: String,
+    /// Integrated, discrete, virtual or software (cpu) adapter.
+    pub device_type: String,
+    /// Name and version of the driver, if the backend reports them.
+    pub driver: String,
+    pub max_texture_dimension_2d: u32,
+    pub max_storage_buffer_binding_size: u32,
+    pub max_buffer_size: u64,
+    pub max_compute_workgroup_size: [u32; 3],
+    pub max_compute_invocations_per_workgroup: u32,
+    /// Names of the enabled features, e.g. "TIMESTAMP_QUERY".
+    pub features: Vec<String>,
+}
+
+impl DeviceInfo {
+    pub fn new(info: &wgpu::AdapterInfo, limits: &wgpu::Limits, features: Features) -> Self {
+        let driver = format!("{} {}", info.driver, info.driver_info).trim().to_string();
+        Self {
+            adapter_name: info.name.clone(),
+            backend: format!("{:?}", info.backend),
+            device_type: format!("{:?}", info.device_type),
+            driver,
+            max_texture_dimension_2d: limits.max_texture_dimension_2d,
+            max_storage_buffer_binding_size: limits.max_storage_buffer_binding_size,
+            max_buffer_size: limits.max_buffer_size,
+            max_compute_workgroup_size: [limits.max_compute_workgroup_size_x, limits.max_compute_workgroup_size_y, limits.max_compute_workgroup_size_z],
+            max_compute_invocations_per_workgroup: limits.max_compute_invocations_per_workgroup,
+            features: features.iter_names().map(|(name, _)| name.to_string()).collect(),
+        }
+    }
+}
+
+impl std::fmt::Display for DeviceInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const MIB: f64 = 1024.0 * 1024.0;
+        let [x, y, z] = self.max_compute_workgroup_size;
+        writeln!(f, "Adapter: {} ({})", self.adapter_name, self.device_type)?;
+        writeln!(f, "Backend: {}", self.backend)?;
+        writeln!(f, "Driver: {}", if self.driver.is_empty() { "unknown" } else { &self.driver })?;
+        writeln!(f, "Max texture size: {}", self.max_texture_dimension_2d)?;
+        writeln!(f, "Max storage buffer binding: {:.0} MiB", self.max_storage_buffer_binding_size as f64 / MIB)?;
+        writeln!(f, "Max buffer size: {:.0} MiB", self.max_buffer_size as f64 / MIB)?;
+        writeln!(f, "Max workgroup size: {} x {} x {} ({} invocations)", x, y, z, self.max_compute_invocations_per_workgroup)?;
+        if self.features.is_empty() {
+            write!(f, "Features: none")
+        } else {
+            write!(f, "Features: {}", self.features.join(", "))
+        }
+    }
+}
+
+
+/// Environment variable selecting the gpu adapter, overrides `[gpu] adapter` in the config.
+pub const ADAPTER_ENV_VAR: &str = "WGPU_ADAPTER";
+
+/// Creates the color texture the compute passes render into, with the size and format of the surface.
+///
+/// Has to be recreated whenever the surface is resized.
+pub fn create_color_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> wgpu::Texture {
     // Create a color texture with a suitable sRGB format
-    let color_texture = device.create_texture(&wgpu::TextureDescriptor {
+    device.create_texture(&wgpu::TextureDescriptor {
         label: Some("Storage Texture"),
         view_formats: &[config.format], // Use sRGB format for storage
         size: wgpu::Extent3d {
@@ -97,14 +201,119 @@ pub async fn setup_gpu<'a> (window: Window, config_path: &str) -> (Window, wgpu:
             | wgpu::TextureUsages::COPY_DST
             | wgpu::TextureUsages::STORAGE_BINDING
             | wgpu::TextureUsages::COPY_SRC,
-    });
-    
-    
-    let color_buffer_view = color_texture.create_view(&wgpu::TextureViewDescriptor::default());
+    })
+}
+
+/// Environment variable selecting the power preference, overrides `[gpu] power_preference` in the config.
+pub const POWER_PREFERENCE_ENV_VAR: &str = "WGPU_POWER_PREF";
+/// Environment variable enabling the software fallback adapter ("1" or "true"), overrides `[gpu] force_fallback_adapter`.
+pub const FALLBACK_ADAPTER_ENV_VAR: &str = "WGPU_FORCE_FALLBACK_ADAPTER";
+
+/// Options used to request the adapter if none is selected explicitly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GpuOptions {
+    pub power_preference: wgpu::PowerPreference,
+    /// Uses a software adapter like WARP or lavapipe, e.g. for tests on machines without a GPU.
+    pub force_fallback_adapter: bool,
+}
+
+impl Default for GpuOptions {
+    fn default() -> Self {
+        Self {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            force_fallback_adapter: false,
+        }
+    }
+}
+
+impl GpuOptions {
+    /// Reads the options from the `[gpu]` section of the config, the environment variables take precedence.
+    pub fn from_config(userconfig: &Config) -> Self {
+        let power_preference = std::env::var(POWER_PREFERENCE_ENV_VAR).ok()
+            .or_else(|| userconfig.gpu_power_preference.clone());
+        let force_fallback_adapter = std::env::var(FALLBACK_ADAPTER_ENV_VAR).ok()
+            .map(|value| matches!(value.trim().to_lowercase().as_str(), "1" | "true"))
+            .unwrap_or(userconfig.gpu_force_fallback_adapter);
+        Self::new(power_preference.as_deref(), force_fallback_adapter)
+    }
 
-    return (window, device, queue, surface, config, color_buffer_view, userconfig, size)
+    /// Creates the options from a power preference name, see `parse_power_preference`. Unknown names use the default.
+    pub fn new(power_preference: Option<&str>, force_fallback_adapter: bool) -> Self {
+        let power_preference = match power_preference {
+            Some(name) => parse_power_preference(name).unwrap_or_else(|| {
+                println!("Unknown power preference {:?}, using high performance", name);
+                GpuOptions::default().power_preference
+            }),
+            None => GpuOptions::default().power_preference,
+        };
+        Self { power_preference, force_fallback_adapter }
+    }
 }
 
+/// Parses "high_performance" / "high", "low_power" / "low" and "none" (case-insensitive).
+pub fn parse_power_preference(name: &str) -> Option<wgpu::PowerPreference> {
+    match name.trim().to_lowercase().as_str() {
+        "high_performance" | "high" => Some(wgpu::PowerPreference::HighPerformance),
+        "low_power" | "low" => Some(wgpu::PowerPreference::LowPower),
+        "none" => Some(wgpu::PowerPreference::None),
+        _ => None,
+    }
+}
+
+/// Selects a gpu adapter by its index in the adapter list or by a part of its name.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AdapterSelector {
+    Index(usize),
+    /// Case-insensitive substring of the adapter name, e.g. "nvidia".
+    Name(String),
+}
+
+impl AdapterSelector {
+    /// Numbers are read as index, everything else as name.
+    pub fn parse(selector: &str) -> Self {
+        let selector = selector.trim();
+        match selector.parse::<usize>() {
+            Ok(index) => AdapterSelector::Index(index),
+            Err(_) => AdapterSelector::Name(selector.to_string()),
+        }
+    }
+
+    /// Returns the index of the first adapter in `adapter_names` matching the selector.
+    pub fn select(&self, adapter_names: &[String]) -> Option<usize> {
+        match self {
+            AdapterSelector::Index(index) => (*index < adapter_names.len()).then_some(*index),
+            AdapterSelector::Name(name) => {
+                let name = name.to_lowercase();
+                adapter_names.iter().position(|adapter_name| adapter_name.to_lowercase().contains(&name))
+            }
+        }
+    }
+}
+
+/// Picks the alpha mode of the surface.
+///
+/// A transparent background needs a surface that is composited with premultiplied alpha.
+/// If the surface doesn't support it, the window stays opaque and the first supported mode is used.
+pub fn select_alpha_mode(alpha_modes: &[wgpu::CompositeAlphaMode], transparent: bool) -> wgpu::CompositeAlphaMode {
+    if transparent {
+        if alpha_modes.contains(&wgpu::CompositeAlphaMode::PreMultiplied) {
+            return wgpu::CompositeAlphaMode::PreMultiplied;
+        }
+        println!("Surface doesn't support premultiplied alpha, the window background stays opaque");
+    }
+    alpha_modes.first().copied().unwrap_or(wgpu::CompositeAlphaMode::Auto)
+}
+
+/// Picks the present mode of the surface.
+///
+/// Uses `preferred` if the surface supports it, otherwise vsync (`Fifo`), which every surface supports.
+pub fn select_present_mode(present_modes: &[wgpu::PresentMode], preferred: wgpu::PresentMode) -> wgpu::PresentMode {
+    if present_modes.contains(&preferred) {
+        preferred
+    } else {
+        wgpu::PresentMode::Fifo
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -119,7 +328,7 @@ mod tests {
             .build(&elwt)
             .unwrap();
 
-        let (window, device, _queue, _surface, config, _color_buffer_view, _userconfig, size) = block_on(setup_gpu(window, "config.toml"));
+        let (window, device, _queue, _surface, config, present_modes, color_texture, _userconfig, size, device_info) = block_on(setup_gpu(window, Config::new("config.toml").unwrap_or_default())).unwrap();
 
         assert_eq!(config.width, 800);  //Checks if config is set correctly
         assert_eq!(config.height, 600);
@@ -128,6 +337,93 @@ mod tests {
         assert_eq!(window.inner_size().width, 800); //Checks if window size is set correctly
         assert_eq!(window.inner_size().height, 600);
         assert_eq!(device.limits().max_bind_groups, 6); //Checks if custom limits are set
+        assert!(present_modes.contains(&config.present_mode)); //Checks if the present mode is supported
+        assert_eq!(color_texture.width(), config.width);
+        assert_eq!(device_info.max_texture_dimension_2d, device.limits().max_texture_dimension_2d);
+    }
+
+    #[test]
+    fn test_select_alpha_mode() {
+        let modes = [wgpu::CompositeAlphaMode::Opaque, wgpu::CompositeAlphaMode::PreMultiplied];
+        assert_eq!(select_alpha_mode(&modes, false), wgpu::CompositeAlphaMode::Opaque);
+        assert_eq!(select_alpha_mode(&modes, true), wgpu::CompositeAlphaMode::PreMultiplied);
+
+        // Falls back to an opaque surface if premultiplied alpha isn't supported
+        assert_eq!(select_alpha_mode(&[wgpu::CompositeAlphaMode::Opaque], true), wgpu::CompositeAlphaMode::Opaque);
+    }
+
+    #[test]
+    fn test_select_present_mode() {
+        let modes = [wgpu::PresentMode::Fifo, wgpu::PresentMode::Mailbox];
+        assert_eq!(select_present_mode(&modes, wgpu::PresentMode::Mailbox), wgpu::PresentMode::Mailbox);
+        assert_eq!(select_present_mode(&modes, wgpu::PresentMode::Fifo), wgpu::PresentMode::Fifo);
+
+        // Unsupported modes fall back to vsync
+        assert_eq!(select_present_mode(&modes, wgpu::PresentMode::Immediate), wgpu::PresentMode::Fifo);
+    }
+
+    #[test]
+    fn test_gpu_options() {
+        let options = GpuOptions::default();
+        assert_eq!(options.power_preference, wgpu::PowerPreference::HighPerformance);
+        assert!(!options.force_fallback_adapter);
+        assert_eq!(GpuOptions::new(None, false), options);
+
+        let options = GpuOptions::new(Some("low_power"), true);
+        assert_eq!(options.power_preference, wgpu::PowerPreference::LowPower);
+        assert!(options.force_fallback_adapter);
+        // Unknown names keep the default
+        assert_eq!(GpuOptions::new(Some("fast"), false).power_preference, wgpu::PowerPreference::HighPerformance);
+
+        assert_eq!(parse_power_preference(" LOW "), Some(wgpu::PowerPreference::LowPower));
+        assert_eq!(parse_power_preference("high"), Some(wgpu::PowerPreference::HighPerformance));
+        assert_eq!(parse_power_preference("none"), Some(wgpu::PowerPreference::None));
+        assert_eq!(parse_power_preference(""), None);
+    }
+
+    #[test]
+    fn test_device_info_display() {
+        let adapter_info = wgpu::AdapterInfo {
+            name: "NVIDIA GeForce RTX 3060 Laptop GPU".to_string(),
+            vendor: 0x10de,
+            device: 0x2560,
+            device_type: wgpu::DeviceType::DiscreteGpu,
+            driver: String::new(),
+            driver_info: String::new(),
+            backend: wgpu::Backend::Dx12,
+        };
+        let device_info = DeviceInfo::new(&adapter_info, &wgpu::Limits::default(), Features::TIMESTAMP_QUERY);
+        assert_eq!(device_info.max_compute_workgroup_size, [256, 256, 64]);
+        assert_eq!(device_info.features, vec!["TIMESTAMP_QUERY".to_string()]);
+
+        let text = device_info.to_string();
+        assert_eq!(text.lines().collect::<Vec<_>>(), vec![
+            "Adapter: NVIDIA GeForce RTX 3060 Laptop GPU (DiscreteGpu)",
+            "Backend: Dx12",
+            "Driver: unknown",
+            "Max texture size: 8192",
+            "Max storage buffer binding: 128 MiB",
+            "Max buffer size: 256 MiB",
+            "Max workgroup size: 256 x 256 x 64 (256 invocations)",
+            "Features: TIMESTAMP_QUERY",
+        ]);
+        assert!(DeviceInfo::new(&adapter_info, &wgpu::Limits::default(), Features::empty()).to_string().ends_with("Features: none"));
+    }
+
+    #[test]
+    fn test_adapter_selector() {
+        let names = vec!["Intel(R) UHD Graphics".to_string(), "NVIDIA GeForce RTX 3060 Laptop GPU".to_string()];
+
+        assert_eq!(AdapterSelector::parse("1"), AdapterSelector::Index(1));
+        assert_eq!(AdapterSelector::parse(" nvidia "), AdapterSelector::Name("nvidia".to_string()));
+
+        assert_eq!(AdapterSelector::parse("1").select(&names), Some(1));
+        assert_eq!(AdapterSelector::parse("nvidia").select(&names), Some(1));
+        assert_eq!(AdapterSelector::parse("Graphics").select(&names), Some(0));
+
+        // No match falls back to the default adapter
+        assert_eq!(AdapterSelector::parse("2").select(&names), None);
+        assert_eq!(AdapterSelector::parse("radeon").select(&names), None);
     }
 
     winit_test::main!(_test_setup_gpu);
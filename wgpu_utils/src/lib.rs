@@ -5,7 +5,12 @@
 //! ## Features
 //!
 //! - `BufferInitDescriptor`, `BindGroupDescriptor`, `BufferType`, `BindingResourceTemplate`: These types are used for managing GPU buffers.
-//! - `setup_gpu`: This function is used to initialize the GPU.
+//! - `setup_gpu`: This function is used to initialize the GPU. The adapter can be chosen with `WGPU_ADAPTER` or `[gpu] adapter` in the config (index or part of the name).
+//!   It also returns the present modes supported by the surface, vsync (`Fifo`) is used by default.
+//! - `DeviceInfo`: Adapter, limits and enabled features of the device created by `setup_gpu`.
+//! - `GpuOptions`: The power preference and the software fallback used to request the adapter, set in `[gpu]` or with
+//!   `WGPU_POWER_PREF` and `WGPU_FORCE_FALLBACK_ADAPTER`.
+//! - `GpuTimer`: Measures the GPU time of a compute pass with timestamp queries, if the GPU supports them.
 //!
 //! ## Examples
 //!
@@ -29,7 +34,10 @@
 
 mod buffer;
 mod gpu;
+mod timer;
 
 
-pub use buffer::{BufferInitDescriptor, BindGroupDescriptor, BufferType, BindingResourceTemplate};
-pub use gpu::setup_gpu;
\ No newline at end of file
+pub use buffer::{BufferInitDescriptor, BindGroupDescriptor, BufferType, BindingResourceTemplate, dynamic_offset_stride};
+pub use gpu::{setup_gpu, create_color_texture, select_present_mode, parse_power_preference, AdapterSelector, DeviceInfo, GpuOptions, ADAPTER_ENV_VAR,
+            FALLBACK_ADAPTER_ENV_VAR, POWER_PREFERENCE_ENV_VAR};
+pub use timer::{GpuTimer, timestamps_to_ms};
\ No newline at end of file
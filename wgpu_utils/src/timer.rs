@@ -0,0 +1,89 @@
+/// Measures the GPU time of a compute pass with timestamp queries.
+///
+/// Only available if the device was created with `Features::TIMESTAMP_QUERY`, which `setup_gpu` requests
+/// whenever the adapter supports it.
+pub struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    // Nanoseconds per timestamp tick
+    period: f32,
+}
+
+/// Size of the two resolved u64 timestamps at the beginning and end of the pass.
+const TIMESTAMPS_SIZE: wgpu::BufferAddress = 2 * std::mem::size_of::<u64>() as wgpu::BufferAddress;
+
+impl GpuTimer {
+    /// Creates the timer, or returns `None` if the device doesn't support timestamp queries.
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("Timestamp Query Set"),
+            ty: wgpu::QueryType::Timestamp,
+            count: 2,
+        });
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Timestamp Resolve Buffer"),
+            size: TIMESTAMPS_SIZE,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Timestamp Readback Buffer"),
+            size: TIMESTAMPS_SIZE,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Some(Self { query_set, resolve_buffer, readback_buffer, period: queue.get_timestamp_period() })
+    }
+
+    /// Timestamp writes for the pass to measure, pass them to `ComputePassDescriptor::timestamp_writes`.
+    pub fn compute_pass_timestamp_writes(&self) -> wgpu::ComputePassTimestampWrites<'_> {
+        wgpu::ComputePassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(0),
+            end_of_pass_write_index: Some(1),
+        }
+    }
+
+    /// Copies the timestamps into the readback buffer, call after the measured pass was recorded.
+    pub fn resolve(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.resolve_query_set(&self.query_set, 0..2, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(&self.resolve_buffer, 0, &self.readback_buffer, 0, TIMESTAMPS_SIZE);
+    }
+
+    /// Waits for the submitted work and returns the duration of the measured pass in milliseconds.
+    pub fn read_ms(&self, device: &wgpu::Device) -> Option<f64> {
+        let slice = self.readback_buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(wgpu::Maintain::Wait);
+        receiver.recv().ok()?.ok()?;
+
+        let timestamps: Vec<u64> = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+        self.readback_buffer.unmap();
+        Some(timestamps_to_ms(timestamps[0], timestamps[1], self.period))
+    }
+}
+
+/// Converts two timestamps into the milliseconds between them, `period` is the length of a tick in nanoseconds.
+pub fn timestamps_to_ms(begin: u64, end: u64, period: f32) -> f64 {
+    // The counter may be reset between the writes, e.g. when the GPU changes its power state
+    end.saturating_sub(begin) as f64 * period as f64 / 1_000_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamps_to_ms() {
+        assert_eq!(timestamps_to_ms(1_000, 3_001_000, 1.0), 3.0);
+        assert_eq!(timestamps_to_ms(0, 1_000_000, 2.5), 2.5);
+        assert_eq!(timestamps_to_ms(5_000, 1_000, 1.0), 0.0);
+    }
+}